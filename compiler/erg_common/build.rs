@@ -5,7 +5,7 @@ mod datetime;
 fn main() -> std::io::Result<()> {
     // recording the build date and the git hash
     let output = Command::new("git")
-        .args(&["rev-parse", "--short", "HEAD"])
+        .args(["rev-parse", "--short", "HEAD"])
         .output()
         .expect("failed to get the git hash");
     let git_hash_short = String::from_utf8_lossy(&output.stdout);
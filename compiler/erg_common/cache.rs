@@ -56,10 +56,10 @@ impl<T: Hash + Eq + Clone> CacheSet<[T]> {
 }
 
 impl<T: Hash + Eq> CacheSet<T> {
-    pub fn get<Q: ?Sized + Hash + Eq>(&self, q: &Q) -> Rc<T>
+    pub fn get<Q>(&self, q: &Q) -> Rc<T>
     where
         Rc<T>: Borrow<Q>,
-        Q: ToOwned<Owned = T>,
+        Q: Hash + Eq + ToOwned<Owned = T> + ?Sized,
     {
         if let Some(cached) = self.0.borrow().get(q) {
             return cached.clone();
@@ -70,4 +70,5 @@ impl<T: Hash + Eq> CacheSet<T> {
     }
 }
 
+#[allow(dead_code)]
 pub struct CacheDict<T: ?Sized>(RefCell<Set<Rc<T>>>);
@@ -7,3 +7,22 @@ pub const GREEN: &str = "\x1b[92m";
 pub const YELLOW: &str = "\x1b[93m";
 pub const BLUE: &str = "\x1b[94m";
 pub const CYAN: &str = "\x1b[96m";
+
+/// Removes the escape sequences (e.g. for non-terminal outputs)
+pub fn strip_escapes(s: &str) -> String {
+    let mut res = String::with_capacity(s.len());
+    let mut chars = s.chars();
+    while let Some(c) = chars.next() {
+        if c == '\x1b' {
+            // skip until the final byte of the sequence (`m`)
+            for c in chars.by_ref() {
+                if c.is_ascii_alphabetic() {
+                    break;
+                }
+            }
+        } else {
+            res.push(c);
+        }
+    }
+    res
+}
@@ -9,6 +9,7 @@ use std::process;
 use std::str::FromStr;
 
 use crate::help_messages::{command_message, mode_message};
+use crate::python_util::PythonVersion;
 use crate::stdin::GLOBAL_STDIN;
 use crate::{power_assert, read_file};
 
@@ -136,7 +137,7 @@ impl Input {
 
 #[derive(Debug, Clone)]
 pub struct ErgConfig {
-    /// options: lex | parse | compile | exec
+    /// options: lex | parse | lower | check | lint | compile | build | exec | read | server
     pub mode: &'static str,
     /// optimization level.
     /// * 0: no optimization
@@ -160,6 +161,15 @@ pub struct ErgConfig {
     /// needed for `jupyter-erg`
     pub ps1: &'static str,
     pub ps2: &'static str,
+    /// output format of errors and warnings (and the code object information of the read mode).
+    /// options: human (default) | json (JSON Lines) | sarif
+    pub output_format: &'static str,
+    /// whether to reuse/store compiled modules in `__erg_cache__`
+    pub use_cache: bool,
+    /// output directory of the build mode (default: `<root>/build`)
+    pub output_dir: Option<PathBuf>,
+    /// re-check the input whenever it or its imports are modified (check mode)
+    pub watch: bool,
 }
 
 impl Default for ErgConfig {
@@ -178,6 +188,10 @@ impl Default for ErgConfig {
             verbose: 2,
             ps1: ">>> ",
             ps2: "... ",
+            output_format: "human",
+            use_cache: true,
+            output_dir: None,
+            watch: false,
         }
     }
 }
@@ -199,6 +213,15 @@ impl ErgConfig {
         }
     }
 
+    /// The config of a module imported from the input (options such as `-o` are inherited)
+    pub fn inherit(&self, path: PathBuf) -> Self {
+        Self {
+            module: Box::leak(path.to_str().unwrap().to_string().into_boxed_str()),
+            input: Input::File(path),
+            ..self.copy()
+        }
+    }
+
     /// cloneのエイリアス(実際のcloneコストは低いので)
     #[inline]
     pub fn copy(&self) -> Self {
@@ -240,6 +263,30 @@ impl ErgConfig {
                     }
                     cfg.mode = Box::leak(mode.into_boxed_str());
                 }
+                "--output-format" | "--format" => {
+                    let format = args
+                        .next()
+                        .expect("the value of `--output-format` is not passed");
+                    cfg.output_format = match &format[..] {
+                        "human" => "human",
+                        "json" => "json",
+                        "sarif" => "sarif",
+                        other => {
+                            eprintln!("invalid output format: {other}");
+                            process::exit(1);
+                        }
+                    };
+                }
+                "--out-dir" => {
+                    let dir = args.next().expect("the value of `--out-dir` is not passed");
+                    cfg.output_dir = Some(PathBuf::from(dir));
+                }
+                "--no-cache" => {
+                    cfg.use_cache = false;
+                }
+                "--watch" => {
+                    cfg.watch = true;
+                }
                 "--ps1" => {
                     let ps1 = args
                         .next()
@@ -267,6 +314,10 @@ impl ErgConfig {
                         .expect("the value of `-p` is not passed")
                         .parse::<u32>()
                         .expect("the value of `-p` is not a number");
+                    if PythonVersion::from_magic_num(py_ver).is_none() {
+                        eprintln!("unsupported Python version: {py_ver}");
+                        process::exit(1);
+                    }
                     cfg.python_ver = Some(py_ver);
                 }
                 "--py-server-timeout" => {
@@ -305,7 +356,8 @@ impl ErgConfig {
                 }
             }
         }
-        if cfg.input == Input::REPL {
+        // the language server reads messages from stdin by itself
+        if cfg.input == Input::REPL && cfg.mode != "server" {
             use crate::tty::IsTty;
             let is_stdin_piped = !stdin().is_tty();
             let input = if is_stdin_piped {
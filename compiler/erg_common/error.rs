@@ -9,6 +9,7 @@ use std::io::{stderr, BufWriter, Write as _};
 use crate::astr::AtomicStr;
 use crate::color::*;
 use crate::config::Input;
+use crate::json::JsonValue;
 use crate::traits::{Locational, Stream};
 use crate::{fmt_option, impl_display_from_debug, switch_lang};
 
@@ -53,6 +54,7 @@ pub enum ErrorKind {
     NameWarning,
     UnusedWarning,
     Warning,
+    PatternWarning,
     /* runtime errors */
     ArithmeticError = 100,
     AssertionError,
@@ -160,6 +162,7 @@ impl From<&str> for ErrorKind {
             "NameWarning" => Self::NameWarning,
             "UnusedWarning" => Self::UnusedWarning,
             "Warning" => Self::Warning,
+            "PatternWarning" => Self::PatternWarning,
             "ArithmeticError" => Self::ArithmeticError,
             "AssertionError" => Self::AssertionError,
             "BlockingIOError" => Self::BlockingIOError,
@@ -312,6 +315,117 @@ impl Location {
             _ => None,
         }
     }
+
+    /// `{"ln_begin", "col_begin", "ln_end", "col_end"}` (columns are 0-origin),
+    /// `{"first", "second"}` for `RangePair`, and `null` for `Unknown`
+    pub fn to_json(&self) -> JsonValue {
+        let range =
+            |ln_begin: usize, col_begin: Option<usize>, ln_end: usize, col_end: Option<usize>| {
+                JsonValue::object(vec![
+                    ("ln_begin", ln_begin.into()),
+                    ("col_begin", col_begin.into()),
+                    ("ln_end", ln_end.into()),
+                    ("col_end", col_end.into()),
+                ])
+            };
+        match *self {
+            Self::RangePair {
+                ln_first,
+                col_first,
+                ln_second,
+                col_second,
+            } => JsonValue::object(vec![
+                (
+                    "first",
+                    range(ln_first.0, Some(col_first.0), ln_first.1, Some(col_first.1)),
+                ),
+                (
+                    "second",
+                    range(
+                        ln_second.0,
+                        Some(col_second.0),
+                        ln_second.1,
+                        Some(col_second.1),
+                    ),
+                ),
+            ]),
+            Self::Range {
+                ln_begin,
+                col_begin,
+                ln_end,
+                col_end,
+            } => range(ln_begin, Some(col_begin), ln_end, Some(col_end)),
+            Self::LineRange(ln_begin, ln_end) => range(ln_begin, None, ln_end, None),
+            Self::Line(lineno) => range(lineno, None, lineno, None),
+            Self::Unknown => JsonValue::Null,
+        }
+    }
+
+    /// The inverse of `to_json` (`Unknown` if `json` is malformed)
+    pub fn from_json(json: &JsonValue) -> Self {
+        let range = |json: &JsonValue| {
+            let get = |key: &str| json.get(key).and_then(|v| v.as_u64()).map(|n| n as usize);
+            match (
+                get("ln_begin"),
+                get("col_begin"),
+                get("ln_end"),
+                get("col_end"),
+            ) {
+                (Some(lb), Some(cb), Some(le), Some(ce)) => Some(Self::range(lb, cb, le, ce)),
+                (Some(lb), _, Some(le), _) if lb == le => Some(Self::Line(lb)),
+                (Some(lb), _, Some(le), _) => Some(Self::LineRange(lb, le)),
+                _ => None,
+            }
+        };
+        match (json.get("first"), json.get("second")) {
+            (Some(first), Some(second)) => match (range(first), range(second)) {
+                (Some(first), Some(second)) => Self::pair(first, second),
+                _ => Self::Unknown,
+            },
+            _ => range(json).unwrap_or(Self::Unknown),
+        }
+    }
+
+    /// SARIF `region`s (lines and columns are 1-origin)
+    fn sarif_regions(&self) -> Vec<JsonValue> {
+        let region =
+            |ln_begin: usize, col_begin: Option<usize>, ln_end: usize, col_end: Option<usize>| {
+                let mut attrs = vec![("startLine", ln_begin.into())];
+                if let Some(col_begin) = col_begin {
+                    attrs.push(("startColumn", (col_begin + 1).into()));
+                }
+                attrs.push(("endLine", ln_end.into()));
+                if let Some(col_end) = col_end {
+                    attrs.push(("endColumn", (col_end + 1).into()));
+                }
+                JsonValue::object(attrs)
+            };
+        match *self {
+            Self::RangePair {
+                ln_first,
+                col_first,
+                ln_second,
+                col_second,
+            } => vec![
+                region(ln_first.0, Some(col_first.0), ln_first.1, Some(col_first.1)),
+                region(
+                    ln_second.0,
+                    Some(col_second.0),
+                    ln_second.1,
+                    Some(col_second.1),
+                ),
+            ],
+            Self::Range {
+                ln_begin,
+                col_begin,
+                ln_end,
+                col_end,
+            } => vec![region(ln_begin, Some(col_begin), ln_end, Some(col_end))],
+            Self::LineRange(ln_begin, ln_end) => vec![region(ln_begin, None, ln_end, None)],
+            Self::Line(lineno) => vec![region(lineno, None, lineno, None)],
+            Self::Unknown => vec![],
+        }
+    }
 }
 
 /// Erg内で使われるエラーの共通部分
@@ -343,13 +457,7 @@ impl ErrorCore {
     }
 
     pub fn dummy(errno: usize) -> Self {
-        Self::new(
-            errno,
-            DummyError,
-            Location::Line(errno as usize),
-            "<dummy>",
-            None,
-        )
+        Self::new(errno, DummyError, Location::Line(errno), "<dummy>", None)
     }
 
     pub fn unreachable(fn_name: &str, line: u32) -> Self {
@@ -372,6 +480,40 @@ impl ErrorCore {
     }
 }
 
+const SARIF_SCHEMA: &str = "https://json.schemastore.org/sarif-2.1.0.json";
+
+fn severity(kind: ErrorKind) -> &'static str {
+    if kind.is_warning() {
+        "warning"
+    } else if kind.is_exception() {
+        "exception"
+    } else {
+        "error"
+    }
+}
+
+fn sarif_locations(input: &Input, loc: &Location) -> Vec<JsonValue> {
+    let physical_location = |region: Option<JsonValue>| {
+        let mut attrs = vec![(
+            "artifactLocation",
+            JsonValue::object(vec![("uri", input.enclosed_name().into())]),
+        )];
+        if let Some(region) = region {
+            attrs.push(("region", region));
+        }
+        JsonValue::object(vec![("physicalLocation", JsonValue::object(attrs))])
+    };
+    let regions = loc.sarif_regions();
+    if regions.is_empty() {
+        vec![physical_location(None)]
+    } else {
+        regions
+            .into_iter()
+            .map(|region| physical_location(Some(region)))
+            .collect()
+    }
+}
+
 pub const VBAR_UNICODE: &str = "│";
 pub const VBAR_BREAK_UNICODE: &str = "·";
 
@@ -575,6 +717,74 @@ pub trait ErrorDisplay {
             },
         }
     }
+
+    /// Serializes the error and its inner errors.
+    /// The escape sequences in `desc` and `hint` are removed.
+    fn to_json(&self) -> JsonValue {
+        let core = self.core();
+        JsonValue::object(vec![
+            ("errno", core.errno.into()),
+            ("kind", core.kind.to_string().into()),
+            ("severity", severity(core.kind).into()),
+            ("file", self.input().enclosed_name().into()),
+            ("loc", core.loc.to_json()),
+            ("desc", strip_escapes(&core.desc).into()),
+            (
+                "hint",
+                core.hint.as_ref().map(|hint| strip_escapes(hint)).into(),
+            ),
+            ("caused_by", self.caused_by().into()),
+            (
+                "inner",
+                self.ref_inner().map(|inner| inner.to_json()).into(),
+            ),
+        ])
+    }
+
+    /// Converts the error into a SARIF `result` object.
+    /// The locations of the inner errors are reported as `relatedLocations`.
+    fn to_sarif_result(&self) -> JsonValue {
+        let core = self.core();
+        let level = if core.kind.is_warning() {
+            "warning"
+        } else {
+            "error"
+        };
+        let mut text = strip_escapes(&core.desc);
+        if let Some(hint) = &core.hint {
+            text += "\nhint: ";
+            text += &strip_escapes(hint);
+        }
+        let mut related = vec![];
+        let mut inner = self.ref_inner();
+        while let Some(err) = inner {
+            for mut loc in sarif_locations(err.input(), &err.core().loc) {
+                if let JsonValue::Object(attrs) = &mut loc {
+                    attrs.push(("id".to_string(), related.len().into()));
+                    attrs.push((
+                        "message".to_string(),
+                        JsonValue::object(vec![("text", strip_escapes(&err.core().desc).into())]),
+                    ));
+                }
+                related.push(loc);
+            }
+            inner = err.ref_inner();
+        }
+        JsonValue::object(vec![
+            ("ruleId", core.kind.to_string().into()),
+            ("level", level.into()),
+            ("message", JsonValue::object(vec![("text", text.into())])),
+            ("locations", sarif_locations(self.input(), &core.loc).into()),
+            ("relatedLocations", related.into()),
+            (
+                "properties",
+                JsonValue::object(vec![
+                    ("errno", core.errno.into()),
+                    ("causedBy", self.caused_by().into()),
+                ]),
+            ),
+        ])
+    }
 }
 
 #[macro_export]
@@ -603,4 +813,45 @@ pub trait MultiErrorDisplay<Item: ErrorDisplay>: Stream<Item> {
         }
         write!(f, "")
     }
+
+    /// Serializes all the errors as a SARIF log
+    fn to_sarif(&self) -> JsonValue {
+        let driver = JsonValue::object(vec![
+            ("name", "erg".into()),
+            ("version", env!("CARGO_PKG_VERSION").into()),
+            ("informationUri", "https://github.com/erg-lang/erg".into()),
+        ]);
+        let run = JsonValue::object(vec![
+            ("tool", JsonValue::object(vec![("driver", driver)])),
+            (
+                "results",
+                self.iter()
+                    .map(|err| err.to_sarif_result())
+                    .collect::<Vec<_>>()
+                    .into(),
+            ),
+        ]);
+        JsonValue::object(vec![
+            ("$schema", SARIF_SCHEMA.into()),
+            ("version", "2.1.0".into()),
+            ("runs", vec![run].into()),
+        ])
+    }
+
+    /// `format`: human | json | sarif (see `ErgConfig::output_format`)
+    ///
+    /// `json` writes one error per line (JSON Lines).
+    /// Nothing is written for `json` and `sarif` if there are no errors.
+    fn write_all_stderr(&self, format: &str) {
+        match format {
+            "json" => {
+                for err in self.iter() {
+                    eprintln!("{}", err.to_json());
+                }
+            }
+            "sarif" if !self.is_empty() => eprintln!("{}", self.to_sarif()),
+            "sarif" => {}
+            _ => self.fmt_all_stderr(),
+        }
+    }
 }
@@ -21,6 +21,10 @@ OPTIONS
     --py-server-timeout (uint 64 number) PythonのREPLサーバーのタイムアウト時間を指定
     --dump-as-pyc                        .pycファイルにダンプ
     --mode lex|parse|compile|exec        指定モードで実行(詳細は--mode --helpを参照)
+    --output-format human|json|sarif     エラー・警告の出力形式を指定
+    --no-cache                           __erg_cache__のモジュールキャッシュを使わない
+    --out-dir <dir>                      buildモードの出力先ディレクトリを指定
+    --watch                              ファイルが変更されるたびに再検査する(checkモード)
 
 SUBCOMMAND
     -c cmd : 文字列をプログラムに譲渡
@@ -44,6 +48,10 @@ OPTIONS
     --py-server-timeout (uint 64 number) Python REPL 服务器超时
     --dump-as-pyc                        转储为 .pyc 文件
     --mode lex|parse|compile|exec        执行模式 (更多信息见`--mode --help`)
+    --output-format human|json|sarif     错误和警告的输出格式
+    --no-cache                           不使用 __erg_cache__ 中的模块缓存
+    --out-dir <dir>                      build 模式的输出目录
+    --watch                              每当文件被修改时重新检查 (check 模式)
 
 SUBCOMMAND
     -c cmd : 作为字符串传入程序
@@ -67,6 +75,10 @@ OPTIONS
     --py-server-timeout (uint 64 number) Python REPL 服務器超時
     --dump-as-pyc                        轉儲為 .pyc 文件
     --mode lex|parse|compile|exec        執行模式 (更多信息見`--mode --help`)
+    --output-format human|json|sarif     錯誤和警告的輸出格式
+    --no-cache                           不使用 __erg_cache__ 中的模塊緩存
+    --out-dir <dir>                      build 模式的輸出目錄
+    --watch                              每當檔案被修改時重新檢查 (check 模式)
 
 SUBCOMMAND
     -c cmd : 作為字串傳入程式
@@ -90,6 +102,10 @@ OPTIONS
     --py-server-timeout (uint 64 number) timeout for the Python REPL server
     --dump-as-pyc                        dump as .pyc file
     --mode lex|parse|compile|exec        execution mode (See `--mode --help` for details)
+    --output-format human|json|sarif     output format of errors and warnings
+    --no-cache                           do not use the module cache in __erg_cache__
+    --out-dir <dir>                      output directory of the build mode
+    --watch                              re-check whenever the files are modified (check mode)
 
 SUBCOMMAND
     -c cmd : program passed in as string
@@ -102,7 +118,7 @@ pub fn mode_message<'a>() -> &'a str {
         "japanese" =>
         "\
 USAGE:
-    erg --mode [lex | parse | lower | check | compile | exec | read] [SUBCOMMAND] [ARGS]...
+    erg --mode [lex | parse | lower | check | lint | compile | build | exec | read | server] [SUBCOMMAND] [ARGS]...
 
 lex
     <filename>.erやREPLなどから入力を受け取り、字句を解析
@@ -120,20 +136,32 @@ check
     lowerを実行
     副作用、所有権を確認しHIRを返す
 
+lint
+    checkを実行
+    未使用の変数、シャドーイング、副作用のないプロシージャなどを警告する
+
 compile
     checkを実行
     HIRをからバイトコードを生成し、<filename>.pycを出力する
 
+build
+    <dir>以下の全モジュールに対してcheckを実行
+    依存順に各モジュールを.pycにコンパイルし、同じパッケージ構成で出力先ディレクトリに出力する
+
 exec
     compileを実行し、更に<filename>.pycを実行
 
 read
-    <filename>.pycをデシリアライズしコードオブジェクトの情報をダンプ",
+    <filename>.pycをデシリアライズしコードオブジェクトの情報をダンプ
+    `--format json`でJSON形式で出力する
+
+server
+    言語サーバーを起動し、標準入出力でLSPメッセージをやり取りする",
 
     "simplified_chinese" =>
     "\
 USAGE:
-    erg --mode [lex | parse | lower | check | compile | exec | read] [SUBCOMMAND] [ARGS]...
+    erg --mode [lex | parse | lower | check | lint | compile | build | exec | read | server] [SUBCOMMAND] [ARGS]...
 
 lex
     从 <filename>.er, REPL 等接受输入, 并标记文本
@@ -151,21 +179,33 @@ check
     执行 lower
     检查副作用、所有权并返回 HIR
 
+lint
+    执行 check
+    警告未使用的变量、遮蔽、没有副作用的过程等
+
 compile
     运行 check 以获取检查完成的 AST
     编译 AST 并返回 <文件名>.pyc
 
+build
+    对 <dir> 下的所有模块执行 check
+    按依赖顺序将每个模块编译为 .pyc, 并以相同的包结构输出到输出目录
+
 exec
     运行 check 以获取检查完成的 AST
     在执行 <文件名>.pyc 后删除 <文件名>.pyc
 
 read
-    反序列化 <文件名>.pyc 和 dump",
+    反序列化 <文件名>.pyc 和 dump
+    使用 `--format json` 以 JSON 格式输出
+
+server
+    启动语言服务器, 通过标准输入输出交换 LSP 消息",
 
     "traditional_chinese" =>
     "\
 USAGE:
-        erg --mode [lex | parse | lower | check | compile | exec | read] [SUBCOMMAND] [ARGS]...
+        erg --mode [lex | parse | lower | check | lint | compile | build | exec | read | server] [SUBCOMMAND] [ARGS]...
 
 lex
     從 <檔名>.er, REPL 等接受輸入, 並標記文字
@@ -183,21 +223,33 @@ check
     執行 lower
     檢查副作用、所有權並返回 HIR
 
+lint
+    執行 check
+    警告未使用的變量、遮蔽、沒有副作用的過程等
+
 compile
     運行 check 以獲取檢查完成的 AST
     編譯 AST 並返回 <檔名>.pyc
 
+build
+    對 <dir> 下的所有模塊執行 check
+    按依賴順序將每個模塊編譯為 .pyc, 並以相同的包結構輸出到輸出目錄
+
 exec
     運行check以獲取檢查完成的 AST
     在執行 <檔名>.pyc 後删除 <檔名>.pyc
 
 read
-    反序列化 <檔名>.pyc 和 dump",
+    反序列化 <檔名>.pyc 和 dump
+    使用 `--format json` 以 JSON 格式輸出
+
+server
+    啟動語言服務器, 通過標準輸入輸出交換 LSP 消息",
 
     "english" =>
     "\
 USAGE:
-    erg --mode [lex | parse | lower | check | compile | exec | read] [SUBCOMMAND] [ARGS]...
+    erg --mode [lex | parse | lower | check | lint | compile | build | exec | read | server] [SUBCOMMAND] [ARGS]...
 
 lex
     Receive input from <filename>.er, REPL, etc. and lex the text
@@ -215,14 +267,26 @@ check
     Execute lower
     Checks for side-effects, ownership, and returns HIR
 
+lint
+    Execute check
+    Warns about unused variables, shadowing, procedures without side-effects, etc.
+
 compile
     Execute check
     Generates bytecode from HIR and outputs <filename>.pyc
 
+build
+    Execute check for all modules under <dir>
+    Compiles each module to a .pyc in dependency order, and outputs them to the output directory in the same package layout
+
 exec
     Execute compile and then <filename>.pyc
 
 read
-    Deserialize <filename>.pyc and dump code object information",
+    Deserialize <filename>.pyc and dump code object information
+    Outputs in JSON with `--format json`
+
+server
+    Start the language server, which communicates LSP messages over stdio",
     )
 }
@@ -1,4 +1,5 @@
 //! provides utilities for parser, compiler, and vm crate.
+#![allow(clippy::result_large_err)]
 use std::fmt;
 
 pub mod astr;
@@ -11,9 +12,11 @@ pub mod env;
 pub mod error;
 pub mod fxhash;
 pub mod help_messages;
+pub mod json;
 pub mod levenshtein;
 pub mod macros;
 pub mod opcode;
+pub mod opcode311;
 pub mod python_util;
 pub mod serialize;
 pub mod set;
@@ -24,6 +27,7 @@ pub mod traits;
 pub mod tsort;
 pub mod tty;
 pub mod vis;
+pub mod watch;
 
 use crate::set::Set;
 pub use crate::str::Str;
@@ -6,14 +6,21 @@
 #![allow(non_camel_case_types)]
 
 use crate::impl_display_from_debug;
+use crate::opcode311;
+use crate::python_util::PythonVersion;
 
 /// Based on Python opcodes.
 /// This is represented by u8.
 ///
+/// The numbers are those of Python 3.10 (except for the opcodes which do not exist in 3.10).
+/// Use `encode`/`decode` for the numbering of other versions.
+///
 /// TODO: implement all opcodes
 #[derive(Debug, Clone, Copy, PartialEq, PartialOrd, Ord, Eq)]
 #[repr(u8)]
 pub enum Opcode {
+    /// Python 3.11+: inline cache entries (see `opcode311::cache_entries`)
+    CACHE = 0,
     POP_TOP = 1,
     ROT_TWO = 2,
     ROT_THREE = 3,
@@ -44,6 +51,10 @@ pub enum Opcode {
     PUSH_EXC_INFO = 35,
     CHECK_EXC_MATCH = 36,
     CHECK_EG_MATCH = 37,
+    /// Python 3.11+: pushes `NULL` (below the callable of `CALL`)
+    PUSH_NULL = 38,
+    /// Python 3.8 only (the real number is 53)
+    BEGIN_FINALLY = 39,
     WITH_EXCEPT_START = 49,
     GET_AITER = 50,
     GET_ANEXT = 51,
@@ -56,20 +67,39 @@ pub enum Opcode {
     INPLACE_MULTIPLY = 57,
     INPLACE_MODULO = 59,
     STORE_SUBSCR = 60,
+    DELETE_SUBSCR = 61,
+    BINARY_LSHIFT = 62,
+    BINARY_RSHIFT = 63,
     BINARY_AND = 64,
     BINARY_XOR = 65,
     BINARY_OR = 66,
+    INPLACE_POWER = 67,
     GET_ITER = 68,
     GET_YIELD_FROM_ITER = 69,
     PRINT_EXPR = 70,
     LOAD_BUILD_CLASS = 71,
+    /// Python 3.7 ~ 3.10
+    YIELD_FROM = 72,
+    /// 131 in Python 3.11+
+    GET_AWAITABLE = 73,
     LOAD_ASSERTION_ERROR = 74,
+    INPLACE_LSHIFT = 75,
+    INPLACE_RSHIFT = 76,
+    INPLACE_AND = 77,
+    INPLACE_XOR = 78,
+    INPLACE_OR = 79,
+    /// Python 3.7/3.8 (the real number is 82)
+    WITH_CLEANUP_FINISH = 80,
+    /// Python 3.7/3.8
+    WITH_CLEANUP_START = 81,
     LIST_TO_TUPLE = 82,
     RETURN_VALUE = 83,
     IMPORT_STAR = 84,
     SETUP_ANNOTATIONS = 85,
     YIELD_VALUE = 86,
     POP_BLOCK = 87,
+    /// Python 3.7/3.8
+    END_FINALLY = 88,
     POP_EXCEPT = 89,
     /* ↓ These opcodes take an arg */
     STORE_NAME = 90,
@@ -78,7 +108,9 @@ pub enum Opcode {
     FOR_ITER = 93,
     UNPACK_EX = 94,
     STORE_ATTR = 95,
+    DELETE_ATTR = 96,
     STORE_GLOBAL = 97,
+    SWAP = 99,
     LOAD_CONST = 100,
     LOAD_NAME = 101,
     BUILD_TUPLE = 102,
@@ -99,21 +131,75 @@ pub enum Opcode {
     IS_OP = 117,
     CONTAINS_OP = 118,
     RERAISE = 119,
+    COPY = 120,
+    /// Python 3.9/3.10
+    JUMP_IF_NOT_EXC_MATCH = 121,
+    BINARY_OP = 122,
+    /// Python 3.11+
+    SEND = 123,
     LOAD_FAST = 124,
     STORE_FAST = 125,
     DELETE_FAST = 126,
+    /// Python 3.11+
+    POP_JUMP_FORWARD_IF_NOT_NONE = 128,
+    /// Python 3.10 only
+    GEN_START = 129,
     RAISE_VARARGS = 130,
     CALL_FUNCTION = 131,
     MAKE_FUNCTION = 132,
+    BUILD_SLICE = 133,
+    /// Python 3.11+
+    JUMP_BACKWARD_NO_INTERRUPT = 134,
     LOAD_CLOSURE = 135,
     LOAD_DEREF = 136,
     STORE_DEREF = 137,
+    /// Python 3.11+ (the real number is 135)
+    MAKE_CELL = 139,
+    JUMP_BACKWARD = 140,
     CALL_FUNCTION_KW = 141,
     CALL_FUNCTION_EX = 142,
     SETUP_WITH = 143,
+    EXTENDED_ARG = 144,
+    LIST_APPEND = 145,
+    SET_ADD = 146,
+    MAP_ADD = 147,
+    COPY_FREE_VARS = 149,
+    RESUME = 151,
+    MATCH_CLASS = 152,
+    /// Python 3.7 ~ 3.10
+    SETUP_ASYNC_WITH = 154,
+    FORMAT_VALUE = 155,
+    BUILD_CONST_KEY_MAP = 156,
+    BUILD_STRING = 157,
+    /// Python 3.7/3.8
+    BUILD_TUPLE_UNPACK_WITH_CALL = 158,
     LOAD_METHOD = 160,
     CALL_METHOD = 161,
     LIST_EXTEND = 162,
+    SET_UPDATE = 163,
+    DICT_MERGE = 164,
+    DICT_UPDATE = 165,
+    PRECALL = 166,
+    CALL = 171,
+    KW_NAMES = 172,
+    /// Python 3.11+ (the real number is 114)
+    POP_JUMP_FORWARD_IF_FALSE = 173,
+    /// Python 3.11+ (the real number is 115)
+    POP_JUMP_FORWARD_IF_TRUE = 174,
+    POP_JUMP_BACKWARD_IF_FALSE = 175,
+    POP_JUMP_BACKWARD_IF_TRUE = 176,
+    /// Python 3.7 ~ 3.10 (the real number is 122)
+    SETUP_FINALLY = 177,
+    /// Python 3.11+ (the real number is 129)
+    POP_JUMP_FORWARD_IF_NONE = 178,
+    /// Python 3.11+ (the real number is 173)
+    POP_JUMP_BACKWARD_IF_NOT_NONE = 179,
+    /// Python 3.11+ (the real number is 174)
+    POP_JUMP_BACKWARD_IF_NONE = 180,
+    /// Python 3.11+ (the real number is 75)
+    RETURN_GENERATOR = 181,
+    /// Python 3.11+ (the real number is 87)
+    ASYNC_GEN_WRAP = 182,
     // Erg-specific opcodes (must have a unary `ERG_`)
     // Define in descending order from 219, 255
     ERG_POP_NTH = 196,
@@ -169,154 +255,478 @@ impl_display_from_debug!(Opcode);
 
 impl From<u8> for Opcode {
     fn from(byte: u8) -> Self {
+        Self::from_u8(byte).unwrap_or_else(|| panic!("not implemented opcode: {byte}"))
+    }
+}
+
+impl Opcode {
+    pub const fn from_u8(byte: u8) -> Option<Self> {
         match byte {
-            1 => POP_TOP,
-            2 => ROT_TWO,
-            3 => ROT_THREE,
-            4 => DUP_TOP,
-            5 => DUP_TOP2,
-            6 => ROT_FOUR,
-            9 => NOP,
-            10 => UNARY_POSITIVE,
-            11 => UNARY_NEGATIVE,
-            12 => UNARY_NOT,
-            15 => UNARY_INVERT,
-            19 => BINARY_POWER,
-            20 => BINARY_MULTIPLY,
-            22 => BINARY_MODULO,
-            23 => BINARY_ADD,
-            24 => BINARY_SUBTRACT,
-            25 => BINARY_SUBSCR,
-            26 => BINARY_FLOOR_DIVIDE,
-            27 => BINARY_TRUE_DIVIDE,
-            28 => INPLACE_FLOOR_DIVIDE,
-            29 => INPLACE_TRUE_DIVIDE,
-            30 => GET_LEN,
-            31 => MATCH_MAPPING,
-            32 => MATCH_SEQUENCE,
-            33 => MATCH_KEYS,
-            35 => PUSH_EXC_INFO,
-            36 => CHECK_EXC_MATCH,
-            37 => CHECK_EG_MATCH,
-            49 => WITH_EXCEPT_START,
-            50 => GET_AITER,
-            51 => GET_ANEXT,
-            52 => BEFORE_ASYNC_WITH,
-            53 => BEFORE_WITH,
-            54 => END_ASYNC_FOR,
-            55 => INPLACE_ADD,
-            56 => INPLACE_SUBTRACT,
-            57 => INPLACE_MULTIPLY,
-            59 => INPLACE_MODULO,
-            60 => STORE_SUBSCR,
-            64 => BINARY_AND,
-            65 => BINARY_XOR,
-            66 => BINARY_OR,
-            68 => GET_ITER,
-            69 => GET_YIELD_FROM_ITER,
-            70 => PRINT_EXPR,
-            71 => LOAD_BUILD_CLASS,
-            74 => LOAD_ASSERTION_ERROR,
-            82 => LIST_TO_TUPLE,
-            83 => RETURN_VALUE,
-            84 => IMPORT_STAR,
-            85 => SETUP_ANNOTATIONS,
-            86 => YIELD_VALUE,
-            87 => POP_BLOCK,
-            89 => POP_EXCEPT,
+            0 => Some(CACHE),
+            1 => Some(POP_TOP),
+            2 => Some(ROT_TWO),
+            3 => Some(ROT_THREE),
+            4 => Some(DUP_TOP),
+            5 => Some(DUP_TOP2),
+            6 => Some(ROT_FOUR),
+            9 => Some(NOP),
+            10 => Some(UNARY_POSITIVE),
+            11 => Some(UNARY_NEGATIVE),
+            12 => Some(UNARY_NOT),
+            15 => Some(UNARY_INVERT),
+            16 => Some(BINARY_MATRIX_MULTIPLY),
+            17 => Some(INPLACE_MATRIX_MULTIPLY),
+            19 => Some(BINARY_POWER),
+            20 => Some(BINARY_MULTIPLY),
+            22 => Some(BINARY_MODULO),
+            23 => Some(BINARY_ADD),
+            24 => Some(BINARY_SUBTRACT),
+            25 => Some(BINARY_SUBSCR),
+            26 => Some(BINARY_FLOOR_DIVIDE),
+            27 => Some(BINARY_TRUE_DIVIDE),
+            28 => Some(INPLACE_FLOOR_DIVIDE),
+            29 => Some(INPLACE_TRUE_DIVIDE),
+            30 => Some(GET_LEN),
+            31 => Some(MATCH_MAPPING),
+            32 => Some(MATCH_SEQUENCE),
+            33 => Some(MATCH_KEYS),
+            35 => Some(PUSH_EXC_INFO),
+            36 => Some(CHECK_EXC_MATCH),
+            37 => Some(CHECK_EG_MATCH),
+            38 => Some(PUSH_NULL),
+            39 => Some(BEGIN_FINALLY),
+            49 => Some(WITH_EXCEPT_START),
+            50 => Some(GET_AITER),
+            51 => Some(GET_ANEXT),
+            52 => Some(BEFORE_ASYNC_WITH),
+            53 => Some(BEFORE_WITH),
+            54 => Some(END_ASYNC_FOR),
+            55 => Some(INPLACE_ADD),
+            56 => Some(INPLACE_SUBTRACT),
+            57 => Some(INPLACE_MULTIPLY),
+            59 => Some(INPLACE_MODULO),
+            60 => Some(STORE_SUBSCR),
+            61 => Some(DELETE_SUBSCR),
+            62 => Some(BINARY_LSHIFT),
+            63 => Some(BINARY_RSHIFT),
+            64 => Some(BINARY_AND),
+            65 => Some(BINARY_XOR),
+            66 => Some(BINARY_OR),
+            67 => Some(INPLACE_POWER),
+            68 => Some(GET_ITER),
+            69 => Some(GET_YIELD_FROM_ITER),
+            70 => Some(PRINT_EXPR),
+            71 => Some(LOAD_BUILD_CLASS),
+            72 => Some(YIELD_FROM),
+            73 => Some(GET_AWAITABLE),
+            74 => Some(LOAD_ASSERTION_ERROR),
+            75 => Some(INPLACE_LSHIFT),
+            76 => Some(INPLACE_RSHIFT),
+            77 => Some(INPLACE_AND),
+            78 => Some(INPLACE_XOR),
+            79 => Some(INPLACE_OR),
+            80 => Some(WITH_CLEANUP_FINISH),
+            81 => Some(WITH_CLEANUP_START),
+            82 => Some(LIST_TO_TUPLE),
+            83 => Some(RETURN_VALUE),
+            84 => Some(IMPORT_STAR),
+            85 => Some(SETUP_ANNOTATIONS),
+            86 => Some(YIELD_VALUE),
+            87 => Some(POP_BLOCK),
+            88 => Some(END_FINALLY),
+            89 => Some(POP_EXCEPT),
             /* ↓ These opcodes take an arg */
-            90 => STORE_NAME,
-            91 => DELETE_NAME,
-            92 => UNPACK_SEQUENCE,
-            93 => FOR_ITER,
-            94 => UNPACK_EX,
-            95 => STORE_ATTR,
-            97 => STORE_GLOBAL,
-            100 => LOAD_CONST,
-            101 => LOAD_NAME,
-            102 => BUILD_TUPLE,
-            103 => BUILD_LIST,
-            104 => BUILD_SET,
-            105 => BUILD_MAP,
-            106 => LOAD_ATTR,
-            107 => COMPARE_OP,
-            108 => IMPORT_NAME,
-            109 => IMPORT_FROM,
-            110 => JUMP_FORWARD,
-            111 => JUMP_IF_FALSE_OR_POP,
-            112 => JUMP_IF_TRUE_OR_POP,
-            113 => JUMP_ABSOLUTE,
-            114 => POP_JUMP_IF_FALSE,
-            115 => POP_JUMP_IF_TRUE,
-            116 => LOAD_GLOBAL,
-            117 => IS_OP,
-            118 => CONTAINS_OP,
-            119 => RERAISE,
-            124 => LOAD_FAST,
-            125 => STORE_FAST,
-            126 => DELETE_FAST,
-            130 => RAISE_VARARGS,
-            131 => CALL_FUNCTION,
-            132 => MAKE_FUNCTION,
-            135 => LOAD_CLOSURE,
-            136 => LOAD_DEREF,
-            137 => STORE_DEREF,
-            141 => CALL_FUNCTION_KW,
-            142 => CALL_FUNCTION_EX,
-            143 => SETUP_WITH,
-            160 => LOAD_METHOD,
-            161 => CALL_METHOD,
-            162 => LIST_EXTEND,
+            90 => Some(STORE_NAME),
+            91 => Some(DELETE_NAME),
+            92 => Some(UNPACK_SEQUENCE),
+            93 => Some(FOR_ITER),
+            94 => Some(UNPACK_EX),
+            95 => Some(STORE_ATTR),
+            96 => Some(DELETE_ATTR),
+            97 => Some(STORE_GLOBAL),
+            99 => Some(SWAP),
+            100 => Some(LOAD_CONST),
+            101 => Some(LOAD_NAME),
+            102 => Some(BUILD_TUPLE),
+            103 => Some(BUILD_LIST),
+            104 => Some(BUILD_SET),
+            105 => Some(BUILD_MAP),
+            106 => Some(LOAD_ATTR),
+            107 => Some(COMPARE_OP),
+            108 => Some(IMPORT_NAME),
+            109 => Some(IMPORT_FROM),
+            110 => Some(JUMP_FORWARD),
+            111 => Some(JUMP_IF_FALSE_OR_POP),
+            112 => Some(JUMP_IF_TRUE_OR_POP),
+            113 => Some(JUMP_ABSOLUTE),
+            114 => Some(POP_JUMP_IF_FALSE),
+            115 => Some(POP_JUMP_IF_TRUE),
+            116 => Some(LOAD_GLOBAL),
+            117 => Some(IS_OP),
+            118 => Some(CONTAINS_OP),
+            119 => Some(RERAISE),
+            120 => Some(COPY),
+            121 => Some(JUMP_IF_NOT_EXC_MATCH),
+            122 => Some(BINARY_OP),
+            123 => Some(SEND),
+            124 => Some(LOAD_FAST),
+            125 => Some(STORE_FAST),
+            126 => Some(DELETE_FAST),
+            128 => Some(POP_JUMP_FORWARD_IF_NOT_NONE),
+            129 => Some(GEN_START),
+            130 => Some(RAISE_VARARGS),
+            131 => Some(CALL_FUNCTION),
+            132 => Some(MAKE_FUNCTION),
+            133 => Some(BUILD_SLICE),
+            134 => Some(JUMP_BACKWARD_NO_INTERRUPT),
+            135 => Some(LOAD_CLOSURE),
+            136 => Some(LOAD_DEREF),
+            137 => Some(STORE_DEREF),
+            139 => Some(MAKE_CELL),
+            140 => Some(JUMP_BACKWARD),
+            141 => Some(CALL_FUNCTION_KW),
+            142 => Some(CALL_FUNCTION_EX),
+            143 => Some(SETUP_WITH),
+            144 => Some(EXTENDED_ARG),
+            145 => Some(LIST_APPEND),
+            146 => Some(SET_ADD),
+            147 => Some(MAP_ADD),
+            149 => Some(COPY_FREE_VARS),
+            151 => Some(RESUME),
+            152 => Some(MATCH_CLASS),
+            154 => Some(SETUP_ASYNC_WITH),
+            155 => Some(FORMAT_VALUE),
+            156 => Some(BUILD_CONST_KEY_MAP),
+            157 => Some(BUILD_STRING),
+            158 => Some(BUILD_TUPLE_UNPACK_WITH_CALL),
+            160 => Some(LOAD_METHOD),
+            161 => Some(CALL_METHOD),
+            162 => Some(LIST_EXTEND),
+            163 => Some(SET_UPDATE),
+            164 => Some(DICT_MERGE),
+            165 => Some(DICT_UPDATE),
+            166 => Some(PRECALL),
+            171 => Some(CALL),
+            172 => Some(KW_NAMES),
+            173 => Some(POP_JUMP_FORWARD_IF_FALSE),
+            174 => Some(POP_JUMP_FORWARD_IF_TRUE),
+            175 => Some(POP_JUMP_BACKWARD_IF_FALSE),
+            176 => Some(POP_JUMP_BACKWARD_IF_TRUE),
+            177 => Some(SETUP_FINALLY),
+            178 => Some(POP_JUMP_FORWARD_IF_NONE),
+            179 => Some(POP_JUMP_BACKWARD_IF_NOT_NONE),
+            180 => Some(POP_JUMP_BACKWARD_IF_NONE),
+            181 => Some(RETURN_GENERATOR),
+            182 => Some(ASYNC_GEN_WRAP),
             // Erg-specific opcodes
-            196 => ERG_POP_NTH,
-            197 => ERG_PEEK_NTH,
-            198 => ERG_INC,
-            199 => ERG_DEC,
-            200 => ERG_LOAD_FAST_IMMUT,
-            201 => ERG_STORE_FAST_IMMUT,
-            202 => ERG_MOVE_FAST,
-            203 => ERG_CLONE_FAST,
-            204 => ERG_COPY_FAST,
-            205 => ERG_REF_FAST,
-            206 => ERG_REF_MUT_FAST,
-            207 => ERG_MOVE_OUTER,
-            208 => ERG_CLONE_OUTER,
-            209 => ERG_COPY_OUTER,
-            210 => ERG_REF_OUTER,
-            211 => ERG_REF_MUT_OUTER,
-            212 => ERG_LESS_THAN,
-            213 => ERG_LESS_EQUAL,
-            214 => ERG_EQUAL,
-            215 => ERG_NOT_EQUAL,
+            196 => Some(ERG_POP_NTH),
+            197 => Some(ERG_PEEK_NTH),
+            198 => Some(ERG_INC),
+            199 => Some(ERG_DEC),
+            200 => Some(ERG_LOAD_FAST_IMMUT),
+            201 => Some(ERG_STORE_FAST_IMMUT),
+            202 => Some(ERG_MOVE_FAST),
+            203 => Some(ERG_CLONE_FAST),
+            204 => Some(ERG_COPY_FAST),
+            205 => Some(ERG_REF_FAST),
+            206 => Some(ERG_REF_MUT_FAST),
+            207 => Some(ERG_MOVE_OUTER),
+            208 => Some(ERG_CLONE_OUTER),
+            209 => Some(ERG_COPY_OUTER),
+            210 => Some(ERG_REF_OUTER),
+            211 => Some(ERG_REF_MUT_OUTER),
+            212 => Some(ERG_LESS_THAN),
+            213 => Some(ERG_LESS_EQUAL),
+            214 => Some(ERG_EQUAL),
+            215 => Some(ERG_NOT_EQUAL),
             // ERG_GREATER_THAN is not necessary (can be done by inverting the argument of LESS_THAN)
-            216 => ERG_MAKE_SLOT,
-            217 => ERG_MAKE_TYPE,
-            218 => ERG_MAKE_PURE_FUNCTION,
-            219 => ERG_CALL_PURE_FUNCTION,
+            216 => Some(ERG_MAKE_SLOT),
+            217 => Some(ERG_MAKE_TYPE),
+            218 => Some(ERG_MAKE_PURE_FUNCTION),
+            219 => Some(ERG_CALL_PURE_FUNCTION),
             /* ↑ These opcodes take an arg ↑ */
             /* ↓ These opcodes take no arg ↓ */
             // ... = 220,
-            242 => ERG_LOAD_EMPTY_SLOT,
-            243 => ERG_LOAD_EMPTY_STR,
-            244 => ERG_LOAD_1_NAT,
-            245 => ERG_LOAD_1_INT,
-            246 => ERG_LOAD_1_REAL,
-            247 => ERG_LOAD_NONE,
-            248 => ERG_MUTATE,
-            249 => ERG_STORE_SUBSCR,
+            242 => Some(ERG_LOAD_EMPTY_SLOT),
+            243 => Some(ERG_LOAD_EMPTY_STR),
+            244 => Some(ERG_LOAD_1_NAT),
+            245 => Some(ERG_LOAD_1_INT),
+            246 => Some(ERG_LOAD_1_REAL),
+            247 => Some(ERG_LOAD_NONE),
+            248 => Some(ERG_MUTATE),
+            249 => Some(ERG_STORE_SUBSCR),
             // 250 =>
-            251 => ERG_BINARY_SUBSCR,
-            252 => ERG_BINARY_RANGE,
-            253 => ERG_TRY_BINARY_DIVIDE,
-            254 => ERG_BINARY_TRUE_DIVIDE,
-            255 => NOT_IMPLEMENTED,
-            other => panic!("not implemented opcode: {other}"),
+            251 => Some(ERG_BINARY_SUBSCR),
+            252 => Some(ERG_BINARY_RANGE),
+            253 => Some(ERG_TRY_BINARY_DIVIDE),
+            254 => Some(ERG_BINARY_TRUE_DIVIDE),
+            255 => Some(NOT_IMPLEMENTED),
+            _ => None,
         }
     }
-}
 
-impl Opcode {
     pub const fn take_arg(&self) -> bool {
         90 <= (*self as u8) && (*self as u8) < 220
     }
+
+    pub const fn is_erg_specific(&self) -> bool {
+        (*self as u8) >= ERG_POP_NTH as u8
+    }
+
+    /// Whether the opcode exists in `ver`
+    pub const fn is_available(&self, ver: PythonVersion) -> bool {
+        let minor = ver.minor();
+        if self.is_erg_specific() {
+            return false;
+        }
+        match self {
+            ROT_TWO
+            | ROT_THREE
+            | DUP_TOP
+            | DUP_TOP2
+            | BINARY_MATRIX_MULTIPLY
+            | INPLACE_MATRIX_MULTIPLY
+            | BINARY_POWER
+            | BINARY_MULTIPLY
+            | BINARY_MODULO
+            | BINARY_ADD
+            | BINARY_SUBTRACT
+            | BINARY_FLOOR_DIVIDE
+            | BINARY_TRUE_DIVIDE
+            | INPLACE_FLOOR_DIVIDE
+            | INPLACE_TRUE_DIVIDE
+            | INPLACE_ADD
+            | INPLACE_SUBTRACT
+            | INPLACE_MULTIPLY
+            | INPLACE_MODULO
+            | BINARY_AND
+            | BINARY_XOR
+            | BINARY_OR
+            | BINARY_LSHIFT
+            | BINARY_RSHIFT
+            | INPLACE_POWER
+            | INPLACE_LSHIFT
+            | INPLACE_RSHIFT
+            | INPLACE_AND
+            | INPLACE_XOR
+            | INPLACE_OR
+            | YIELD_FROM
+            | POP_BLOCK
+            | JUMP_ABSOLUTE
+            | POP_JUMP_IF_FALSE
+            | POP_JUMP_IF_TRUE
+            | CALL_FUNCTION
+            | CALL_FUNCTION_KW
+            | SETUP_WITH
+            | CALL_METHOD
+            | SETUP_FINALLY
+            | SETUP_ASYNC_WITH => minor <= 10,
+            ROT_FOUR => minor == 8 || minor == 9 || minor == 10,
+            END_ASYNC_FOR => minor >= 8,
+            BEGIN_FINALLY => minor == 8,
+            WITH_CLEANUP_START
+            | WITH_CLEANUP_FINISH
+            | END_FINALLY
+            | BUILD_TUPLE_UNPACK_WITH_CALL => minor <= 8,
+            LOAD_ASSERTION_ERROR | LIST_TO_TUPLE | LIST_EXTEND | IS_OP | CONTAINS_OP | RERAISE
+            | WITH_EXCEPT_START | SET_UPDATE | DICT_MERGE | DICT_UPDATE => minor >= 9,
+            JUMP_IF_NOT_EXC_MATCH => minor == 9 || minor == 10,
+            GEN_START => minor == 10,
+            GET_LEN | MATCH_MAPPING | MATCH_SEQUENCE | MATCH_KEYS | MATCH_CLASS => minor >= 10,
+            CACHE
+            | PUSH_NULL
+            | PUSH_EXC_INFO
+            | CHECK_EXC_MATCH
+            | CHECK_EG_MATCH
+            | BEFORE_WITH
+            | SWAP
+            | COPY
+            | BINARY_OP
+            | MAKE_CELL
+            | JUMP_BACKWARD
+            | COPY_FREE_VARS
+            | RESUME
+            | PRECALL
+            | CALL
+            | KW_NAMES
+            | POP_JUMP_FORWARD_IF_FALSE
+            | POP_JUMP_FORWARD_IF_TRUE
+            | POP_JUMP_BACKWARD_IF_FALSE
+            | POP_JUMP_BACKWARD_IF_TRUE
+            | POP_JUMP_FORWARD_IF_NONE
+            | POP_JUMP_FORWARD_IF_NOT_NONE
+            | POP_JUMP_BACKWARD_IF_NONE
+            | POP_JUMP_BACKWARD_IF_NOT_NONE
+            | JUMP_BACKWARD_NO_INTERRUPT
+            | SEND
+            | RETURN_GENERATOR
+            | ASYNC_GEN_WRAP => minor >= 11,
+            _ => true,
+        }
+    }
+
+    /// The opcode number in `ver` (`None` if the opcode does not exist in `ver`)
+    pub const fn encode(&self, ver: PythonVersion) -> Option<u8> {
+        if !self.is_available(ver) {
+            return None;
+        }
+        if let PythonVersion::V3_11 = ver {
+            return Some(opcode311::encode(*self));
+        }
+        match self {
+            BEGIN_FINALLY => Some(53),
+            WITH_CLEANUP_FINISH => Some(82),
+            RERAISE if ver.minor() == 9 => Some(48),
+            SETUP_FINALLY => Some(122),
+            other => Some(*other as u8),
+        }
+    }
+
+    /// The inverse of `encode`
+    pub const fn decode(byte: u8, ver: PythonVersion) -> Option<Self> {
+        let op = match (ver, byte) {
+            (PythonVersion::V3_11, _) => return opcode311::decode(byte),
+            (PythonVersion::V3_8, 53) => BEGIN_FINALLY,
+            (PythonVersion::V3_7 | PythonVersion::V3_8, 82) => WITH_CLEANUP_FINISH,
+            (PythonVersion::V3_9, 48) => RERAISE,
+            (_, 122) => SETUP_FINALLY,
+            _ => match Self::from_u8(byte) {
+                Some(op) => op,
+                None => return None,
+            },
+        };
+        // e.g. the number of an opcode which is renumbered in `ver`
+        match op.encode(ver) {
+            Some(encoded) if encoded == byte => Some(op),
+            _ => None,
+        }
+    }
+
+    /// Whether the argument has a meaning in `ver` (`HAVE_ARGUMENT` is 90 in all versions)
+    pub const fn take_arg_in(&self, ver: PythonVersion) -> bool {
+        match self.encode(ver) {
+            Some(byte) => byte >= 90,
+            None => false,
+        }
+    }
+
+    /// The net stack effect of the instruction in `ver` (equivalent to `dis.stack_effect`).
+    /// `jump` is whether the jump is taken (or the exception handler is entered, for `SETUP_*`).
+    ///
+    /// `None` if the opcode does not exist in `ver` or is Erg-specific
+    pub const fn stack_effect(&self, arg: u32, jump: bool, ver: PythonVersion) -> Option<i32> {
+        if !self.is_available(ver) {
+            return None;
+        }
+        let minor = ver.minor();
+        let arg = arg as i32;
+        let effect = match self {
+            NOP | EXTENDED_ARG | CACHE | RESUME | MAKE_CELL | COPY_FREE_VARS | RETURN_GENERATOR
+            | ASYNC_GEN_WRAP | KW_NAMES | SWAP | ROT_TWO | ROT_THREE | ROT_FOUR => 0,
+            UNARY_POSITIVE | UNARY_NEGATIVE | UNARY_NOT | UNARY_INVERT | GET_ITER
+            | GET_YIELD_FROM_ITER | GET_AWAITABLE | GET_AITER | LIST_TO_TUPLE | LOAD_ATTR
+            | SETUP_ANNOTATIONS | YIELD_VALUE | POP_BLOCK | DELETE_NAME | DELETE_FAST
+            | CHECK_EXC_MATCH | CHECK_EG_MATCH => 0,
+            JUMP_FORWARD | JUMP_ABSOLUTE | JUMP_BACKWARD | JUMP_BACKWARD_NO_INTERRUPT => 0,
+            DUP_TOP | COPY | PUSH_NULL | PUSH_EXC_INFO | LOAD_CONST | LOAD_NAME | LOAD_FAST
+            | LOAD_CLOSURE | LOAD_DEREF | LOAD_METHOD | LOAD_BUILD_CLASS | LOAD_ASSERTION_ERROR
+            | IMPORT_FROM | GET_ANEXT | BEFORE_ASYNC_WITH | BEFORE_WITH | WITH_EXCEPT_START
+            | GET_LEN | MATCH_MAPPING | MATCH_SEQUENCE => 1,
+            DUP_TOP2 => 2,
+            POP_TOP | PRINT_EXPR | RETURN_VALUE | IMPORT_STAR | YIELD_FROM | STORE_NAME
+            | STORE_FAST | STORE_DEREF | STORE_GLOBAL | DELETE_ATTR | IMPORT_NAME | COMPARE_OP
+            | IS_OP | CONTAINS_OP | SET_ADD | LIST_APPEND | LIST_EXTEND | SET_UPDATE
+            | DICT_MERGE | DICT_UPDATE | GEN_START | BINARY_OP => -1,
+            BINARY_MATRIX_MULTIPLY
+            | INPLACE_MATRIX_MULTIPLY
+            | BINARY_POWER
+            | BINARY_MULTIPLY
+            | BINARY_MODULO
+            | BINARY_ADD
+            | BINARY_SUBTRACT
+            | BINARY_SUBSCR
+            | BINARY_FLOOR_DIVIDE
+            | BINARY_TRUE_DIVIDE
+            | BINARY_LSHIFT
+            | BINARY_RSHIFT
+            | BINARY_AND
+            | BINARY_XOR
+            | BINARY_OR
+            | INPLACE_FLOOR_DIVIDE
+            | INPLACE_TRUE_DIVIDE
+            | INPLACE_ADD
+            | INPLACE_SUBTRACT
+            | INPLACE_MULTIPLY
+            | INPLACE_MODULO
+            | INPLACE_POWER
+            | INPLACE_LSHIFT
+            | INPLACE_RSHIFT
+            | INPLACE_AND
+            | INPLACE_XOR
+            | INPLACE_OR => -1,
+            POP_JUMP_IF_FALSE
+            | POP_JUMP_IF_TRUE
+            | POP_JUMP_FORWARD_IF_FALSE
+            | POP_JUMP_FORWARD_IF_TRUE
+            | POP_JUMP_BACKWARD_IF_FALSE
+            | POP_JUMP_BACKWARD_IF_TRUE
+            | POP_JUMP_FORWARD_IF_NONE
+            | POP_JUMP_FORWARD_IF_NOT_NONE
+            | POP_JUMP_BACKWARD_IF_NONE
+            | POP_JUMP_BACKWARD_IF_NOT_NONE => -1,
+            STORE_ATTR | DELETE_SUBSCR | MAP_ADD | JUMP_IF_NOT_EXC_MATCH => -2,
+            STORE_SUBSCR => -3,
+            // the exception (and the previous one) are popped/pushed as 3 values each until 3.10
+            POP_EXCEPT | RERAISE if minor >= 11 => -1,
+            POP_EXCEPT | RERAISE => -3,
+            END_ASYNC_FOR if minor >= 11 => -2,
+            END_ASYNC_FOR => -7,
+            BEGIN_FINALLY => 6,
+            END_FINALLY => -6,
+            WITH_CLEANUP_START => 2,
+            WITH_CLEANUP_FINISH => -3,
+            SETUP_FINALLY if jump => 6,
+            SETUP_WITH if jump => 6,
+            SETUP_ASYNC_WITH if jump => 5,
+            SETUP_FINALLY | SETUP_ASYNC_WITH => 0,
+            SETUP_WITH => 1,
+            FOR_ITER if jump => -1,
+            FOR_ITER => 1,
+            SEND if jump => -1,
+            SEND => 0,
+            JUMP_IF_FALSE_OR_POP | JUMP_IF_TRUE_OR_POP if jump => 0,
+            JUMP_IF_FALSE_OR_POP | JUMP_IF_TRUE_OR_POP => -1,
+            // the lowest bit of the argument indicates whether `NULL` is pushed
+            LOAD_GLOBAL if minor >= 11 => 1 + (arg & 1),
+            LOAD_GLOBAL => 1,
+            UNPACK_SEQUENCE => arg - 1,
+            UNPACK_EX => (arg & 0xFF) + (arg >> 8),
+            BUILD_TUPLE | BUILD_LIST | BUILD_SET | BUILD_STRING | BUILD_TUPLE_UNPACK_WITH_CALL => {
+                1 - arg
+            }
+            BUILD_MAP => 1 - 2 * arg,
+            BUILD_CONST_KEY_MAP => -arg,
+            BUILD_SLICE if arg == 3 => -2,
+            BUILD_SLICE => -1,
+            RAISE_VARARGS => -arg,
+            CALL_FUNCTION => -arg,
+            CALL_METHOD | CALL_FUNCTION_KW => -arg - 1,
+            PRECALL => -arg,
+            CALL => -1,
+            CALL_FUNCTION_EX if minor >= 11 => -2 - (arg & 1),
+            CALL_FUNCTION_EX => -1 - (arg & 1),
+            // the qualified name is popped until 3.10
+            MAKE_FUNCTION if minor >= 11 => -((arg & 0x0F).count_ones() as i32),
+            MAKE_FUNCTION => -1 - (arg & 0x0F).count_ones() as i32,
+            // with format spec
+            FORMAT_VALUE if arg & 0x04 != 0 => -1,
+            FORMAT_VALUE => 0,
+            MATCH_CLASS if minor >= 11 => -2,
+            MATCH_CLASS => -1,
+            MATCH_KEYS if minor >= 11 => 1,
+            MATCH_KEYS => 2,
+            _ => return None,
+        };
+        Some(effect)
+    }
 }
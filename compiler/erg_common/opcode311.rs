@@ -0,0 +1,107 @@
+//! defines the opcode numbering of Python 3.11.
+//!
+//! Python 3.11のオペコード番号を定義する
+use crate::opcode::Opcode::{self, *};
+
+/// Only the opcodes which exist in 3.11 should be passed
+pub const fn encode(op: Opcode) -> u8 {
+    match op {
+        CACHE => 0,
+        PUSH_NULL => 2,
+        MAKE_CELL => 135,
+        LOAD_CLOSURE => 136,
+        LOAD_DEREF => 137,
+        STORE_DEREF => 138,
+        POP_JUMP_FORWARD_IF_FALSE => 114,
+        POP_JUMP_FORWARD_IF_TRUE => 115,
+        POP_JUMP_FORWARD_IF_NONE => 129,
+        POP_JUMP_BACKWARD_IF_NOT_NONE => 173,
+        POP_JUMP_BACKWARD_IF_NONE => 174,
+        GET_AWAITABLE => 131,
+        RETURN_GENERATOR => 75,
+        ASYNC_GEN_WRAP => 87,
+        // the others have the same number as the abstract opcode
+        other => other as u8,
+    }
+}
+
+pub const fn decode(byte: u8) -> Option<Opcode> {
+    let op = match byte {
+        0 => CACHE,
+        2 => PUSH_NULL,
+        75 => RETURN_GENERATOR,
+        87 => ASYNC_GEN_WRAP,
+        114 => POP_JUMP_FORWARD_IF_FALSE,
+        115 => POP_JUMP_FORWARD_IF_TRUE,
+        129 => POP_JUMP_FORWARD_IF_NONE,
+        131 => GET_AWAITABLE,
+        135 => MAKE_CELL,
+        136 => LOAD_CLOSURE,
+        137 => LOAD_DEREF,
+        138 => STORE_DEREF,
+        173 => POP_JUMP_BACKWARD_IF_NOT_NONE,
+        174 => POP_JUMP_BACKWARD_IF_NONE,
+        _ => match Opcode::from_u8(byte) {
+            Some(op) => op,
+            None => return None,
+        },
+    };
+    // the abstract number of a renumbered opcode (e.g. 139 is `MAKE_CELL`) is not valid in 3.11
+    if op.is_available(crate::python_util::PythonVersion::V3_11) && encode(op) == byte {
+        Some(op)
+    } else {
+        None
+    }
+}
+
+/// The number of `CACHE` entries following the instruction (`_inline_cache_entries` in `opcode.py`)
+pub const fn cache_entries(op: Opcode) -> usize {
+    match op {
+        BINARY_SUBSCR => 4,
+        STORE_SUBSCR => 1,
+        UNPACK_SEQUENCE => 1,
+        STORE_ATTR => 4,
+        LOAD_ATTR => 4,
+        COMPARE_OP => 2,
+        LOAD_GLOBAL => 5,
+        BINARY_OP => 1,
+        LOAD_METHOD => 10,
+        PRECALL => 1,
+        CALL => 4,
+        _ => 0,
+    }
+}
+
+/// The argument of `BINARY_OP` (`NB_*` in `opcode.py`) corresponding to the binary operation opcodes of 3.10
+pub const fn binary_op_arg(op: Opcode) -> Option<u8> {
+    let arg = match op {
+        BINARY_ADD => 0,
+        BINARY_AND => 1,
+        BINARY_FLOOR_DIVIDE => 2,
+        BINARY_LSHIFT => 3,
+        BINARY_MATRIX_MULTIPLY => 4,
+        BINARY_MULTIPLY => 5,
+        BINARY_MODULO => 6,
+        BINARY_OR => 7,
+        BINARY_POWER => 8,
+        BINARY_RSHIFT => 9,
+        BINARY_SUBTRACT => 10,
+        BINARY_TRUE_DIVIDE => 11,
+        BINARY_XOR => 12,
+        INPLACE_ADD => 13,
+        INPLACE_AND => 14,
+        INPLACE_FLOOR_DIVIDE => 15,
+        INPLACE_LSHIFT => 16,
+        INPLACE_MATRIX_MULTIPLY => 17,
+        INPLACE_MULTIPLY => 18,
+        INPLACE_MODULO => 19,
+        INPLACE_OR => 20,
+        INPLACE_POWER => 21,
+        INPLACE_RSHIFT => 22,
+        INPLACE_SUBTRACT => 23,
+        INPLACE_TRUE_DIVIDE => 24,
+        INPLACE_XOR => 25,
+        _ => return None,
+    };
+    Some(arg)
+}
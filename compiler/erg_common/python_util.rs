@@ -1,11 +1,14 @@
 //! utilities for calling CPython.
 //!
 //! CPythonを呼び出すためのユーティリティー
+use std::cell::Cell;
+use std::fmt;
 use std::process::Command;
 
 use crate::serialize::get_magic_num_from_bytes;
 
-pub const BUILTIN_PYTHON_MODS: [&str; 10] = [
+pub const BUILTIN_PYTHON_MODS: [&str; 11] = [
+    "functools",
     "glob",
     "importlib",
     "io",
@@ -18,6 +21,73 @@ pub const BUILTIN_PYTHON_MODS: [&str; 10] = [
     "urllib",
 ];
 
+/// Python versions whose bytecode can be generated.
+/// Each version is identified by the magic number of `.pyc` files.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum PythonVersion {
+    V3_7,
+    V3_8,
+    V3_9,
+    V3_10,
+    V3_11,
+}
+
+impl fmt::Display for PythonVersion {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "3.{}", self.minor())
+    }
+}
+
+impl PythonVersion {
+    pub const ALL: [Self; 5] = [Self::V3_7, Self::V3_8, Self::V3_9, Self::V3_10, Self::V3_11];
+
+    /// Magic numbers of alpha/beta releases are also accepted.
+    pub const fn from_magic_num(magic_num: u32) -> Option<Self> {
+        match magic_num {
+            3390..=3394 => Some(Self::V3_7),
+            3400..=3413 => Some(Self::V3_8),
+            3420..=3425 => Some(Self::V3_9),
+            3430..=3439 => Some(Self::V3_10),
+            3450..=3495 => Some(Self::V3_11),
+            _ => None,
+        }
+    }
+
+    /// Same as `from_magic_num`, but panics if the version is not supported.
+    /// The version must have been checked (e.g. by `codegen::check_python_version` or `Deserializer`),
+    /// since rounding it to another version produces bytecode that crashes CPython.
+    pub fn from_checked_magic_num(magic_num: u32) -> Self {
+        Self::from_magic_num(magic_num)
+            .unwrap_or_else(|| panic!("unsupported Python version: {magic_num}"))
+    }
+
+    /// The magic number of the final release
+    pub const fn magic_num(&self) -> u32 {
+        match self {
+            Self::V3_7 => 3394,
+            Self::V3_8 => 3413,
+            Self::V3_9 => 3425,
+            Self::V3_10 => 3439,
+            Self::V3_11 => 3495,
+        }
+    }
+
+    pub const fn minor(&self) -> u32 {
+        match self {
+            Self::V3_7 => 7,
+            Self::V3_8 => 8,
+            Self::V3_9 => 9,
+            Self::V3_10 => 10,
+            Self::V3_11 => 11,
+        }
+    }
+
+    /// e.g. `python3.11`
+    pub fn command(&self) -> String {
+        format!("python{self}")
+    }
+}
+
 pub fn which_python() -> String {
     let (cmd, python) = if cfg!(windows) {
         ("where", "python")
@@ -40,7 +110,15 @@ pub fn which_python() -> String {
     res
 }
 
+thread_local! {
+    static MAGIC_NUMBER: Cell<Option<u32>> = const { Cell::new(None) };
+}
+
+/// The result is memoized, since this spawns a Python process
 pub fn detect_magic_number() -> u32 {
+    if let Some(magic_num) = MAGIC_NUMBER.with(|m| m.get()) {
+        return magic_num;
+    }
     let out = if cfg!(windows) {
         Command::new("cmd")
             .arg("/C")
@@ -63,7 +141,9 @@ pub fn detect_magic_number() -> u32 {
     let s_hex_magic_num = String::from_utf8(out.stdout).unwrap();
     let first_byte = u8::from_str_radix(&s_hex_magic_num[0..=1], 16).unwrap();
     let second_byte = u8::from_str_radix(&s_hex_magic_num[2..=3], 16).unwrap();
-    get_magic_num_from_bytes(&[first_byte, second_byte, 0, 0])
+    let magic_num = get_magic_num_from_bytes(&[first_byte, second_byte, 0, 0]);
+    MAGIC_NUMBER.with(|m| m.set(Some(magic_num)));
+    magic_num
 }
 
 /// executes over a shell, cause `python` may not exist as an executable file (like pyenv)
@@ -72,7 +152,7 @@ pub fn exec_pyc<S: Into<String>>(file: S) -> Option<i32> {
         Command::new("cmd")
             .arg("/C")
             .arg(which_python())
-            .arg(&file.into())
+            .arg(file.into())
             .spawn()
             .expect("cannot execute python")
     } else {
@@ -92,7 +172,7 @@ pub fn eval_pyc<S: Into<String>>(file: S) -> String {
         Command::new("cmd")
             .arg("/C")
             .arg(which_python())
-            .arg(&file.into())
+            .arg(file.into())
             .spawn()
             .expect("cannot execute python")
     } else {
@@ -125,6 +205,30 @@ pub fn exec_py(code: &str) -> Option<i32> {
     child.wait().expect("python doesn't work").code()
 }
 
+/// executes `code` with `args` as `sys.argv[1:]`
+pub fn exec_py_with_args(code: &str, args: &[&str]) -> Option<i32> {
+    let mut child = if cfg!(windows) {
+        Command::new(which_python())
+            .arg("-c")
+            .arg(code)
+            .args(args)
+            .spawn()
+            .expect("cannot execute python")
+    } else {
+        // the arguments are passed as the positional parameters of the shell, so they are not interpreted
+        let python_command = format!("{} -c \"{}\" \"$@\"", which_python(), code);
+        Command::new("sh")
+            .arg("-c")
+            .arg(python_command)
+            .arg("sh")
+            .args(args)
+            .spawn()
+            .expect("cannot execute python")
+    };
+    child.wait().expect("python doesn't work").code()
+}
+
+#[allow(clippy::zombie_processes)]
 pub fn spawn_py(code: &str) {
     if cfg!(windows) {
         Command::new(which_python())
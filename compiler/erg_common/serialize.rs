@@ -32,6 +32,9 @@ pub fn get_timestamp_bytes() -> [u8; 4] {
     secs.to_le_bytes()
 }
 
+/// If the prefix has this bit, the object is stored in the reference table and can be referred by `Ref`
+pub const FLAG_REF: u8 = 0x80;
+
 #[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
 #[repr(u8)]
 pub enum DataTypePrefix {
@@ -47,16 +50,24 @@ pub enum DataTypePrefix {
     False = b'F',      // 0x46
     None = b'N',       // 0x4E
     StopIter = b'S',   // 0x53
-    Ref = b'r',
+    Ellipsis = b'.',   // 0x2E
+    Null = b'0',       // 0x30 (the terminator of `Dict`)
+    Ref = b'r',        // 0x72 + index: u32
     /* unsized objects (ref counted) */
     Long = b'l',       // 0x6C + len:  u32 + payload: 2*len+3byte (~ -2^31-1 && 2^31 ~)
-    Str = b's',        // 0x73 + len:  u32 + payload
+    Str = b's',        // 0x73 + len:  u32 + payload (bytes)
     ShortAscii = b'z', // 0x7A + len:  u8 + payload
     ShortAsciiInterned = b'Z', //  0x5A + len:  u8 + payload
+    AsciiInterned = b'A', //  0x41 + len:  u32 + payload
+    Ascii = b'a',      // 0x61 + len:  u32 + payload
     Unicode = b'u',    // 0x75 + len:  u32 + payload
     Interned = b't',   // 0x74 + len + payload
     SmallTuple = b')', // 0x29 + len:  u8 + payload
     Tuple = b'(',      // 0x28 + len:  u32 + payload
+    List = b'[',       // 0x5B + len:  u32 + payload
+    Dict = b'{',       // 0x7B + (key + value)* + Null
+    Set = b'<',        // 0x3C + len:  u32 + payload
+    FrozenSet = b'>',  // 0x3E + len:  u32 + payload
     Code = b'c',       // 0x63
     /* Erg specific prefix */
     Builtin = b'b', // 0x62 + str
@@ -66,9 +77,10 @@ pub enum DataTypePrefix {
 impl_display_from_debug!(DataTypePrefix);
 
 impl From<u8> for DataTypePrefix {
+    /// `FLAG_REF` is ignored
     fn from(item: u8) -> Self {
-        match item as char {
-            'i' | '\u{00E9}' => Self::Int32,
+        match (item & !FLAG_REF) as char {
+            'i' => Self::Int32,
             'I' => Self::Int64,
             'l' => Self::Long,
             'f' => Self::Float,
@@ -79,14 +91,23 @@ impl From<u8> for DataTypePrefix {
             'F' => Self::False,
             'N' => Self::None,
             'S' => Self::StopIter,
-            's' | '\u{00F3}' => Self::Str,
-            'Z' | '\u{00DA}' => Self::ShortAsciiInterned,
-            'z' | '\u{00FA}' => Self::ShortAscii,
+            '.' => Self::Ellipsis,
+            '0' => Self::Null,
+            'r' => Self::Ref,
+            's' => Self::Str,
+            'a' => Self::Ascii,
+            'A' => Self::AsciiInterned,
+            'Z' => Self::ShortAsciiInterned,
+            'z' => Self::ShortAscii,
             'u' => Self::Unicode,
             't' => Self::Interned,
-            '(' | '\u{00A8}' => Self::Tuple,
-            ')' | '\u{00A9}' => Self::SmallTuple,
-            'c' | '\u{00E3}' => Self::Code,
+            '(' => Self::Tuple,
+            ')' => Self::SmallTuple,
+            '[' => Self::List,
+            '{' => Self::Dict,
+            '<' => Self::Set,
+            '>' => Self::FrozenSet,
+            'c' => Self::Code,
             'b' => Self::Builtin,
             'n' => Self::Nat,
             /*'\u{00F9}' => DataTypeUnaryOp::ErgInt8,
@@ -107,12 +128,18 @@ impl DataTypePrefix {
             self,
             Self::Long
                 | Self::Str
+                | Self::Ascii
+                | Self::AsciiInterned
                 | Self::ShortAscii
                 | Self::ShortAsciiInterned
                 | Self::Unicode
                 | Self::Interned
                 | Self::SmallTuple
                 | Self::Tuple
+                | Self::List
+                | Self::Dict
+                | Self::Set
+                | Self::FrozenSet
                 | Self::Code
                 | Self::Builtin
         )
@@ -136,13 +163,21 @@ pub fn strs_into_bytes(names: Vec<Str>) -> Vec<u8> {
 
 pub fn str_into_bytes(cont: Str, is_interned: bool) -> Vec<u8> {
     let mut bytes = vec![];
-    if cont.is_ascii() {
+    if cont.is_ascii() && cont.len() <= u8::MAX as usize {
         if is_interned {
             bytes.push(DataTypePrefix::ShortAsciiInterned as u8);
         } else {
             bytes.push(DataTypePrefix::ShortAscii as u8);
         }
         bytes.push(cont.len() as u8);
+    } else if cont.is_ascii() {
+        // the length does not fit in `ShortAscii`
+        if is_interned {
+            bytes.push(DataTypePrefix::AsciiInterned as u8);
+        } else {
+            bytes.push(DataTypePrefix::Ascii as u8);
+        }
+        bytes.append(&mut (cont.len() as u32).to_le_bytes().to_vec());
     } else {
         bytes.push(DataTypePrefix::Unicode as u8);
         bytes.append(&mut (cont.len() as u32).to_le_bytes().to_vec());
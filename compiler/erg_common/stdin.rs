@@ -28,7 +28,7 @@ impl StdinReader {
 }
 
 thread_local! {
-    static READER: RefCell<StdinReader> = RefCell::new(StdinReader{ lineno: 0, buf: vec![] });
+    static READER: RefCell<StdinReader> = const { RefCell::new(StdinReader{ lineno: 0, buf: vec![] }) };
 }
 
 #[derive(Debug)]
@@ -0,0 +1,80 @@
+use erg_common::json::{escape, JsonValue};
+
+fn parse_str(src: &str) -> String {
+    JsonValue::parse(src).unwrap().as_str().unwrap().to_string()
+}
+
+fn error_desc(src: &str) -> &'static str {
+    JsonValue::parse(src).unwrap_err().desc
+}
+
+#[test]
+fn json_escapes() {
+    assert_eq!(
+        parse_str(r#""a\"b\\c\/d\n\t\r\b\f""#),
+        "a\"b\\c/d\n\t\r\u{8}\u{c}"
+    );
+    assert_eq!(escape("a\"b\\c\n\t\r"), r#""a\"b\\c\n\t\r""#);
+    assert_eq!(escape("\u{1}\u{1f}"), r#""\u0001\u001f""#);
+    for s in ["", "abc", "\"\\\u{0}\u{7f}", "日本語 😀", "line\nbreak"] {
+        assert_eq!(parse_str(&escape(s)), s);
+    }
+}
+
+#[test]
+fn json_unicode_escapes() {
+    assert_eq!(parse_str(r#""\u00e9\u3042""#), "éあ");
+    assert_eq!(parse_str(r#""\uD83D\uDE00""#), "😀");
+    assert_eq!(parse_str(r#""\ud83d\ude00!""#), "😀!");
+    // a lone low surrogate is not a char
+    assert_eq!(parse_str(r#""\uDE00""#), "\u{FFFD}");
+    assert_eq!(error_desc(r#""\uD83D\u0041""#), "invalid surrogate pair");
+    assert_eq!(error_desc(r#""\uD83Dx""#), "unexpected character");
+    assert_eq!(error_desc(r#""\u12G4""#), "invalid unicode escape");
+}
+
+#[test]
+fn json_numbers() {
+    let num = |src: &str| JsonValue::parse(src).unwrap().as_f64().unwrap();
+    assert_eq!(num("0"), 0.0);
+    assert_eq!(num("-12"), -12.0);
+    assert_eq!(num("3.25"), 3.25);
+    assert_eq!(num("1e3"), 1000.0);
+    assert_eq!(num("-2.5E-2"), -0.025);
+    assert_eq!(JsonValue::parse("42").unwrap().as_u64(), Some(42));
+    assert_eq!(JsonValue::parse("-1").unwrap().as_u64(), None);
+    assert_eq!(JsonValue::parse("1.5").unwrap().as_u64(), None);
+    // integral numbers are printed without the fraction part
+    assert_eq!(JsonValue::from(3u32).to_string(), "3");
+    assert_eq!(JsonValue::from(-7i64).to_string(), "-7");
+    assert_eq!(JsonValue::from(0.5).to_string(), "0.5");
+    assert_eq!(JsonValue::from(f64::NAN).to_string(), "null");
+}
+
+#[test]
+fn json_structures() {
+    let src = r#" {"b": [1, true, null], "a": {"c": "d"}} "#;
+    let val = JsonValue::parse(src).unwrap();
+    assert_eq!(val.path(&["a", "c"]).and_then(JsonValue::as_str), Some("d"));
+    assert_eq!(
+        val.get("b").and_then(JsonValue::as_array).map(Vec::len),
+        Some(3)
+    );
+    // the order of the keys is kept
+    assert_eq!(val.to_string(), r#"{"b":[1,true,null],"a":{"c":"d"}}"#);
+    assert_eq!(JsonValue::parse(&val.to_string()).unwrap(), val);
+}
+
+#[test]
+fn json_errors() {
+    assert_eq!(error_desc(""), "unexpected end of input");
+    assert_eq!(error_desc("[1,"), "unexpected end of input");
+    assert_eq!(error_desc(r#"{"a" 1}"#), "unexpected character");
+    assert_eq!(error_desc("tru"), "unexpected character");
+    assert_eq!(error_desc(r#""abc"#), "unterminated string");
+    assert_eq!(error_desc(r#""\x""#), "invalid escape sequence");
+    assert_eq!(error_desc("1e"), "invalid number");
+    assert_eq!(error_desc("--1"), "invalid number");
+    assert_eq!(error_desc("[1] x"), "trailing characters");
+    assert_eq!(JsonValue::parse("[1] x").unwrap_err().pos, 4);
+}
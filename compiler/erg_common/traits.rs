@@ -320,7 +320,7 @@ pub trait LimitedDisplay {
 // for Runnable::run
 fn expect_block(src: &str) -> bool {
     let src = src.trim_end();
-    src.ends_with(&['.', '=', ':'])
+    src.ends_with(['.', '=', ':'])
         || src.ends_with("->")
         || src.ends_with("=>")
         // when `"""` are on the same line
@@ -331,7 +331,7 @@ fn expect_block(src: &str) -> bool {
 // In the REPL, it is invalid for these symbols to be at the beginning of a line
 fn expect_invalid_block(src: &str) -> bool {
     let src = src.trim_start();
-    src.starts_with(&['.', '=', ':']) || src.starts_with("->") || src.starts_with("=>")
+    src.starts_with(['.', '=', ':']) || src.starts_with("->") || src.starts_with("=>")
 }
 
 fn is_in_the_expected_block(src: &str, lines: &str, in_block: &mut bool) -> bool {
@@ -455,7 +455,7 @@ pub trait Runnable: Sized {
                                 log!(info_f output, "The REPL has finished successfully.\n");
                                 process::exit(0);
                             }
-                            errs.fmt_all_stderr();
+                            errs.write_all_stderr(instance.cfg().output_format);
                         }
                     }
                     output.write_all(instance.ps1().as_bytes()).unwrap();
@@ -466,7 +466,7 @@ pub trait Runnable: Sized {
             Input::Dummy => switch_unreachable!(),
         };
         if let Err(e) = res {
-            e.fmt_all_stderr();
+            e.write_all_stderr(instance.cfg().output_format);
             std::process::exit(1);
         }
     }
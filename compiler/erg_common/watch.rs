@@ -0,0 +1,64 @@
+//! provides a polling file watcher (used by `--watch`).
+//!
+//! ファイルの変更をポーリングで検知する
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::thread;
+use std::time::{Duration, SystemTime};
+
+use crate::dict::Dict;
+
+#[derive(Debug, Default)]
+pub struct FileWatcher {
+    /// `None` if the file does not exist
+    mtimes: Dict<PathBuf, Option<SystemTime>>,
+    interval: Duration,
+}
+
+impl FileWatcher {
+    pub fn new(interval: Duration) -> Self {
+        Self {
+            mtimes: Dict::new(),
+            interval,
+        }
+    }
+
+    fn mtime(path: &Path) -> Option<SystemTime> {
+        fs::metadata(path).and_then(|meta| meta.modified()).ok()
+    }
+
+    /// Replaces the watched files with `paths`, recording their current modification times
+    pub fn watch<I: IntoIterator<Item = PathBuf>>(&mut self, paths: I) {
+        self.mtimes = paths
+            .into_iter()
+            .map(|path| {
+                let mtime = Self::mtime(&path);
+                (path, mtime)
+            })
+            .collect();
+    }
+
+    /// The files modified (or created/removed) since the last `watch`/`changed` call
+    pub fn changed(&mut self) -> Vec<PathBuf> {
+        let mut changed = vec![];
+        for (path, mtime) in self.mtimes.iter_mut() {
+            let new = Self::mtime(path);
+            if *mtime != new {
+                *mtime = new;
+                changed.push(path.clone());
+            }
+        }
+        changed
+    }
+
+    /// Blocks until some of the watched files are modified
+    pub fn wait(&mut self) -> Vec<PathBuf> {
+        loop {
+            let changed = self.changed();
+            if !changed.is_empty() {
+                return changed;
+            }
+            thread::sleep(self.interval);
+        }
+    }
+}
@@ -1,6 +1,10 @@
-use erg_common::config::ErgConfig;
+use std::path::PathBuf;
+use std::time::Duration;
+
+use erg_common::config::{ErgConfig, Input};
 use erg_common::error::MultiErrorDisplay;
-use erg_common::traits::Runnable;
+use erg_common::traits::{Runnable, Stream};
+use erg_common::watch::FileWatcher;
 use erg_common::Str;
 
 use erg_parser::ast::AST;
@@ -8,7 +12,7 @@ use erg_parser::build_ast::ASTBuilder;
 
 use crate::context::Context;
 use crate::effectcheck::SideEffectChecker;
-use crate::error::{CompileError, CompileErrors};
+use crate::error::{CompileError, CompileErrors, CompileWarnings};
 use crate::hir::HIR;
 use crate::lower::ASTLowerer;
 use crate::mod_cache::SharedModuleCache;
@@ -75,10 +79,20 @@ impl HIRBuilder {
     }
 
     pub fn check(&mut self, ast: AST, mode: &str) -> Result<HIR, (Option<HIR>, CompileErrors)> {
-        let (hir, warns) = self.lowerer.lower(ast, mode)?;
+        let (hir, warns) = self.check_with_warns(ast, mode)?;
         if self.cfg().verbose >= 2 {
-            warns.fmt_all_stderr();
+            warns.write_all_stderr(self.cfg().output_format);
         }
+        Ok(hir)
+    }
+
+    /// Same as `check`, but the warnings are returned instead of being printed
+    pub fn check_with_warns(
+        &mut self,
+        ast: AST,
+        mode: &str,
+    ) -> Result<(HIR, CompileWarnings), (Option<HIR>, CompileErrors)> {
+        let (hir, warns) = self.lowerer.lower(ast, mode)?;
         let effect_checker = SideEffectChecker::new(self.cfg().clone());
         let hir = effect_checker
             .check(hir)
@@ -87,7 +101,7 @@ impl HIRBuilder {
             .ownership_checker
             .check(hir)
             .map_err(|(hir, errs)| (Some(hir), errs))?;
-        Ok(hir)
+        Ok((hir, warns))
     }
 
     pub fn build(&mut self, src: String, mode: &str) -> Result<HIR, (Option<HIR>, CompileErrors)> {
@@ -99,7 +113,73 @@ impl HIRBuilder {
         Ok(hir)
     }
 
+    pub fn build_with_warns(
+        &mut self,
+        src: String,
+        mode: &str,
+    ) -> Result<(HIR, CompileWarnings), (Option<HIR>, CompileErrors)> {
+        let mut ast_builder = ASTBuilder::new(self.cfg().copy());
+        let ast = ast_builder
+            .build(src)
+            .map_err(|errs| (None, CompileErrors::from(errs)))?;
+        self.check_with_warns(ast, mode)
+    }
+
     pub fn pop_mod_ctx(&mut self) -> Context {
         self.lowerer.ctx.pop_mod()
     }
+
+    /// The input and the Erg modules it imports (transitively)
+    pub fn watched_files(&self, mod_cache: &SharedModuleCache) -> Vec<PathBuf> {
+        let mut files = vec![];
+        let mut stack = match self.input() {
+            Input::File(path) => vec![path.canonicalize().unwrap_or_else(|_| path.clone())],
+            _ => vec![],
+        };
+        while let Some(path) = stack.pop() {
+            if files.contains(&path) {
+                continue;
+            }
+            stack.extend(mod_cache.dependencies(&path));
+            files.push(path);
+        }
+        files
+    }
+
+    /// Checks the input every time it or its imports are modified (`--watch`).
+    /// Only the entries of the modified modules (and the modules which import them) are
+    /// removed from the module cache, the other modules are not checked again.
+    pub fn watch(&mut self) {
+        if !matches!(self.input(), Input::File(_)) {
+            eprintln!("--watch is only available for files");
+            return;
+        }
+        let mut watcher = FileWatcher::new(Duration::from_millis(300));
+        loop {
+            self.lowerer.reset_mod_ctx();
+            match self.build_with_warns(self.input().read(), "exec") {
+                Ok((_, warns)) => {
+                    warns.write_all_stderr(self.cfg().output_format);
+                    println!("{}: no errors found", self.input().filename());
+                }
+                Err((_, errs)) => {
+                    errs.write_all_stderr(self.cfg().output_format);
+                    println!("{}: {} errors found", self.input().filename(), errs.len());
+                }
+            }
+            self.wait_for_changes(&mut watcher);
+        }
+    }
+
+    /// Blocks until the files checked last time are modified.
+    /// Returns the modules removed from the module cache (the modified ones and their importers).
+    pub fn wait_for_changes(&self, watcher: &mut FileWatcher) -> Vec<PathBuf> {
+        let mod_cache = self.lowerer.ctx.mod_cache.as_ref().unwrap();
+        watcher.watch(self.watched_files(mod_cache));
+        let mut invalidated = vec![];
+        for path in watcher.wait() {
+            invalidated.extend(mod_cache.invalidate(&path));
+        }
+        invalidated
+    }
 }
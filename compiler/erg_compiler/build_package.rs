@@ -0,0 +1,248 @@
+//! defines `PackageBuilder` (the build mode).
+//!
+//! パッケージ内の各モジュールを個別の.pycファイルにコンパイルする
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use erg_common::config::{ErgConfig, Input};
+use erg_common::error::{Location, MultiErrorDisplay};
+use erg_common::traits::{Runnable, Stream};
+use erg_common::tsort::{tsort, Graph, Node};
+use erg_common::{log, Str};
+
+use crate::build_hir::HIRBuilder;
+use crate::codegen::{check_python_version, CodeGenerator};
+use crate::desugar_hir::HIRDesugarer;
+use crate::disk_cache::CACHE_DIR;
+use crate::error::{CompileError, CompileErrors};
+use crate::link::Linker;
+use crate::mod_cache::SharedModuleCache;
+use crate::optimize::HIROptimizer;
+use crate::{source_map_path, Compiler};
+
+/// Compiles every Erg module under the root directory (`cfg.input`) to its own `.pyc` file.
+/// The output directory (`--out-dir`, default: `<root>/build`) has the same layout as the root,
+/// so it can be imported by CPython as it is.
+///
+/// Erg modules are not inlined (unlike `Compiler`), imports between them are compiled into `__import__`.
+#[derive(Debug)]
+pub struct PackageBuilder {
+    cfg: ErgConfig,
+    mod_cache: SharedModuleCache,
+    py_mod_cache: SharedModuleCache,
+}
+
+impl Runnable for PackageBuilder {
+    type Err = CompileError;
+    type Errs = CompileErrors;
+    const NAME: &'static str = "Erg package builder";
+
+    fn new(cfg: ErgConfig) -> Self {
+        Self {
+            cfg,
+            mod_cache: SharedModuleCache::new(),
+            py_mod_cache: SharedModuleCache::new(),
+        }
+    }
+
+    #[inline]
+    fn cfg(&self) -> &ErgConfig {
+        &self.cfg
+    }
+
+    #[inline]
+    fn finish(&mut self) {}
+
+    fn clear(&mut self) {
+        self.mod_cache = SharedModuleCache::new();
+        self.py_mod_cache = SharedModuleCache::new();
+    }
+
+    fn exec(&mut self) -> Result<i32, Self::Errs> {
+        self.build()?;
+        Ok(0)
+    }
+
+    /// There is no package in the REPL, so this is the same as `Compiler::eval`
+    fn eval(&mut self, src: String) -> Result<String, CompileErrors> {
+        Compiler::new(self.cfg.copy()).eval(src)
+    }
+}
+
+impl PackageBuilder {
+    /// Returns the paths of the generated `.pyc` files (in dependency order)
+    pub fn build(&mut self) -> Result<Vec<PathBuf>, CompileErrors> {
+        log!(info "the package building process has started.");
+        let root = match self.input() {
+            Input::File(root) => root.clone(),
+            _ => PathBuf::from("."),
+        };
+        let root = root.canonicalize().map_err(|e| self.io_error(&root, e))?;
+        let out_dir = self
+            .cfg
+            .output_dir
+            .clone()
+            .unwrap_or_else(|| root.join("build"));
+        fs::create_dir_all(&out_dir).map_err(|e| self.io_error(&out_dir, e))?;
+        let out_dir = out_dir
+            .canonicalize()
+            .map_err(|e| self.io_error(&out_dir, e))?;
+        let mut modules = vec![];
+        let mut py_files = vec![];
+        self.collect(&root, &out_dir, &mut modules, &mut py_files)?;
+        for py_file in py_files.iter() {
+            let dest = out_dir.join(py_file.strip_prefix(&root).unwrap());
+            Self::create_parent(&dest)
+                .and_then(|_| fs::copy(py_file, &dest))
+                .map_err(|e| self.io_error(&dest, e))?;
+        }
+        self.check_modules(&root, &modules)?;
+        let python_ver = check_python_version(&self.cfg).map_err(CompileErrors::from)?;
+        let mut pyc_paths = vec![];
+        for path in self.dependency_order(&modules)? {
+            let hir = self
+                .mod_cache
+                .get_mut(&path)
+                .and_then(|entry| entry.hir.take())
+                .unwrap();
+            let cfg = ErgConfig {
+                python_ver: Some(python_ver),
+                ..self.cfg.inherit(path.clone())
+            };
+            let hir = Linker::new_package(&cfg, &self.mod_cache, &root).link(hir);
+            let (hir, warns) = HIROptimizer::new(cfg.copy()).optimize(hir);
+            if self.cfg.verbose >= 2 {
+                warns.write_all_stderr(self.cfg.output_format);
+            }
+            let hir = HIRDesugarer::desugar(hir);
+            let code = CodeGenerator::new(cfg).emit(hir);
+            let pyc_path = out_dir
+                .join(path.strip_prefix(&root).unwrap())
+                .with_extension("pyc");
+            Self::create_parent(&pyc_path)
+                .and_then(|_| code.dump_source_map(source_map_path(&pyc_path), python_ver))
+                .and_then(|_| code.dump_as_pyc(&pyc_path, Some(python_ver)))
+                .map_err(|e| self.io_error(&pyc_path, e))?;
+            pyc_paths.push(pyc_path);
+        }
+        log!(info "the package building process has completed");
+        Ok(pyc_paths)
+    }
+
+    /// Collects `.er` (except `.d.er`) and `.py` files under `dir` recursively.
+    /// Hidden directories, caches and the output directory are skipped.
+    fn collect(
+        &self,
+        dir: &Path,
+        out_dir: &Path,
+        modules: &mut Vec<PathBuf>,
+        py_files: &mut Vec<PathBuf>,
+    ) -> Result<(), CompileErrors> {
+        let entries = fs::read_dir(dir).map_err(|e| self.io_error(dir, e))?;
+        let mut paths = entries
+            .map(|entry| entry.map(|entry| entry.path()))
+            .collect::<io::Result<Vec<_>>>()
+            .map_err(|e| self.io_error(dir, e))?;
+        paths.sort();
+        for path in paths {
+            let name = path.file_name().unwrap().to_string_lossy();
+            if path.is_dir() {
+                if name.starts_with('.')
+                    || name == CACHE_DIR
+                    || name == "__pycache__"
+                    || path == out_dir
+                {
+                    continue;
+                }
+                self.collect(&path, out_dir, modules, py_files)?;
+            } else if name.ends_with(".er") && !name.ends_with(".d.er") {
+                modules.push(path);
+            } else if name.ends_with(".py") {
+                py_files.push(path);
+            }
+        }
+        Ok(())
+    }
+
+    /// Type-checks all modules. Modules which have already been imported by other modules are not checked twice.
+    fn check_modules(&self, root: &Path, modules: &[PathBuf]) -> Result<(), CompileErrors> {
+        let mut errs = CompileErrors::empty();
+        for path in modules.iter() {
+            if self.mod_cache.get(path).is_some() {
+                continue;
+            }
+            let cfg = ErgConfig {
+                use_cache: false,
+                ..self.cfg.inherit(path.clone())
+            };
+            let src = cfg.input.read();
+            let mut builder = HIRBuilder::new_with_cache(
+                cfg,
+                Self::module_name(root, path),
+                self.mod_cache.clone(),
+                self.py_mod_cache.clone(),
+            );
+            match builder.build(src, "exec") {
+                Ok(hir) => {
+                    let ctx = builder.pop_mod_ctx();
+                    self.mod_cache.register(path.clone(), Some(hir), ctx);
+                }
+                Err((_, es)) => errs.extend(es.into_iter()),
+            }
+        }
+        if errs.is_empty() {
+            Ok(())
+        } else {
+            Err(errs)
+        }
+    }
+
+    /// Sorts the modules so that every module comes after the modules it imports
+    fn dependency_order(&self, modules: &[PathBuf]) -> Result<Vec<PathBuf>, CompileErrors> {
+        let graph: Graph<PathBuf, PathBuf> = modules
+            .iter()
+            .map(|path| {
+                let deps = self
+                    .mod_cache
+                    .dependencies(path)
+                    .into_iter()
+                    .filter(|dep| modules.contains(dep))
+                    .collect();
+                Node::new(path.clone(), path.clone(), deps)
+            })
+            .collect();
+        let sorted = tsort(graph).map_err(|_| {
+            CompileErrors::from(CompileError::cyclic_import_error(
+                self.input().clone(),
+                line!() as usize,
+                "".into(),
+            ))
+        })?;
+        Ok(sorted.into_iter().map(|node| node.data).collect())
+    }
+
+    /// `<root>/a/b.er` -> `a.b`
+    fn module_name(root: &Path, path: &Path) -> Str {
+        let rel = path.strip_prefix(root).unwrap().with_extension("");
+        Str::rc(&rel.to_string_lossy().replace(['/', '\\'], "."))
+    }
+
+    fn create_parent(path: &Path) -> io::Result<()> {
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        Ok(())
+    }
+
+    fn io_error(&self, path: &Path, err: io::Error) -> CompileErrors {
+        CompileErrors::from(CompileError::file_error(
+            self.input().clone(),
+            line!() as usize,
+            format!("{}: {err}", path.display()),
+            Location::Unknown,
+            "".into(),
+            None,
+        ))
+    }
+}
@@ -4,13 +4,14 @@
 use std::fmt;
 use std::process;
 
-use crate::ty::codeobj::{CodeObj, CodeObjFlags};
+use crate::ty::codeobj::{CodeObj, CodeObjFlags, CodeSpan, ExceptionTableEntry};
 use erg_common::astr::AtomicStr;
 use erg_common::cache::CacheSet;
 use erg_common::config::{ErgConfig, Input};
 use erg_common::env::erg_std_path;
 use erg_common::error::{ErrorDisplay, Location};
 use erg_common::opcode::Opcode;
+use erg_common::python_util::{detect_magic_number, PythonVersion};
 use erg_common::traits::{Locational, Stream};
 use erg_common::Str;
 use erg_common::{
@@ -21,7 +22,7 @@ use erg_parser::ast::DefId;
 use erg_parser::ast::DefKind;
 use Opcode::*;
 
-use erg_parser::ast::{ParamPattern, ParamSignature, Params, VarName};
+use erg_parser::ast::{ConstAccessor, ConstExpr, ParamPattern, ParamSignature, Params, VarName};
 use erg_parser::token::{Token, TokenKind};
 
 use crate::compile::{AccessKind, Name, StoreLoadKind};
@@ -29,13 +30,14 @@ use crate::context::eval::type_from_token_kind;
 use crate::error::CompileError;
 use crate::hir::{
     Accessor, Args, Array, AttrDef, Attribute, BinOp, Block, Call, ClassDef, Def, DefBody, Expr,
-    Identifier, Lambda, Literal, PosArg, Record, Signature, SubrSignature, Tuple, UnaryOp,
-    VarSignature, HIR,
+    Identifier, Lambda, Literal, PosArg, Record, Signature, StrInterp, SubrSignature, Tuple,
+    UnaryOp, VarSignature, HIR,
 };
 use crate::ty::free::fresh_varname;
+use crate::ty::typaram::TyParam;
 use crate::ty::value::TypeKind;
 use crate::ty::value::ValueObj;
-use crate::ty::{HasType, Type, TypeCode, TypePair};
+use crate::ty::{HasType, Predicate, Type, TypeCode, TypePair};
 use AccessKind::*;
 
 fn is_python_special(name: &str) -> bool {
@@ -207,7 +209,7 @@ fn is_python_global(name: &str) -> bool {
 }
 
 fn is_erg_global(name: &str) -> bool {
-    matches!(name, "Nat",)
+    matches!(name, "Nat" | "Error")
 }
 
 fn is_fake_method(class: &str, name: &str) -> bool {
@@ -266,6 +268,30 @@ fn escape_attr(class: &str, uniq_obj_name: Option<&str>, ident: Identifier) -> S
     }
 }
 
+fn is_int(t: &Type) -> bool {
+    matches!(t, Type::Int | Type::Nat | Type::Bool)
+}
+
+/// Whether a match target of type `target_t` needs to be checked at runtime to be bound to `t`
+fn needs_type_check(t: &Type, target_t: &Type) -> bool {
+    match t {
+        Type::Obj => false,
+        Type::Refinement(_) => true,
+        _ => *t != target_t.derefine(),
+    }
+}
+
+/// The name of the class that can be used with `isinstance`
+fn builtin_class_name(t: &Type) -> Option<&'static str> {
+    match t {
+        Type::Int | Type::Nat => Some("Int"),
+        Type::Float => Some("Float"),
+        Type::Str => Some("Str"),
+        Type::Bool => Some("Bool"),
+        _ => None,
+    }
+}
+
 fn convert_to_python_name(name: Str) -> Str {
     match &name[..] {
         "abs" => Str::ever("abs"),
@@ -318,6 +344,27 @@ fn escape_name(ident: Identifier) -> Str {
     }
 }
 
+/// a `for!`/`while!` loop being emitted
+#[derive(Debug, Clone)]
+pub(crate) struct LoopInfo {
+    /// `continue` jumps here
+    begin: usize,
+    /// whether the iterator of `for!` is on the stack (`break` discards it)
+    has_iterator: bool,
+    /// the jump instructions of `break`, edited to jump to the end of the loop
+    break_points: Vec<usize>,
+}
+
+impl LoopInfo {
+    const fn new(begin: usize, has_iterator: bool) -> Self {
+        Self {
+            begin,
+            has_iterator,
+            break_points: vec![],
+        }
+    }
+}
+
 #[derive(Debug, Clone)]
 pub struct CodeGenUnit {
     pub(crate) id: usize,
@@ -326,6 +373,13 @@ pub struct CodeGenUnit {
     pub(crate) prev_lineno: usize,
     pub(crate) lasti: usize,
     pub(crate) prev_lasti: usize,
+    /// the number of `with!` blocks being emitted
+    /// (in each of them, `stack_len` is one more than the actual stack size)
+    pub(crate) with_depth: u32,
+    /// whether `?` can return from this unit (the body of a subroutine)
+    pub(crate) is_subr: bool,
+    /// the loops being emitted (innermost last)
+    pub(crate) loops: Vec<LoopInfo>,
     pub(crate) _refs: Vec<ValueObj>, // ref-counted objects
 }
 
@@ -342,7 +396,7 @@ impl fmt::Display for CodeGenUnit {
             f,
             "CompilerUnit{{\nid: {}\ncode:\n{}\n}}",
             self.id,
-            self.codeobj.code_info()
+            self.codeobj.code_info(None)
         )
     }
 }
@@ -362,6 +416,9 @@ impl CodeGenUnit {
             prev_lineno: firstlineno,
             lasti: 0,
             prev_lasti: 0,
+            with_depth: 0,
+            is_subr: false,
+            loops: vec![],
             _refs: vec![],
         }
     }
@@ -375,6 +432,7 @@ impl_stream_for_wrapper!(CodeGenStack, CodeGenUnit);
 #[derive(Debug)]
 pub struct CodeGenerator {
     cfg: ErgConfig,
+    py_version: PythonVersion,
     str_cache: CacheSet<str>,
     prelude_loaded: bool,
     record_type_loaded: bool,
@@ -384,9 +442,28 @@ pub struct CodeGenerator {
     units: CodeGenStack,
 }
 
+/// Returns the magic number of the target Python (the Python on PATH if not specified).
+/// Bytecode of another version crashes CPython, so unsupported versions are rejected.
+pub fn check_python_version(cfg: &ErgConfig) -> Result<u32, CompileError> {
+    let magic_num = cfg.python_ver.unwrap_or_else(detect_magic_number);
+    if PythonVersion::from_magic_num(magic_num).is_some() {
+        Ok(magic_num)
+    } else {
+        Err(CompileError::unsupported_python_version_error(
+            cfg.input.clone(),
+            magic_num,
+        ))
+    }
+}
+
 impl CodeGenerator {
     pub fn new(cfg: ErgConfig) -> Self {
         Self {
+            // determined in `emit`
+            py_version: cfg
+                .python_ver
+                .and_then(PythonVersion::from_magic_num)
+                .unwrap_or(PythonVersion::V3_10),
             cfg,
             str_cache: CacheSet::new(),
             prelude_loaded: false,
@@ -407,6 +484,12 @@ impl CodeGenerator {
         &self.cfg.input
     }
 
+    /// The magic number of the target Python (determined in `emit`)
+    #[inline]
+    pub fn python_ver(&self) -> Option<u32> {
+        self.cfg.python_ver
+    }
+
     fn get_cached(&self, s: &str) -> Str {
         self.str_cache.get(s)
     }
@@ -492,24 +575,80 @@ impl CodeGenerator {
         }
     }
 
+    /// In Python 3.11+, `CALL` requires `NULL` below the callable (except for methods)
+    fn emit_push_null(&mut self) {
+        if self.py_version >= PythonVersion::V3_11 {
+            self.write_instr(PUSH_NULL);
+            self.write_arg(0);
+            self.stack_inc();
+        }
+    }
+
+    /// pops `NULL` pushed by `emit_push_null` (call this after the call)
+    fn stack_dec_null(&mut self) {
+        if self.py_version >= PythonVersion::V3_11 {
+            self.stack_dec();
+        }
+    }
+
+    fn register_const<C: Into<ValueObj>>(&mut self, cons: C) -> usize {
+        let value = cons.into();
+        self.mut_cur_block_codeobj()
+            .consts
+            .iter()
+            .position(|c| c == &value)
+            .unwrap_or_else(|| {
+                self.mut_cur_block_codeobj().consts.push(value);
+                self.mut_cur_block_codeobj().consts.len() - 1
+            })
+    }
+
+    /// Loads the keyword names of `CALL_FUNCTION_KW` and returns the number of objects pushed onto the stack.
+    /// In Python 3.11+, they are passed by `KW_NAMES` (not pushed).
+    fn emit_kw_names(&mut self, kws: Vec<ValueObj>) -> usize {
+        if self.py_version >= PythonVersion::V3_11 {
+            let idx = self.register_const(kws);
+            self.write_instr(KW_NAMES);
+            self.write_arg(idx as u8);
+            0
+        } else {
+            self.emit_load_const(kws);
+            1
+        }
+    }
+
+    /// Loads `code` and its qualified name, and then writes `MAKE_FUNCTION`.
+    /// In Python 3.11+, the qualified name is held by the code object.
+    /// The stack is treated as `<code obj> + <name>` anyway, and the closure tuple (`flag & 8`) is popped here.
+    fn emit_make_function(&mut self, mut code: CodeObj, qualname: Str, flag: u8) {
+        if self.py_version >= PythonVersion::V3_11 {
+            code.qualname = qualname;
+            self.emit_load_const(code);
+            self.stack_inc();
+        } else {
+            self.emit_load_const(code);
+            self.emit_load_const(qualname);
+        }
+        self.write_instr(MAKE_FUNCTION);
+        self.write_arg(flag);
+        if flag & 8 != 0 {
+            self.stack_dec();
+        }
+    }
+
     fn emit_load_const<C: Into<ValueObj>>(&mut self, cons: C) {
         let value = cons.into();
         let is_nat = value.is_nat();
         let is_bool = value.is_bool();
+        if is_nat {
+            self.emit_push_null();
+        }
         if is_bool {
             self.emit_load_name_instr(Identifier::public("Bool"));
         } else if is_nat {
             self.emit_load_name_instr(Identifier::public("Nat"));
         }
-        let idx = self
-            .mut_cur_block_codeobj()
-            .consts
-            .iter()
-            .position(|c| c == &value)
-            .unwrap_or_else(|| {
-                self.mut_cur_block_codeobj().consts.push(value);
-                self.mut_cur_block_codeobj().consts.len() - 1
-            });
+        let idx = self.register_const(value);
         self.write_instr(Opcode::LOAD_CONST);
         self.write_arg(idx as u8);
         self.stack_inc();
@@ -517,6 +656,7 @@ impl CodeGenerator {
             self.write_instr(Opcode::CALL_FUNCTION);
             self.write_arg(1);
             self.stack_dec();
+            self.stack_dec_null();
         }
     }
 
@@ -739,6 +879,10 @@ impl CodeGenerator {
         };
         self.write_instr(instr);
         self.write_arg(name.idx as u8);
+        // LOAD_METHOD replaces the object with the method and the object (or NULL)
+        if instr == LOAD_METHOD {
+            self.stack_inc();
+        }
     }
 
     fn emit_store_instr(&mut self, ident: Identifier, acc_kind: AccessKind) {
@@ -831,30 +975,41 @@ impl CodeGenerator {
         }
     }
 
+    /// Python requires the variable-length parameters to be placed last (`def f(x, y=1, *args, **kwargs)`)
     fn gen_param_names(&self, params: &Params) -> Vec<Str> {
         params
             .non_defaults
             .iter()
+            .chain(params.defaults.iter())
+            .chain(params.var_args.as_deref())
+            .chain(params.kw_var_args.as_deref())
             .map(|p| p.inspect().map(|s| &s[..]).unwrap_or("_"))
-            .chain(if let Some(var_args) = &params.var_args {
-                vec![var_args.inspect().map(|s| &s[..]).unwrap_or("_")]
-            } else {
-                vec![]
-            })
-            .chain(
-                params
-                    .defaults
-                    .iter()
-                    .map(|p| p.inspect().map(|s| &s[..]).unwrap_or("_")),
-            )
             .map(|s| format!("::{s}"))
             .map(|s| self.get_cached(&s))
             .collect()
     }
 
+    /// The parameters following `*args` can only be passed by keyword
+    fn set_var_params_info(code: &mut CodeObj, params: &Params) {
+        if params.var_args.is_some() {
+            code.argcount = params.non_defaults.len() as u32;
+            code.kwonlyargcount = params.defaults.len() as u32;
+            code.flags += CodeObjFlags::VarArgs as u32;
+        }
+        if params.kw_var_args.is_some() {
+            if params.var_args.is_none() {
+                code.argcount = (params.non_defaults.len() + params.defaults.len()) as u32;
+            }
+            code.flags += CodeObjFlags::VarKeywords as u32;
+        }
+    }
+
     fn emit_acc(&mut self, acc: Accessor) {
         log!(info "entered {} ({acc})", fn_name!());
         match acc {
+            Accessor::Ident(ident) if ident.vis().is_private() && ident.name.is_loop_control() => {
+                self.emit_loop_control(ident);
+            }
             Accessor::Ident(ident) => {
                 self.emit_load_name_instr(ident);
             }
@@ -897,21 +1052,20 @@ impl CodeGenerator {
             self.load_abc();
             self.abc_loaded = true;
         }
+        self.emit_push_null();
         self.write_instr(LOAD_BUILD_CLASS);
         self.write_arg(0);
         self.stack_inc();
         let code = self.emit_trait_block(def.def_kind(), &def.sig, def.body.block);
-        self.emit_load_const(code);
-        self.emit_load_const(def.sig.ident().inspect().clone());
-        self.write_instr(MAKE_FUNCTION);
-        self.write_arg(0);
+        self.emit_make_function(code, def.sig.ident().inspect().clone(), 0);
         self.emit_load_const(def.sig.ident().inspect().clone());
         self.emit_load_name_instr(Identifier::private("#ABCMeta"));
-        self.emit_load_const(vec![ValueObj::from("metaclass")]);
+        let kwsc = self.emit_kw_names(vec![ValueObj::from("metaclass")]);
         let subclasses_len = 1;
         self.write_instr(Opcode::CALL_FUNCTION_KW);
         self.write_arg(2 + subclasses_len as u8);
-        self.stack_dec_n((1 + 2 + 1 + subclasses_len) - 1);
+        self.stack_dec_n((1 + 2 + kwsc + subclasses_len) - 1);
+        self.stack_dec_null();
         self.emit_store_instr(def.sig.into_ident(), Name);
         self.stack_dec();
     }
@@ -981,15 +1135,6 @@ impl CodeGenerator {
         }
         // end of flagging
         let unit = self.units.pop().unwrap();
-        if !self.units.is_empty() {
-            let ld = unit.prev_lineno - self.cur_block().prev_lineno;
-            if ld != 0 {
-                if let Some(l) = self.mut_cur_block_codeobj().lnotab.last_mut() {
-                    *l += ld as u8;
-                }
-                self.mut_cur_block().prev_lineno += ld;
-            }
-        }
         unit.codeobj
     }
 
@@ -1002,6 +1147,7 @@ impl CodeGenerator {
         log!(info "entered {} ({ident})", fn_name!());
         let deco_is_some = deco.is_some();
         if let Some(deco) = deco {
+            self.emit_push_null();
             self.emit_load_name_instr(deco);
         }
         let code = {
@@ -1017,31 +1163,19 @@ impl CodeGenerator {
             self.write_instr(RETURN_VALUE);
             self.write_arg(0u8);
             let unit = self.units.pop().unwrap();
-            if !self.units.is_empty() {
-                let ld = unit
-                    .prev_lineno
-                    .saturating_sub(self.cur_block().prev_lineno);
-                if ld != 0 {
-                    if let Some(l) = self.mut_cur_block_codeobj().lnotab.last_mut() {
-                        *l += ld as u8;
-                    }
-                    self.mut_cur_block().prev_lineno += ld;
-                }
-            }
             unit.codeobj
         };
-        self.emit_load_const(code);
-        if let Some(class) = class_name {
-            self.emit_load_const(Str::from(format!("{class}.{}", ident.name.inspect())));
+        let qualname = if let Some(class) = class_name {
+            Str::from(format!("{class}.{}", ident.name.inspect()))
         } else {
-            self.emit_load_const(ident.name.inspect().clone());
-        }
-        self.write_instr(MAKE_FUNCTION);
-        self.write_arg(0);
+            ident.name.inspect().clone()
+        };
+        self.emit_make_function(code, qualname, 0);
         if deco_is_some {
             self.write_instr(CALL_FUNCTION);
             self.write_arg(1);
             self.stack_dec();
+            self.stack_dec_null();
         }
         // stack_dec: (<abstractmethod>) + <code obj> + <name> -> <function>
         self.stack_dec();
@@ -1053,24 +1187,47 @@ impl CodeGenerator {
         let ident = class_def.sig.ident().clone();
         let kind = class_def.kind;
         let require_or_sup = class_def.require_or_sup.clone();
+        let decorators_len = self.emit_load_decorators(class_def.decorators.clone());
+        self.emit_push_null();
         self.write_instr(LOAD_BUILD_CLASS);
         self.write_arg(0);
         self.stack_inc();
         let code = self.emit_class_block(class_def);
-        self.emit_load_const(code);
-        self.emit_load_const(ident.inspect().clone());
-        self.write_instr(MAKE_FUNCTION);
-        self.write_arg(0);
+        self.emit_make_function(code, ident.inspect().clone(), 0);
         self.emit_load_const(ident.inspect().clone());
         // LOAD subclasses
         let subclasses_len = self.emit_require_type(kind, *require_or_sup);
         self.write_instr(CALL_FUNCTION);
         self.write_arg(2 + subclasses_len as u8);
         self.stack_dec_n((1 + 2 + subclasses_len) - 1);
+        self.stack_dec_null();
+        self.emit_apply_decorators(decorators_len);
         self.emit_store_instr(ident, Name);
         self.stack_dec();
     }
 
+    /// Loads the decorators before the decorated object is created, as Python does.
+    /// Returns the number of the decorators.
+    fn emit_load_decorators(&mut self, decorators: Vec<Expr>) -> usize {
+        let len = decorators.len();
+        for deco in decorators.into_iter() {
+            self.emit_push_null();
+            self.emit_expr(deco);
+        }
+        len
+    }
+
+    /// Applies the decorators loaded by `emit_load_decorators` to the stack top (from the bottom up)
+    fn emit_apply_decorators(&mut self, len: usize) {
+        for _ in 0..len {
+            self.write_instr(CALL_FUNCTION);
+            self.write_arg(1);
+            // <decorator> + <object> -> <decorated object>
+            self.stack_dec();
+            self.stack_dec_null();
+        }
+    }
+
     // NOTE: use `TypeVar`, `Generic` in `typing` module
     // fn emit_poly_type_def(&mut self, sig: SubrSignature, body: DefBody) {}
 
@@ -1106,29 +1263,25 @@ impl CodeGenerator {
     fn emit_subr_def(&mut self, class_name: Option<&str>, sig: SubrSignature, body: DefBody) {
         log!(info "entered {} ({sig} = {})", fn_name!(), body.block);
         let name = sig.ident.inspect().clone();
+        let decorators_len = self.emit_load_decorators(sig.decorators);
         let mut make_function_flag = 0u8;
         let params = self.gen_param_names(&sig.params);
-        let code = self.emit_block(body.block, Some(name.clone()), params);
+        let mut code = self.emit_block(body.block, Some(name.clone()), params);
+        Self::set_var_params_info(&mut code, &sig.params);
         if !self.cur_block_codeobj().cellvars.is_empty() {
-            let cellvars_len = self.cur_block_codeobj().cellvars.len() as u8;
-            for i in 0..cellvars_len {
-                self.write_instr(LOAD_CLOSURE);
-                self.write_arg(i);
-            }
-            self.write_instr(BUILD_TUPLE);
-            self.write_arg(cellvars_len);
+            let cellvars_len = self.cur_block_codeobj().cellvars.len();
+            self.emit_closure_tuple((0..cellvars_len).collect());
             make_function_flag += 8;
         }
-        self.emit_load_const(code);
-        if let Some(class) = class_name {
-            self.emit_load_const(Str::from(format!("{class}.{name}")));
+        let qualname = if let Some(class) = class_name {
+            Str::from(format!("{class}.{name}"))
         } else {
-            self.emit_load_const(name);
-        }
-        self.write_instr(MAKE_FUNCTION);
-        self.write_arg(make_function_flag);
+            name
+        };
+        self.emit_make_function(code, qualname, make_function_flag);
         // stack_dec: <code obj> + <name> -> <function>
         self.stack_dec();
+        self.emit_apply_decorators(decorators_len);
         self.emit_store_instr(sig.ident, Name);
     }
 
@@ -1136,27 +1289,206 @@ impl CodeGenerator {
         log!(info "entered {} ({lambda})", fn_name!());
         let mut make_function_flag = 0u8;
         let params = self.gen_param_names(&lambda.params);
-        let code = self.emit_block(lambda.body, Some("<lambda>".into()), params);
+        let mut code = self.emit_block(lambda.body, Some("<lambda>".into()), params);
+        Self::set_var_params_info(&mut code, &lambda.params);
         if !self.cur_block_codeobj().cellvars.is_empty() {
-            let cellvars_len = self.cur_block_codeobj().cellvars.len() as u8;
-            for i in 0..cellvars_len {
-                self.write_instr(LOAD_CLOSURE);
-                self.write_arg(i);
-            }
-            self.write_instr(BUILD_TUPLE);
-            self.write_arg(cellvars_len);
+            let cellvars_len = self.cur_block_codeobj().cellvars.len();
+            self.emit_closure_tuple((0..cellvars_len).collect());
             make_function_flag += 8;
         }
-        self.emit_load_const(code);
-        self.emit_load_const("<lambda>");
-        self.write_instr(MAKE_FUNCTION);
-        self.write_arg(make_function_flag);
+        self.emit_make_function(code, Str::ever("<lambda>"), make_function_flag);
         // stack_dec: <lambda code obj> + <name "<lambda>"> -> <function>
         self.stack_dec();
     }
 
+    /// Builds the closure tuple of `code` (the cells of its free variables) and returns the flag of `MAKE_FUNCTION`.
+    /// The free variables which are not defined in the current block are passed through it,
+    /// e.g. `x` of `f x = [[x + i | i <- xs] | _ <- ys]` is a free variable of the outer comprehension too.
+    fn emit_closure_of(&mut self, code: &CodeObj) -> u8 {
+        if code.freevars.is_empty() {
+            return 0;
+        }
+        let mut idxs = vec![];
+        for name in code.freevars.iter() {
+            let cellvars = &self.cur_block_codeobj().cellvars;
+            let idx = if let Some(idx) = cellvars.iter().position(|c| c == name) {
+                idx
+            } else if let Some(idx) = self
+                .cur_block_codeobj()
+                .freevars
+                .iter()
+                .position(|f| f == name)
+            {
+                cellvars.len() + idx
+            } else {
+                let len = cellvars.len();
+                self.mut_cur_block_codeobj().freevars.push(name.clone());
+                len + self.cur_block_codeobj().freevars.len() - 1
+            };
+            idxs.push(idx);
+        }
+        self.emit_closure_tuple(idxs);
+        8
+    }
+
+    /// `LOAD_CLOSURE`s of the cells `idxs` and `BUILD_TUPLE` (popped by `MAKE_FUNCTION`)
+    fn emit_closure_tuple(&mut self, idxs: Vec<usize>) {
+        let len = idxs.len();
+        for idx in idxs {
+            self.write_instr(LOAD_CLOSURE);
+            self.write_arg(idx as u8);
+        }
+        self.stack_inc_n(len);
+        self.write_instr(BUILD_TUPLE);
+        self.write_arg(len as u8);
+        self.stack_dec_n(len - 1);
+    }
+
+    /// Comprehensions are compiled into nested functions as in Python.
+    /// `elems` is `[elem]` for arrays/sets and `[key, value]` for dicts.
+    /// ```erg
+    /// [x + 1 | x <- xs; x > 0]
+    /// ```
+    /// ↓
+    /// ```python
+    /// def <listcomp>(.0):
+    ///     BUILD_LIST 0; LOAD_FAST .0; FOR_ITER; STORE_FAST x; (guard); POP_JUMP_IF_FALSE; (elem); LIST_APPEND 2; ...
+    /// <listcomp>(iter(xs))
+    /// ```
+    fn emit_comprehension(
+        &mut self,
+        name: &'static str,
+        (build, add): (Opcode, Opcode),
+        generators: Vec<(Identifier, Expr)>,
+        guards: Vec<Expr>,
+        elems: Vec<Expr>,
+    ) {
+        log!(info "entered {} ({name})", fn_name!());
+        let mut generators = generators.into_iter();
+        let (first_ident, first_iterable) = generators.next().unwrap();
+        let firstlineno = first_iterable.ln_begin().unwrap_or(0);
+        self.unit_size += 1;
+        self.units.push(CodeGenUnit::new(
+            self.unit_size,
+            vec![Str::ever(".0")],
+            Str::rc(self.cfg.input.enclosed_name()),
+            name,
+            firstlineno,
+        ));
+        let generators = [(first_ident, None)]
+            .into_iter()
+            .chain(generators.map(|(ident, iterable)| (ident, Some(iterable))))
+            .collect::<Vec<_>>();
+        // the bound variables are local to the comprehension
+        for (ident, _) in generators.iter() {
+            let escaped = escape_name(ident.clone());
+            if !self.cur_block_codeobj().varnames.contains(&escaped) {
+                self.mut_cur_block_codeobj().varnames.push(escaped);
+            }
+        }
+        self.write_instr(build);
+        self.write_arg(0);
+        self.stack_inc();
+        let depth = generators.len();
+        let mut for_iter_points = vec![];
+        for (ident, iterable) in generators.into_iter() {
+            if let Some(iterable) = iterable {
+                self.emit_expr(iterable);
+                self.write_instr(GET_ITER);
+                self.write_arg(0);
+            } else {
+                self.write_instr(LOAD_FAST);
+                self.write_arg(0);
+                self.stack_inc();
+            }
+            for_iter_points.push(self.cur_block().lasti);
+            self.write_instr(FOR_ITER);
+            self.write_arg(0);
+            self.stack_inc();
+            self.emit_store_instr(ident, Name);
+        }
+        let innermost = *for_iter_points.last().unwrap();
+        for guard in guards.into_iter() {
+            self.emit_expr(guard);
+            self.write_instr(POP_JUMP_IF_FALSE);
+            self.write_arg((innermost / 2) as u8);
+            self.stack_dec();
+        }
+        let len = elems.len();
+        // `MAP_ADD` of 3.7 takes the key from TOS
+        if add == MAP_ADD && self.py_version < PythonVersion::V3_8 {
+            for elem in elems.into_iter().rev() {
+                self.emit_expr(elem);
+            }
+        } else {
+            for elem in elems.into_iter() {
+                self.emit_expr(elem);
+            }
+        }
+        self.write_instr(add);
+        self.write_arg((depth + 1) as u8);
+        self.stack_dec_n(len);
+        for for_iter_point in for_iter_points.into_iter().rev() {
+            self.write_instr(JUMP_ABSOLUTE);
+            self.write_arg((for_iter_point / 2) as u8);
+            let idx_end = self.cur_block().lasti;
+            self.edit_code(for_iter_point + 1, (idx_end - for_iter_point - 2) / 2);
+            // the exhausted iterator is popped
+            self.stack_dec();
+        }
+        self.write_instr(RETURN_VALUE);
+        self.write_arg(0u8);
+        self.mut_cur_block_codeobj().flags += CodeObjFlags::NewLocals as u32;
+        self.mut_cur_block_codeobj().nlocals = self.cur_block_codeobj().varnames.len() as u32;
+        let code = self.units.pop().unwrap().codeobj;
+        self.emit_push_null();
+        let make_function_flag = self.emit_closure_of(&code);
+        self.emit_make_function(code, Str::ever(name), make_function_flag);
+        // stack_dec: <code obj> + <name> -> <function>
+        self.stack_dec();
+        self.emit_expr(first_iterable);
+        self.write_instr(GET_ITER);
+        self.write_arg(0);
+        self.write_instr(CALL_FUNCTION);
+        self.write_arg(1);
+        self.stack_dec();
+        self.stack_dec_null();
+    }
+
+    /// ```erg
+    /// "a\{x}b"
+    /// ```
+    /// ↓
+    /// ```python
+    /// LOAD_CONST "a"; (x); FORMAT_VALUE 0; LOAD_CONST "b"; BUILD_STRING 3
+    /// ```
+    fn emit_str_interp(&mut self, interp: StrInterp) {
+        log!(info "entered {} ({interp})", fn_name!());
+        let mut len = 0;
+        let mut exprs = interp.exprs.into_iter();
+        for fragment in interp.fragments.into_iter() {
+            if !matches!(&fragment.value, ValueObj::Str(s) if s.is_empty()) {
+                self.emit_load_const(fragment.value);
+                len += 1;
+            }
+            if let Some(expr) = exprs.next() {
+                self.emit_expr(expr);
+                // no conversion (`!s`, `!r`, `!a`) and no format spec
+                self.write_instr(FORMAT_VALUE);
+                self.write_arg(0);
+                len += 1;
+            }
+        }
+        self.write_instr(BUILD_STRING);
+        self.write_arg(len as u8);
+        self.stack_dec_n(len - 1);
+    }
+
     fn emit_unaryop(&mut self, unary: UnaryOp) {
         log!(info "entered {} ({unary})", fn_name!());
+        if unary.op.is(TokenKind::Try) {
+            return self.emit_try(unary);
+        }
         let tycode = TypeCode::from(unary.lhs_t());
         self.emit_expr(*unary.expr);
         let instr = match &unary.op.kind {
@@ -1179,6 +1511,43 @@ impl CodeGenerator {
         self.write_arg(tycode as u8);
     }
 
+    /// `x?`: returns `x` from the subroutine if it is an `Error` (raises it at the top level)
+    fn emit_try(&mut self, unary: UnaryOp) {
+        log!(info "entered {} ({unary})", fn_name!());
+        self.emit_expr(*unary.expr);
+        // x x
+        self.write_instr(DUP_TOP);
+        self.write_arg(0);
+        self.stack_inc();
+        if self.py_version >= PythonVersion::V3_11 {
+            // x NULL x
+            self.emit_push_null();
+            self.write_instr(ROT_TWO);
+            self.write_arg(0);
+        }
+        // x (NULL) #is_ok x
+        self.emit_load_name_instr(Identifier::private("#is_ok"));
+        self.write_instr(ROT_TWO);
+        self.write_arg(0);
+        self.write_instr(CALL_FUNCTION);
+        self.write_arg(1);
+        self.stack_dec();
+        self.stack_dec_null();
+        let pop_jump_point = self.cur_block().lasti;
+        self.write_instr(POP_JUMP_IF_TRUE);
+        self.write_arg(0);
+        self.stack_dec();
+        if self.cur_block().is_subr {
+            self.write_instr(RETURN_VALUE);
+            self.write_arg(0);
+        } else {
+            self.write_instr(RAISE_VARARGS);
+            self.write_arg(1);
+        }
+        let idx = self.cur_block().lasti;
+        self.edit_code(pop_jump_point + 1, idx / 2);
+    }
+
     fn emit_binop(&mut self, bin: BinOp) {
         log!(info "entered {} ({bin})", fn_name!());
         // TODO: and/orのプリミティブ命令の実装
@@ -1186,10 +1555,12 @@ impl CodeGenerator {
         match &bin.op.kind {
             // l..<r == range(l, r)
             TokenKind::RightOpen => {
+                self.emit_push_null();
                 self.emit_load_name_instr(Identifier::public("range"));
             }
             TokenKind::LeftOpen | TokenKind::Closed | TokenKind::Open => todo!(),
             TokenKind::InOp => {
+                self.emit_push_null();
                 self.emit_load_name_instr(Identifier::private("#in_operator"));
             }
             _ => {}
@@ -1253,6 +1624,7 @@ impl CodeGenerator {
             | TokenKind::Closed
             | TokenKind::InOp => {
                 self.stack_dec();
+                self.stack_dec_null();
             }
             _ => {}
         }
@@ -1317,6 +1689,8 @@ impl CodeGenerator {
             self.stack_dec();
         } else {
             // no else block
+            self.write_instr(JUMP_FORWARD); // skip the `None` below
+            self.write_arg(1_u8);
             let idx_end = self.cur_block().lasti;
             self.edit_code(idx_pop_jump_if_false + 1, idx_end / 2);
             self.emit_load_const(ValueObj::None);
@@ -1337,46 +1711,137 @@ impl CodeGenerator {
         // but after executing this instruction, stack_len should be 1
         // cannot detect where to jump to at this moment, so put as 0
         self.write_arg(0);
+        // the iterator stays on the stack during the loop, but it is not counted in `stack_len`
+        // (the parameter is stored in its place), so make room for it
+        self.mut_cur_block_codeobj().stacksize += 1;
         let lambda = enum_unwrap!(args.remove(0), Expr::Lambda);
         let params = self.gen_param_names(&lambda.params);
-        self.emit_frameless_block(lambda.body, params); // ここでPOPされる
+        self.mut_cur_block()
+            .loops
+            .push(LoopInfo::new(idx_for_iter, true));
+        self.emit_frameless_block(lambda.body, params);
+        // discard the result of the body
+        if self.cur_block().stack_len == 1 {
+            self.emit_pop_top();
+        }
         self.write_instr(JUMP_ABSOLUTE);
         self.write_arg((idx_for_iter / 2) as u8);
         let idx_end = self.cur_block().lasti;
         self.edit_code(idx_for_iter + 1, (idx_end - idx_for_iter - 2) / 2);
-        self.emit_pop_top();
+        self.emit_loop_end(idx_end);
+        self.emit_load_const(ValueObj::None);
+    }
+
+    fn emit_while_instr(&mut self, mut args: Args) {
+        log!(info "entered {} ({})", fn_name!(), args);
+        let cond = args.remove(0);
+        // the condition is evaluated on each iteration
+        let idx_while = self.cur_block().lasti;
+        self.emit_expr(cond);
+        let idx_pop_jump_if_false = self.cur_block().lasti;
+        self.write_instr(POP_JUMP_IF_FALSE);
+        // cannot detect where to jump to at this moment, so put as 0
+        self.write_arg(0);
+        self.stack_dec();
+        let lambda = enum_unwrap!(args.remove(0), Expr::Lambda);
+        self.mut_cur_block()
+            .loops
+            .push(LoopInfo::new(idx_while, false));
+        self.emit_frameless_block(lambda.body, vec![]);
+        // discard the result of the body
+        if self.cur_block().stack_len == 1 {
+            self.emit_pop_top();
+        }
+        self.write_instr(JUMP_ABSOLUTE);
+        self.write_arg((idx_while / 2) as u8);
+        let idx_end = self.cur_block().lasti;
+        self.edit_code(idx_pop_jump_if_false + 1, idx_end / 2);
+        self.emit_loop_end(idx_end);
+        self.emit_load_const(ValueObj::None);
+    }
+
+    /// Makes the `break`s of the innermost loop jump to `idx_end`
+    fn emit_loop_end(&mut self, idx_end: usize) {
+        let loop_info = self.mut_cur_block().loops.pop().unwrap();
+        for break_point in loop_info.break_points.into_iter() {
+            self.edit_code(break_point + 1, idx_end / 2);
+        }
+    }
+
+    /// `break`/`continue` of the innermost loop
+    fn emit_loop_control(&mut self, ident: Identifier) {
+        log!(info "entered {} ({ident})", fn_name!());
+        let Some(loop_info) = self.cur_block().loops.last() else {
+            self.crash("`break`/`continue` outside of loops");
+        };
+        let (begin, has_iterator) = (loop_info.begin, loop_info.has_iterator);
+        if &ident.inspect()[..] == "break" {
+            if has_iterator {
+                // not `emit_pop_top`: the iterator is not counted in `stack_len`
+                self.write_instr(POP_TOP);
+                self.write_arg(0);
+            }
+            let idx_jump = self.cur_block().lasti;
+            self.write_instr(JUMP_ABSOLUTE);
+            self.write_arg(0);
+            self.mut_cur_block()
+                .loops
+                .last_mut()
+                .unwrap()
+                .break_points
+                .push(idx_jump);
+        } else {
+            self.write_instr(JUMP_ABSOLUTE);
+            self.write_arg((begin / 2) as u8);
+        }
+        // unreachable, but `break`/`continue` is an expression
         self.emit_load_const(ValueObj::None);
     }
 
     fn emit_match_instr(&mut self, mut args: Args, _use_erg_specific: bool) {
         log!(info "entered {}", fn_name!());
         let expr = args.remove(0);
+        let target_t = expr.ref_t().clone();
         self.emit_expr(expr);
-        let len = args.len();
         let mut absolute_jump_points = vec![];
         while let Some(expr) = args.try_remove(0) {
-            // パターンが複数ある場合引数を複製する、ただし最後はしない
-            if len > 1 && !args.is_empty() {
-                self.write_instr(Opcode::DUP_TOP);
-                self.write_arg(0);
-                self.stack_inc();
-            }
+            // the pattern consumes the duplicated target, whether it matches or not
+            self.write_instr(Opcode::DUP_TOP);
+            self.write_arg(0);
+            self.stack_inc();
             // compilerで型チェック済み(可読性が下がるため、matchでNamedは使えない)
             let mut lambda = enum_unwrap!(expr, Expr::Lambda);
             debug_power_assert!(lambda.params.len(), ==, 1);
-            if !lambda.params.defaults.is_empty() {
-                todo!("default values in match expression are not supported yet")
-            }
-            let pat = lambda.params.non_defaults.remove(0).pat;
-            let pop_jump_points = self.emit_match_pattern(pat);
+            // the default value of the arm (`(x := 1) -> ...`) is not used since the target is always passed
+            let (param_t, pat) = if lambda.params.non_defaults.is_empty() {
+                let param_t = lambda
+                    .t
+                    .default_params()
+                    .and_then(|params| params.first())
+                    .map(|param| param.typ().clone());
+                (param_t, lambda.params.defaults.remove(0).pat)
+            } else {
+                let param_t = lambda
+                    .t
+                    .non_default_params()
+                    .and_then(|params| params.first())
+                    .map(|param| param.typ().clone());
+                (param_t, lambda.params.non_defaults.remove(0).pat)
+            };
+            let param_t = param_t.unwrap_or(Type::Obj);
+            let pop_jump_points = self.emit_match_pattern(pat, &param_t, &target_t);
+            // matched: discard the target and evaluate the body
+            self.emit_pop_top();
             self.emit_frameless_block(lambda.body, Vec::new());
-            for pop_jump_point in pop_jump_points.into_iter() {
-                let idx = self.cur_block().lasti + 2;
-                self.edit_code(pop_jump_point + 1, idx / 2); // jump to POP_TOP
+            if !args.is_empty() {
                 absolute_jump_points.push(self.cur_block().lasti);
                 self.write_instr(Opcode::JUMP_ABSOLUTE); // jump to the end
                 self.write_arg(0);
             }
+            let idx = self.cur_block().lasti;
+            for pop_jump_point in pop_jump_points.into_iter() {
+                self.edit_code(pop_jump_point + 1, idx / 2); // jump to the next case
+            }
         }
         let lasti = self.cur_block().lasti;
         for absolute_jump_point in absolute_jump_points.into_iter() {
@@ -1384,13 +1849,29 @@ impl CodeGenerator {
         }
     }
 
-    fn emit_match_pattern(&mut self, pat: ParamPattern) -> Vec<usize> {
+    /// Emits the code to test the duplicated match target against `pat`.
+    /// The duplicated target is consumed in both cases,
+    /// and the returned jump points are taken when the pattern does not match.
+    fn emit_match_pattern(&mut self, pat: ParamPattern, t: &Type, target_t: &Type) -> Vec<usize> {
         log!(info "entered {}", fn_name!());
         let mut pop_jump_points = vec![];
         match pat {
             ParamPattern::VarName(name) => {
                 let ident = Identifier::bare(None, name);
-                self.emit_store_instr(ident, AccessKind::Name);
+                self.emit_store_instr(ident.clone(), AccessKind::Name);
+                if needs_type_check(t, target_t) {
+                    pop_jump_points = self.emit_type_check(&ident, t, target_t);
+                }
+            }
+            ParamPattern::Discard(_) => {
+                if needs_type_check(t, target_t) {
+                    let ident =
+                        Identifier::bare(None, VarName::from_str(Str::from(fresh_varname())));
+                    self.emit_store_instr(ident.clone(), AccessKind::Name);
+                    pop_jump_points = self.emit_type_check(&ident, t, target_t);
+                } else {
+                    self.emit_pop_top();
+                }
             }
             ParamPattern::Lit(lit) => {
                 let value = {
@@ -1404,46 +1885,317 @@ impl CodeGenerator {
                 pop_jump_points.push(self.cur_block().lasti);
                 self.write_instr(Opcode::POP_JUMP_IF_FALSE); // jump to the next case
                 self.write_arg(0);
-                self.emit_pop_top();
                 self.stack_dec();
             }
             ParamPattern::Array(arr) => {
-                let len = arr.len();
-                self.write_instr(Opcode::MATCH_SEQUENCE);
-                self.write_arg(0);
-                pop_jump_points.push(self.cur_block().lasti);
-                self.write_instr(Opcode::POP_JUMP_IF_FALSE);
-                self.write_arg(0);
-                self.stack_dec();
-                self.write_instr(Opcode::GET_LEN);
+                let loc = arr.loc();
+                pop_jump_points = self.emit_match_sequence_pattern(arr.elems, loc);
+            }
+            ParamPattern::Tuple(tup) => {
+                let loc = tup.loc();
+                pop_jump_points = self.emit_match_sequence_pattern(tup.elems, loc);
+            }
+            ParamPattern::Record(rec) => {
+                let stash = Identifier::bare(None, VarName::from_str(Str::from(fresh_varname())));
+                self.emit_store_instr(stash.clone(), AccessKind::Name);
+                for attr in rec.elems.into_iter() {
+                    // the fields of a record are not mangled
+                    let field = attr.lhs.inspect().clone();
+                    if let Some(default) = attr.rhs.opt_default_val {
+                        // getattr(stash, field, default)
+                        self.emit_push_null();
+                        self.emit_load_name_instr(Identifier::public("getattr"));
+                        self.emit_load_name_instr(stash.clone());
+                        self.emit_load_const(field);
+                        self.emit_const_expr(default);
+                        self.write_instr(Opcode::CALL_FUNCTION);
+                        self.write_arg(3);
+                        self.stack_dec_n((1 + 3) - 1);
+                        self.stack_dec_null();
+                    } else {
+                        self.emit_load_name_instr(stash.clone());
+                        let name = self
+                            .local_search(&field, Attr)
+                            .unwrap_or_else(|| self.register_attr(field));
+                        self.write_instr(Opcode::LOAD_ATTR);
+                        self.write_arg(name.idx as u8);
+                    }
+                    pop_jump_points.append(&mut self.emit_match_pattern(
+                        attr.rhs.pat,
+                        &Type::Obj,
+                        &Type::Obj,
+                    ));
+                }
+            }
+            ParamPattern::Ref(name) | ParamPattern::RefMut(name) => {
+                let ident = Identifier::bare(None, name);
+                self.emit_store_instr(ident, AccessKind::Name);
+            }
+        }
+        pop_jump_points
+    }
+
+    /// Emits the code to test the duplicated match target against an array or tuple pattern.
+    fn emit_match_sequence_pattern(&mut self, elems: Params, loc: Location) -> Vec<usize> {
+        log!(info "entered {}", fn_name!());
+        if self.py_version < PythonVersion::V3_10 {
+            CompileError::feature_error(
+                self.cfg.input.clone(),
+                loc,
+                &format!("array patterns for Python {}", self.py_version),
+                AtomicStr::ever("<array pattern>"),
+            )
+            .write_to_stderr();
+            self.crash("array patterns require Python 3.10 or later");
+        }
+        let mut pop_jump_points = vec![];
+        let min_len = elems.non_defaults.len();
+        let max_len = elems.len();
+        // stash the target so that no elements are left on the stack when the match fails
+        let stash = Identifier::bare(None, VarName::from_str(Str::from(fresh_varname())));
+        self.emit_store_instr(stash.clone(), AccessKind::Name);
+        self.emit_load_name_instr(stash.clone());
+        self.write_instr(Opcode::MATCH_SEQUENCE);
+        self.write_arg(0);
+        self.stack_inc();
+        self.write_instr(Opcode::ROT_TWO);
+        self.write_arg(0);
+        self.emit_pop_top();
+        pop_jump_points.push(self.emit_pop_jump_if_false());
+        let len = Identifier::bare(None, VarName::from_str(Str::from(fresh_varname())));
+        self.emit_load_name_instr(stash.clone());
+        self.write_instr(Opcode::GET_LEN);
+        self.write_arg(0);
+        self.stack_inc();
+        self.write_instr(Opcode::ROT_TWO);
+        self.write_arg(0);
+        self.emit_pop_top();
+        self.emit_store_instr(len.clone(), AccessKind::Name);
+        // `[x, y := 1]` matches sequences of length 1 and 2
+        let bounds = if min_len == max_len {
+            vec![(min_len, 2)] // ==
+        } else {
+            vec![(min_len, 5), (max_len, 1)] // >=, <=
+        };
+        for (bound, cmp) in bounds {
+            self.emit_load_name_instr(len.clone());
+            self.emit_load_const(bound);
+            self.write_instr(Opcode::COMPARE_OP);
+            self.write_arg(cmp);
+            self.stack_dec();
+            pop_jump_points.push(self.emit_pop_jump_if_false());
+        }
+        for (i, elem) in elems.non_defaults.into_iter().enumerate() {
+            self.emit_load_name_instr(stash.clone());
+            self.emit_load_const(i);
+            self.write_instr(Opcode::BINARY_SUBSCR);
+            self.write_arg(0);
+            self.stack_dec();
+            pop_jump_points.append(&mut self.emit_match_pattern(elem.pat, &Type::Obj, &Type::Obj));
+        }
+        for (i, elem) in elems.defaults.into_iter().enumerate() {
+            let i = min_len + i;
+            // stash[i] if len > i else default
+            self.emit_load_name_instr(len.clone());
+            self.emit_load_const(i);
+            self.write_instr(Opcode::COMPARE_OP);
+            self.write_arg(4); // >
+            self.stack_dec();
+            let idx_pop_jump_if_false = self.emit_pop_jump_if_false();
+            self.emit_load_name_instr(stash.clone());
+            self.emit_load_const(i);
+            self.write_instr(Opcode::BINARY_SUBSCR);
+            self.write_arg(0);
+            self.stack_dec();
+            let idx_jump_forward = self.cur_block().lasti;
+            self.write_instr(Opcode::JUMP_FORWARD);
+            self.write_arg(0);
+            self.stack_dec();
+            let idx_else_begin = self.cur_block().lasti;
+            self.edit_code(idx_pop_jump_if_false + 1, idx_else_begin / 2);
+            self.emit_const_expr(elem.opt_default_val.unwrap());
+            let idx_end = self.cur_block().lasti;
+            self.edit_code(idx_jump_forward + 1, (idx_end - idx_jump_forward - 2) / 2);
+            pop_jump_points.append(&mut self.emit_match_pattern(elem.pat, &Type::Obj, &Type::Obj));
+        }
+        pop_jump_points
+    }
+
+    /// Emits the default value of a parameter pattern
+    fn emit_const_expr(&mut self, expr: ConstExpr) {
+        log!(info "entered {} ({expr})", fn_name!());
+        match expr {
+            ConstExpr::Lit(lit) => {
+                let t = type_from_token_kind(lit.token.kind);
+                self.emit_load_const(ValueObj::from_str(t, lit.token.content).unwrap());
+            }
+            ConstExpr::Accessor(ConstAccessor::Local(local)) => {
+                let ident = Identifier::bare(None, VarName::new(local.symbol));
+                self.emit_load_name_instr(ident);
+            }
+            ConstExpr::Array(arr) => {
+                let elems = arr.elems.into_iters().0.into_iter().collect::<Vec<_>>();
+                let len = elems.len();
+                for elem in elems.into_iter() {
+                    self.emit_const_expr(elem.expr);
+                }
+                self.write_instr(BUILD_LIST);
+                self.write_arg(len as u8);
+                if len == 0 {
+                    self.stack_inc();
+                } else {
+                    self.stack_dec_n(len - 1);
+                }
+            }
+            ConstExpr::UnaryOp(unary) if unary.op.is(TokenKind::PreMinus) => {
+                self.emit_const_expr(*unary.expr);
+                self.write_instr(UNARY_NEGATIVE);
                 self.write_arg(0);
-                self.emit_load_const(len);
-                self.write_instr(Opcode::COMPARE_OP);
-                self.write_arg(2); // ==
-                self.stack_dec();
-                pop_jump_points.push(self.cur_block().lasti);
-                self.write_instr(Opcode::POP_JUMP_IF_FALSE);
+            }
+            ConstExpr::BinOp(bin) => {
+                let instr = match &bin.op.kind {
+                    TokenKind::Plus => BINARY_ADD,
+                    TokenKind::Minus => BINARY_SUBTRACT,
+                    TokenKind::Star => BINARY_MULTIPLY,
+                    TokenKind::Slash => BINARY_TRUE_DIVIDE,
+                    _ => {
+                        return self.emit_unsupported_const_expr(ConstExpr::BinOp(bin));
+                    }
+                };
+                self.emit_const_expr(*bin.lhs);
+                self.emit_const_expr(*bin.rhs);
+                self.write_instr(instr);
                 self.write_arg(0);
                 self.stack_dec();
-                self.write_instr(Opcode::UNPACK_SEQUENCE);
-                self.write_arg(len as u8);
-                self.stack_inc_n(len - 1);
-                for elem in arr.elems.non_defaults {
-                    pop_jump_points.append(&mut self.emit_match_pattern(elem.pat));
-                }
-                if !arr.elems.defaults.is_empty() {
-                    todo!("default values in match are not supported yet")
-                }
             }
-            _other => {
-                todo!()
+            other => self.emit_unsupported_const_expr(other),
+        }
+    }
+
+    fn emit_unsupported_const_expr(&mut self, expr: ConstExpr) {
+        CompileError::feature_error(
+            self.cfg.input.clone(),
+            expr.loc(),
+            &format!("default value `{expr}`"),
+            AtomicStr::ever("<default value>"),
+        )
+        .write_to_stderr();
+        self.crash("unsupported default value");
+    }
+
+    /// Checks at runtime that the value bound to `ident` is an instance of `t`.
+    /// Returns the jump points taken when the check fails.
+    fn emit_type_check(&mut self, ident: &Identifier, t: &Type, target_t: &Type) -> Vec<usize> {
+        log!(info "entered {}", fn_name!());
+        let mut pop_jump_points = vec![];
+        let (base, preds) = match t {
+            Type::Refinement(refine) => (refine.t.as_ref(), Some(&refine.preds)),
+            other => (other, None),
+        };
+        let target_base = target_t.derefine();
+        if needs_type_check(base, &target_base) && !(is_int(base) && is_int(&target_base)) {
+            if let Some(class) = builtin_class_name(base) {
+                self.emit_push_null();
+                self.emit_load_name_instr(Identifier::public("isinstance"));
+                self.emit_load_name_instr(ident.clone());
+                self.emit_load_name_instr(Identifier::public(class));
+                self.write_instr(Opcode::CALL_FUNCTION);
+                self.write_arg(2);
+                self.stack_dec_n(2);
+                self.stack_dec_null();
+                pop_jump_points.push(self.emit_pop_jump_if_false());
+            }
+        }
+        if *base == Type::Nat && target_base != Type::Nat {
+            let pred = Predicate::ge(ident.inspect().clone(), TyParam::value(0));
+            self.emit_pred(ident, &pred);
+            pop_jump_points.push(self.emit_pop_jump_if_false());
+        }
+        // the predicates of a refinement type are joined by `or`
+        if let Some(preds) = preds {
+            let mut preds = preds.iter();
+            if let Some(first) = preds.next() {
+                self.emit_pred(ident, first);
+                for pred in preds {
+                    self.emit_pred(ident, pred);
+                    self.write_instr(Opcode::BINARY_OR);
+                    self.write_arg(0);
+                    self.stack_dec();
+                }
+                pop_jump_points.push(self.emit_pop_jump_if_false());
             }
         }
         pop_jump_points
     }
 
+    /// Returns the index of the emitted instruction (the jump target will be set later)
+    fn emit_pop_jump_if_false(&mut self) -> usize {
+        let idx = self.cur_block().lasti;
+        self.write_instr(Opcode::POP_JUMP_IF_FALSE);
+        self.write_arg(0);
+        self.stack_dec();
+        idx
+    }
+
+    fn emit_pred(&mut self, ident: &Identifier, pred: &Predicate) {
+        let (rhs, cmp) = match pred {
+            Predicate::Value(value) => {
+                self.emit_load_const(value.clone());
+                return;
+            }
+            Predicate::Equal { rhs, .. } => (rhs, 2),
+            Predicate::NotEqual { rhs, .. } => (rhs, 3),
+            Predicate::GreaterEqual { rhs, .. } => (rhs, 5),
+            Predicate::LessEqual { rhs, .. } => (rhs, 1),
+            Predicate::And(lhs, rhs) | Predicate::Or(lhs, rhs) | Predicate::Not(lhs, rhs) => {
+                self.emit_pred(ident, lhs);
+                self.emit_pred(ident, rhs);
+                if let Predicate::Not(..) = pred {
+                    self.write_instr(Opcode::UNARY_NOT);
+                    self.write_arg(0);
+                }
+                let op = if let Predicate::Or(..) = pred {
+                    Opcode::BINARY_OR
+                } else {
+                    Opcode::BINARY_AND
+                };
+                self.write_instr(op);
+                self.write_arg(0);
+                self.stack_dec();
+                return;
+            }
+            Predicate::Const(name) => {
+                self.emit_load_name_instr(Identifier::bare(None, VarName::from_str(name.clone())));
+                return;
+            }
+        };
+        let TyParam::Value(value) = rhs else {
+            CompileError::feature_error(
+                self.cfg.input.clone(),
+                ident.loc(),
+                &format!("the predicate `{pred}`"),
+                AtomicStr::ever("<refinement pattern>"),
+            )
+            .write_to_stderr();
+            self.crash("cannot compile the predicate");
+        };
+        self.emit_load_name_instr(ident.clone());
+        self.emit_load_const(value.clone());
+        self.write_instr(Opcode::COMPARE_OP);
+        self.write_arg(cmp);
+        self.stack_dec();
+    }
+
     fn emit_with_instr(&mut self, args: Args) {
         log!(info "entered {}", fn_name!());
+        match self.py_version {
+            PythonVersion::V3_7 | PythonVersion::V3_8 => self.emit_with_instr_308(args),
+            PythonVersion::V3_9 | PythonVersion::V3_10 => self.emit_with_instr_310(args),
+            PythonVersion::V3_11 => self.emit_with_instr_311(args),
+        }
+    }
+
+    fn emit_with_instr_308(&mut self, args: Args) {
         let mut args = args;
         let expr = args.remove(0);
         let lambda = enum_unwrap!(args.remove(0), Expr::Lambda);
@@ -1454,6 +2206,52 @@ impl CodeGenerator {
         self.write_arg(0);
         // push __exit__, __enter__() to the stack
         self.stack_inc_n(2);
+        // the exception handler pushes 6 objects (type, value and traceback twice) and the result of __exit__
+        self.stack_inc_n(7);
+        self.stack_dec_n(7);
+        let lambda_line = lambda.body.last().unwrap().ln_begin().unwrap_or(0);
+        self.emit_with_block(lambda.body, params);
+        let stash = Identifier::private_with_line(Str::from(fresh_varname()), lambda_line);
+        self.emit_store_instr(stash.clone(), Name);
+        self.write_instr(POP_BLOCK);
+        self.write_arg(0);
+        if self.py_version == PythonVersion::V3_8 {
+            // push NULL
+            self.write_instr(BEGIN_FINALLY);
+            self.write_arg(0);
+            self.stack_inc();
+        } else {
+            self.emit_load_const(ValueObj::None);
+        }
+        self.edit_code(
+            idx_setup_with + 1,
+            (self.cur_block().lasti - idx_setup_with - 2) / 2,
+        );
+        self.write_instr(WITH_CLEANUP_START);
+        self.write_arg(0);
+        self.write_instr(WITH_CLEANUP_FINISH);
+        self.write_arg(0);
+        self.write_instr(END_FINALLY);
+        self.write_arg(0);
+        // __exit__ and NULL (None) are popped (the same stack accounting as `emit_with_instr_310`)
+        self.stack_dec_n(3);
+        self.emit_load_name_instr(stash);
+    }
+
+    fn emit_with_instr_310(&mut self, args: Args) {
+        let mut args = args;
+        let expr = args.remove(0);
+        let lambda = enum_unwrap!(args.remove(0), Expr::Lambda);
+        let params = self.gen_param_names(&lambda.params);
+        self.emit_expr(expr);
+        let idx_setup_with = self.cur_block().lasti;
+        self.write_instr(SETUP_WITH);
+        self.write_arg(0);
+        // push __exit__, __enter__() to the stack
+        self.stack_inc_n(2);
+        // the exception handler pushes 6 objects (type, value and traceback twice) and the result of __exit__
+        self.stack_inc_n(7);
+        self.stack_dec_n(7);
         let lambda_line = lambda.body.last().unwrap().ln_begin().unwrap_or(0);
         self.emit_with_block(lambda.body, params);
         let stash = Identifier::private_with_line(Str::from(fresh_varname()), lambda_line);
@@ -1486,13 +2284,105 @@ impl CodeGenerator {
         self.write_instr(RERAISE);
         self.write_arg(1);
         self.edit_code(idx_pop_jump_if_true + 1, self.cur_block().lasti / 2);
-        // self.emit_pop_top();
-        // self.emit_pop_top();
+        // pop the exception info (the stack of this path is not tracked by `stack_len`)
+        for _ in 0..3 {
+            self.write_instr(POP_TOP);
+            self.write_arg(0);
+        }
+        self.write_instr(POP_EXCEPT);
+        self.write_arg(0);
+        // pop __exit__
         self.emit_pop_top();
+        let idx_end = self.cur_block().lasti;
+        self.edit_code(idx_jump_forward + 1, (idx_end - idx_jump_forward - 2) / 2);
+        self.emit_load_name_instr(stash);
+    }
+
+    fn emit_with_instr_311(&mut self, args: Args) {
+        let mut args = args;
+        let expr = args.remove(0);
+        let lambda = enum_unwrap!(args.remove(0), Expr::Lambda);
+        let params = self.gen_param_names(&lambda.params);
+        // the actual stack size (used by the exception table)
+        let depth = self.cur_block().stack_len - self.cur_block().with_depth;
+        self.emit_expr(expr);
+        self.write_instr(BEFORE_WITH);
+        self.write_arg(0);
+        // push __exit__, __enter__() to the stack
+        self.stack_inc_n(2);
+        // the exception handlers push (lasti, prev_exc, exc) and a copy
+        self.stack_inc_n(4);
+        self.stack_dec_n(4);
+        let idx_body = self.cur_block().lasti;
+        let lambda_line = lambda.body.last().unwrap().ln_begin().unwrap_or(0);
+        self.mut_cur_block().with_depth += 1;
+        self.emit_with_block(lambda.body, params);
+        self.mut_cur_block().with_depth -= 1;
+        let stash = Identifier::private_with_line(Str::from(fresh_varname()), lambda_line);
+        self.emit_store_instr(stash.clone(), Name);
+        let idx_body_end = self.cur_block().lasti;
+        // __exit__(None, None, None)
+        self.emit_load_const(ValueObj::None);
+        self.emit_load_const(ValueObj::None);
+        self.emit_load_const(ValueObj::None);
+        self.write_instr(CALL_METHOD);
+        self.write_arg(2);
+        self.stack_dec_n(3);
+        self.emit_pop_top();
+        let idx_jump_forward = self.cur_block().lasti;
+        self.write_instr(JUMP_FORWARD);
+        self.write_arg(0);
+        let idx_handler = self.cur_block().lasti;
+        self.write_instr(PUSH_EXC_INFO);
+        self.write_arg(0);
+        self.write_instr(WITH_EXCEPT_START);
+        self.write_arg(0);
+        let idx_pop_jump_if_true = self.cur_block().lasti;
+        self.write_instr(POP_JUMP_IF_TRUE);
+        self.write_arg(0);
+        self.write_instr(RERAISE);
+        self.write_arg(2);
+        let idx_cleanup = self.cur_block().lasti;
+        self.write_instr(COPY);
+        self.write_arg(3);
         self.write_instr(POP_EXCEPT);
         self.write_arg(0);
+        self.write_instr(RERAISE);
+        self.write_arg(1);
+        // the exception is suppressed
+        let idx_suppressed = self.cur_block().lasti;
+        self.edit_code(idx_pop_jump_if_true + 1, idx_suppressed / 2);
+        self.emit_pop_top();
+        self.write_instr(POP_EXCEPT);
+        self.write_arg(0);
+        self.write_instr(POP_TOP);
+        self.write_arg(0);
+        self.write_instr(POP_TOP);
+        self.write_arg(0);
         let idx_end = self.cur_block().lasti;
         self.edit_code(idx_jump_forward + 1, (idx_end - idx_jump_forward - 2) / 2);
+        let table = &mut self.mut_cur_block_codeobj().exception_table;
+        table.push(ExceptionTableEntry::new(
+            idx_body,
+            idx_body_end,
+            idx_handler,
+            depth + 1,
+            true,
+        ));
+        table.push(ExceptionTableEntry::new(
+            idx_handler,
+            idx_cleanup,
+            idx_cleanup,
+            depth + 3,
+            true,
+        ));
+        table.push(ExceptionTableEntry::new(
+            idx_suppressed,
+            idx_suppressed + 2,
+            idx_cleanup,
+            depth + 3,
+            true,
+        ));
         self.emit_load_name_instr(stash);
     }
 
@@ -1506,6 +2396,7 @@ impl CodeGenerator {
                     self.emit_call_local(ident, call.args)
                 }
                 other => {
+                    self.emit_push_null();
                     self.emit_expr(other);
                     self.emit_args(call.args, Name);
                 }
@@ -1520,6 +2411,7 @@ impl CodeGenerator {
             "Del" => self.emit_del_instr(args),
             "discard" => self.emit_discard_instr(args),
             "for" | "for!" => self.emit_for_instr(args),
+            "while!" => self.emit_while_instr(args),
             "if" | "if!" => self.emit_if_instr(args),
             "match" | "match!" => self.emit_match_instr(args, true),
             "with!" => self.emit_with_instr(args),
@@ -1528,10 +2420,12 @@ impl CodeGenerator {
                     self.load_module_type();
                     self.module_type_loaded = true;
                 }
+                self.emit_push_null();
                 self.emit_load_name_instr(local);
                 self.emit_args(args, Name);
             }
             _ => {
+                self.emit_push_null();
                 self.emit_load_name_instr(local);
                 self.emit_args(args, Name);
             }
@@ -1560,12 +2454,16 @@ impl CodeGenerator {
             self.emit_expr(arg.expr);
         }
         if let Some(var_args) = &args.var_args {
+            let pre_309 = self.py_version < PythonVersion::V3_9;
             if pos_len > 0 {
-                self.write_instr(BUILD_LIST);
+                self.write_instr(if pre_309 { BUILD_TUPLE } else { BUILD_LIST });
                 self.write_arg(pos_len as u8);
             }
             self.emit_expr(var_args.expr.clone());
-            if pos_len > 0 {
+            if pos_len > 0 && pre_309 {
+                self.write_instr(BUILD_TUPLE_UNPACK_WITH_CALL);
+                self.write_arg(2);
+            } else if pos_len > 0 {
                 self.write_instr(LIST_EXTEND);
                 self.write_arg(1);
                 self.write_instr(LIST_TO_TUPLE);
@@ -1577,11 +2475,10 @@ impl CodeGenerator {
             self.emit_expr(arg.expr);
         }
         let kwsc = if !kws.is_empty() {
-            let kws_tuple = ValueObj::from(kws);
-            self.emit_load_const(kws_tuple);
+            let kwsc = self.emit_kw_names(kws);
             self.write_instr(CALL_FUNCTION_KW);
             self.write_arg(argc as u8);
-            1
+            kwsc
         } else {
             if args.var_args.is_some() {
                 self.write_instr(CALL_FUNCTION_EX);
@@ -1602,6 +2499,12 @@ impl CodeGenerator {
         };
         // (1 (subroutine) + argc + kwsc) input objects -> 1 return object
         self.stack_dec_n((1 + argc + kwsc) - 1);
+        if kind.is_method() {
+            // the object (or NULL) pushed by LOAD_METHOD
+            self.stack_dec();
+        } else {
+            self.stack_dec_null();
+        }
     }
 
     /// X.update! x -> x + 1
@@ -1611,12 +2514,14 @@ impl CodeGenerator {
         log!(info "entered {}", fn_name!());
         let acc = enum_unwrap!(obj, Expr::Accessor);
         let func = args.remove_left_or_key("f").unwrap();
+        self.emit_push_null();
         self.emit_expr(func);
         self.emit_acc(acc.clone());
         self.write_instr(CALL_FUNCTION);
         self.write_arg(1);
         // (1 (subroutine) + argc) input objects -> 1 return object
         self.stack_dec_n((1 + 1) - 1);
+        self.stack_dec_null();
         self.store_acc(acc);
     }
 
@@ -1624,6 +2529,7 @@ impl CodeGenerator {
     fn emit_call_fake_method(&mut self, obj: Expr, mut method_name: Identifier, mut args: Args) {
         log!(info "entered {}", fn_name!());
         method_name.dot = None;
+        self.emit_push_null();
         self.emit_load_name_instr(method_name);
         args.insert_pos(0, PosArg::new(obj));
         self.emit_args(args, Name);
@@ -1637,12 +2543,22 @@ impl CodeGenerator {
         self.write_instr(Opcode::POP_JUMP_IF_TRUE);
         self.write_arg(0);
         self.stack_dec();
-        self.write_instr(Opcode::LOAD_ASSERTION_ERROR);
-        self.write_arg(0);
-        if let Some(expr) = args.try_remove(0) {
+        let msg = args.try_remove(0);
+        if msg.is_some() {
+            self.emit_push_null();
+        }
+        if self.py_version >= PythonVersion::V3_9 {
+            self.write_instr(Opcode::LOAD_ASSERTION_ERROR);
+            self.write_arg(0);
+        } else {
+            self.emit_load_name_instr(Identifier::public("AssertionError"));
+            self.stack_dec();
+        }
+        if let Some(expr) = msg {
             self.emit_expr(expr);
             self.write_instr(Opcode::CALL_FUNCTION);
             self.write_arg(1);
+            self.stack_dec_null();
         }
         self.write_instr(Opcode::RAISE_VARARGS);
         self.write_arg(1);
@@ -1656,6 +2572,7 @@ impl CodeGenerator {
         let attrs_len = rec.attrs.len();
         // making record type
         let ident = Identifier::private("#NamedTuple");
+        self.emit_push_null();
         self.emit_load_name_instr(ident);
         // record name, let it be anonymous
         self.emit_load_const("Record");
@@ -1673,10 +2590,12 @@ impl CodeGenerator {
         self.write_arg(2);
         // (1 (subroutine) + argc + kwsc) input objects -> 1 return object
         self.stack_dec_n((1 + 2 + 0) - 1);
+        self.stack_dec_null();
         let ident = Identifier::private("#rec");
         self.emit_store_instr(ident, Name);
         // making record instance
         let ident = Identifier::private("#rec");
+        self.emit_push_null();
         self.emit_load_name_instr(ident);
         for field in rec.attrs.into_iter() {
             self.emit_frameless_block(field.body.block, vec![]);
@@ -1685,43 +2604,23 @@ impl CodeGenerator {
         self.write_arg(attrs_len as u8);
         // (1 (subroutine) + argc + kwsc) input objects -> 1 return object
         self.stack_dec_n((1 + attrs_len + 0) - 1);
+        self.stack_dec_null();
     }
 
     fn emit_expr(&mut self, expr: Expr) {
         log!(info "entered {} ({expr})", fn_name!());
-        if expr.ln_begin().unwrap_or_else(|| panic!("{expr}")) > self.cur_block().prev_lineno {
+        let ln_begin = expr.ln_begin().unwrap_or_else(|| panic!("{expr}"));
+        if ln_begin > self.cur_block().prev_lineno {
             let sd = self.cur_block().lasti - self.cur_block().prev_lasti;
-            let ld = expr.ln_begin().unwrap() - self.cur_block().prev_lineno;
-            if ld != 0 {
-                if sd != 0 {
-                    self.mut_cur_block_codeobj().lnotab.push(sd as u8);
-                    self.mut_cur_block_codeobj().lnotab.push(ld as u8);
-                } else {
-                    // empty lines
-                    if let Some(last_ld) = self.mut_cur_block_codeobj().lnotab.last_mut() {
-                        *last_ld += ld as u8;
-                    } else {
-                        // a block starts with an empty line
-                        self.mut_cur_block_codeobj().lnotab.push(0);
-                        self.mut_cur_block_codeobj().lnotab.push(ld as u8);
-                    }
-                }
-                self.mut_cur_block().prev_lineno += ld;
-                self.mut_cur_block().prev_lasti = self.cur_block().lasti;
-            } else {
-                CompileError::compiler_bug(
-                    0,
-                    self.cfg.input.clone(),
-                    expr.loc(),
-                    fn_name_full!(),
-                    line!(),
-                )
-                .write_to_stderr();
-                self.crash("codegen failed: invalid bytecode format");
-            }
+            let ld = ln_begin - self.cur_block().prev_lineno;
+            self.mut_cur_block_codeobj().push_lnotab(sd, ld as i64);
+            self.mut_cur_block().prev_lineno += ld;
+            self.mut_cur_block().prev_lasti = self.cur_block().lasti;
         }
+        let (loc, start) = (expr.loc(), self.cur_block().lasti);
         match expr {
             Expr::Lit(lit) => self.emit_load_const(lit.value),
+            Expr::StrInterp(interp) => self.emit_str_interp(interp),
             Expr::Accessor(acc) => self.emit_acc(acc),
             Expr::Def(def) => self.emit_def(def),
             Expr::ClassDef(class) => self.emit_class_def(class),
@@ -1730,7 +2629,6 @@ impl CodeGenerator {
             Expr::UnaryOp(unary) => self.emit_unaryop(unary),
             Expr::BinOp(bin) => self.emit_binop(bin),
             Expr::Call(call) => self.emit_call(call),
-            // TODO: list comprehension
             Expr::Array(arr) => match arr {
                 Array::Normal(mut arr) => {
                     let len = arr.elems.len();
@@ -1754,7 +2652,13 @@ impl CodeGenerator {
                     self.write_arg(0);
                     self.stack_dec();
                 }
-                other => todo!("{other}"),
+                Array::Comprehension(arr) => self.emit_comprehension(
+                    "<listcomp>",
+                    (BUILD_LIST, LIST_APPEND),
+                    arr.generators,
+                    arr.guards,
+                    vec![*arr.elem],
+                ),
             },
             // TODO: tuple comprehension
             // TODO: tuples can be const
@@ -1792,6 +2696,13 @@ impl CodeGenerator {
                     self.write_instr(BUILD_SET);
                     self.write_arg(1u8);
                 }
+                crate::hir::Set::Comprehension(st) => self.emit_comprehension(
+                    "<setcomp>",
+                    (BUILD_SET, SET_ADD),
+                    st.generators,
+                    st.guards,
+                    vec![*st.elem],
+                ),
             },
             Expr::Dict(dict) => match dict {
                 crate::hir::Dict::Normal(dic) => {
@@ -1808,7 +2719,13 @@ impl CodeGenerator {
                         self.stack_dec_n(2 * len - 1);
                     }
                 }
-                other => todo!("{other}"),
+                crate::hir::Dict::Comprehension(dic) => self.emit_comprehension(
+                    "<dictcomp>",
+                    (BUILD_MAP, MAP_ADD),
+                    dic.generators,
+                    dic.guards,
+                    vec![*dic.key, *dic.value],
+                ),
             },
             Expr::Record(rec) => self.emit_record(rec),
             Expr::Code(code) => {
@@ -1831,6 +2748,10 @@ impl CodeGenerator {
                 self.crash("cannot compile this expression at this time");
             }
         }
+        let end = self.cur_block().lasti;
+        self.mut_cur_block_codeobj()
+            .spans
+            .push(CodeSpan::new(start, end, loc));
     }
 
     /// forブロックなどで使う
@@ -1924,15 +2845,6 @@ impl CodeGenerator {
         }
         // end of flagging
         let unit = self.units.pop().unwrap();
-        if !self.units.is_empty() {
-            let ld = unit.prev_lineno - self.cur_block().prev_lineno;
-            if ld != 0 {
-                if let Some(l) = self.mut_cur_block_codeobj().lnotab.last_mut() {
-                    *l += ld as u8;
-                }
-                self.mut_cur_block().prev_lineno += ld;
-            }
-        }
         unit.codeobj
     }
 
@@ -1947,7 +2859,7 @@ impl CodeGenerator {
         let self_param = VarName::from_str_and_line(Str::ever("self"), line);
         let self_param = ParamSignature::new(ParamPattern::VarName(self_param), None, None);
         let params = Params::new(vec![self_param, param], None, vec![], None);
-        let subr_sig = SubrSignature::new(ident, params, __new__.clone());
+        let subr_sig = SubrSignature::new(vec![], ident, params, __new__.clone());
         let mut attrs = vec![];
         match __new__.non_default_params().unwrap()[0].typ() {
             // namedtupleは仕様上::xなどの名前を使えない
@@ -2004,7 +2916,12 @@ impl CodeGenerator {
         let param_name = fresh_varname();
         let param = VarName::from_str_and_line(Str::from(param_name.clone()), line);
         let param = ParamSignature::new(ParamPattern::VarName(param), None, None);
-        let sig = SubrSignature::new(ident, Params::new(vec![param], None, vec![], None), __new__);
+        let sig = SubrSignature::new(
+            vec![],
+            ident,
+            Params::new(vec![param], None, vec![], None),
+            __new__,
+        );
         let arg = PosArg::new(Expr::Accessor(Accessor::private_with_line(
             Str::from(param_name),
             line,
@@ -2045,6 +2962,7 @@ impl CodeGenerator {
             &name,
             firstlineno,
         ));
+        self.mut_cur_block().is_subr = true;
         for expr in block.into_iter() {
             self.emit_expr(expr);
             // NOTE: 各行のトップレベルでは0個または1個のオブジェクトが残っている
@@ -2078,18 +2996,6 @@ impl CodeGenerator {
         }
         // end of flagging
         let unit = self.units.pop().unwrap();
-        // increase lineno
-        if !self.units.is_empty() {
-            let ld = unit
-                .prev_lineno
-                .saturating_sub(self.cur_block().prev_lineno);
-            if ld != 0 {
-                if let Some(l) = self.mut_cur_block_codeobj().lnotab.last_mut() {
-                    *l += ld as u8;
-                }
-                self.mut_cur_block().prev_lineno += ld;
-            }
-        }
         unit.codeobj
     }
 
@@ -2112,15 +3018,21 @@ impl CodeGenerator {
         self.emit_load_const(erg_std_path().to_str().unwrap());
         self.write_instr(CALL_METHOD);
         self.write_arg(1u8);
-        self.stack_dec();
+        self.stack_dec_n((2 + 1) - 1);
         self.emit_pop_top();
         // escaping
         self.emit_global_import_items(
             Identifier::public("_erg_std_prelude"),
-            vec![(
-                Identifier::public("in_operator"),
-                Some(Identifier::private("#in_operator")),
-            )],
+            vec![
+                (
+                    Identifier::public("in_operator"),
+                    Some(Identifier::private("#in_operator")),
+                ),
+                (
+                    Identifier::public("is_ok"),
+                    Some(Identifier::private("#is_ok")),
+                ),
+            ],
         );
         self.emit_import_all_instr(Identifier::public("_erg_std_prelude"));
     }
@@ -2163,6 +3075,8 @@ impl CodeGenerator {
 
     pub fn emit(&mut self, hir: HIR) -> CodeObj {
         log!(info "the code-generating process has started.{RESET}");
+        let magic_num = *self.cfg.python_ver.get_or_insert_with(detect_magic_number);
+        self.py_version = PythonVersion::from_checked_magic_num(magic_num);
         self.unit_size += 1;
         self.units.push(CodeGenUnit::new(
             self.unit_size,
@@ -2175,14 +3089,6 @@ impl CodeGenerator {
             self.load_prelude();
             self.prelude_loaded = true;
         }
-        let mut print_point = 0;
-        if self.input().is_repl() {
-            print_point = self.cur_block().lasti;
-            self.emit_load_name_instr(Identifier::public("print"));
-            // Consistency will be taken later (when NOP replacing)
-            // 後で(NOP書き換え時)整合性を取る
-            self.stack_dec();
-        }
         for expr in hir.module.into_iter() {
             self.emit_expr(expr);
             // TODO: discard
@@ -2190,18 +3096,8 @@ impl CodeGenerator {
                 self.emit_pop_top();
             }
         }
+        // In the REPL, the last value is returned to be displayed by the REPL server
         self.cancel_pop_top(); // 最後の値は戻り値として取っておく
-        if self.input().is_repl() {
-            if self.cur_block().stack_len == 0 {
-                // remains `print`, nothing to be printed
-                self.edit_code(print_point, NOP as usize);
-            } else {
-                self.stack_inc();
-                self.write_instr(CALL_FUNCTION);
-                self.write_arg(1_u8);
-            }
-            self.stack_dec_n(self.cur_block().stack_len as usize);
-        }
         if self.cur_block().stack_len == 0 {
             self.emit_load_const(ValueObj::None);
         } else if self.cur_block().stack_len > 1 {
@@ -2225,15 +3121,6 @@ impl CodeGenerator {
         }
         // end of flagging
         let unit = self.units.pop().unwrap();
-        if !self.units.is_empty() {
-            let ld = unit.prev_lineno - self.cur_block().prev_lineno;
-            if ld != 0 {
-                if let Some(l) = self.mut_cur_block_codeobj().lnotab.last_mut() {
-                    *l += ld as u8;
-                }
-                self.mut_cur_block().prev_lineno += ld;
-            }
-        }
         log!(info "the code-generating process has completed.{RESET}");
         unit.codeobj
     }
@@ -1,21 +1,30 @@
 //! defines `Compiler`.
 //!
 //! コンパイラーを定義する
-use std::path::Path;
+use std::path::{Path, PathBuf};
 
 use erg_common::config::ErgConfig;
+use erg_common::error::MultiErrorDisplay;
 use erg_common::log;
 use erg_common::traits::{Runnable, Stream};
 
 use crate::ty::codeobj::CodeObj;
+use crate::ty::peephole::peephole;
+use crate::ty::verify::verify_generated;
 
 use crate::build_hir::HIRBuilder;
-use crate::codegen::CodeGenerator;
+use crate::codegen::{check_python_version, CodeGenerator};
 use crate::desugar_hir::HIRDesugarer;
 use crate::error::{CompileError, CompileErrors};
 use crate::hir::{Expr, HIR};
 use crate::link::Linker;
 use crate::mod_cache::SharedModuleCache;
+use crate::optimize::HIROptimizer;
+
+/// The path of the source map (see `CodeObj::source_map`) dumped with `pyc_path`
+pub fn source_map_path<P: AsRef<Path>>(pyc_path: P) -> PathBuf {
+    pyc_path.as_ref().with_extension("pyc.map")
+}
 
 /// * registered as global -> Global
 /// * defined in the toplevel scope (and called in the inner scope) -> Global
@@ -96,6 +105,7 @@ pub struct Compiler {
     pub cfg: ErgConfig,
     builder: HIRBuilder,
     mod_cache: SharedModuleCache,
+    optimizer: HIROptimizer,
     code_generator: CodeGenerator,
 }
 
@@ -114,6 +124,7 @@ impl Runnable for Compiler {
                 mod_cache.clone(),
                 py_mod_cache,
             ),
+            optimizer: HIROptimizer::new(cfg.copy()),
             code_generator: CodeGenerator::new(cfg.copy()),
             mod_cache,
             cfg,
@@ -140,7 +151,7 @@ impl Runnable for Compiler {
 
     fn eval(&mut self, src: String) -> Result<String, CompileErrors> {
         let codeobj = self.compile(src, "eval")?;
-        Ok(codeobj.code_info())
+        Ok(codeobj.code_info(None))
     }
 }
 
@@ -152,7 +163,11 @@ impl Compiler {
         mode: &str,
     ) -> Result<(), CompileErrors> {
         let code = self.compile(src, mode)?;
-        code.dump_as_pyc(pyc_path, self.cfg.python_ver)
+        // the target version is determined by the code generator
+        let python_ver = self.code_generator.python_ver();
+        code.dump_source_map(source_map_path(&pyc_path), python_ver.unwrap())
+            .expect("failed to dump a source map (maybe permission denied)");
+        code.dump_as_pyc(pyc_path, python_ver)
             .expect("failed to dump a .pyc file (maybe permission denied)");
         Ok(())
     }
@@ -164,16 +179,21 @@ impl Compiler {
         mode: &str,
     ) -> Result<Option<Expr>, CompileErrors> {
         let (code, last) = self.eval_compile(src, mode)?;
-        code.dump_as_pyc(pyc_path, self.cfg.python_ver)
+        code.dump_as_pyc(pyc_path, self.code_generator.python_ver())
             .expect("failed to dump a .pyc file (maybe permission denied)");
         Ok(last)
     }
 
     pub fn compile(&mut self, src: String, mode: &str) -> Result<CodeObj, CompileErrors> {
         log!(info "the compiling process has started.");
+        check_python_version(&self.cfg).map_err(CompileErrors::from)?;
         let hir = self.build_link_desugar(src, mode)?;
-        let codeobj = self.code_generator.emit(hir);
-        log!(info "code object:\n{}", codeobj.code_info());
+        let mut codeobj = self.code_generator.emit(hir);
+        if self.cfg.opt_level >= 2 {
+            peephole(&mut codeobj);
+        }
+        log!(info "code object:\n{}", codeobj.code_info(None));
+        self.verify(&codeobj)?;
         log!(info "the compiling process has completed");
         Ok(codeobj)
     }
@@ -184,18 +204,40 @@ impl Compiler {
         mode: &str,
     ) -> Result<(CodeObj, Option<Expr>), CompileErrors> {
         log!(info "the compiling process has started.");
+        check_python_version(&self.cfg).map_err(CompileErrors::from)?;
         let hir = self.build_link_desugar(src, mode)?;
         let last = hir.module.last().cloned();
-        let codeobj = self.code_generator.emit(hir);
-        log!(info "code object:\n{}", codeobj.code_info());
+        let mut codeobj = self.code_generator.emit(hir);
+        if self.cfg.opt_level >= 2 {
+            peephole(&mut codeobj);
+        }
+        log!(info "code object:\n{}", codeobj.code_info(None));
+        self.verify(&codeobj)?;
         log!(info "the compiling process has completed");
         Ok((codeobj, last))
     }
 
+    /// Verifies the stack depth, the jump targets and the operand indices of the generated bytecode (only in the debug mode).
+    /// `CodeGenerator` computes the stack size by hand, and a mistake causes a segfault or a `SystemError` at runtime
+    fn verify(&self, codeobj: &CodeObj) -> Result<(), CompileErrors> {
+        if cfg!(feature = "debug") {
+            let python_ver = self.code_generator.python_ver().unwrap();
+            verify_generated(codeobj, python_ver).map_err(|err| {
+                let caused_by = err.caused_by.clone();
+                CompileError::new(err.into(), self.cfg.input.clone(), caused_by)
+            })?;
+        }
+        Ok(())
+    }
+
     fn build_link_desugar(&mut self, src: String, mode: &str) -> Result<HIR, CompileErrors> {
         let hir = self.builder.build(src, mode).map_err(|(_, errs)| errs)?;
         let linker = Linker::new(&self.cfg, &self.mod_cache);
         let hir = linker.link(hir);
+        let (hir, warns) = self.optimizer.optimize(hir);
+        if self.cfg.verbose >= 2 {
+            warns.write_all_stderr(self.cfg.output_format);
+        }
         Ok(HIRDesugarer::desugar(hir))
     }
 }
@@ -189,6 +189,11 @@ impl Context {
                         .as_ref()
                         .map(|va| self.supertype_of(&Type, va.typ()))
                         .unwrap_or(true)
+                    && subr
+                        .kw_var_params
+                        .as_ref()
+                        .map(|kva| self.supertype_of(&Type, kva.typ()))
+                        .unwrap_or(true)
                     && self.supertype_of(&Type, &subr.return_t),
             ),
             (
@@ -301,10 +306,8 @@ impl Context {
                     (Absolutely, true) => {
                         return (Absolutely, true);
                     }
-                    (Maybe, _) => {
-                        if self.structural_supertype_of(lhs, &rhs_sup) {
-                            return (Absolutely, true);
-                        }
+                    (Maybe, _) if self.structural_supertype_of(lhs, &rhs_sup) => {
+                        return (Absolutely, true);
                     }
                     _ => {}
                 }
@@ -337,10 +340,8 @@ impl Context {
                     (Absolutely, true) => {
                         return (Absolutely, true);
                     }
-                    (Maybe, _) => {
-                        if self.structural_supertype_of(lhs, &rhs_sup) {
-                            return (Absolutely, true);
-                        }
+                    (Maybe, _) if self.structural_supertype_of(lhs, &rhs_sup) => {
+                        return (Absolutely, true);
                     }
                     _ => {}
                 }
@@ -414,6 +415,9 @@ impl Context {
                 && ls.var_params.as_ref().zip(rs.var_params.as_ref()).map(|(l, r)| {
                     self.subtype_of(l.typ(), r.typ())
                 }).unwrap_or(true)
+                && ls.kw_var_params.as_ref().zip(rs.kw_var_params.as_ref()).map(|(l, r)| {
+                    self.subtype_of(l.typ(), r.typ())
+                }).unwrap_or(true)
                 && kw_check() // contravariant
             }
             // ?T(<: Nat) !:> ?U(:> Int)
@@ -571,6 +575,9 @@ impl Context {
                 if !self.supertype_of(&l.t, &r.t) {
                     return false;
                 }
+                if self.is_trivial_refinement(l) {
+                    return true;
+                }
                 let mut r_preds_clone = r.preds.clone();
                 for l_pred in l.preds.iter() {
                     for r_pred in r.preds.iter() {
@@ -605,10 +612,14 @@ impl Context {
                 self.supertype_of(&l, &r.t)
             }
             // ({I: Int | True} :> Int) == true, ({N: Nat | ...} :> Int) == false, ({I: Int | I >= 0} :> Int) == false
+            // ({I: Int | I >= 0 or I <= -1} :> Int) == true
             (Refinement(l), r) => {
                 if l.preds.is_empty() {
                     unreachable!()
                 }
+                if self.is_trivial_refinement(l) {
+                    return self.supertype_of(&l.t, r);
+                }
                 if l.preds
                     .iter()
                     .any(|p| p.mentions(&l.var) && p.can_be_false())
@@ -641,11 +652,12 @@ impl Context {
                     .unwrap();
                 self.structural_supertype_of(l, &q_callable)
             }
+            // Int :> (Nat or Str) == Int :> Nat && Int :> Str == false
+            // (Int or Str) :> (Nat or Str) == (Int or Str) :> Nat && (Int or Str) :> Str == true
+            (lhs, Or(l_or, r_or)) => self.supertype_of(lhs, l_or) && self.supertype_of(lhs, r_or),
             // (Int or Str) :> Nat == Int :> Nat || Str :> Nat == true
             // (Num or Show) :> Show == Num :> Show || Show :> Num == true
             (Or(l_or, r_or), rhs) => self.supertype_of(l_or, rhs) || self.supertype_of(r_or, rhs),
-            // Int :> (Nat or Str) == Int :> Nat && Int :> Str == false
-            (lhs, Or(l_or, r_or)) => self.supertype_of(lhs, l_or) && self.supertype_of(lhs, r_or),
             // (Num and Show) :> Show == false
             (And(l_and, r_and), rhs) => {
                 self.supertype_of(l_and, rhs) && self.supertype_of(r_and, rhs)
@@ -825,10 +837,10 @@ impl Context {
                 } else { Some(Any) }
             },
             (TyParam::FreeVar(fv), p) if fv.is_linked() => {
-                self.try_cmp(&*fv.crack(), p)
+                self.try_cmp(&fv.crack(), p)
             }
             (p, TyParam::FreeVar(fv)) if fv.is_linked() => {
-                self.try_cmp(p, &*fv.crack())
+                self.try_cmp(p, &fv.crack())
             }
             (
                 l @ (TyParam::FreeVar(_) | TyParam::Erased(_) | TyParam::MonoQVar(_)),
@@ -948,6 +960,10 @@ impl Context {
             }
             (Refinement(l), Refinement(r)) => Type::Refinement(self.union_refinement(l, r)),
             (t, Type::Never) | (Type::Never, t) => t.clone(),
+            // `Error or {0}` cannot be expressed as a refinement type
+            (t, Refinement(r)) | (Refinement(r), t) if matches!(self.union(t, &r.t), Or(_, _)) => {
+                or(t.clone(), Type::Refinement(r.clone()))
+            }
             (t, Refinement(r)) | (Refinement(r), t) => {
                 let t = self.into_refinement(t.clone());
                 Type::Refinement(self.union_refinement(&t, r))
@@ -994,6 +1010,22 @@ impl Context {
         }
     }
 
+    /// Splits `T or E` (`E <: Error`) into `(T, E)`
+    pub(crate) fn split_error_t(&self, t: &Type) -> (Type, Type) {
+        match t {
+            FreeVar(fv) if fv.is_linked() => self.split_error_t(&fv.crack()),
+            Or(l, r) => {
+                let (l_ok, l_err) = self.split_error_t(l);
+                let (r_ok, r_err) = self.split_error_t(r);
+                (self.union(&l_ok, &r_ok), self.union(&l_err, &r_err))
+            }
+            t if !t.is_unbound_var() && self.subtype_of(t, &Type::Error) => {
+                (Type::Never, t.clone())
+            }
+            t => (t.clone(), Type::Never),
+        }
+    }
+
     /// see doc/LANG/compiler/refinement_subtyping.md
     /// ```python
     /// assert is_super_pred({I >= 0}, {I == 0})
@@ -1045,6 +1077,76 @@ impl Context {
         }
     }
 
+    /// Returns `true` if the predicates of the refinement type hold for every integer,
+    /// i.e. the refinement type is equal to its base type.
+    /// e.g. `{I: Int | I >= 0 or I <= -1}`, `{I: Int | I == 0 or I != 0}`
+    fn is_trivial_refinement(&self, refine: &RefinementType) -> bool {
+        if !self.subtype_of(&refine.t, &Int) {
+            return false;
+        }
+        let mut intervals = vec![];
+        for pred in refine.preds.iter() {
+            intervals.extend(Self::int_intervals(&refine.var, pred));
+        }
+        intervals.sort();
+        // the smallest integer not covered yet
+        let mut next = i128::MIN;
+        for (lo, hi) in intervals.into_iter() {
+            if lo > next {
+                return false;
+            }
+            if hi == i128::MAX {
+                return true;
+            }
+            next = next.max(hi + 1);
+        }
+        false
+    }
+
+    /// Converts the predicate into integer intervals (closed, `i128::MIN`/`MAX` means -∞/∞).
+    /// Predicates that cannot be converted are regarded as empty intervals.
+    fn int_intervals(var: &str, pred: &Predicate) -> Vec<(i128, i128)> {
+        let int = |tp: &TyParam| match tp {
+            TyParam::Value(ValueObj::Int(i)) => Some(*i as i128),
+            TyParam::Value(ValueObj::Nat(n)) => Some(*n as i128),
+            _ => None,
+        };
+        match pred {
+            Pred::Value(ValueObj::Bool(true)) => vec![(i128::MIN, i128::MAX)],
+            Pred::GreaterEqual { lhs, rhs } if &lhs[..] == var => {
+                int(rhs).map(|n| vec![(n, i128::MAX)]).unwrap_or_default()
+            }
+            Pred::LessEqual { lhs, rhs } if &lhs[..] == var => {
+                int(rhs).map(|n| vec![(i128::MIN, n)]).unwrap_or_default()
+            }
+            Pred::Equal { lhs, rhs } if &lhs[..] == var => {
+                int(rhs).map(|n| vec![(n, n)]).unwrap_or_default()
+            }
+            Pred::NotEqual { lhs, rhs } if &lhs[..] == var => int(rhs)
+                .map(|n| vec![(i128::MIN, n - 1), (n + 1, i128::MAX)])
+                .unwrap_or_default(),
+            Pred::Or(l, r) => {
+                let mut intervals = Self::int_intervals(var, l);
+                intervals.extend(Self::int_intervals(var, r));
+                intervals
+            }
+            Pred::And(l, r) => {
+                let rhs = Self::int_intervals(var, r);
+                let mut intervals = vec![];
+                for (llo, lhi) in Self::int_intervals(var, l) {
+                    for (rlo, rhi) in rhs.iter() {
+                        let (lo, hi) = (llo.max(*rlo), lhi.min(*rhi));
+                        if lo <= hi {
+                            intervals.push((lo, hi));
+                        }
+                    }
+                }
+                intervals
+            }
+            _ => vec![],
+        }
+    }
+
     pub(crate) fn is_sub_constraint_of(&self, l: &Constraint, r: &Constraint) -> bool {
         match (l, r) {
             // (?I: Nat) <: (?I: Int)
@@ -50,7 +50,7 @@ pub fn type_from_token_kind(kind: TokenKind) -> Type {
     }
 }
 
-fn try_get_op_kind_from_token(kind: TokenKind) -> EvalResult<OpKind> {
+pub(crate) fn try_get_op_kind_from_token(kind: TokenKind) -> EvalResult<OpKind> {
     match kind {
         TokenKind::Plus => Ok(OpKind::Add),
         TokenKind::Minus => Ok(OpKind::Sub),
@@ -72,6 +72,9 @@ fn try_get_op_kind_from_token(kind: TokenKind) -> EvalResult<OpKind> {
         TokenKind::BitOr => Ok(OpKind::BitOr),
         TokenKind::Shl => Ok(OpKind::Shl),
         TokenKind::Shr => Ok(OpKind::Shr),
+        TokenKind::PrePlus => Ok(OpKind::Pos),
+        TokenKind::PreMinus => Ok(OpKind::Neg),
+        TokenKind::PreBitNot => Ok(OpKind::Invert),
         TokenKind::Mutate => Ok(OpKind::Mutate),
         _other => todo!("{_other}"),
     }
@@ -155,7 +158,7 @@ impl<'c> SubstContext<'c> {
             );
         }
         let params = param_names
-            .zip(substituted.typarams().into_iter())
+            .zip(substituted.typarams())
             .collect::<Dict<_, _>>();
         if cfg!(feature = "debug") {
             for v in params.values() {
@@ -242,6 +245,9 @@ impl<'c> SubstContext<'c> {
                 for d_param in subr.default_params.iter() {
                     self.substitute_t(d_param.typ())?;
                 }
+                if let Some(kw_var_params) = &subr.kw_var_params {
+                    self.substitute_t(kw_var_params.typ())?;
+                }
                 self.substitute_t(&subr.return_t)?;
             }
             Type::And(l, r) | Type::Or(l, r) | Type::Not(l, r) => {
@@ -433,9 +439,8 @@ impl Context {
             };
             // TODO: set params
             self.grow(__name__, ContextKind::Instant, vis, tv_ctx);
-            let obj = self.eval_const_block(&def.body.block).map_err(|e| {
+            let obj = self.eval_const_block(&def.body.block).inspect_err(|_e| {
                 self.pop();
-                e
             })?;
             match self.check_decls_and_pop() {
                 Ok(_) => {
@@ -528,6 +533,12 @@ impl Context {
                 self.instantiate_param_ty(sig, None, Some(&tv_ctx), RegistrationMode::Normal)?;
             default_params.push(pt);
         }
+        let kw_var_params = if let Some(p) = lambda.sig.params.kw_var_args.as_ref() {
+            let pt = self.instantiate_param_ty(p, None, Some(&tv_ctx), RegistrationMode::Normal)?;
+            Some(pt)
+        } else {
+            None
+        };
         // HACK: should avoid cloning
         let mut lambda_ctx = Context::instant(
             Str::ever("<lambda>"),
@@ -546,6 +557,7 @@ impl Context {
             non_default_params.clone(),
             var_params.clone(),
             default_params.clone(),
+            kw_var_params.clone(),
             v_enum(set![return_t.clone()]),
         );
         let sig_t = self.generalize_t(sig_t);
@@ -554,6 +566,7 @@ impl Context {
             non_default_params,
             var_params,
             default_params,
+            kw_var_params,
             // TODO: unwrap
             return_t.as_type().unwrap().into_typ(),
         );
@@ -715,7 +728,7 @@ impl Context {
             (TyParam::Value(lhs), TyParam::Value(rhs)) => self
                 .eval_bin(op, lhs.clone(), rhs.clone())
                 .map(TyParam::value),
-            (TyParam::FreeVar(fv), r) if fv.is_linked() => self.eval_bin_tp(op, &*fv.crack(), r),
+            (TyParam::FreeVar(fv), r) if fv.is_linked() => self.eval_bin_tp(op, &fv.crack(), r),
             (TyParam::FreeVar(_), _) if op.is_comparison() => Ok(TyParam::value(true)),
             // _: Nat <= 10 => true
             // TODO: maybe this is wrong, we should do the type-checking of `<=`
@@ -725,7 +738,7 @@ impl Context {
                 Ok(TyParam::value(true))
             }
             (TyParam::FreeVar(_), _) => Ok(TyParam::bin(op, lhs.clone(), rhs.clone())),
-            (l, TyParam::FreeVar(fv)) if fv.is_linked() => self.eval_bin_tp(op, l, &*fv.crack()),
+            (l, TyParam::FreeVar(fv)) if fv.is_linked() => self.eval_bin_tp(op, l, &fv.crack()),
             (_, TyParam::FreeVar(_)) if op.is_comparison() => Ok(TyParam::value(true)),
             // 10 <= _: Nat => true
             (_, TyParam::Erased(t))
@@ -741,9 +754,33 @@ impl Context {
 
     fn eval_unary(&self, op: OpKind, val: ValueObj) -> EvalResult<ValueObj> {
         match op {
-            Pos => todo!(),
-            Neg => todo!(),
-            Invert => todo!(),
+            Pos => match val {
+                ValueObj::Int(_) | ValueObj::Nat(_) | ValueObj::Float(_) => Ok(val),
+                _ => Err(EvalErrors::from(EvalError::unreachable(
+                    self.cfg.input.clone(),
+                    fn_name!(),
+                    line!(),
+                ))),
+            },
+            Neg => match val {
+                ValueObj::Int(i) if i != i32::MIN => Ok(-val),
+                ValueObj::Nat(n) if n <= i32::MAX as u64 => Ok(-val),
+                ValueObj::Float(_) | ValueObj::Inf | ValueObj::NegInf => Ok(-val),
+                _ => Err(EvalErrors::from(EvalError::unreachable(
+                    self.cfg.input.clone(),
+                    fn_name!(),
+                    line!(),
+                ))),
+            },
+            Invert => match val {
+                ValueObj::Int(i) => Ok(ValueObj::Int(!i)),
+                ValueObj::Nat(n) if n <= i32::MAX as u64 => Ok(ValueObj::Int(!(n as i32))),
+                _ => Err(EvalErrors::from(EvalError::unreachable(
+                    self.cfg.input.clone(),
+                    fn_name!(),
+                    line!(),
+                ))),
+            },
             Mutate => Ok(ValueObj::Mut(Shared::new(val))),
             other => todo!("{other}"),
         }
@@ -752,7 +789,7 @@ impl Context {
     fn eval_unary_tp(&self, op: OpKind, val: &TyParam) -> EvalResult<TyParam> {
         match val {
             TyParam::Value(c) => self.eval_unary(op, c.clone()).map(TyParam::Value),
-            TyParam::FreeVar(fv) if fv.is_linked() => self.eval_unary_tp(op, &*fv.crack()),
+            TyParam::FreeVar(fv) if fv.is_linked() => self.eval_unary_tp(op, &fv.crack()),
             e @ TyParam::Erased(_) => Ok(e.clone()),
             TyParam::MonoQVar(n) => todo!("not instantiated variable: {n}"),
             other => todo!("{op} {other}"),
@@ -836,12 +873,17 @@ impl Context {
                 for pt in subr.default_params.iter_mut() {
                     *pt.typ_mut() = self.eval_t_params(mem::take(pt.typ_mut()), level, t_loc)?;
                 }
+                if let Some(kw_var_args) = subr.kw_var_params.as_mut() {
+                    *kw_var_args.typ_mut() =
+                        self.eval_t_params(mem::take(kw_var_args.typ_mut()), level, t_loc)?;
+                }
                 let return_t = self.eval_t_params(*subr.return_t, level, t_loc)?;
                 Ok(subr_t(
                     subr.kind,
                     subr.non_default_params,
                     subr.var_params.map(|v| *v),
                     subr.default_params,
+                    subr.kw_var_params.map(|v| *v),
                     return_t,
                 ))
             }
@@ -944,15 +986,15 @@ impl Context {
             }
             for (class, methods) in ty_ctx.methods_list.iter() {
                 match (class, &opt_sup) {
-                    (ClassDefType::ImplTrait { impl_trait, .. }, Some(sup)) => {
-                        if !self.supertype_of(impl_trait, sup) {
-                            continue;
-                        }
+                    (ClassDefType::ImplTrait { impl_trait, .. }, Some(sup))
+                        if !self.supertype_of(impl_trait, sup) =>
+                    {
+                        continue;
                     }
-                    (ClassDefType::ImplTrait { impl_trait, .. }, None) => {
-                        if !self.supertype_of(impl_trait, &sub) {
-                            continue;
-                        }
+                    (ClassDefType::ImplTrait { impl_trait, .. }, None)
+                        if !self.supertype_of(impl_trait, &sub) =>
+                    {
+                        continue;
                     }
                     _ => {}
                 }
@@ -982,9 +1024,8 @@ impl Context {
         let coerced = self.deref_tyvar(lhs.clone(), Variance::Covariant, t_loc)?;
         if lhs != coerced {
             let proj = proj(coerced, rhs);
-            self.eval_t_params(proj, level, t_loc).map(|t| {
+            self.eval_t_params(proj, level, t_loc).inspect(|_t| {
                 self.coerce(&lhs);
-                t
             })
         } else {
             let proj = proj(lhs, rhs);
@@ -1011,10 +1052,8 @@ impl Context {
         if let Ok(obj) = methods.get_const_local(&Token::symbol(rhs), &self.name) {
             #[allow(clippy::single_match)]
             match (&opt_sup, methods.impl_of()) {
-                (Some(sup), Some(trait_)) => {
-                    if !self.supertype_of(&trait_, sup) {
-                        return None;
-                    }
+                (Some(sup), Some(trait_)) if !self.supertype_of(&trait_, sup) => {
+                    return None;
                 }
                 _ => {}
             }
@@ -1105,9 +1144,8 @@ impl Context {
         let coerced = self.deref_tp(lhs.clone(), Variance::Covariant, t_loc)?;
         if lhs != coerced {
             let proj = proj_call(coerced, attr_name, args);
-            self.eval_t_params(proj, level, t_loc).map(|t| {
+            self.eval_t_params(proj, level, t_loc).inspect(|_t| {
                 self.coerce_tp(&lhs);
-                t
             })
         } else {
             let proj = proj_call(lhs, attr_name, args);
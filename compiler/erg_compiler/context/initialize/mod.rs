@@ -862,6 +862,14 @@ impl Context {
         let mut nonetype_show = Self::builtin_methods(Some(mono("Show")), 1);
         nonetype_show.register_builtin_impl("to_str", fn0_met(NoneType, Str), Immutable, Public);
         nonetype.register_trait(NoneType, nonetype_show);
+        /* Error */
+        let mut error = Self::builtin_mono_class("Error", 10);
+        error.register_superclass(Obj, &obj);
+        error.register_builtin_impl("new", func1(Str, Error), Immutable, Public);
+        error.register_builtin_impl("message", Str, Immutable, Public);
+        let mut error_show = Self::builtin_methods(Some(mono("Show")), 1);
+        error_show.register_builtin_impl("to_str", fn0_met(Error, Str), Immutable, Public);
+        error.register_trait(Error, error_show);
         /* Type */
         let mut type_ = Self::builtin_mono_class("Type", 2);
         type_.register_superclass(Obj, &obj);
@@ -989,7 +997,10 @@ impl Context {
             Self::builtin_methods(Some(poly("Iterable", vec![ty_tp(mono_q("T"))])), 2);
         array_iterable.register_builtin_impl(
             "iter",
-            fn0_met(Str, mono("ArrayIterator")),
+            fn0_met(
+                arr_t.clone(),
+                poly("ArrayIterator", vec![ty_tp(mono_q("T"))]),
+            ),
             Immutable,
             Public,
         );
@@ -1115,6 +1126,8 @@ impl Context {
         str_iterator.register_superclass(Obj, &obj);
         let mut array_iterator = Self::builtin_poly_class("ArrayIterator", vec![PS::t_nd("T")], 1);
         array_iterator.register_superclass(Obj, &obj);
+        let mut range_iterator = Self::builtin_poly_class("RangeIterator", vec![PS::t_nd("T")], 1);
+        range_iterator.register_superclass(Obj, &obj);
         /* Float_mut */
         let mut float_mut = Self::builtin_mono_class("Float!", 2);
         float_mut.register_superclass(Float, &float);
@@ -1351,6 +1364,18 @@ impl Context {
             Public,
         );
         range.register_trait(range_t.clone(), range_eq);
+        let mut range_iterable =
+            Self::builtin_methods(Some(poly("Iterable", vec![ty_tp(mono_q("T"))])), 2);
+        range_iterable.register_builtin_impl(
+            "iter",
+            fn0_met(
+                range_t.clone(),
+                poly("RangeIterator", vec![ty_tp(mono_q("T"))]),
+            ),
+            Immutable,
+            Public,
+        );
+        range.register_trait(range_t.clone(), range_iterable);
         /* Proc */
         let mut proc = Self::builtin_mono_class("Proc", 2);
         proc.register_superclass(Obj, &obj);
@@ -1374,6 +1399,7 @@ impl Context {
         self.register_builtin_type(Bool, bool_, Private, Const);
         self.register_builtin_type(Str, str_, Private, Const);
         self.register_builtin_type(NoneType, nonetype, Private, Const);
+        self.register_builtin_type(Error, error, Private, Const);
         self.register_builtin_type(Type, type_, Private, Const);
         self.register_builtin_type(ClassType, class_type, Private, Const);
         self.register_builtin_type(TraitType, trait_type, Private, Const);
@@ -1395,6 +1421,12 @@ impl Context {
             Private,
             Const,
         );
+        self.register_builtin_type(
+            poly("RangeIterator", vec![ty_tp(mono_q("T"))]),
+            range_iterator,
+            Private,
+            Const,
+        );
         self.register_builtin_type(mono("Int!"), int_mut, Private, Const);
         self.register_builtin_type(mono("Nat!"), nat_mut, Private, Const);
         self.register_builtin_type(mono("Float!"), float_mut, Private, Const);
@@ -1515,10 +1547,24 @@ impl Context {
             module(mono_q_tp("Path")),
         );
         let t_pyimport = quant(t_pyimport, set! {static_instance("Path", Str)});
+        let t_property = nd_func(
+            vec![kw(
+                "fget",
+                nd_func(vec![anon(mono_q("T"))], None, mono_q("U")),
+            )],
+            None,
+            mono_q("U"),
+        );
+        let t_property = quant(
+            t_property,
+            set! {static_instance("T", Type), static_instance("U", Type)},
+        );
         let t_quit = func(vec![], None, vec![kw("code", Int)], NoneType);
         let t_exit = t_quit.clone();
         let t_repr = nd_func(vec![kw("object", Obj)], None, Str);
         let t_round = nd_func(vec![kw("number", Float)], None, Int);
+        let t_staticmethod = nd_func(vec![kw("func", mono_q("T"))], None, mono_q("T"));
+        let t_staticmethod = quant(t_staticmethod, set! {static_instance("T", Type)});
         self.register_builtin_impl("abs", t_abs, Immutable, Private);
         self.register_builtin_impl("ascii", t_ascii, Immutable, Private);
         self.register_builtin_impl("assert", t_assert, Const, Private); // assert casting に悪影響が出る可能性があるため、Constとしておく
@@ -1539,6 +1585,7 @@ impl Context {
         self.register_builtin_impl("ord", t_ord, Immutable, Private);
         self.register_builtin_impl("panic", t_panic, Immutable, Private);
         self.register_builtin_impl("pow", t_pow, Immutable, Private);
+        self.register_builtin_impl("property", t_property, Immutable, Private);
         if cfg!(feature = "debug") {
             self.register_builtin_impl("py", t_pyimport.clone(), Immutable, Private);
         }
@@ -1546,6 +1593,7 @@ impl Context {
         self.register_builtin_impl("quit", t_quit, Immutable, Private);
         self.register_builtin_impl("repr", t_repr, Immutable, Private);
         self.register_builtin_impl("round", t_round, Immutable, Private);
+        self.register_builtin_impl("staticmethod", t_staticmethod, Immutable, Private);
     }
 
     fn init_builtin_const_funcs(&mut self) {
@@ -1648,7 +1696,8 @@ impl Context {
         let t_locals = proc(vec![], None, vec![], dict! { Str => Obj }.into());
         let t_while = nd_proc(
             vec![
-                kw("cond", mono("Bool!")),
+                // the condition is evaluated on each iteration, so it does not consume the variables
+                kw("cond", ref_(Bool)),
                 kw("p", nd_proc(vec![], None, NoneType)),
             ],
             None,
@@ -1691,6 +1740,9 @@ impl Context {
         self.register_builtin_impl("globals!", t_globals, Immutable, Private);
         self.register_builtin_impl("locals!", t_locals, Immutable, Private);
         self.register_builtin_impl("while!", t_while, Immutable, Private);
+        // `break` and `continue` are only valid in the body of `for!`/`while!` (checked by the lowerer)
+        self.register_builtin_impl("break", Never, Immutable, Private);
+        self.register_builtin_impl("continue", Never, Immutable, Private);
         self.register_builtin_impl("open!", t_open, Immutable, Private);
         self.register_builtin_impl("with!", t_with, Immutable, Private);
     }
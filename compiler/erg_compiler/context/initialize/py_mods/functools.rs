@@ -0,0 +1,29 @@
+use erg_common::python_util::PythonVersion;
+use erg_common::set;
+use erg_common::vis::Visibility;
+
+use crate::ty::constructors::{kw, mono_q, nd_func, quant, static_instance};
+use crate::ty::Type;
+use Type::*;
+
+use crate::context::Context;
+use crate::varinfo::Mutability;
+use Mutability::*;
+use Visibility::*;
+
+impl Context {
+    /// `python_ver` is the magic number of the target Python (`None` if unknown)
+    pub(crate) fn init_py_functools_mod(python_ver: Option<u32>) -> Self {
+        let mut functools = Context::builtin_module("functools", 5);
+        // `functools.cache` is new in Python 3.9
+        if python_ver
+            .and_then(PythonVersion::from_magic_num)
+            .is_none_or(|ver| ver >= PythonVersion::V3_9)
+        {
+            let t = nd_func(vec![kw("user_function", mono_q("T"))], None, mono_q("T"));
+            let t = quant(t, set! {static_instance("T", Type)});
+            functools.register_builtin_impl("cache", t, Immutable, Public);
+        }
+        functools
+    }
+}
@@ -1,3 +1,4 @@
+pub mod functools;
 pub mod glob;
 pub mod importlib;
 pub mod io;
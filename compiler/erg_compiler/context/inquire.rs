@@ -3,6 +3,7 @@ use std::option::Option; // conflicting to Type::Option
 use std::path::{Path, PathBuf};
 
 use erg_common::config::Input;
+use erg_common::dict::Dict;
 use erg_common::error::{ErrorCore, ErrorKind, Location};
 use erg_common::levenshtein::get_similar_name;
 use erg_common::set::Set;
@@ -16,7 +17,10 @@ use ast::VarName;
 use erg_parser::ast::{self, Identifier};
 use erg_parser::token::Token;
 
-use crate::ty::constructors::{anon, free_var, func, module, mono, poly, proj, subr_t, v_enum};
+use crate::ty::constructors::{
+    anon, array_t, free_var, func, module, mono, nd_proc, poly, proj, subr_t, tuple_t, ty_tp,
+    v_enum,
+};
 use crate::ty::free::Constraint;
 use crate::ty::typaram::TyParam;
 use crate::ty::value::{GenTypeObj, TypeObj, ValueObj};
@@ -36,6 +40,43 @@ use Visibility::*;
 
 use super::MethodType;
 
+/// The parameter of a `match` arm (e.g. `x` of `x -> ...`, `x := 1` of `(x := 1) -> ...`)
+fn arm_param(params: &ast::Params) -> &ast::ParamSignature {
+    params
+        .non_defaults
+        .first()
+        .or_else(|| params.defaults.first())
+        .unwrap()
+}
+
+/// `Array(Int, 2)` => `Int`
+fn array_elem_t(t: &Type) -> Type {
+    match t {
+        Poly { name, params } if &name[..] == "Array" => match params.first() {
+            Some(TyParam::Type(t)) => t.as_ref().clone(),
+            _ => Obj,
+        },
+        _ => Obj,
+    }
+}
+
+/// `Tuple([Int, Str])` => `[Int, Str]` (the missing elements are `Obj`)
+fn tuple_elem_ts(t: &Type, len: usize) -> Vec<Type> {
+    let ts = match t {
+        Poly { name, params } if &name[..] == "Tuple" => match params.first() {
+            Some(TyParam::Array(ts)) => ts.as_slice(),
+            _ => &[],
+        },
+        _ => &[],
+    };
+    (0..len)
+        .map(|i| match ts.get(i) {
+            Some(TyParam::Type(t)) => t.as_ref().clone(),
+            _ => Obj,
+        })
+        .collect()
+}
+
 impl Context {
     pub(crate) fn validate_var_sig_t(
         &self,
@@ -205,6 +246,43 @@ impl Context {
         }
     }
 
+    /// `for!: (iterable: Iterable(T), p: T => NoneType) => NoneType`
+    ///
+    /// `T` is not unified with the element type of `iterable` but derefined (see `get_loop_var_t`)
+    fn get_for_call_t(&self, pos_args: &[hir::PosArg]) -> TyCheckResult<Type> {
+        let iterable = &pos_args[0].expr;
+        let Some(loop_var_t) = self.get_loop_var_t(iterable.ref_t()) else {
+            let iterable_t = poly("Iterable", vec![ty_tp(Obj)]);
+            return Err(TyCheckErrors::from(TyCheckError::type_mismatch_error(
+                self.cfg.input.clone(),
+                line!() as usize,
+                iterable.loc(),
+                self.caused_by(),
+                "for!::iterable",
+                &iterable_t,
+                iterable.ref_t(),
+                self.get_candidates(iterable.ref_t()),
+                self.get_type_mismatch_hint(&iterable_t, iterable.ref_t()),
+            )));
+        };
+        let body = &pos_args[1].expr;
+        let body_t = nd_proc(vec![anon(loop_var_t)], None, NoneType);
+        self.sub_unify(
+            body.ref_t(),
+            &body_t,
+            body.loc(),
+            Some(&Str::ever("for!::p")),
+        )?;
+        Ok(nd_proc(
+            vec![
+                ParamTy::kw(Str::ever("iterable"), iterable.t()),
+                ParamTy::kw(Str::ever("p"), body.t()),
+            ],
+            None,
+            NoneType,
+        ))
+    }
+
     fn get_match_call_t(
         &self,
         pos_args: &[hir::PosArg],
@@ -233,11 +311,9 @@ impl Context {
         let match_target_expr_t = pos_args[0].expr.ref_t();
         // Never or T => T
         let mut union_pat_t = Type::Never;
+        let mut exhaustive = false;
         for (i, pos_arg) in pos_args.iter().skip(1).enumerate() {
             let lambda = erg_common::enum_unwrap!(&pos_arg.expr, hir::Expr::Lambda);
-            if !lambda.params.defaults.is_empty() {
-                todo!()
-            }
             // TODO: If the first argument of the match is a tuple?
             if lambda.params.len() != 1 {
                 return Err(TyCheckErrors::from(TyCheckError::argument_error(
@@ -254,15 +330,29 @@ impl Context {
                         .unwrap_or(0),
                 )));
             }
-            let rhs =
-                self.instantiate_param_sig_t(&lambda.params.non_defaults[0], None, None, Normal)?;
+            // the default value of the arm (e.g. `(x := 1) -> ...`) is never used,
+            // since the match target is always passed
+            let param = arm_param(&lambda.params);
+            self.check_pattern_defaults(param, match_target_expr_t)?;
+            let rhs = self.pattern_t(param, match_target_expr_t, true)?;
+            // catch-all arm (e.g. `_ -> ...`): the whole target is covered
+            if &rhs == match_target_expr_t {
+                exhaustive = true;
+                // `x -> ...`: x is the target itself
+                if let Some(param_t) = lambda.t.non_default_params().and_then(|ps| ps.first()) {
+                    self.sub_unify(match_target_expr_t, param_t.typ(), param.loc(), None)?;
+                } else if let Some(param_t) = lambda.t.default_params().and_then(|ps| ps.first()) {
+                    self.sub_unify(match_target_expr_t, param_t.typ(), param.loc(), None)?;
+                }
+            }
             union_pat_t = self.union(&union_pat_t, &rhs);
         }
         // NG: expr_t: Nat, union_pat_t: {1, 2}
         // OK: expr_t: Int, union_pat_t: {1} or 'T
-        if self
-            .sub_unify(match_target_expr_t, &union_pat_t, pos_args[0].loc(), None)
-            .is_err()
+        if !exhaustive
+            && self
+                .sub_unify(match_target_expr_t, &union_pat_t, pos_args[0].loc(), None)
+                .is_err()
         {
             return Err(TyCheckErrors::from(TyCheckError::match_error(
                 self.cfg.input.clone(),
@@ -281,19 +371,212 @@ impl Context {
         for arg_t in branch_ts.iter().skip(1) {
             return_t = self.union(&return_t, arg_t.typ().return_t().unwrap());
         }
+        // e.g. `?T.Output` (?T := Int) => `Int`
+        let return_t = self
+            .eval_t_params(return_t.clone(), self.level, pos_args[0].loc())
+            .unwrap_or(return_t);
         let param_ty = ParamTy::anonymous(match_target_expr_t.clone());
         let param_ts = [vec![param_ty], branch_ts.to_vec()].concat();
         let t = func(param_ts, None, vec![], return_t);
         Ok(t)
     }
 
+    /// Returns the type of the values that the pattern of `sig` matches
+    /// when the match target is of type `target_t`.
+    ///
+    /// If `surely` is true, the result is an under-approximation (all the values of the type are matched),
+    /// otherwise it is an over-approximation (the values out of the type are never matched).
+    /// e.g. `[1, y]` (target: `Array(Int, 2)`) => `Never` (surely), `Array(Int, 2)` (not surely)
+    fn pattern_t(
+        &self,
+        sig: &ast::ParamSignature,
+        target_t: &Type,
+        surely: bool,
+    ) -> TyCheckResult<Type> {
+        if let FreeVar(fv) = target_t {
+            if fv.is_linked() {
+                return self.pattern_t(sig, &fv.crack(), surely);
+            }
+        }
+        let spec_t = if let Some(t_spec) = &sig.t_spec {
+            Some(self.instantiate_typespec(&t_spec.t_spec, None, None, Normal)?)
+        } else {
+            None
+        };
+        let target_t = spec_t.as_ref().unwrap_or(target_t);
+        match &sig.pat {
+            ast::ParamPattern::VarName(_)
+            | ast::ParamPattern::Discard(_)
+            | ast::ParamPattern::Ref(_)
+            | ast::ParamPattern::RefMut(_) => Ok(target_t.clone()),
+            ast::ParamPattern::Lit(lit) => Ok(v_enum(set! {self.eval_lit(lit)?})),
+            ast::ParamPattern::Array(arr) => {
+                let elem_t = array_elem_t(target_t);
+                // `[1, y]` does not cover `Array(Int, 2)`, but `[x, y]` does
+                if surely {
+                    for elem in arr
+                        .elems
+                        .non_defaults
+                        .iter()
+                        .chain(arr.elems.defaults.iter())
+                    {
+                        let t = self.pattern_t(elem, &elem_t, true)?;
+                        if !self.supertype_of(&t, &elem_t) {
+                            return Ok(Never);
+                        }
+                    }
+                }
+                // `[x, y := 1]` matches arrays of length 1 and 2
+                let mut union = Never;
+                for len in arr.elems.non_defaults.len()..=arr.len() {
+                    union = self.union(&union, &array_t(elem_t.clone(), TyParam::value(len)));
+                }
+                Ok(union)
+            }
+            ast::ParamPattern::Tuple(tup) => {
+                let elem_ts = tuple_elem_ts(target_t, tup.elems.len());
+                let mut ts = vec![];
+                for (elem, elem_t) in tup.elems.non_defaults.iter().zip(elem_ts.iter()) {
+                    let t = self.pattern_t(elem, elem_t, surely)?;
+                    if t == Never {
+                        return Ok(Never);
+                    }
+                    ts.push(t);
+                }
+                let mut union = tuple_t(ts.clone());
+                for (elem, elem_t) in tup.elems.defaults.iter().zip(elem_ts.iter().skip(ts.len())) {
+                    let t = self.pattern_t(elem, elem_t, surely)?;
+                    ts.push(t);
+                    union = self.union(&union, &tuple_t(ts.clone()));
+                }
+                Ok(union)
+            }
+            ast::ParamPattern::Record(rec) => {
+                let mut attrs = Dict::new();
+                // fields with default values (e.g. `{x; y = (y := 1)}`) may be missing
+                for attr in rec
+                    .elems
+                    .iter()
+                    .filter(|attr| attr.rhs.opt_default_val.is_none())
+                {
+                    let field = Field::new(attr.lhs.vis(), attr.lhs.inspect().clone());
+                    let field_t = match target_t {
+                        Record(rec) => rec.get(&field).cloned().unwrap_or(Obj),
+                        _ => Obj,
+                    };
+                    let t = self.pattern_t(&attr.rhs, &field_t, surely)?;
+                    if t == Never {
+                        return Ok(Never);
+                    }
+                    attrs.insert(field, t);
+                }
+                Ok(Type::Record(attrs))
+            }
+        }
+    }
+
+    /// Checks that the default values in the pattern (e.g. `[x, y := 1]`) are of the pattern types.
+    fn check_pattern_defaults(
+        &self,
+        sig: &ast::ParamSignature,
+        target_t: &Type,
+    ) -> TyCheckResult<()> {
+        if let Some(default) = &sig.opt_default_val {
+            let pat_t = self.pattern_t(sig, target_t, false)?;
+            let default = self.eval_tp(&self.instantiate_const_expr(default)?)?;
+            let default_t = self.get_tp_t(&default)?;
+            if self.sub_unify(&default_t, &pat_t, sig.loc(), None).is_err() {
+                return Err(TyCheckErrors::from(TyCheckError::type_mismatch_error(
+                    self.cfg.input.clone(),
+                    line!() as usize,
+                    sig.loc(),
+                    self.caused_by(),
+                    sig.inspect().map(|s| &s[..]).unwrap_or("_"),
+                    &pat_t,
+                    &default_t,
+                    self.get_candidates(&default_t),
+                    None,
+                )));
+            }
+        }
+        match &sig.pat {
+            ast::ParamPattern::Array(arr) => {
+                let elem_t = array_elem_t(target_t);
+                for elem in arr
+                    .elems
+                    .non_defaults
+                    .iter()
+                    .chain(arr.elems.defaults.iter())
+                {
+                    self.check_pattern_defaults(elem, &elem_t)?;
+                }
+            }
+            ast::ParamPattern::Tuple(tup) => {
+                let elem_ts = tuple_elem_ts(target_t, tup.elems.len());
+                let elems = tup
+                    .elems
+                    .non_defaults
+                    .iter()
+                    .chain(tup.elems.defaults.iter());
+                for (elem, elem_t) in elems.zip(elem_ts.iter()) {
+                    self.check_pattern_defaults(elem, elem_t)?;
+                }
+            }
+            ast::ParamPattern::Record(rec) => {
+                for attr in rec.elems.iter() {
+                    let field = Field::new(attr.lhs.vis(), attr.lhs.inspect().clone());
+                    let field_t = match target_t {
+                        Record(rec) => rec.get(&field).cloned().unwrap_or(Obj),
+                        _ => Obj,
+                    };
+                    self.check_pattern_defaults(&attr.rhs, &field_t)?;
+                }
+            }
+            _ => {}
+        }
+        Ok(())
+    }
+
+    /// Returns the arms of `match` that are never reached because the previous arms cover them.
+    pub(crate) fn get_unreachable_match_arms<'a>(
+        &self,
+        pos_args: &'a [hir::PosArg],
+    ) -> TyCheckResult<Vec<&'a hir::PosArg>> {
+        let Some(target) = pos_args.first() else {
+            return Ok(vec![]);
+        };
+        let target_t = target.expr.ref_t();
+        let mut covered = Never;
+        let mut unreachables = vec![];
+        for arm in pos_args.iter().skip(1) {
+            let hir::Expr::Lambda(lambda) = &arm.expr else {
+                continue;
+            };
+            if lambda.params.is_empty() {
+                continue;
+            }
+            let param = arm_param(&lambda.params);
+            let may_match = self.pattern_t(param, target_t, false)?;
+            // cannot judge if type variables are involved
+            if covered.has_no_unbound_var()
+                && may_match.has_no_unbound_var()
+                && self.supertype_of(&covered, &may_match)
+            {
+                unreachables.push(arm);
+            }
+            let surely_match = self.pattern_t(param, target_t, true)?;
+            covered = self.union(&covered, &surely_match);
+        }
+        Ok(unreachables)
+    }
+
     fn get_import_call_t(
         &self,
         pos_args: &[hir::PosArg],
         kw_args: &[hir::KwArg],
     ) -> TyCheckResult<Type> {
         let mod_name = pos_args
-            .get(0)
+            .first()
             .map(|a| &a.expr)
             .or_else(|| {
                 kw_args
@@ -471,6 +754,7 @@ impl Context {
 
     /// get type from given attributive type (Record).
     /// not ModuleType or ClassType etc.
+    #[allow(clippy::only_used_in_recursion)]
     fn get_attr_t_from_attributive(
         &self,
         obj: &hir::Expr,
@@ -802,7 +1086,7 @@ impl Context {
                     };
                     let ret_t = free_var(self.level, Constraint::new_type_of(Type));
                     let non_default_params = pos_args.iter().map(|a| anon(a.expr.t())).collect();
-                    let subr_t = subr_t(kind, non_default_params, None, vec![], ret_t);
+                    let subr_t = subr_t(kind, non_default_params, None, vec![], None, ret_t);
                     fv.link(&subr_t);
                     Ok(())
                 }
@@ -827,9 +1111,9 @@ impl Context {
                     obj.clone()
                 };
                 let params_len = subr.non_default_params.len() + subr.default_params.len();
-                if (params_len < pos_args.len() || params_len < pos_args.len() + kw_args.len())
-                    && subr.var_params.is_none()
-                {
+                let too_many_kw_args =
+                    params_len < pos_args.len() + kw_args.len() && subr.kw_var_params.is_none();
+                if (params_len < pos_args.len() || too_many_kw_args) && subr.var_params.is_none() {
                     return Err(TyCheckErrors::from(TyCheckError::too_many_args_error(
                         self.cfg.input.clone(),
                         line!() as usize,
@@ -879,6 +1163,7 @@ impl Context {
                             &callee,
                             kw_arg,
                             &subr.default_params,
+                            subr.kw_var_params.as_deref(),
                             &mut passed_params,
                         )?;
                     }
@@ -956,7 +1241,7 @@ impl Context {
             .map_err(|errs| {
                 log!(err "semi-unification failed with {callee}\n{arg_t} !<: {param_t}");
                 // REVIEW:
-                let name = callee.show_acc().unwrap_or_else(|| "".to_string());
+                let name = callee.show_acc().unwrap_or_default();
                 let name = name + "::" + param.name().map(|s| readable_name(&s[..])).unwrap_or("");
                 TyCheckErrors::new(
                     errs.into_iter()
@@ -1005,7 +1290,7 @@ impl Context {
             .map_err(|errs| {
                 log!(err "semi-unification failed with {callee}\n{arg_t} !<: {param_t}");
                 // REVIEW:
-                let name = callee.show_acc().unwrap_or_else(|| "".to_string());
+                let name = callee.show_acc().unwrap_or_default();
                 let name = name + "::" + param.name().map(|s| readable_name(&s[..])).unwrap_or("");
                 TyCheckErrors::new(
                     errs.into_iter()
@@ -1032,6 +1317,7 @@ impl Context {
         callee: &hir::Expr,
         arg: &hir::KwArg,
         default_params: &[ParamTy],
+        kw_var_param: Option<&ParamTy>,
         passed_params: &mut Set<Str>,
     ) -> TyCheckResult<()> {
         let arg_t = arg.expr.ref_t();
@@ -1048,15 +1334,17 @@ impl Context {
         } else {
             passed_params.insert(kw_name.clone());
         }
+        // unknown keywords are collected into `**kwargs`, if any
         if let Some(pt) = default_params
             .iter()
             .find(|pt| pt.name().unwrap() == kw_name)
+            .or(kw_var_param)
         {
             self.sub_unify(arg_t, pt.typ(), arg.loc(), Some(kw_name))
                 .map_err(|errs| {
                     log!(err "semi-unification failed with {callee}\n{arg_t} !<: {}", pt.typ());
                     // REVIEW:
-                    let name = callee.show_acc().unwrap_or_else(|| "".to_string());
+                    let name = callee.show_acc().unwrap_or_default();
                     let name = name + "::" + readable_name(kw_name);
                     TyCheckErrors::new(
                         errs.into_iter()
@@ -1104,6 +1392,9 @@ impl Context {
                     "match" => {
                         return self.get_match_call_t(pos_args, kw_args);
                     }
+                    "for!" if pos_args.len() == 2 && kw_args.is_empty() => {
+                        return self.get_for_call_t(pos_args);
+                    }
                     "import" | "pyimport" | "py" => {
                         return self.get_import_call_t(pos_args, kw_args);
                     }
@@ -1434,6 +1725,32 @@ impl Context {
         })
     }
 
+    /// The type of the elements obtained by iterating over an object of `t` (e.g. `Array(Int, 3)` -> `Int`).
+    /// Returns `None` if it cannot be determined from the structure of `t`.
+    pub(crate) fn get_iter_elem_t(&self, t: &Type) -> Option<Type> {
+        match t {
+            Type::FreeVar(fv) if fv.is_linked() => self.get_iter_elem_t(&fv.crack()),
+            Type::Refinement(refine) => self.get_iter_elem_t(&refine.t),
+            Type::Ref(t) => self.get_iter_elem_t(t),
+            Type::Str => Some(Type::Str),
+            Type::Mono(name) if &name[..] == "Str!" => Some(Type::Str),
+            Type::Poly { name, params } => match &name[..] {
+                "Array" | "Array!" | "Set" | "Set!" | "Range" | "Iterable" | "Iterator" => {
+                    Type::try_from(params.first()?.clone()).ok()
+                }
+                // iterating over a dict yields its keys
+                "Dict" | "Dict!" => match params.first()? {
+                    TyParam::Dict(kvs) if kvs.len() == 1 => {
+                        Type::try_from(kvs.keys().next()?.clone()).ok()
+                    }
+                    _ => None,
+                },
+                _ => None,
+            },
+            _ => None,
+        }
+    }
+
     // TODO: Never
     pub(crate) fn get_nominal_type_ctx<'a>(&'a self, typ: &Type) -> Option<&'a Context> {
         match typ {
@@ -1619,6 +1936,55 @@ impl Context {
         None
     }
 
+    /// Returns the element type of `t` as an `Iterable`.
+    /// ```erg
+    /// get_iterable_elem_t(Array(Int, 3)) == Int
+    /// get_iterable_elem_t(Range(Nat)) == Nat
+    /// get_iterable_elem_t(Str) == Str
+    /// ```
+    pub(crate) fn get_iterable_elem_t(&self, t: &Type) -> Option<Type> {
+        match t {
+            Type::FreeVar(fv) if fv.is_linked() => self.get_iterable_elem_t(&fv.crack()),
+            Type::Refinement(refine) => self.get_iterable_elem_t(&refine.t),
+            Type::Ref(t) | Type::RefMut { before: t, .. } => self.get_iterable_elem_t(t),
+            _ => {
+                let t_ctx = self.get_nominal_type_ctx(t)?;
+                for ctx in self.get_simple_nominal_super_type_ctxs(t)? {
+                    let Some(iterable) = ctx
+                        .super_traits
+                        .iter()
+                        .find(|sup| &sup.qual_name()[..] == "Iterable")
+                    else {
+                        continue;
+                    };
+                    let Some(TyParam::Type(elem_t)) = iterable.typarams().into_iter().next() else {
+                        return None;
+                    };
+                    // e.g. `Array(T, N)` implements `Iterable(T)`: `T` is replaced with the actual parameter
+                    if let Type::MonoQVar(name) = elem_t.as_ref() {
+                        let idx = t_ctx.params.iter().position(|(param, _)| {
+                            param.as_ref().map(|param| param.inspect()) == Some(name)
+                        })?;
+                        return match t.typarams().into_iter().nth(idx) {
+                            Some(TyParam::Type(elem_t)) => Some(*elem_t),
+                            _ => None,
+                        };
+                    }
+                    return Some(*elem_t);
+                }
+                None
+            }
+        }
+    }
+
+    /// The type of `i` in `for! iterable, i => ...`
+    pub(crate) fn get_loop_var_t(&self, iterable_t: &Type) -> Option<Type> {
+        let elem_t = self.get_iterable_elem_t(iterable_t)?;
+        self.coerce(&elem_t);
+        // e.g. `0..<10: Range({0, 10})`, but `i` takes all values in the range
+        Some(elem_t.derefine())
+    }
+
     pub(crate) fn get_trait_impls(&self, t: &Type) -> Set<TraitInstance> {
         match t {
             // And(Add, Sub) == intersection({Int <: Add(Int), Bool <: Add(Bool) ...}, {Int <: Sub(Int), ...})
@@ -1728,7 +2094,7 @@ impl Context {
     pub(crate) fn rec_get_self_t(&self) -> Option<Type> {
         if self.kind.is_method_def() || self.kind.is_type() {
             // TODO: poly type
-            let name = self.name.split(&[':', '.']).last().unwrap();
+            let name = self.name.split(&[':', '.']).next_back().unwrap();
             // let mono_t = mono(self.path(), Str::rc(name));
             if let Some((t, _)) = self.rec_get_type(name) {
                 Some(t.clone())
@@ -1904,6 +2270,7 @@ impl Context {
                                 subr.non_default_params.clone(),
                                 subr.var_params.as_ref().map(|p| *p.clone()),
                                 subr.default_params.clone(),
+                                subr.kw_var_params.as_ref().map(|p| *p.clone()),
                                 ret_t,
                             );
                             Type::Subr(subr)
@@ -21,12 +21,13 @@ use erg_parser::token::TokenKind;
 
 use crate::ty::constructors::*;
 use crate::ty::free::{Constraint, Cyclicity, FreeTyVar};
-use crate::ty::typaram::{IntervalOp, TyParam, TyParamOrdering};
+use crate::ty::typaram::{IntervalOp, OpKind, TyParam, TyParamOrdering};
 use crate::ty::value::ValueObj;
 use crate::ty::{HasType, ParamTy, Predicate, SubrKind, TyBound, Type};
 use TyParamOrdering::*;
 use Type::*;
 
+use crate::context::eval::try_get_op_kind_from_token;
 use crate::context::{Context, RegistrationMode};
 use crate::error::{SingleTyCheckResult, TyCheckError, TyCheckErrors, TyCheckResult};
 use crate::hir;
@@ -538,6 +539,14 @@ impl Context {
                 .and_then(|subr| subr.default_params.get(n));
             defaults.push(self.instantiate_param_ty(p, opt_decl_t, Some(&tv_ctx), mode)?);
         }
+        let kw_var_args = if let Some(kw_var_args) = sig.params.kw_var_args.as_ref() {
+            let opt_decl_t = opt_decl_sig_t
+                .as_ref()
+                .and_then(|subr| subr.kw_var_params.as_ref().map(|v| v.as_ref()));
+            Some(self.instantiate_param_ty(kw_var_args, opt_decl_t, Some(&tv_ctx), mode)?)
+        } else {
+            None
+        };
         let spec_return_t = if let Some(s) = sig.return_t_spec.as_ref() {
             let opt_decl_t = opt_decl_sig_t
                 .as_ref()
@@ -552,11 +561,19 @@ impl Context {
             };
             free_var(level, Constraint::new_type_of(Type))
         };
-        Ok(if sig.ident.is_procedural() {
-            proc(non_defaults, var_args, defaults, spec_return_t)
+        let kind = if sig.ident.is_procedural() {
+            SubrKind::Proc
         } else {
-            func(non_defaults, var_args, defaults, spec_return_t)
-        })
+            SubrKind::Func
+        };
+        Ok(subr_t(
+            kind,
+            non_defaults,
+            var_args,
+            defaults,
+            kw_var_args,
+            spec_return_t,
+        ))
     }
 
     /// spec_t == Noneかつリテラル推論が不可能なら型変数を発行する
@@ -591,7 +608,7 @@ impl Context {
                     .as_ref()
                     .map(|s| s.loc())
                     .unwrap_or_else(|| sig.loc()),
-                None,
+                sig.inspect(),
             )?;
         }
         Ok(spec_t)
@@ -649,6 +666,7 @@ impl Context {
             "Str" => Ok(Type::Str),
             "Bool" => Ok(Type::Bool),
             "NoneType" => Ok(Type::NoneType),
+            "Error" => Ok(Type::Error),
             "Ellipsis" => Ok(Type::Ellipsis),
             "NotImplemented" => Ok(Type::NotImplemented),
             "Inf" => Ok(Type::Inf),
@@ -688,6 +706,11 @@ impl Context {
                 }
                 if let Some((typ, _)) = self.rec_get_type(other) {
                     Ok(typ.clone())
+                } else if other == "Self" {
+                    // e.g. `Trait {.norm = (self: Self) -> Nat}`
+                    Ok(self
+                        .rec_get_self_t()
+                        .unwrap_or_else(|| free_var(self.level, Constraint::new_type_of(Type))))
                 } else {
                     Err(TyCheckErrors::from(TyCheckError::no_var_error(
                         self.cfg.input.clone(),
@@ -742,10 +765,104 @@ impl Context {
                     Ok(TyParam::Mono(name.inspect().clone()))
                 }
             }
+            ast::ConstExpr::BinOp(bin) => {
+                let op = try_get_op_kind_from_token(bin.op.kind)?;
+                let lhs = self.instantiate_const_expr(&bin.lhs)?;
+                let rhs = self.instantiate_const_expr(&bin.rhs)?;
+                Ok(TyParam::bin(op, lhs, rhs))
+            }
+            ast::ConstExpr::UnaryOp(unary) => {
+                let op = try_get_op_kind_from_token(unary.op.kind)?;
+                let val = self.instantiate_const_expr(&unary.expr)?;
+                Ok(TyParam::unary(op, val))
+            }
             _ => todo!(),
         }
     }
 
+    /// e.g. `I >= 0` => `Predicate::ge("I", 0)`, `0 < I` => `Predicate::ge("I", 1)`
+    fn instantiate_pred(&self, var: &Str, expr: &ast::ConstExpr) -> TyCheckResult<Predicate> {
+        let is_subject = |expr: &ast::ConstExpr| matches!(expr, ast::ConstExpr::Accessor(ast::ConstAccessor::Local(name)) if name.inspect() == var);
+        let bin = match expr {
+            ast::ConstExpr::BinOp(bin) => bin,
+            other => {
+                return Err(TyCheckErrors::from(TyCheckError::feature_error(
+                    self.cfg.input.clone(),
+                    other.loc(),
+                    &format!("predicate `{other}`"),
+                    self.caused_by(),
+                )));
+            }
+        };
+        match bin.op.kind {
+            TokenKind::AndOp => {
+                let lhs = self.instantiate_pred(var, &bin.lhs)?;
+                let rhs = self.instantiate_pred(var, &bin.rhs)?;
+                return Ok(Predicate::and(lhs, rhs));
+            }
+            TokenKind::OrOp => {
+                let lhs = self.instantiate_pred(var, &bin.lhs)?;
+                let rhs = self.instantiate_pred(var, &bin.rhs)?;
+                return Ok(Predicate::or(lhs, rhs));
+            }
+            _ => {}
+        }
+        // `0 <= I` => `I >= 0`
+        let (kind, rhs) = if is_subject(&bin.lhs) {
+            (bin.op.kind, bin.rhs.as_ref())
+        } else if is_subject(&bin.rhs) {
+            let kind = match bin.op.kind {
+                TokenKind::Less => TokenKind::Gre,
+                TokenKind::Gre => TokenKind::Less,
+                TokenKind::LessEq => TokenKind::GreEq,
+                TokenKind::GreEq => TokenKind::LessEq,
+                other => other,
+            };
+            (kind, bin.lhs.as_ref())
+        } else {
+            return Err(TyCheckErrors::from(TyCheckError::feature_error(
+                self.cfg.input.clone(),
+                bin.loc(),
+                &format!("predicate `{bin}`"),
+                self.caused_by(),
+            )));
+        };
+        let rhs = self.instantiate_const_expr(rhs)?;
+        let rhs = self.eval_tp(&rhs)?;
+        let is_int = matches!(&rhs, TyParam::Value(ValueObj::Int(_) | ValueObj::Nat(_)));
+        match kind {
+            TokenKind::DblEq => Ok(Predicate::eq(var.clone(), rhs)),
+            TokenKind::NotEq => Ok(Predicate::ne(var.clone(), rhs)),
+            TokenKind::GreEq => Ok(Predicate::ge(var.clone(), rhs)),
+            TokenKind::LessEq => Ok(Predicate::le(var.clone(), rhs)),
+            // `I > 0` => `I >= 1`
+            TokenKind::Gre if is_int => Ok(Predicate::ge(
+                var.clone(),
+                self.eval_bin_tp(OpKind::Add, &rhs, &TyParam::value(1))?,
+            )),
+            // `F > 0.0` => `F >= 0.0 and F != 0.0`
+            TokenKind::Gre => Ok(Predicate::and(
+                Predicate::ge(var.clone(), rhs.clone()),
+                Predicate::ne(var.clone(), rhs),
+            )),
+            // `I < 0` => `I <= -1`
+            TokenKind::Less if is_int => Ok(Predicate::le(
+                var.clone(),
+                self.eval_bin_tp(OpKind::Sub, &rhs, &TyParam::value(1))?,
+            )),
+            TokenKind::Less => Ok(Predicate::and(
+                Predicate::le(var.clone(), rhs.clone()),
+                Predicate::ne(var.clone(), rhs),
+            )),
+            _ => Err(TyCheckErrors::from(TyCheckError::feature_error(
+                self.cfg.input.clone(),
+                bin.loc(),
+                &format!("predicate `{bin}`"),
+                self.caused_by(),
+            ))),
+        }
+    }
+
     pub(crate) fn instantiate_const_expr_as_type(
         &self,
         expr: &ast::ConstExpr,
@@ -781,11 +898,11 @@ impl Context {
             TypeSpec::PreDeclTy(predecl) => {
                 Ok(self.instantiate_predecl_t(predecl, opt_decl_t, tmp_tv_ctx)?)
             }
-            TypeSpec::And(lhs, rhs) => Ok(self.union(
+            TypeSpec::And(lhs, rhs) => Ok(self.intersection(
                 &self.instantiate_typespec(lhs, opt_decl_t, tmp_tv_ctx, mode)?,
                 &self.instantiate_typespec(rhs, opt_decl_t, tmp_tv_ctx, mode)?,
             )),
-            TypeSpec::Or(lhs, rhs) => Ok(self.intersection(
+            TypeSpec::Or(lhs, rhs) => Ok(self.union(
                 &self.instantiate_typespec(lhs, opt_decl_t, tmp_tv_ctx, mode)?,
                 &self.instantiate_typespec(rhs, opt_decl_t, tmp_tv_ctx, mode)?,
             )),
@@ -845,6 +962,21 @@ impl Context {
                 }
                 Ok(int_interval(op, l, r))
             }
+            TypeSpec::Refinement(refine) => {
+                let t = self.instantiate_typespec(&refine.typ, opt_decl_t, tmp_tv_ctx, mode)?;
+                let var = refine.var.inspect().clone();
+                let mut pred = None;
+                for p in refine.preds.iter() {
+                    let p = self.instantiate_pred(&var, p)?;
+                    pred = Some(match pred {
+                        Some(acc) => Predicate::and(acc, p),
+                        None => p,
+                    });
+                }
+                // the predicates of a refinement type are joined by `or`
+                let preds = pred.map(Predicate::split_or).unwrap_or_default();
+                Ok(refinement(var, t, preds))
+            }
             TypeSpec::Subr(subr) => {
                 let non_defaults = try_map_mut(subr.non_defaults.iter(), |p| {
                     self.instantiate_func_param_spec(p, opt_decl_t, tmp_tv_ctx, mode)
@@ -866,6 +998,7 @@ impl Context {
                     non_defaults,
                     var_args,
                     defaults,
+                    None,
                     return_t,
                 ))
             }
@@ -975,6 +1108,15 @@ impl Context {
                     Ok(TyParam::t(t))
                 }
             }
+            TyParam::Dict(dict) => {
+                let mut new_dict = Dict::new();
+                for (k, v) in dict.into_iter() {
+                    let k = self.instantiate_tp(k, tmp_tv_ctx, loc)?;
+                    let v = self.instantiate_tp(v, tmp_tv_ctx, loc)?;
+                    new_dict.insert(k, v);
+                }
+                Ok(TyParam::Dict(new_dict))
+            }
             TyParam::FreeVar(fv) if fv.is_linked() => {
                 self.instantiate_tp(fv.crack().clone(), tmp_tv_ctx, loc)
             }
@@ -1056,12 +1198,17 @@ impl Context {
                 for pt in subr.default_params.iter_mut() {
                     *pt.typ_mut() = self.instantiate_t(mem::take(pt.typ_mut()), tmp_tv_ctx, loc)?;
                 }
+                if let Some(kw_var_args) = subr.kw_var_params.as_mut() {
+                    *kw_var_args.typ_mut() =
+                        self.instantiate_t(mem::take(kw_var_args.typ_mut()), tmp_tv_ctx, loc)?;
+                }
                 let return_t = self.instantiate_t(*subr.return_t, tmp_tv_ctx, loc)?;
                 let res = subr_t(
                     subr.kind,
                     subr.non_default_params,
                     subr.var_params.map(|p| *p),
                     subr.default_params,
+                    subr.kw_var_params.map(|p| *p),
                     return_t,
                 );
                 Ok(res)
@@ -938,13 +938,20 @@ impl Context {
         vars
     }
 
-    fn type_dir(&self) -> Vec<(&VarName, &VarInfo)> {
+    pub fn type_dir(&self) -> Vec<(&VarName, &VarInfo)> {
         self.locals
             .iter()
             .chain(self.methods_list.iter().flat_map(|(_, ctx)| ctx.dir()))
             .collect()
     }
 
+    /// Returns the attributes and methods that an object of type `t` has
+    pub fn get_type_members(&self, t: &Type) -> Vec<(&VarName, &VarInfo)> {
+        self.get_nominal_type_ctx(t)
+            .map(|ctx| ctx.type_dir())
+            .unwrap_or_default()
+    }
+
     pub fn get_receiver_ctx(&self, receiver_name: &str) -> Option<&Context> {
         self.get_mod(receiver_name)
             .or_else(|| {
@@ -1,28 +1,31 @@
 use std::option::Option;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 
 use crate::ty::free::HasLevel;
 use erg_common::config::{ErgConfig, Input};
+use erg_common::dict::Dict;
 use erg_common::levenshtein::get_similar_name;
-use erg_common::python_util::BUILTIN_PYTHON_MODS;
+use erg_common::python_util::{detect_magic_number, BUILTIN_PYTHON_MODS};
 use erg_common::set::Set;
 use erg_common::traits::{Locational, Stream};
 use erg_common::vis::Visibility;
 use erg_common::Str;
-use erg_common::{enum_unwrap, get_hash, log, option_enum_unwrap, set};
+use erg_common::{dict, enum_unwrap, get_hash, log, option_enum_unwrap, set};
 
 use ast::{DefId, Identifier, VarName};
 use erg_parser::ast;
 
-use crate::ty::constructors::{func, func1, proc, ref_, ref_mut, v_enum};
+use crate::ty::constructors::{array_t, dict_t, func1, ref_, ref_mut, subr_t, v_enum};
 use crate::ty::free::{Constraint, Cyclicity, FreeKind};
+use crate::ty::typaram::TyParam;
 use crate::ty::value::{GenTypeObj, TypeKind, TypeObj, ValueObj};
-use crate::ty::{HasType, ParamTy, SubrType, Type};
+use crate::ty::{HasType, ParamTy, SubrKind, SubrType, Type};
 
 use crate::build_hir::HIRBuilder;
 use crate::context::{
     ClassDefType, Context, ContextKind, DefaultInfo, MethodType, RegistrationMode, TraitInstance,
 };
+use crate::disk_cache::{declarations, same_signature, CachedModule};
 use crate::error::readable_name;
 use crate::error::{
     CompileResult, SingleTyCheckResult, TyCheckError, TyCheckErrors, TyCheckResult,
@@ -114,18 +117,19 @@ impl Context {
                 _ => None,
             })
             .collect::<Set<_>>();
-        let t = self.instantiate_sub_sig_t(sig, PreRegister).map_err(|e| {
-            let vi = VarInfo::new(
-                Type::Failure,
-                muty,
-                vis,
-                kind.clone(),
-                Some(comptime_decos.clone()),
-                self.impl_of(),
-            );
-            self.decls.insert(sig.ident.name.clone(), vi);
-            e
-        })?;
+        let t = self
+            .instantiate_sub_sig_t(sig, PreRegister)
+            .inspect_err(|_e| {
+                let vi = VarInfo::new(
+                    Type::Failure,
+                    muty,
+                    vis,
+                    kind.clone(),
+                    Some(comptime_decos.clone()),
+                    self.impl_of(),
+                );
+                self.decls.insert(sig.ident.name.clone(), vi);
+            })?;
         let vi = VarInfo::new(t, muty, vis, kind, Some(comptime_decos), self.impl_of());
         if let Some(_decl) = self.decls.remove(name) {
             Err(TyCheckErrors::from(TyCheckError::duplicate_decl_error(
@@ -184,8 +188,28 @@ impl Context {
         opt_decl_t: Option<&ParamTy>,
     ) -> TyCheckResult<()> {
         match &sig.pat {
-            ast::ParamPattern::Lit(_) => Ok(()),
-            ast::ParamPattern::Discard(_token) => Ok(()),
+            // nested literals and discards are not bound to any name
+            ast::ParamPattern::Lit(_) | ast::ParamPattern::Discard(_) => {
+                if outer.is_none() {
+                    let spec_t = self.instantiate_param_sig_t(sig, opt_decl_t, None, Normal)?;
+                    self.push_anonymous_param(sig, spec_t, ParamIdx::Nth(nth));
+                }
+                Ok(())
+            }
+            ast::ParamPattern::Array(arr) => {
+                let elems = arr.elems.non_defaults.iter();
+                let elems = elems.chain(arr.elems.defaults.iter());
+                self.assign_nested_params(sig, elems, outer, nth, opt_decl_t)
+            }
+            ast::ParamPattern::Tuple(tup) => {
+                let elems = tup.elems.non_defaults.iter();
+                let elems = elems.chain(tup.elems.defaults.iter());
+                self.assign_nested_params(sig, elems, outer, nth, opt_decl_t)
+            }
+            ast::ParamPattern::Record(rec) => {
+                let elems = rec.elems.iter().map(|attr| &attr.rhs);
+                self.assign_nested_params(sig, elems, outer, nth, opt_decl_t)
+            }
             ast::ParamPattern::VarName(name) => {
                 if self
                     .registered_info(name.inspect(), name.is_const())
@@ -303,13 +327,42 @@ impl Context {
                     Ok(())
                 }
             }
-            other => {
-                log!(err "{other}");
-                unreachable!()
-            }
         }
     }
 
+    /// Registers the elements of an array, tuple or record pattern (e.g. `[x, y]`, `{x; y}`)
+    fn assign_nested_params<'a>(
+        &mut self,
+        sig: &ast::ParamSignature,
+        elems: impl Iterator<Item = &'a ast::ParamSignature>,
+        outer: Option<ParamIdx>,
+        nth: usize,
+        opt_decl_t: Option<&ParamTy>,
+    ) -> TyCheckResult<()> {
+        let idx = if let Some(outer) = outer {
+            ParamIdx::nested(outer, nth)
+        } else {
+            let spec_t = self.instantiate_param_sig_t(sig, opt_decl_t, None, Normal)?;
+            self.push_anonymous_param(sig, spec_t, ParamIdx::Nth(nth));
+            ParamIdx::Nth(nth)
+        };
+        for (i, elem) in elems.enumerate() {
+            self.assign_param(elem, Some(idx.clone()), i, None)?;
+        }
+        Ok(())
+    }
+
+    fn push_anonymous_param(&mut self, sig: &ast::ParamSignature, t: Type, idx: ParamIdx) {
+        let default = if sig.opt_default_val.is_some() {
+            DefaultInfo::WithDefault
+        } else {
+            DefaultInfo::NonDefault
+        };
+        let kind = VarKind::parameter(DefId(get_hash(&(&self.name, &idx))), idx, default);
+        self.params
+            .push((None, VarInfo::new(t, Immutable, Private, kind, None, None)));
+    }
+
     pub(crate) fn assign_params(
         &mut self,
         params: &ast::Params,
@@ -338,6 +391,15 @@ impl Context {
                 // TODO: .clone()
                 self.assign_param(sig, None, nth, Some(pt))?;
             }
+            let nth = params.non_defaults.len() + params.defaults.len();
+            if let Some(sig) = params.var_args.as_ref() {
+                let pt = decl_subr_t.var_params.as_deref();
+                self.assign_var_params(sig, nth, pt, false)?;
+            }
+            if let Some(sig) = params.kw_var_args.as_ref() {
+                let pt = decl_subr_t.kw_var_params.as_deref();
+                self.assign_var_params(sig, nth + 1, pt, true)?;
+            }
         } else {
             for (nth, sig) in params
                 .non_defaults
@@ -347,10 +409,91 @@ impl Context {
             {
                 self.assign_param(sig, None, nth, None)?;
             }
+            let nth = params.non_defaults.len() + params.defaults.len();
+            if let Some(sig) = params.var_args.as_ref() {
+                self.assign_var_params(sig, nth, None, false)?;
+            }
+            if let Some(sig) = params.kw_var_args.as_ref() {
+                self.assign_var_params(sig, nth + 1, None, true)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// `*args: T` is registered as `args: Array(T, _)`, and `**kwargs: T` as `kwargs: {Str: T}`
+    fn assign_var_params(
+        &mut self,
+        sig: &ast::ParamSignature,
+        nth: usize,
+        opt_decl_t: Option<&ParamTy>,
+        is_kw: bool,
+    ) -> TyCheckResult<()> {
+        // the parser only accepts simple names for variable-length parameters
+        let name = enum_unwrap!(&sig.pat, ast::ParamPattern::VarName);
+        if self
+            .registered_info(name.inspect(), name.is_const())
+            .is_some()
+        {
+            return Err(TyCheckErrors::from(TyCheckError::reassign_error(
+                self.cfg.input.clone(),
+                line!() as usize,
+                name.loc(),
+                self.caused_by(),
+                name.inspect(),
+            )));
         }
+        let elem_t = self.instantiate_param_sig_t(sig, opt_decl_t, None, Normal)?;
+        let spec_t = if is_kw {
+            dict_t(TyParam::Dict(
+                dict! { TyParam::t(Type::Str) => TyParam::t(elem_t) },
+            ))
+        } else {
+            array_t(elem_t, TyParam::erased(Type::Nat))
+        };
+        let kind = VarKind::parameter(
+            DefId(get_hash(&(&self.name, name))),
+            ParamIdx::Nth(nth),
+            DefaultInfo::NonDefault,
+        );
+        self.params.push((
+            Some(name.clone()),
+            VarInfo::new(spec_t, Immutable, Private, kind, None, None),
+        ));
         Ok(())
     }
 
+    /// Returns the element types of `*args` and `**kwargs` registered by `assign_var_params`
+    pub(crate) fn get_var_params_t(
+        &self,
+        params: &ast::Params,
+    ) -> (Option<ParamTy>, Option<ParamTy>) {
+        let get_param = |sig: &ast::ParamSignature| {
+            let name = sig.inspect()?;
+            self.params
+                .iter()
+                .find(|(n, _)| n.as_ref().map(|n| n.inspect()) == Some(name))
+                .map(|(_, vi)| (name.clone(), vi.t.clone()))
+        };
+        let var_params = params
+            .var_args
+            .as_deref()
+            .and_then(get_param)
+            .and_then(|(name, t)| {
+                let elem_t = t.typarams().into_iter().next()?;
+                Some(ParamTy::pos(Some(name), Type::try_from(elem_t).ok()?))
+            });
+        let kw_var_params =
+            params
+                .kw_var_args
+                .as_deref()
+                .and_then(get_param)
+                .and_then(|(name, t)| {
+                    let elem_t = Dict::<Type, Type>::try_from(t).ok()?.into_values().next()?;
+                    Some(ParamTy::pos(Some(name), elem_t))
+                });
+        (var_params, kw_var_params)
+    }
+
     /// ## Errors
     /// * TypeError: if `return_t` != typeof `body`
     /// * AssignError: if `name` has already been registered
@@ -381,6 +524,7 @@ impl Context {
         let non_default_params = t.non_default_params().unwrap();
         let var_args = t.var_args();
         let default_params = t.default_params().unwrap();
+        let kw_var_args = t.kw_var_args();
         if let Some(spec_ret_t) = t.return_t() {
             self.sub_unify(body_t, spec_ret_t, sig.loc(), None)
                 .map_err(|errs| {
@@ -401,21 +545,19 @@ impl Context {
                     )
                 })?;
         }
-        let sub_t = if sig.ident.is_procedural() {
-            proc(
-                non_default_params.clone(),
-                var_args.cloned(),
-                default_params.clone(),
-                body_t.clone(),
-            )
+        let kind = if sig.ident.is_procedural() {
+            SubrKind::Proc
         } else {
-            func(
-                non_default_params.clone(),
-                var_args.cloned(),
-                default_params.clone(),
-                body_t.clone(),
-            )
+            SubrKind::Func
         };
+        let sub_t = subr_t(
+            kind,
+            non_default_params.clone(),
+            var_args.cloned(),
+            default_params.clone(),
+            kw_var_args.cloned(),
+            body_t.clone(),
+        );
         sub_t.lift();
         let found_t = self.generalize_t(sub_t);
         if let Some(mut vi) = self.decls.remove(name) {
@@ -1024,16 +1166,52 @@ impl Context {
                 return Err(err);
             }
         };
+        if let Input::File(importer) = &self.cfg.input {
+            let importer = importer.canonicalize().unwrap_or_else(|_| importer.clone());
+            mod_cache.add_dependency(importer, path.clone());
+        }
         if mod_cache.get(&path).is_some() {
             return Ok(path);
         }
-        let cfg = ErgConfig::with_module_path(path.clone());
+        let cfg = self.cfg.inherit(path.clone());
+        if let Some(cached) = self.load_module_cache(&path) {
+            let mut builder = HIRBuilder::new_with_cache(
+                cfg.copy(),
+                __name__.clone(),
+                mod_cache.clone(),
+                py_mod_cache.clone(),
+            );
+            if builder.build(cached.decls.clone(), "declare").is_ok() {
+                mod_cache.register(path.clone(), None, builder.pop_mod_ctx());
+                mod_cache.get_mut(&path).unwrap().cache = Some(cached);
+                return Ok(path);
+            }
+        }
         let src = cfg.input.read();
-        let mut builder =
-            HIRBuilder::new_with_cache(cfg, __name__, mod_cache.clone(), py_mod_cache.clone());
-        match builder.build(src, "exec") {
+        let mut builder = HIRBuilder::new_with_cache(
+            cfg.copy(),
+            __name__.clone(),
+            mod_cache.clone(),
+            py_mod_cache.clone(),
+        );
+        match builder.build(src.clone(), "exec") {
             Ok(hir) => {
-                mod_cache.register(path.clone(), Some(hir), builder.pop_mod_ctx());
+                let ctx = builder.pop_mod_ctx();
+                let cache = if cfg.use_cache {
+                    Self::new_module_cache(
+                        cfg,
+                        __name__,
+                        &path,
+                        &src,
+                        &ctx,
+                        mod_cache,
+                        py_mod_cache,
+                    )
+                } else {
+                    None
+                };
+                mod_cache.register(path.clone(), Some(hir), ctx);
+                mod_cache.get_mut(&path).unwrap().cache = cache;
             }
             Err((maybe_hir, errs)) => {
                 if let Some(hir) = maybe_hir {
@@ -1045,6 +1223,49 @@ impl Context {
         Ok(path)
     }
 
+    /// The cache of the module at `path`, if it is up to date.
+    /// If the module is linked, the code object must be cached as well.
+    /// The Python version is not detected unless it is needed for the code object.
+    fn load_module_cache(&self, path: &Path) -> Option<CachedModule> {
+        if !self.cfg.use_cache {
+            return None;
+        }
+        let mut cached = CachedModule::load(path, self.cfg.python_ver)?;
+        if matches!(self.cfg.mode, "exec" | "compile") {
+            let python_ver = self.cfg.python_ver.unwrap_or_else(detect_magic_number);
+            cached.load_code(path, python_ver, self.cfg.opt_level)?;
+        }
+        Some(cached)
+    }
+
+    /// Returns `None` if the signature of the module cannot be cached (see `disk_cache::same_signature`)
+    fn new_module_cache(
+        cfg: ErgConfig,
+        __name__: Str,
+        path: &PathBuf,
+        src: &str,
+        ctx: &Context,
+        mod_cache: &SharedModuleCache,
+        py_mod_cache: &SharedModuleCache,
+    ) -> Option<CachedModule> {
+        let mut deps = vec![];
+        for dep in mod_cache.dependencies(path) {
+            let fingerprint = mod_cache.get(&dep)?.cache.as_ref()?.fingerprint.clone();
+            deps.push((dep, fingerprint));
+        }
+        let decls = declarations(ctx);
+        let python_ver = cfg.python_ver;
+        let mut builder =
+            HIRBuilder::new_with_cache(cfg, __name__, mod_cache.clone(), py_mod_cache.clone());
+        builder.build(decls.clone(), "declare").ok()?;
+        if !same_signature(ctx, &builder.pop_mod_ctx()) {
+            return None;
+        }
+        let cache = CachedModule::new(python_ver, src, deps, decls);
+        cache.store(path).ok()?;
+        Some(cache)
+    }
+
     fn import_py_mod(&mut self, mod_name: &Literal) -> CompileResult<PathBuf> {
         let __name__ = enum_unwrap!(mod_name.value.clone(), ValueObj::Str);
         let py_mod_cache = self.py_mod_cache.as_ref().unwrap();
@@ -1053,6 +1274,17 @@ impl Context {
             return Ok(builtin_path);
         }
         match &__name__[..] {
+            "functools" => {
+                // the bytecode is generated for the Python of `python_ver`
+                let python_ver = if matches!(self.cfg.mode, "exec" | "compile") {
+                    Some(self.cfg.python_ver.unwrap_or_else(detect_magic_number))
+                } else {
+                    self.cfg.python_ver
+                };
+                let functools = Self::init_py_functools_mod(python_ver);
+                py_mod_cache.register(builtin_path.clone(), None, functools);
+                Ok(builtin_path)
+            }
             "glob" => {
                 py_mod_cache.register(builtin_path.clone(), None, Self::init_py_glob_mod());
                 Ok(builtin_path)
@@ -221,6 +221,14 @@ impl Context {
                         lazy_inits,
                     );
                 });
+                if let Some(kw_var_args) = &mut subr.kw_var_params {
+                    *kw_var_args.typ_mut() = self.generalize_t_inner(
+                        mem::take(kw_var_args.typ_mut()),
+                        Contravariant,
+                        bounds,
+                        lazy_inits,
+                    );
+                }
                 let return_t =
                     self.generalize_t_inner(*subr.return_t, Covariant, bounds, lazy_inits);
                 subr_t(
@@ -228,6 +236,7 @@ impl Context {
                     subr.non_default_params,
                     subr.var_params.map(|x| *x),
                     subr.default_params,
+                    subr.kw_var_params.map(|x| *x),
                     return_t,
                 )
             }
@@ -500,7 +509,7 @@ impl Context {
                 let typ = poly(&name, params.clone());
                 let ctx = self.get_nominal_type_ctx(&typ).unwrap();
                 let variances = ctx.type_params_variance();
-                for (param, variance) in params.iter_mut().zip(variances.into_iter()) {
+                for (param, variance) in params.iter_mut().zip(variances) {
                     *param = self.deref_tp(mem::take(param), variance, loc)?;
                 }
                 Ok(Type::Poly { name, params })
@@ -518,6 +527,10 @@ impl Context {
                     *d_param.typ_mut() =
                         self.deref_tyvar(mem::take(d_param.typ_mut()), Contravariant, loc)?;
                 }
+                if let Some(kw_var_args) = &mut subr.kw_var_params {
+                    *kw_var_args.typ_mut() =
+                        self.deref_tyvar(mem::take(kw_var_args.typ_mut()), Contravariant, loc)?;
+                }
                 subr.return_t =
                     Box::new(self.deref_tyvar(mem::take(&mut subr.return_t), Covariant, loc)?);
                 Ok(Type::Subr(subr))
@@ -724,9 +737,31 @@ impl Context {
         }
     }
 
+    /// for comprehensions
+    fn resolve_clauses_t(
+        &self,
+        generators: &mut [(hir::Identifier, hir::Expr)],
+        guards: &mut [hir::Expr],
+    ) -> SingleTyCheckResult<()> {
+        for (ident, iterable) in generators.iter_mut() {
+            ident.t = self.deref_tyvar(mem::take(&mut ident.t), Covariant, ident.loc())?;
+            self.resolve_expr_t(iterable)?;
+        }
+        for guard in guards.iter_mut() {
+            self.resolve_expr_t(guard)?;
+        }
+        Ok(())
+    }
+
     fn resolve_expr_t(&self, expr: &mut hir::Expr) -> SingleTyCheckResult<()> {
         match expr {
             hir::Expr::Lit(_) => Ok(()),
+            hir::Expr::StrInterp(interp) => {
+                for expr in interp.exprs.iter_mut() {
+                    self.resolve_expr_t(expr)?;
+                }
+                Ok(())
+            }
             hir::Expr::Accessor(acc) => {
                 let loc = acc.loc();
                 let t = acc.ref_mut_t();
@@ -765,7 +800,12 @@ impl Context {
                     self.resolve_expr_t(&mut arr.len)?;
                     Ok(())
                 }
-                _ => todo!(),
+                hir::Array::Comprehension(arr) => {
+                    let loc = arr.loc();
+                    arr.t = self.deref_tyvar(mem::take(&mut arr.t), Covariant, loc)?;
+                    self.resolve_clauses_t(&mut arr.generators, &mut arr.guards)?;
+                    self.resolve_expr_t(&mut arr.elem)
+                }
             },
             hir::Expr::Tuple(tuple) => match tuple {
                 hir::Tuple::Normal(tup) => {
@@ -791,6 +831,12 @@ impl Context {
                     self.resolve_expr_t(&mut st.len)?;
                     Ok(())
                 }
+                hir::Set::Comprehension(st) => {
+                    let loc = st.loc();
+                    st.t = self.deref_tyvar(mem::take(&mut st.t), Covariant, loc)?;
+                    self.resolve_clauses_t(&mut st.generators, &mut st.guards)?;
+                    self.resolve_expr_t(&mut st.elem)
+                }
             },
             hir::Expr::Dict(dict) => match dict {
                 hir::Dict::Normal(dic) => {
@@ -802,7 +848,13 @@ impl Context {
                     }
                     Ok(())
                 }
-                other => todo!("{other}"),
+                hir::Dict::Comprehension(dic) => {
+                    let loc = dic.loc();
+                    dic.t = self.deref_tyvar(mem::take(&mut dic.t), Covariant, loc)?;
+                    self.resolve_clauses_t(&mut dic.generators, &mut dic.guards)?;
+                    self.resolve_expr_t(&mut dic.key)?;
+                    self.resolve_expr_t(&mut dic.value)
+                }
             },
             hir::Expr::Record(record) => {
                 for attr in record.attrs.iter_mut() {
@@ -933,6 +985,9 @@ impl Context {
                 if let Some(var_params) = subr.var_params.as_ref() {
                     self.occur(var_params.typ(), maybe_sup, loc)?;
                 }
+                if let Some(kw_var_params) = subr.kw_var_params.as_ref() {
+                    self.occur(kw_var_params.typ(), maybe_sup, loc)?;
+                }
                 for non_default_t in subr.non_default_params.iter().map(|pt| pt.typ()) {
                     self.occur(non_default_t, maybe_sup, loc)?;
                 }
@@ -946,6 +1001,9 @@ impl Context {
                 if let Some(var_params) = subr.var_params.as_ref() {
                     self.occur(maybe_sub, var_params.typ(), loc)?;
                 }
+                if let Some(kw_var_params) = subr.kw_var_params.as_ref() {
+                    self.occur(maybe_sub, kw_var_params.typ(), loc)?;
+                }
                 for non_default_t in subr.non_default_params.iter().map(|pt| pt.typ()) {
                     self.occur(maybe_sub, non_default_t, loc)?;
                 }
@@ -985,6 +1043,7 @@ impl Context {
     }
 
     /// allow_divergence = trueにすると、Num型変数と±Infの単一化を許す
+    #[allow(clippy::only_used_in_recursion)]
     pub(crate) fn sub_unify_tp(
         &self,
         maybe_sub: &TyParam,
@@ -1521,6 +1580,12 @@ impl Context {
                     // contravariant
                     |(l, r)| self.sub_unify(r.typ(), l.typ(), loc, param_name),
                 )?;
+                if let Some((l, r)) = lsub.var_params.as_ref().zip(rsub.var_params.as_ref()) {
+                    self.sub_unify(r.typ(), l.typ(), loc, param_name)?;
+                }
+                if let Some((l, r)) = lsub.kw_var_params.as_ref().zip(rsub.kw_var_params.as_ref()) {
+                    self.sub_unify(r.typ(), l.typ(), loc, param_name)?;
+                }
                 // covariant
                 self.sub_unify(&lsub.return_t, &rsub.return_t, loc, param_name)?;
                 Ok(())
@@ -1600,6 +1665,32 @@ impl Context {
                 }
                 todo!("{l}, {r}")
             },
+            // e.g. Str <: Iterable(?T)
+            (Mono(_) | Refinement(_), Poly { params: rps, .. }) => {
+                let sub = if let Refinement(refine) = maybe_sub {
+                    refine.t.as_ref()
+                } else {
+                    maybe_sub
+                };
+                if let Some(sub_ctx) = self.get_nominal_type_ctx(sub) {
+                    for sup_trait in sub_ctx.super_traits.iter() {
+                        if self.supertype_of(maybe_sup, sup_trait) {
+                            for (l_maybe_sub, r_maybe_sup) in sup_trait.typarams().iter().zip(rps.iter()) {
+                                self.sub_unify_tp(l_maybe_sub, r_maybe_sup, None, loc, false)?;
+                            }
+                            return Ok(());
+                        }
+                    }
+                }
+                Err(TyCheckErrors::from(TyCheckError::unification_error(
+                    self.cfg.input.clone(),
+                    line!() as usize,
+                    maybe_sub,
+                    maybe_sup,
+                    loc,
+                    self.caused_by(),
+                )))
+            }
             (Type::Subr(_) | Type::Record(_), Type) => Ok(()),
             // REVIEW: correct?
             (Type::Poly{ name, .. }, Type) if &name[..] == "Array" || &name[..] == "Tuple" => Ok(()),
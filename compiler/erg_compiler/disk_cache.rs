@@ -0,0 +1,306 @@
+//! defines `CachedModule`, the on-disk cache of an imported module.
+//!
+//! `foo.er` is cached in `__erg_cache__` (next to `foo.er`):
+//! * `foo.json` holds the public signature of the module (in the `.d.er` format), which is read by the type checker
+//! * `foo.<magic number>.opt-<opt level>.pyc` holds the code object, which is read by the linker
+//!
+//! The signature does not depend on the Python version, so the check mode does not need Python.
+//! A cache is used only if the compiler build, the source and the caches of the dependencies are unchanged.
+//!
+//! インポートされたモジュールのディスクキャッシュを定義する
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use erg_common::config::{BUILD_DATE, GIT_HASH_SHORT, SEMVER};
+use erg_common::json::JsonValue;
+use erg_common::serialize::get_magic_num_bytes;
+
+use crate::context::Context;
+use crate::ty::codeobj::CodeObj;
+use crate::ty::typaram::TyParam;
+use crate::ty::value::ValueObj;
+use crate::ty::{Predicate, Type};
+
+pub const CACHE_DIR: &str = "__erg_cache__";
+
+/// The flags of a hash-based .pyc (PEP 552) whose source is not checked by Python
+const UNCHECKED_HASH_BASED: u32 = 0b01;
+
+/// FNV-1a (the hash of `std` is not guaranteed to be stable between runs)
+fn hash(bytes: &[u8]) -> u64 {
+    let mut h: u64 = 0xcbf29ce484222325;
+    for b in bytes {
+        h ^= *b as u64;
+        h = h.wrapping_mul(0x100000001b3);
+    }
+    h
+}
+
+/// The compiler is identified by the build date as well, since the version is not bumped on every change
+fn compiler_build() -> String {
+    format!("{SEMVER} ({}, {BUILD_DATE})", GIT_HASH_SHORT.trim())
+}
+
+fn fingerprint(python_ver: Option<u32>, source_hash: &str, deps: &[(PathBuf, String)]) -> String {
+    let mut key = format!("{}:{python_ver:?}:{source_hash}", compiler_build());
+    for (_, dep) in deps.iter() {
+        key.push(':');
+        key.push_str(dep);
+    }
+    format!("{:016x}", hash(key.as_bytes()))
+}
+
+fn cache_dir(path: &Path) -> PathBuf {
+    let mut dir = path.parent().map(Path::to_path_buf).unwrap_or_default();
+    dir.push(CACHE_DIR);
+    dir
+}
+
+fn file_stem(path: &Path) -> &str {
+    path.file_stem().and_then(|s| s.to_str()).unwrap_or("")
+}
+
+/// `__erg_cache__/foo.json` for `foo.er`
+pub fn cache_path(path: &Path) -> PathBuf {
+    cache_dir(path).join(format!("{}.json", file_stem(path)))
+}
+
+/// `__erg_cache__/foo.3439.opt-1.pyc` for `foo.er`
+pub fn code_cache_path(path: &Path, python_ver: u32, opt_level: u8) -> PathBuf {
+    cache_dir(path).join(format!(
+        "{}.{python_ver}.opt-{opt_level}.pyc",
+        file_stem(path)
+    ))
+}
+
+/// Writes `contents` to a temporary file and renames it, so that a half-written cache is never read
+fn write_atomically(path: &Path, contents: &[u8]) -> io::Result<()> {
+    fs::create_dir_all(path.parent().unwrap())?;
+    let mut tmp_path = path.as_os_str().to_owned();
+    tmp_path.push(format!(".{}", std::process::id()));
+    fs::write(&tmp_path, contents)?;
+    fs::rename(&tmp_path, path)
+}
+
+#[derive(Debug, Clone)]
+pub struct CachedModule {
+    /// hash of the compiler build, the Python version (if specified), the source and the fingerprints of the dependencies
+    pub fingerprint: String,
+    source_hash: String,
+    /// (path, fingerprint) of the imported Erg modules
+    deps: Vec<(PathBuf, String)>,
+    /// the public signature in the `.d.er` format
+    pub decls: String,
+    /// the marshaled code object, `None` until it is read or compiled by the linker
+    pub code: Option<Vec<u8>>,
+}
+
+impl CachedModule {
+    /// `python_ver` is the version specified by the user (`None` if it is detected)
+    pub fn new(
+        python_ver: Option<u32>,
+        src: &str,
+        mut deps: Vec<(PathBuf, String)>,
+        decls: String,
+    ) -> Self {
+        deps.sort();
+        let source_hash = format!("{:016x}", hash(src.as_bytes()));
+        Self {
+            fingerprint: fingerprint(python_ver, &source_hash, &deps),
+            source_hash,
+            deps,
+            decls,
+            code: None,
+        }
+    }
+
+    /// Loads the signature of the module at `path`. Returns `None` if it is missing or outdated.
+    pub fn load(path: &Path, python_ver: Option<u32>) -> Option<Self> {
+        let json = fs::read_to_string(cache_path(path)).ok()?;
+        let json = JsonValue::parse(&json).ok()?;
+        if json.get("compiler")?.as_str()? != compiler_build() {
+            return None;
+        }
+        let source_hash = json.get("source")?.as_str()?.to_string();
+        if format!("{:016x}", hash(fs::read(path).ok()?.as_slice())) != source_hash {
+            return None;
+        }
+        let mut deps = vec![];
+        for dep in json.get("deps")?.as_array()? {
+            let dep_path = PathBuf::from(dep.get("path")?.as_str()?);
+            let dep_fingerprint = dep.get("fingerprint")?.as_str()?;
+            if Self::load(&dep_path, python_ver)?.fingerprint != dep_fingerprint {
+                return None;
+            }
+            deps.push((dep_path, dep_fingerprint.to_string()));
+        }
+        let fingerprint = fingerprint(python_ver, &source_hash, &deps);
+        if json.get("fingerprint")?.as_str()? != fingerprint {
+            return None;
+        }
+        Some(Self {
+            fingerprint,
+            source_hash,
+            deps,
+            decls: json.get("decls")?.as_str()?.to_string(),
+            code: None,
+        })
+    }
+
+    /// Writes the signature of the module at `path`.
+    pub fn store(&self, path: &Path) -> io::Result<()> {
+        let deps = self
+            .deps
+            .iter()
+            .map(|(path, fingerprint)| {
+                JsonValue::object(vec![
+                    ("path", path.to_string_lossy().to_string().into()),
+                    ("fingerprint", fingerprint.clone().into()),
+                ])
+            })
+            .collect::<Vec<_>>();
+        let json = JsonValue::object(vec![
+            ("compiler", compiler_build().into()),
+            ("fingerprint", self.fingerprint.clone().into()),
+            ("source", self.source_hash.clone().into()),
+            ("deps", deps.into()),
+            ("decls", self.decls.clone().into()),
+        ]);
+        write_atomically(&cache_path(path), json.to_string().as_bytes())
+    }
+
+    /// The header of the code cache: a hash-based .pyc whose hash is the fingerprint of the module
+    fn pyc_header(&self, python_ver: u32) -> Vec<u8> {
+        let fingerprint = u64::from_str_radix(&self.fingerprint, 16).unwrap_or(0);
+        let mut header = get_magic_num_bytes(python_ver).to_vec();
+        header.extend(UNCHECKED_HASH_BASED.to_le_bytes());
+        header.extend(fingerprint.to_le_bytes());
+        header
+    }
+
+    /// Reads the marshaled code object of the module at `path`.
+    /// Returns `None` if it is missing or was compiled from another version of the module.
+    pub fn load_code(&mut self, path: &Path, python_ver: u32, opt_level: u8) -> Option<Vec<u8>> {
+        if self.code.is_none() {
+            let pyc = fs::read(code_cache_path(path, python_ver, opt_level)).ok()?;
+            let header = self.pyc_header(python_ver);
+            self.code = pyc.strip_prefix(&header[..]).map(<[u8]>::to_vec);
+        }
+        self.code.clone()
+    }
+
+    /// Writes the code object of the module at `path` and returns it marshaled.
+    pub fn store_code(
+        &mut self,
+        path: &Path,
+        python_ver: u32,
+        opt_level: u8,
+        code: CodeObj,
+    ) -> io::Result<Vec<u8>> {
+        let code = code.into_bytes(python_ver);
+        let mut pyc = self.pyc_header(python_ver);
+        pyc.extend(&code);
+        write_atomically(&code_cache_path(path, python_ver, opt_level), &pyc)?;
+        self.code = Some(code.clone());
+        Ok(code)
+    }
+}
+
+/// The public variables of `ctx` as declarations (`.x: Int`)
+pub(crate) fn declarations(ctx: &Context) -> String {
+    let mut decls = ctx
+        .locals
+        .iter()
+        .filter(|(_, vi)| vi.vis.is_public())
+        .map(|(name, vi)| format!(".{name}: {}\n", type_spec(&vi.t)))
+        .collect::<Vec<_>>();
+    decls.sort();
+    decls.concat()
+}
+
+/// `t` in the type specification syntax (`Display` of `Type` is not always parsable, e.g. `{1, }`)
+fn type_spec(t: &Type) -> String {
+    match t {
+        // `{1, 2}` → `{V: Int | V == 1 or V == 2}` (the variable name of `refine` is not parsable, e.g. `%v1`)
+        Type::Refinement(refine) if refine.preds.iter().all(|pred| pred.is_equal()) => {
+            let mut preds = refine
+                .preds
+                .iter()
+                .map(|pred| match pred {
+                    Predicate::Equal { rhs, .. } => format!("V == {}", typaram_spec(rhs)),
+                    _ => unreachable!(),
+                })
+                .collect::<Vec<_>>();
+            preds.sort();
+            format!("{{V: {} | {}}}", type_spec(&refine.t), preds.join(" or "))
+        }
+        Type::Subr(subr) if subr.var_params.is_none() => {
+            let mut params = subr
+                .non_default_params
+                .iter()
+                .map(|pt| match pt.name() {
+                    Some(name) => format!("{name}: {}", type_spec(pt.typ())),
+                    None => type_spec(pt.typ()),
+                })
+                .collect::<Vec<_>>();
+            for pt in subr.default_params.iter() {
+                params.push(format!("{} := {}", pt.name().unwrap(), type_spec(pt.typ())));
+            }
+            // `(x: Int)` is not a parameter list but a type ascription
+            let trailing = if params.len() == 1 { "," } else { "" };
+            format!(
+                "({}{trailing}) {} {}",
+                params.join(", "),
+                subr.kind.arrow(),
+                type_spec(&subr.return_t)
+            )
+        }
+        Type::Poly { name, params } => {
+            let params = params.iter().map(typaram_spec).collect::<Vec<_>>();
+            format!("{name}({})", params.join(", "))
+        }
+        other => other.to_string(),
+    }
+}
+
+fn typaram_spec(tp: &TyParam) -> String {
+    match tp {
+        TyParam::Type(t) => type_spec(t),
+        TyParam::Value(ValueObj::Int(i)) => i.to_string(),
+        TyParam::Value(ValueObj::Nat(n)) => n.to_string(),
+        TyParam::Value(ValueObj::Float(f)) => format!("{f:?}"),
+        TyParam::Value(ValueObj::Str(s)) => format!("\"{s}\""),
+        TyParam::Value(ValueObj::Bool(b)) => if *b { "True" } else { "False" }.to_string(),
+        other => other.to_string(),
+    }
+}
+
+/// Whether the public variables of `decl_ctx` (built from `declarations(ctx)`) have the same types as `ctx`.
+/// Signatures that cannot be written as declarations (e.g. classes, constants) are not cached.
+pub(crate) fn same_signature(ctx: &Context, decl_ctx: &Context) -> bool {
+    if !ctx.mono_types.is_empty()
+        || !ctx.poly_types.is_empty()
+        || !ctx.patches.is_empty()
+        || !ctx.consts.is_empty()
+    {
+        return false;
+    }
+    let publics = ctx
+        .locals
+        .iter()
+        .filter(|(_, vi)| vi.vis.is_public())
+        .collect::<Vec<_>>();
+    publics.len()
+        == decl_ctx
+            .locals
+            .iter()
+            .filter(|(_, vi)| vi.vis.is_public())
+            .count()
+        && publics.iter().all(|(name, vi)| {
+            decl_ctx
+                .locals
+                .get(name.inspect())
+                .is_some_and(|decl| ctx.same_type_of(&decl.t, &vi.t))
+        })
+}
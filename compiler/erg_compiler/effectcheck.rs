@@ -12,7 +12,7 @@ use Visibility::*;
 use crate::ty::HasType;
 
 use crate::error::{EffectError, EffectErrors};
-use crate::hir::{Array, Def, Dict, Expr, Set, Signature, Tuple, HIR};
+use crate::hir::{Accessor, Array, Call, Def, Dict, Expr, Set, Signature, Tuple, HIR};
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
 enum BlockKind {
@@ -96,12 +96,7 @@ impl SideEffectChecker {
                     }
                 }
                 Expr::Call(call) => {
-                    for parg in call.args.pos_args.iter() {
-                        self.check_expr(&parg.expr);
-                    }
-                    for kwarg in call.args.kw_args.iter() {
-                        self.check_expr(&kwarg.expr);
-                    }
+                    self.check_args(call);
                 }
                 Expr::BinOp(bin) => {
                     self.check_expr(&bin.lhs);
@@ -111,6 +106,11 @@ impl SideEffectChecker {
                     self.check_expr(&unary.expr);
                 }
                 Expr::Accessor(_) | Expr::Lit(_) => {}
+                Expr::StrInterp(interp) => {
+                    for expr in interp.exprs.iter() {
+                        self.check_expr(expr);
+                    }
+                }
                 Expr::Array(array) => match array {
                     Array::Normal(arr) => {
                         for elem in arr.elems.pos_args.iter() {
@@ -121,9 +121,8 @@ impl SideEffectChecker {
                         self.check_expr(&arr.elem);
                         self.check_expr(&arr.len);
                     }
-                    Array::Comprehension(arr) => {
-                        self.check_expr(&arr.elem);
-                        self.check_expr(&arr.guard);
+                    Array::Comprehension(_) => {
+                        expr.for_each_child(&mut |child| self.check_expr(child));
                     }
                 },
                 Expr::Tuple(tuple) => match tuple {
@@ -152,6 +151,9 @@ impl SideEffectChecker {
                         self.check_expr(&set.elem);
                         self.check_expr(&set.len);
                     }
+                    Set::Comprehension(_) => {
+                        expr.for_each_child(&mut |child| self.check_expr(child));
+                    }
                 },
                 Expr::Dict(dict) => match dict {
                     Dict::Normal(dict) => {
@@ -160,7 +162,9 @@ impl SideEffectChecker {
                             self.check_expr(&kv.value);
                         }
                     }
-                    other => todo!("{other}"),
+                    Dict::Comprehension(_) => {
+                        expr.for_each_child(&mut |child| self.check_expr(child));
+                    }
                 },
                 Expr::TypeAsc(tasc) => {
                     self.check_expr(&tasc.expr);
@@ -190,6 +194,20 @@ impl SideEffectChecker {
     }
 
     fn check_def(&mut self, def: &Def) {
+        // decorators are applied in the outer scope
+        if let Signature::Subr(subr) = &def.sig {
+            for deco in subr.decorators.iter() {
+                self.check_expr(deco);
+                if deco.t().is_procedural() && !self.in_context_effects_allowed() {
+                    self.errs.push(EffectError::has_effect(
+                        self.cfg.input.clone(),
+                        line!() as usize,
+                        deco,
+                        self.full_path(),
+                    ));
+                }
+            }
+        }
         let name_and_vis = match &def.sig {
             Signature::Var(var) => (var.inspect().clone(), var.vis()),
             Signature::Subr(subr) => (subr.ident.inspect().clone(), subr.ident.vis()),
@@ -281,9 +299,8 @@ impl SideEffectChecker {
                     self.check_expr(&arr.elem);
                     self.check_expr(&arr.len);
                 }
-                Array::Comprehension(arr) => {
-                    self.check_expr(&arr.elem);
-                    self.check_expr(&arr.guard);
+                Array::Comprehension(_) => {
+                    expr.for_each_child(&mut |child| self.check_expr(child));
                 }
             },
             Expr::Tuple(tuple) => match tuple {
@@ -312,6 +329,9 @@ impl SideEffectChecker {
                     self.check_expr(&set.elem);
                     self.check_expr(&set.len);
                 }
+                Set::Comprehension(_) => {
+                    expr.for_each_child(&mut |child| self.check_expr(child));
+                }
             },
             Expr::Dict(dict) => match dict {
                 Dict::Normal(dict) => {
@@ -320,7 +340,9 @@ impl SideEffectChecker {
                         self.check_expr(&kv.value);
                     }
                 }
-                other => todo!("{other}"),
+                Dict::Comprehension(_) => {
+                    expr.for_each_child(&mut |child| self.check_expr(child));
+                }
             },
             // 引数がproceduralでも関数呼び出しなら副作用なし
             Expr::Call(call) => {
@@ -339,14 +361,7 @@ impl SideEffectChecker {
                         self.full_path(),
                     ));
                 }
-                call.args
-                    .pos_args
-                    .iter()
-                    .for_each(|parg| self.check_expr(&parg.expr));
-                call.args
-                    .kw_args
-                    .iter()
-                    .for_each(|kwarg| self.check_expr(&kwarg.expr));
+                self.check_args(call);
             }
             Expr::UnaryOp(unary) => {
                 self.check_expr(&unary.expr);
@@ -355,6 +370,11 @@ impl SideEffectChecker {
                 self.check_expr(&bin.lhs);
                 self.check_expr(&bin.rhs);
             }
+            Expr::StrInterp(interp) => {
+                for expr in interp.exprs.iter() {
+                    self.check_expr(expr);
+                }
+            }
             Expr::Lambda(lambda) => {
                 let is_proc = lambda.is_procedural();
                 if is_proc {
@@ -374,4 +394,41 @@ impl SideEffectChecker {
             _ => {}
         }
     }
+
+    fn check_args(&mut self, call: &Call) {
+        let is_while = call.attr_name.is_none()
+            && matches!(
+                call.obj.as_ref(),
+                Expr::Accessor(Accessor::Ident(ident))
+                    if ident.vis().is_private() && &ident.inspect()[..] == "while!"
+            );
+        for (i, parg) in call.args.pos_args.iter().enumerate() {
+            if is_while && i == 0 {
+                self.check_loop_cond(&parg.expr);
+            } else {
+                self.check_expr(&parg.expr);
+            }
+        }
+        for kwarg in call.args.kw_args.iter() {
+            if is_while && &kwarg.keyword.inspect()[..] == "cond" {
+                self.check_loop_cond(&kwarg.expr);
+            } else {
+                self.check_expr(&kwarg.expr);
+            }
+        }
+    }
+
+    /// The condition of `while!` is evaluated on every iteration, so it must not have side effects.
+    /// Mutations should be done in the loop body (procedure block).
+    /// ```python
+    /// while! i < 10, do!: i.inc!() # OK
+    /// while! i.inc!() < 10, do!: ... # NG
+    /// ```
+    fn check_loop_cond(&mut self, cond: &Expr) {
+        self.path_stack.push((Str::ever("<while!::cond>"), Private));
+        self.block_stack.push(Func);
+        self.check_expr(cond);
+        self.path_stack.pop();
+        self.block_stack.pop();
+    }
 }
@@ -4,6 +4,7 @@ use erg_common::astr::AtomicStr;
 use erg_common::color::{GREEN, RED, RESET, YELLOW};
 use erg_common::config::Input;
 use erg_common::error::{ErrorCore, ErrorDisplay, ErrorKind::*, Location, MultiErrorDisplay};
+use erg_common::python_util::PythonVersion;
 use erg_common::set::Set;
 use erg_common::traits::{Locational, Stream};
 use erg_common::vis::Visibility;
@@ -218,6 +219,30 @@ impl CompileError {
         )
     }
 
+    pub fn unsupported_python_version_error(input: Input, magic_num: u32) -> Self {
+        let supported = PythonVersion::ALL
+            .iter()
+            .map(|ver| format!("{ver} ({})", ver.magic_num()))
+            .collect::<Vec<_>>()
+            .join(", ");
+        Self::new(
+            ErrorCore::new(
+                0,
+                EnvironmentError,
+                Location::Unknown,
+                switch_lang!(
+                    "japanese" => format!("サポートされていないPythonのバージョンです (マジックナンバー: {magic_num})"),
+                    "simplified_chinese" => format!("不支持的Python版本 (魔数: {magic_num})"),
+                    "traditional_chinese" => format!("不支援的Python版本 (魔數: {magic_num})"),
+                    "english" => format!("unsupported Python version (magic number: {magic_num})"),
+                ),
+                Some(format!("supported versions: {supported}").into()),
+            ),
+            input,
+            "".into(),
+        )
+    }
+
     pub fn system_exit() -> Self {
         Self::new(
             ErrorCore::new(
@@ -449,7 +474,7 @@ impl TyCheckError {
         Self::new(
             ErrorCore::new(
                 errno,
-                TypeError,
+                PatternError,
                 loc,
                 switch_lang!(
                     "japanese" => format!("{expr_t}型の全パターンを網羅していません"),
@@ -464,6 +489,38 @@ impl TyCheckError {
         )
     }
 
+    pub fn unreachable_pattern_warning(
+        input: Input,
+        errno: usize,
+        loc: Location,
+        caused_by: AtomicStr,
+    ) -> Self {
+        Self::new(
+            ErrorCore::new(
+                errno,
+                PatternWarning,
+                loc,
+                switch_lang!(
+                    "japanese" => "このパターンには到達しません",
+                    "simplified_chinese" => "此模式无法到达",
+                    "traditional_chinese" => "此模式無法到達",
+                    "english" => "this pattern is unreachable",
+                ),
+                Some(
+                    switch_lang!(
+                        "japanese" => "前のパターンで既に網羅されています",
+                        "simplified_chinese" => "之前的模式已经涵盖了它",
+                        "traditional_chinese" => "之前的模式已經涵蓋了它",
+                        "english" => "the previous patterns already cover it",
+                    )
+                    .into(),
+                ),
+            ),
+            input,
+            caused_by,
+        )
+    }
+
     pub fn infer_error(
         input: Input,
         errno: usize,
@@ -1553,6 +1610,25 @@ impl LowerError {
         Self::file_error(input, errno, desc, loc, caused_by, hint)
     }
 
+    pub fn cyclic_import_error(input: Input, errno: usize, caused_by: AtomicStr) -> Self {
+        Self::new(
+            ErrorCore::new(
+                errno,
+                ImportError,
+                Location::Unknown,
+                switch_lang!(
+                    "japanese" => format!("モジュールが循環インポートしています"),
+                    "simplified_chinese" => format!("检测到模块的循环导入"),
+                    "traditional_chinese" => format!("檢測到模組的循環導入"),
+                    "english" => format!("cyclic imports between modules are detected"),
+                ),
+                None,
+            ),
+            input,
+            caused_by,
+        )
+    }
+
     pub fn inner_typedef_error(
         input: Input,
         errno: usize,
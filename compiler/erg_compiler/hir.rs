@@ -1,5 +1,6 @@
 /// defines High-level Intermediate Representation
 use std::fmt;
+use std::fmt::Write as _;
 
 use erg_common::dict::Dict as HashMap;
 use erg_common::error::Location;
@@ -7,13 +8,13 @@ use erg_common::traits::{Locational, NestedDisplay, Stream};
 use erg_common::vis::{Field, Visibility};
 use erg_common::Str;
 use erg_common::{
-    enum_unwrap, fmt_option, fmt_vec, impl_display_for_enum, impl_display_from_nested,
+    dict, enum_unwrap, fmt_option, fmt_vec, impl_display_for_enum, impl_display_from_nested,
     impl_locational, impl_locational_for_enum, impl_nested_display_for_chunk_enum,
     impl_nested_display_for_enum, impl_stream_for_wrapper,
 };
 
 use erg_parser::ast::{fmt_lines, DefId, DefKind, Params, TypeSpec, VarName};
-use erg_parser::token::{Token, TokenKind};
+use erg_parser::token::{Token, TokenCategory, TokenKind};
 
 use crate::ty::constructors::{array_t, dict_t, set_t, tuple_t};
 use crate::ty::typaram::TyParam;
@@ -63,12 +64,69 @@ impl TryFrom<Token> for Literal {
 }
 
 impl Literal {
+    pub fn new(value: ValueObj, token: Token) -> Self {
+        Self {
+            t: value.t(),
+            value,
+            token,
+        }
+    }
+
     #[inline]
     pub fn is(&self, kind: TokenKind) -> bool {
         self.token.is(kind)
     }
 }
 
+/// String interpolation
+/// e.g. `"Hello, \{name}!"`
+///
+/// `exprs[i]` is placed between `fragments[i]` and `fragments[i + 1]`.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct StrInterp {
+    pub fragments: Vec<Literal>,
+    pub exprs: Vec<Expr>,
+    t: Type,
+}
+
+impl_t!(StrInterp);
+
+impl NestedDisplay for StrInterp {
+    fn fmt_nest(&self, f: &mut fmt::Formatter<'_>, _level: usize) -> fmt::Result {
+        write!(f, "\"")?;
+        for (fragment, expr) in self.fragments.iter().zip(self.exprs.iter()) {
+            write!(f, "{}\\{{{expr}}}", fragment.value)?;
+        }
+        write!(
+            f,
+            "{}\" (: {})",
+            self.fragments.last().unwrap().value,
+            self.t
+        )
+    }
+}
+
+impl_display_from_nested!(StrInterp);
+
+impl Locational for StrInterp {
+    fn loc(&self) -> Location {
+        Location::concat(
+            self.fragments.first().unwrap(),
+            self.fragments.last().unwrap(),
+        )
+    }
+}
+
+impl StrInterp {
+    pub fn new(fragments: Vec<Literal>, exprs: Vec<Expr>) -> Self {
+        Self {
+            fragments,
+            exprs,
+            t: Type::Str,
+        }
+    }
+}
+
 #[derive(Debug, Clone, PartialEq, Eq, Hash)]
 pub struct PosArg {
     pub expr: Expr,
@@ -274,7 +332,7 @@ impl Args {
 
     pub fn get_left_or_key(&self, key: &str) -> Option<&Expr> {
         if !self.pos_args.is_empty() {
-            Some(&self.pos_args.get(0)?.expr)
+            Some(&self.pos_args.first()?.expr)
         } else if let Some(pos) = self
             .kw_args
             .iter()
@@ -303,6 +361,30 @@ impl Args {
     pub fn insert_pos(&mut self, idx: usize, pos: PosArg) {
         self.pos_args.insert(idx, pos);
     }
+
+    pub fn for_each_expr(&self, f: &mut impl FnMut(&Expr)) {
+        for arg in self.pos_args.iter() {
+            f(&arg.expr);
+        }
+        if let Some(var_args) = &self.var_args {
+            f(&var_args.expr);
+        }
+        for arg in self.kw_args.iter() {
+            f(&arg.expr);
+        }
+    }
+
+    pub fn for_each_expr_mut(&mut self, f: &mut impl FnMut(&mut Expr)) {
+        for arg in self.pos_args.iter_mut() {
+            f(&mut arg.expr);
+        }
+        if let Some(var_args) = &mut self.var_args {
+            f(&mut var_args.expr);
+        }
+        for arg in self.kw_args.iter_mut() {
+            f(&mut arg.expr);
+        }
+    }
 }
 
 #[derive(Debug, Clone, PartialEq, Eq, Hash)]
@@ -572,11 +654,35 @@ impl Accessor {
         match self {
             Self::Ident(ident) => ident.qual_name.as_ref().map(|s| {
                 let name = s.split("::").last().unwrap_or(&s[..]);
-                name.split('.').last().unwrap_or(name)
+                name.split('.').next_back().unwrap_or(name)
             }),
             _ => None,
         }
     }
+
+    pub fn for_each_child(&self, f: &mut impl FnMut(&Expr)) {
+        match self {
+            Accessor::Ident(_) => {}
+            Accessor::Attr(attr) => f(&attr.obj),
+            Accessor::TupleAttr(attr) => f(&attr.obj),
+            Accessor::Subscr(subscr) => {
+                f(&subscr.obj);
+                f(&subscr.index);
+            }
+        }
+    }
+
+    pub fn for_each_child_mut(&mut self, f: &mut impl FnMut(&mut Expr)) {
+        match self {
+            Accessor::Ident(_) => {}
+            Accessor::Attr(attr) => f(&mut attr.obj),
+            Accessor::TupleAttr(attr) => f(&mut attr.obj),
+            Accessor::Subscr(subscr) => {
+                f(&mut subscr.obj);
+                f(&mut subscr.index);
+            }
+        }
+    }
 }
 
 #[derive(Debug, Clone, PartialEq, Eq, Hash)]
@@ -610,19 +716,40 @@ impl ArrayWithLength {
     }
 }
 
-// TODO: generators
+/// `i <- xs; j <- ys; i + j > 0`
+fn fmt_comprehension_clauses(generators: &[(Identifier, Expr)], guards: &[Expr]) -> String {
+    let mut clauses = String::new();
+    for (name, gen) in generators.iter() {
+        write!(clauses, "{name} <- {gen}; ").unwrap();
+    }
+    for guard in guards.iter() {
+        write!(clauses, "{guard}; ").unwrap();
+    }
+    clauses.trim_end_matches("; ").to_string()
+}
+
+/// e.g. `[i * 2 | i <- xs; i > 0]`
+///
+/// The generators are nested in order (the last one is the innermost loop).
 #[derive(Debug, Clone, PartialEq, Eq, Hash)]
 pub struct ArrayComprehension {
     pub l_sqbr: Token,
     pub r_sqbr: Token,
     pub t: Type,
     pub elem: Box<Expr>,
-    pub guard: Box<Expr>,
+    pub generators: Vec<(Identifier, Expr)>,
+    pub guards: Vec<Expr>,
 }
 
 impl NestedDisplay for ArrayComprehension {
     fn fmt_nest(&self, f: &mut fmt::Formatter<'_>, _level: usize) -> fmt::Result {
-        write!(f, "[{} | {}](: {})", self.elem, self.guard, self.t)
+        write!(
+            f,
+            "[{} | {}](: {})",
+            self.elem,
+            fmt_comprehension_clauses(&self.generators, &self.guards),
+            self.t
+        )
     }
 }
 
@@ -630,6 +757,26 @@ impl_display_from_nested!(ArrayComprehension);
 impl_locational!(ArrayComprehension, l_sqbr, r_sqbr);
 impl_t!(ArrayComprehension);
 
+impl ArrayComprehension {
+    pub fn new(
+        l_sqbr: Token,
+        r_sqbr: Token,
+        elem: Expr,
+        generators: Vec<(Identifier, Expr)>,
+        guards: Vec<Expr>,
+    ) -> Self {
+        let t = array_t(elem.t(), TyParam::erased(Type::Nat));
+        Self {
+            l_sqbr,
+            r_sqbr,
+            t,
+            elem: Box::new(elem),
+            generators,
+            guards,
+        }
+    }
+}
+
 #[derive(Debug, Clone, PartialEq, Eq, Hash)]
 pub struct NormalArray {
     pub l_sqbr: Token,
@@ -770,30 +917,58 @@ impl NormalDict {
     }
 }
 
+/// e.g. `{k: v | k <- ks; v <- vs}`
 #[derive(Debug, Clone, PartialEq, Eq, Hash)]
 pub struct DictComprehension {
-    pub l_sqbr: Token,
-    pub r_sqbr: Token,
+    pub l_brace: Token,
+    pub r_brace: Token,
     pub t: Type,
     pub key: Box<Expr>,
     pub value: Box<Expr>,
-    pub guard: Box<Expr>,
+    pub generators: Vec<(Identifier, Expr)>,
+    pub guards: Vec<Expr>,
 }
 
 impl NestedDisplay for DictComprehension {
     fn fmt_nest(&self, f: &mut fmt::Formatter<'_>, _level: usize) -> fmt::Result {
         write!(
             f,
-            "[{}: {} | {}](: {})",
-            self.key, self.value, self.guard, self.t
+            "{{{}: {} | {}}}(: {})",
+            self.key,
+            self.value,
+            fmt_comprehension_clauses(&self.generators, &self.guards),
+            self.t
         )
     }
 }
 
 impl_display_from_nested!(DictComprehension);
-impl_locational!(DictComprehension, l_sqbr, r_sqbr);
+impl_locational!(DictComprehension, l_brace, r_brace);
 impl_t!(DictComprehension);
 
+impl DictComprehension {
+    pub fn new(
+        l_brace: Token,
+        r_brace: Token,
+        kv: KeyValue,
+        generators: Vec<(Identifier, Expr)>,
+        guards: Vec<Expr>,
+    ) -> Self {
+        let t = dict_t(TyParam::Dict(
+            dict! { TyParam::t(kv.key.t()) => TyParam::t(kv.value.t()) },
+        ));
+        Self {
+            l_brace,
+            r_brace,
+            t,
+            key: Box::new(kv.key),
+            value: Box::new(kv.value),
+            generators,
+            guards,
+        }
+    }
+}
+
 #[derive(Debug, Clone, PartialEq, Eq, Hash)]
 pub enum Dict {
     Normal(NormalDict),
@@ -868,16 +1043,64 @@ impl SetWithLength {
     }
 }
 
+/// e.g. `{i % 2 | i <- xs}`
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct SetComprehension {
+    pub l_brace: Token,
+    pub r_brace: Token,
+    pub t: Type,
+    pub elem: Box<Expr>,
+    pub generators: Vec<(Identifier, Expr)>,
+    pub guards: Vec<Expr>,
+}
+
+impl NestedDisplay for SetComprehension {
+    fn fmt_nest(&self, f: &mut fmt::Formatter<'_>, _level: usize) -> fmt::Result {
+        write!(
+            f,
+            "{{{} | {}}}(: {})",
+            self.elem,
+            fmt_comprehension_clauses(&self.generators, &self.guards),
+            self.t
+        )
+    }
+}
+
+impl_display_from_nested!(SetComprehension);
+impl_locational!(SetComprehension, l_brace, r_brace);
+impl_t!(SetComprehension);
+
+impl SetComprehension {
+    pub fn new(
+        l_brace: Token,
+        r_brace: Token,
+        elem: Expr,
+        generators: Vec<(Identifier, Expr)>,
+        guards: Vec<Expr>,
+    ) -> Self {
+        let t = set_t(elem.t(), TyParam::erased(Type::Nat));
+        Self {
+            l_brace,
+            r_brace,
+            t,
+            elem: Box::new(elem),
+            generators,
+            guards,
+        }
+    }
+}
+
 #[derive(Debug, Clone, PartialEq, Eq, Hash)]
 pub enum Set {
     Normal(NormalSet),
     WithLength(SetWithLength),
+    Comprehension(SetComprehension),
 }
 
-impl_nested_display_for_enum!(Set; Normal, WithLength);
-impl_display_for_enum!(Set; Normal, WithLength);
-impl_locational_for_enum!(Set; Normal, WithLength);
-impl_t_for_enum!(Set; Normal, WithLength);
+impl_nested_display_for_enum!(Set; Normal, WithLength, Comprehension);
+impl_display_for_enum!(Set; Normal, WithLength, Comprehension);
+impl_locational_for_enum!(Set; Normal, WithLength, Comprehension);
+impl_t_for_enum!(Set; Normal, WithLength, Comprehension);
 
 #[derive(Debug, Clone, PartialEq, Eq, Hash)]
 pub struct RecordAttrs(Vec<Def>);
@@ -1069,7 +1292,17 @@ impl NestedDisplay for UnaryOp {
 }
 
 impl_display_from_nested!(UnaryOp);
-impl_locational!(UnaryOp, op, expr);
+
+impl Locational for UnaryOp {
+    fn loc(&self) -> Location {
+        // a postfix operator (`x?`) follows the operand
+        if self.op.category_is(TokenCategory::PostfixOp) {
+            Location::concat(self.expr.as_ref(), &self.op)
+        } else {
+            Location::concat(&self.op, self.expr.as_ref())
+        }
+    }
+}
 
 impl UnaryOp {
     pub fn new(op: Token, expr: Expr, sig_t: Type) -> Self {
@@ -1134,7 +1367,19 @@ impl HasType for Call {
 
 impl Locational for Call {
     fn loc(&self) -> Location {
-        Location::concat(self.obj.as_ref(), &self.args)
+        // the arguments of a desugared pipeline call (`x |> f`) may precede the callee
+        let args_end = (self.args.ln_end(), self.args.col_end());
+        if let Some(attr_name) = self.attr_name.as_ref() {
+            if args_end > (attr_name.ln_end(), attr_name.col_end()) {
+                Location::concat(self.obj.as_ref(), &self.args)
+            } else {
+                Location::concat(self.obj.as_ref(), attr_name)
+            }
+        } else if args_end > (self.obj.ln_end(), self.obj.col_end()) {
+            Location::concat(self.obj.as_ref(), &self.args)
+        } else {
+            self.obj.loc()
+        }
     }
 }
 
@@ -1235,8 +1480,11 @@ impl VarSignature {
 
 #[derive(Debug, Clone, PartialEq, Eq, Hash)]
 pub struct SubrSignature {
+    /// runtime decorators (compile-time ones are not included)
+    pub decorators: Vec<Expr>,
     pub ident: Identifier,
     pub params: Params,
+    /// the type before decoration
     pub t: Type,
 }
 
@@ -1251,8 +1499,13 @@ impl_locational!(SubrSignature, ident, params);
 impl_t!(SubrSignature);
 
 impl SubrSignature {
-    pub const fn new(ident: Identifier, params: Params, t: Type) -> Self {
-        Self { ident, params, t }
+    pub const fn new(decorators: Vec<Expr>, ident: Identifier, params: Params, t: Type) -> Self {
+        Self {
+            decorators,
+            ident,
+            params,
+            t,
+        }
     }
 
     pub fn is_procedural(&self) -> bool {
@@ -1533,6 +1786,8 @@ impl Methods {
 
 #[derive(Debug, Clone, PartialEq, Eq, Hash)]
 pub struct ClassDef {
+    /// runtime decorators (compile-time ones are not included)
+    pub decorators: Vec<Expr>,
     pub kind: TypeKind,
     pub sig: Signature,
     pub require_or_sup: Box<Expr>,
@@ -1574,6 +1829,7 @@ impl HasType for ClassDef {
 
 impl ClassDef {
     pub fn new(
+        decorators: Vec<Expr>,
         kind: TypeKind,
         sig: Signature,
         require_or_sup: Expr,
@@ -1582,6 +1838,7 @@ impl ClassDef {
         methods: Block,
     ) -> Self {
         Self {
+            decorators,
             kind,
             sig,
             require_or_sup: Box::new(require_or_sup),
@@ -1680,6 +1937,7 @@ impl TypeAscription {
 #[derive(Debug, Clone, PartialEq, Eq, Hash)]
 pub enum Expr {
     Lit(Literal),
+    StrInterp(StrInterp),
     Accessor(Accessor),
     Array(Array),
     Tuple(Tuple),
@@ -1699,10 +1957,10 @@ pub enum Expr {
     Compound(Block), // compound statement
 }
 
-impl_nested_display_for_chunk_enum!(Expr; Lit, Accessor, Array, Tuple, Dict, Record, BinOp, UnaryOp, Call, Lambda, Decl, Def, ClassDef, AttrDef, Code, Compound, TypeAsc, Set);
+impl_nested_display_for_chunk_enum!(Expr; Lit, StrInterp, Accessor, Array, Tuple, Dict, Record, BinOp, UnaryOp, Call, Lambda, Decl, Def, ClassDef, AttrDef, Code, Compound, TypeAsc, Set);
 impl_display_from_nested!(Expr);
-impl_locational_for_enum!(Expr; Lit, Accessor, Array, Tuple, Dict, Record, BinOp, UnaryOp, Call, Lambda, Decl, Def, ClassDef, AttrDef, Code, Compound, TypeAsc, Set);
-impl_t_for_enum!(Expr; Lit, Accessor, Array, Tuple, Dict, Record, BinOp, UnaryOp, Call, Lambda, Decl, Def, ClassDef, AttrDef, Code, Compound, TypeAsc, Set);
+impl_locational_for_enum!(Expr; Lit, StrInterp, Accessor, Array, Tuple, Dict, Record, BinOp, UnaryOp, Call, Lambda, Decl, Def, ClassDef, AttrDef, Code, Compound, TypeAsc, Set);
+impl_t_for_enum!(Expr; Lit, StrInterp, Accessor, Array, Tuple, Dict, Record, BinOp, UnaryOp, Call, Lambda, Decl, Def, ClassDef, AttrDef, Code, Compound, TypeAsc, Set);
 
 impl Default for Expr {
     fn default() -> Self {
@@ -1744,6 +2002,166 @@ impl Expr {
     pub fn is_type_asc(&self) -> bool {
         matches!(self, Expr::TypeAsc(_))
     }
+
+    /// Calls `f` for each direct sub-expression
+    pub fn for_each_child(&self, f: &mut impl FnMut(&Expr)) {
+        match self {
+            Expr::Lit(_) | Expr::Decl(_) => {}
+            Expr::StrInterp(interp) => interp.exprs.iter().for_each(f),
+            Expr::Accessor(acc) => acc.for_each_child(f),
+            Expr::Array(array) => match array {
+                Array::Normal(arr) => arr.elems.for_each_expr(f),
+                Array::WithLength(arr) => {
+                    f(&arr.elem);
+                    f(&arr.len);
+                }
+                Array::Comprehension(arr) => {
+                    arr.generators.iter().for_each(|(_, gen)| f(gen));
+                    arr.guards.iter().for_each(&mut *f);
+                    f(&arr.elem);
+                }
+            },
+            Expr::Tuple(Tuple::Normal(tup)) => tup.elems.for_each_expr(f),
+            Expr::Set(set) => match set {
+                Set::Normal(st) => st.elems.for_each_expr(f),
+                Set::WithLength(st) => {
+                    f(&st.elem);
+                    f(&st.len);
+                }
+                Set::Comprehension(st) => {
+                    st.generators.iter().for_each(|(_, gen)| f(gen));
+                    st.guards.iter().for_each(&mut *f);
+                    f(&st.elem);
+                }
+            },
+            Expr::Dict(dict) => match dict {
+                Dict::Normal(dic) => {
+                    for kv in dic.kvs.iter() {
+                        f(&kv.key);
+                        f(&kv.value);
+                    }
+                }
+                Dict::Comprehension(dic) => {
+                    dic.generators.iter().for_each(|(_, gen)| f(gen));
+                    dic.guards.iter().for_each(&mut *f);
+                    f(&dic.key);
+                    f(&dic.value);
+                }
+            },
+            Expr::Record(record) => {
+                for attr in record.attrs.iter() {
+                    attr.body.block.iter().for_each(&mut *f);
+                }
+            }
+            Expr::BinOp(bin) => {
+                f(&bin.lhs);
+                f(&bin.rhs);
+            }
+            Expr::UnaryOp(unary) => f(&unary.expr),
+            Expr::Call(call) => {
+                f(&call.obj);
+                call.args.for_each_expr(f);
+            }
+            Expr::Lambda(lambda) => lambda.body.iter().for_each(f),
+            Expr::Def(def) => {
+                if let Signature::Subr(sig) = &def.sig {
+                    sig.decorators.iter().for_each(&mut *f);
+                }
+                def.body.block.iter().for_each(f);
+            }
+            Expr::ClassDef(class_def) => {
+                class_def.decorators.iter().for_each(&mut *f);
+                f(&class_def.require_or_sup);
+                class_def.methods.iter().for_each(f);
+            }
+            Expr::AttrDef(attr_def) => {
+                attr_def.attr.for_each_child(f);
+                attr_def.block.iter().for_each(f);
+            }
+            Expr::TypeAsc(tasc) => f(&tasc.expr),
+            Expr::Code(chunks) | Expr::Compound(chunks) => chunks.iter().for_each(f),
+        }
+    }
+
+    /// Calls `f` for each direct sub-expression
+    pub fn for_each_child_mut(&mut self, f: &mut impl FnMut(&mut Expr)) {
+        match self {
+            Expr::Lit(_) | Expr::Decl(_) => {}
+            Expr::StrInterp(interp) => interp.exprs.iter_mut().for_each(f),
+            Expr::Accessor(acc) => acc.for_each_child_mut(f),
+            Expr::Array(array) => match array {
+                Array::Normal(arr) => arr.elems.for_each_expr_mut(f),
+                Array::WithLength(arr) => {
+                    f(&mut arr.elem);
+                    f(&mut arr.len);
+                }
+                Array::Comprehension(arr) => {
+                    arr.generators.iter_mut().for_each(|(_, gen)| f(gen));
+                    arr.guards.iter_mut().for_each(&mut *f);
+                    f(&mut arr.elem);
+                }
+            },
+            Expr::Tuple(Tuple::Normal(tup)) => tup.elems.for_each_expr_mut(f),
+            Expr::Set(set) => match set {
+                Set::Normal(st) => st.elems.for_each_expr_mut(f),
+                Set::WithLength(st) => {
+                    f(&mut st.elem);
+                    f(&mut st.len);
+                }
+                Set::Comprehension(st) => {
+                    st.generators.iter_mut().for_each(|(_, gen)| f(gen));
+                    st.guards.iter_mut().for_each(&mut *f);
+                    f(&mut st.elem);
+                }
+            },
+            Expr::Dict(dict) => match dict {
+                Dict::Normal(dic) => {
+                    for kv in dic.kvs.iter_mut() {
+                        f(&mut kv.key);
+                        f(&mut kv.value);
+                    }
+                }
+                Dict::Comprehension(dic) => {
+                    dic.generators.iter_mut().for_each(|(_, gen)| f(gen));
+                    dic.guards.iter_mut().for_each(&mut *f);
+                    f(&mut dic.key);
+                    f(&mut dic.value);
+                }
+            },
+            Expr::Record(record) => {
+                for attr in record.attrs.iter_mut() {
+                    attr.body.block.iter_mut().for_each(&mut *f);
+                }
+            }
+            Expr::BinOp(bin) => {
+                f(&mut bin.lhs);
+                f(&mut bin.rhs);
+            }
+            Expr::UnaryOp(unary) => f(&mut unary.expr),
+            Expr::Call(call) => {
+                f(&mut call.obj);
+                call.args.for_each_expr_mut(f);
+            }
+            Expr::Lambda(lambda) => lambda.body.iter_mut().for_each(f),
+            Expr::Def(def) => {
+                if let Signature::Subr(sig) = &mut def.sig {
+                    sig.decorators.iter_mut().for_each(&mut *f);
+                }
+                def.body.block.iter_mut().for_each(f);
+            }
+            Expr::ClassDef(class_def) => {
+                class_def.decorators.iter_mut().for_each(&mut *f);
+                f(&mut class_def.require_or_sup);
+                class_def.methods.iter_mut().for_each(f);
+            }
+            Expr::AttrDef(attr_def) => {
+                attr_def.attr.for_each_child_mut(f);
+                attr_def.block.iter_mut().for_each(f);
+            }
+            Expr::TypeAsc(tasc) => f(&mut tasc.expr),
+            Expr::Code(chunks) | Expr::Compound(chunks) => chunks.iter_mut().for_each(f),
+        }
+    }
 }
 
 /// Toplevel grammar unit
@@ -1,14 +1,17 @@
 //! defines the compiler for Erg (ergc).
 #![allow(clippy::large_enum_variant)]
+#![allow(clippy::result_large_err)]
 extern crate erg_common;
 pub extern crate erg_parser;
 
 pub mod build_hir;
+pub mod build_package;
 mod compile;
 pub use compile::*;
 mod codegen;
 pub mod context;
 pub mod desugar_hir;
+pub mod disk_cache;
 pub mod effectcheck;
 pub mod error;
 pub mod hir;
@@ -1,19 +1,38 @@
+import sys
 from collections.abc import Iterable, Sequence, Iterator, Container
 from typing import TypeVar, Union, _SpecialForm, _type_check
 
-class Error:
+class Error(Exception):
     def __init__(self, message):
+        super().__init__(message)
         self.message = message
 
+    @classmethod
+    def new(cls, message):
+        return cls(message)
+
 T = TypeVar("T")
-@_SpecialForm
-def Result(self, parameters):
-    """Result type.
-
-    Result[T] is equivalent to Union[T, Error].
-    """
-    arg = _type_check(parameters, f"{self} requires a single type.")
-    return Union[arg, Error]
+if sys.version_info >= (3, 9):
+    @_SpecialForm
+    def Result(self, parameters):
+        """Result type.
+
+        Result[T] is equivalent to Union[T, Error].
+        """
+        arg = _type_check(parameters, f"{self} requires a single type.")
+        return Union[arg, Error]
+else:
+    # `_SpecialForm` cannot be used as a decorator before Python 3.9
+    class _Result:
+        """Result type.
+
+        Result[T] is equivalent to Union[T, Error].
+        """
+        def __getitem__(self, parameters):
+            arg = _type_check(parameters, "Result requires a single type.")
+            return Union[arg, Error]
+
+    Result = _Result()
 
 def is_ok(obj: Result[T]) -> bool:
     return not isinstance(obj, Error)
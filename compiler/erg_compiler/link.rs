@@ -2,7 +2,7 @@ use std::mem;
 use std::path::{Path, PathBuf};
 
 use erg_common::config::{ErgConfig, Input};
-use erg_common::python_util::BUILTIN_PYTHON_MODS;
+use erg_common::python_util::{detect_magic_number, BUILTIN_PYTHON_MODS};
 use erg_common::traits::{Locational, Stream};
 use erg_common::Str;
 use erg_common::{enum_unwrap, log};
@@ -10,23 +10,47 @@ use erg_common::{enum_unwrap, log};
 use erg_parser::ast::DefId;
 use erg_parser::token::{Token, TokenKind};
 
+use crate::ty::codeobj::CodeObj;
+use crate::ty::constructors::{func1, mono};
 use crate::ty::free::fresh_varname;
 use crate::ty::typaram::TyParam;
 use crate::ty::value::ValueObj;
 use crate::ty::{HasType, Type};
 
+use crate::codegen::CodeGenerator;
 use crate::context::OperationKind;
+use crate::desugar_hir::HIRDesugarer;
 use crate::hir::*;
 use crate::mod_cache::SharedModuleCache;
+use crate::optimize::HIROptimizer;
 
 pub struct Linker<'a> {
     cfg: &'a ErgConfig,
     mod_cache: &'a SharedModuleCache,
+    /// the root directory of the package (only in the build mode)
+    /// if this is set, Erg modules are not inlined but imported as Python modules
+    pkg_root: Option<&'a Path>,
 }
 
 impl<'a> Linker<'a> {
     pub fn new(cfg: &'a ErgConfig, mod_cache: &'a SharedModuleCache) -> Self {
-        Self { cfg, mod_cache }
+        Self {
+            cfg,
+            mod_cache,
+            pkg_root: None,
+        }
+    }
+
+    pub fn new_package(
+        cfg: &'a ErgConfig,
+        mod_cache: &'a SharedModuleCache,
+        pkg_root: &'a Path,
+    ) -> Self {
+        Self {
+            cfg,
+            mod_cache,
+            pkg_root: Some(pkg_root),
+        }
     }
 
     pub fn link(&self, mut main: HIR) -> HIR {
@@ -41,6 +65,11 @@ impl<'a> Linker<'a> {
     fn replace_import(&self, expr: &mut Expr) {
         match expr {
             Expr::Lit(_) => {}
+            Expr::StrInterp(interp) => {
+                for expr in interp.exprs.iter_mut() {
+                    self.replace_import(expr);
+                }
+            }
             Expr::Accessor(acc) => match acc {
                 Accessor::Attr(attr) => {
                     self.replace_import(&mut attr.obj);
@@ -64,7 +93,9 @@ impl<'a> Linker<'a> {
                     self.replace_import(&mut arr.elem);
                     self.replace_import(&mut arr.len);
                 }
-                _ => todo!(),
+                Array::Comprehension(_) => {
+                    expr.for_each_child_mut(&mut |child| self.replace_import(child))
+                }
             },
             Expr::Tuple(tuple) => match tuple {
                 Tuple::Normal(tup) => {
@@ -83,6 +114,9 @@ impl<'a> Linker<'a> {
                     self.replace_import(&mut st.elem);
                     self.replace_import(&mut st.len);
                 }
+                Set::Comprehension(_) => {
+                    expr.for_each_child_mut(&mut |child| self.replace_import(child))
+                }
             },
             Expr::Dict(dict) => match dict {
                 Dict::Normal(dic) => {
@@ -91,7 +125,9 @@ impl<'a> Linker<'a> {
                         self.replace_import(&mut elem.value);
                     }
                 }
-                other => todo!("{other}"),
+                Dict::Comprehension(_) => {
+                    expr.for_each_child_mut(&mut |child| self.replace_import(child))
+                }
             },
             Expr::Record(record) => {
                 for attr in record.attrs.iter_mut() {
@@ -171,15 +207,31 @@ impl<'a> Linker<'a> {
             enum_unwrap!(expr.ref_t().typarams().remove(0), TyParam::Value:(ValueObj::Str:(_)));
         let path = Path::new(&path[..]);
         let path = self.cfg.input.resolve(path).unwrap();
-        // In the case of REPL, entries cannot be used up
-        let hir = if self.cfg.input.is_repl() {
-            self.mod_cache
-                .get(path.as_path())
-                .and_then(|entry| entry.hir.clone())
+        if let Some(root) = self.pkg_root {
+            let mod_path = path.strip_prefix(root).unwrap_or(&path).with_extension("");
+            let args = &mut enum_unwrap!(expr, Expr::Call).args;
+            let mod_name_lit = enum_unwrap!(args.remove_left_or_key("Path").unwrap(), Expr::Lit);
+            Self::import_as_py_module(expr, &mod_name_lit, &mod_path);
+            return;
+        }
+        let cached = self
+            .mod_cache
+            .get(path.as_path())
+            .is_some_and(|entry| entry.cache.is_some());
+        let code = if cached {
+            self.cached_module_code(&path, line)
         } else {
-            self.mod_cache
-                .remove(path.as_path())
-                .and_then(|entry| entry.hir)
+            // In the case of REPL, entries cannot be used up
+            let hir = if self.cfg.input.is_repl() {
+                self.mod_cache
+                    .get(path.as_path())
+                    .and_then(|entry| entry.hir.clone())
+            } else {
+                self.mod_cache
+                    .remove(path.as_path())
+                    .and_then(|entry| entry.hir)
+            };
+            hir.map(|hir| Expr::Code(Block::new(Vec::from(hir.module))))
         };
         let mod_name = enum_unwrap!(expr, Expr::Call)
             .args
@@ -187,8 +239,7 @@ impl<'a> Linker<'a> {
             .unwrap();
         // let sig = option_enum_unwrap!(&def.sig, Signature::Var)
         //    .unwrap_or_else(|| todo!("module subroutines are not allowed"));
-        if let Some(hir) = hir {
-            let code = Expr::Code(Block::new(Vec::from(hir.module)));
+        if let Some(code) = code {
             let module_type =
                 Expr::Accessor(Accessor::private_with_line(Str::ever("#ModuleType"), line));
             let args = Args::new(vec![PosArg::new(mod_name.clone())], None, vec![], None);
@@ -229,6 +280,63 @@ impl<'a> Linker<'a> {
         }
     }
 
+    /// The code object of a module which can be cached (see `disk_cache`).
+    /// The module is compiled separately and stored in `__erg_cache__` on the first import,
+    /// and the marshaled code object is loaded at runtime: `__import__("marshal").loads(b"...")`.
+    /// Cached entries are not used up, since they may be imported again by other modules.
+    fn cached_module_code(&self, path: &Path, line: usize) -> Option<Expr> {
+        let entry = self.mod_cache.get(path)?;
+        let mut cache = entry.cache.clone()?;
+        let python_ver = self.cfg.python_ver.unwrap_or_else(detect_magic_number);
+        let code = if let Some(code) = cache.load_code(path, python_ver, self.cfg.opt_level) {
+            code
+        } else {
+            let hir = entry.hir.clone()?;
+            let cfg = ErgConfig {
+                python_ver: Some(python_ver),
+                ..self.cfg.inherit(path.to_path_buf())
+            };
+            let code = Self::compile_module(&cfg, self.mod_cache, hir.clone());
+            let entry = self.mod_cache.get_mut(path)?;
+            match cache.store_code(path, python_ver, cfg.opt_level, code) {
+                Ok(code) => {
+                    entry.hir = None;
+                    entry.cache = Some(cache);
+                    code
+                }
+                // cannot be cached (e.g. permission denied)
+                Err(_) => {
+                    entry.cache = None;
+                    return Some(Expr::Code(Block::new(Vec::from(hir.module))));
+                }
+            }
+        };
+        let import = Expr::Accessor(Accessor::public_with_line(Str::ever("__import__"), line));
+        let marshal = Expr::Lit(Literal::new(
+            ValueObj::Str(Str::ever("marshal")),
+            Token::dummy(),
+        ));
+        let args = Args::new(vec![PosArg::new(marshal)], None, vec![], None);
+        // `ref_t` of the receiver of a method call must be the return type of a subroutine
+        let import_t = func1(Type::Str, mono("GenericModule"));
+        let marshal = Expr::Call(Call::new(import, None, args, import_t));
+        let code = Expr::Lit(Literal::new(ValueObj::Bytes(code.into()), Token::dummy()));
+        let args = Args::new(vec![PosArg::new(code)], None, vec![], None);
+        Some(Expr::Call(Call::new(
+            marshal,
+            Some(Identifier::public("loads")),
+            args,
+            Type::Uninited,
+        )))
+    }
+
+    fn compile_module(cfg: &ErgConfig, mod_cache: &SharedModuleCache, hir: HIR) -> CodeObj {
+        let hir = Linker::new(cfg, mod_cache).link(hir);
+        let (hir, _warns) = HIROptimizer::new(cfg.copy()).optimize(hir);
+        let hir = HIRDesugarer::desugar(hir);
+        CodeGenerator::new(cfg.copy()).emit(hir)
+    }
+
     /// ```erg
     /// x = pyimport "x" # called from dir "a"
     /// ```
@@ -243,6 +351,11 @@ impl<'a> Linker<'a> {
         } else {
             PathBuf::new()
         };
+        if let Some(root) = self.pkg_root {
+            if let Ok(rel) = dir.strip_prefix(root) {
+                dir = rel.to_path_buf();
+            }
+        }
         let args = &mut enum_unwrap!(expr, Expr::Call).args;
         let mod_name_lit = enum_unwrap!(args.remove_left_or_key("Path").unwrap(), Expr::Lit);
         let mod_name_str = enum_unwrap!(mod_name_lit.value.clone(), ValueObj::Str);
@@ -256,9 +369,14 @@ impl<'a> Linker<'a> {
             &mod_name_str
         };
         dir.push(mod_name_str);
-        let mut comps = dir.components();
+        Self::import_as_py_module(expr, &mod_name_lit, &dir);
+    }
+
+    /// `import_call` (whose module name argument has been removed) → `__import__("a.b").b`
+    fn import_as_py_module(import_call: &mut Expr, mod_name_lit: &Literal, mod_path: &Path) {
+        let mut comps = mod_path.components();
         let _first = comps.next().unwrap();
-        let path = dir.to_string_lossy().replace('/', ".").replace('\\', ".");
+        let path = mod_path.to_string_lossy().replace(['/', '\\'], ".");
         let token = Token::new(
             TokenKind::StrLit,
             path,
@@ -266,12 +384,13 @@ impl<'a> Linker<'a> {
             mod_name_lit.col_begin().unwrap(),
         );
         let mod_name = Expr::Lit(Literal::try_from(token).unwrap());
+        let args = &mut enum_unwrap!(import_call, Expr::Call).args;
         args.insert_pos(0, PosArg::new(mod_name));
-        let line = expr.ln_begin().unwrap_or(0);
+        let line = import_call.ln_begin().unwrap_or(0);
         for attr in comps {
-            *expr = Expr::Accessor(Accessor::attr(
+            *import_call = Expr::Accessor(Accessor::attr(
                 // instead of mem::take(),
-                mem::replace(expr, Expr::Code(Block::empty())),
+                mem::replace(import_call, Expr::Code(Block::empty())),
                 Identifier::public_with_line(
                     Token::dummy(),
                     Str::rc(attr.as_os_str().to_str().unwrap()),
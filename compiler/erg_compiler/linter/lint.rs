@@ -1,19 +1,377 @@
+use std::mem;
+use std::path::Path;
+
+use erg_common::astr::AtomicStr;
+use erg_common::config::ErgConfig;
+use erg_common::error::{Location, MultiErrorDisplay};
+use erg_common::traits::{Locational, Runnable, Stream};
 use erg_common::Str;
 
-use crate::error::CompileWarnings;
-use crate::hir::HIR;
+use erg_parser::ast::{DefKind, ParamPattern, Params};
+
+use crate::build_hir::HIRBuilder;
+use crate::error::{CompileError, CompileErrors};
+use crate::hir::{
+    Accessor, ClassDef, Def, Expr, Identifier, Lambda, Signature, TypeAscription, HIR,
+};
+use crate::linter::{LintWarning, LintWarnings};
+use crate::mod_cache::SharedModuleCache;
+
+#[derive(Debug)]
+struct Binding {
+    name: Str,
+    loc: Location,
+    used: bool,
+    /// parameters and public variables are not reported even if unused
+    check_unused: bool,
+}
+
+#[derive(Debug)]
+struct Scope {
+    name: Str,
+    bindings: Vec<Binding>,
+}
+
+impl Scope {
+    fn new(name: Str) -> Self {
+        Self {
+            name,
+            bindings: vec![],
+        }
+    }
+
+    fn get_mut(&mut self, name: &str) -> Option<&mut Binding> {
+        self.bindings.iter_mut().rev().find(|b| &b.name[..] == name)
+    }
+}
+
+fn is_pascal_case(name: &str) -> bool {
+    name.chars().next().map(char::is_uppercase).unwrap_or(false) && !name.contains('_')
+}
+
+fn is_snake_case(name: &str) -> bool {
+    !name.chars().any(char::is_uppercase)
+}
+
+fn is_procedural_acc(expr: &Expr) -> bool {
+    match expr {
+        Expr::Accessor(Accessor::Ident(ident)) => ident.is_procedural(),
+        Expr::Accessor(Accessor::Attr(attr)) => attr.ident.is_procedural(),
+        _ => false,
+    }
+}
+
+/// Returns true if `expr` contains a call of a procedure or a procedural method
+fn has_side_effect(expr: &Expr) -> bool {
+    if let Expr::Call(call) = expr {
+        if is_procedural_acc(&call.obj)
+            || call
+                .attr_name
+                .as_ref()
+                .map(|name| name.is_procedural())
+                .unwrap_or(false)
+        {
+            return true;
+        }
+    }
+    let mut found = false;
+    expr.for_each_child(&mut |child| found = found || has_side_effect(child));
+    found
+}
 
-#[derive(Debug, Default)]
+/// Checks type-checked HIR for the following:
+///
+/// * unused local variables
+/// * variables that shadow outer or builtin ones
+/// * procedures without side-effects
+/// * redundant type ascriptions
+/// * naming conventions (`PascalCase` for classes and traits, `snake_case` for procedures)
+#[derive(Debug)]
 pub struct Linter {
-    _used: Vec<Str>,
+    builder: HIRBuilder,
+    mod_cache: SharedModuleCache,
+    scopes: Vec<Scope>,
+    warns: LintWarnings,
+}
+
+impl Runnable for Linter {
+    type Err = CompileError;
+    type Errs = CompileErrors;
+    const NAME: &'static str = "Erg linter";
+
+    fn new(cfg: ErgConfig) -> Self {
+        let mod_cache = SharedModuleCache::new();
+        Self {
+            builder: HIRBuilder::new_with_cache(
+                cfg,
+                Str::ever("<module>"),
+                mod_cache.clone(),
+                SharedModuleCache::new(),
+            ),
+            mod_cache,
+            scopes: vec![],
+            warns: LintWarnings::empty(),
+        }
+    }
+
+    #[inline]
+    fn cfg(&self) -> &ErgConfig {
+        self.builder.cfg()
+    }
+
+    #[inline]
+    fn finish(&mut self) {}
+
+    fn clear(&mut self) {
+        self.scopes.clear();
+        self.warns.clear();
+    }
+
+    fn exec(&mut self) -> Result<i32, Self::Errs> {
+        let (hir, mut warns) = self
+            .builder
+            .build_with_warns(self.input().read(), "exec")
+            .map_err(|(_, errs)| errs)?;
+        warns.extend(self.lint(&hir).into_iter());
+        warns.write_all_stderr(self.cfg().output_format);
+        Ok(0)
+    }
+
+    fn eval(&mut self, src: String) -> Result<String, Self::Errs> {
+        let (hir, mut warns) = self
+            .builder
+            .build_with_warns(src, "eval")
+            .map_err(|(_, errs)| errs)?;
+        warns.extend(self.lint(&hir).into_iter());
+        warns.write_all_stderr(self.cfg().output_format);
+        Ok(String::new())
+    }
 }
 
 impl Linter {
-    pub fn new() -> Self {
-        Self { _used: Vec::new() }
+    pub fn lint(&mut self, hir: &HIR) -> LintWarnings {
+        self.scopes.push(Scope::new(Str::ever("<module>")));
+        self.lint_chunks(hir.module.iter());
+        // In the REPL, module-level variables may be used by later inputs
+        let report_unused = !self.input().is_repl();
+        self.pop_scope(report_unused);
+        mem::replace(&mut self.warns, LintWarnings::empty())
+    }
+
+    fn caused_by(&self) -> AtomicStr {
+        self.scopes
+            .last()
+            .map(|scope| AtomicStr::from(&scope.name))
+            .unwrap_or_else(|| AtomicStr::ever("<module>"))
+    }
+
+    fn is_builtin(&self, name: &str) -> bool {
+        self.mod_cache
+            .ref_ctx(Path::new("<builtins>"))
+            .map(|ctx| {
+                ctx.get_current_scope_var(name).is_some() || ctx.rec_get_const_obj(name).is_some()
+            })
+            .unwrap_or(false)
+    }
+
+    fn declare(&mut self, name: &Str, loc: Location, check_unused: bool) {
+        if &name[..] == "_" {
+            return;
+        }
+        let outer_scopes = &self.scopes[..self.scopes.len().saturating_sub(1)];
+        if outer_scopes
+            .iter()
+            .any(|scope| scope.bindings.iter().any(|b| &b.name == name))
+            || self.is_builtin(name)
+        {
+            self.warns.push(LintWarning::shadowing_warning(
+                self.input().clone(),
+                line!() as usize,
+                loc,
+                name,
+                self.caused_by(),
+            ));
+        }
+        if let Some(scope) = self.scopes.last_mut() {
+            scope.bindings.push(Binding {
+                name: name.clone(),
+                loc,
+                used: false,
+                check_unused: check_unused && !name.starts_with('_'),
+            });
+        }
+    }
+
+    fn declare_ident(&mut self, ident: &Identifier) {
+        self.declare(ident.inspect(), ident.loc(), ident.vis().is_private());
+    }
+
+    fn declare_params(&mut self, params: &Params) {
+        for param in params
+            .non_defaults
+            .iter()
+            .chain(params.var_args.iter().map(|p| p.as_ref()))
+            .chain(params.defaults.iter())
+        {
+            match &param.pat {
+                ParamPattern::VarName(name)
+                | ParamPattern::Ref(name)
+                | ParamPattern::RefMut(name) => {
+                    self.declare(name.inspect(), name.loc(), false);
+                }
+                _ => {}
+            }
+        }
+    }
+
+    fn mark_used(&mut self, name: &str) {
+        for scope in self.scopes.iter_mut().rev() {
+            if let Some(binding) = scope.get_mut(name) {
+                binding.used = true;
+                return;
+            }
+        }
+    }
+
+    fn pop_scope(&mut self, report_unused: bool) {
+        let scope = self.scopes.pop().unwrap();
+        if !report_unused {
+            return;
+        }
+        for binding in scope.bindings.iter() {
+            if binding.check_unused && !binding.used {
+                self.warns.push(LintWarning::unused_warning(
+                    self.input().clone(),
+                    line!() as usize,
+                    binding.loc,
+                    &binding.name,
+                    AtomicStr::from(&scope.name),
+                ));
+            }
+        }
+    }
+
+    /// Variables in a block can be referred to before their definitions (e.g. mutual recursion),
+    /// so all of them are declared first
+    fn lint_chunks<'e>(&mut self, chunks: impl Iterator<Item = &'e Expr> + Clone) {
+        for chunk in chunks.clone() {
+            match chunk {
+                Expr::Def(def) => self.declare_ident(def.sig.ident()),
+                Expr::ClassDef(class_def) => self.declare_ident(class_def.sig.ident()),
+                _ => {}
+            }
+        }
+        for chunk in chunks {
+            self.lint_expr(chunk);
+        }
+    }
+
+    fn lint_expr(&mut self, expr: &Expr) {
+        match expr {
+            Expr::Accessor(Accessor::Ident(ident)) => self.mark_used(ident.inspect()),
+            Expr::Def(def) => self.lint_def(def),
+            Expr::ClassDef(class_def) => self.lint_class_def(class_def),
+            Expr::Lambda(lambda) => self.lint_lambda(lambda),
+            Expr::TypeAsc(tasc) => {
+                self.check_type_asc(tasc);
+                self.lint_expr(&tasc.expr);
+            }
+            _ => expr.for_each_child(&mut |child| self.lint_expr(child)),
+        }
+    }
+
+    fn lint_def(&mut self, def: &Def) {
+        let ident = def.sig.ident();
+        self.check_naming(ident, def.def_kind());
+        let proc_body = match (&def.sig, def.body.block.first()) {
+            (Signature::Subr(_), _) => Some(def.body.block.iter()),
+            (Signature::Var(_), Some(Expr::Lambda(lambda))) if def.body.block.len() == 1 => {
+                Some(lambda.body.iter())
+            }
+            _ => None,
+        };
+        if let Some(mut body) = proc_body {
+            if def.sig.is_procedural() && !body.any(has_side_effect) {
+                self.warns.push(LintWarning::no_effect_proc_warning(
+                    self.input().clone(),
+                    line!() as usize,
+                    ident.loc(),
+                    ident.inspect(),
+                    self.caused_by(),
+                ));
+            }
+        }
+        let scope_name = match &def.sig {
+            Signature::Subr(_) => ident.inspect().clone(),
+            // the body of a variable is evaluated in the enclosing scope
+            Signature::Var(_) => self
+                .scopes
+                .last()
+                .map(|scope| scope.name.clone())
+                .unwrap_or_else(|| Str::ever("<module>")),
+        };
+        self.scopes.push(Scope::new(scope_name));
+        if let Signature::Subr(subr) = &def.sig {
+            self.declare_params(&subr.params);
+        }
+        self.lint_chunks(def.body.block.iter());
+        self.pop_scope(true);
+    }
+
+    fn lint_class_def(&mut self, class_def: &ClassDef) {
+        self.check_naming(class_def.sig.ident(), DefKind::Class);
+        self.lint_expr(&class_def.require_or_sup);
+        // methods are not variables, so they are not declared
+        for method in class_def.methods.iter() {
+            self.lint_expr(method);
+        }
+    }
+
+    fn lint_lambda(&mut self, lambda: &Lambda) {
+        self.scopes.push(Scope::new(Str::ever("<lambda>")));
+        self.declare_params(&lambda.params);
+        self.lint_chunks(lambda.body.iter());
+        self.pop_scope(true);
+    }
+
+    fn check_naming(&mut self, ident: &Identifier, kind: DefKind) {
+        let name = ident.inspect().trim_end_matches('!');
+        let convention = if kind.is_class_or_trait() {
+            if is_pascal_case(name) {
+                return;
+            }
+            "PascalCase"
+        } else if ident.is_procedural() {
+            if is_snake_case(name) {
+                return;
+            }
+            "snake_case"
+        } else {
+            return;
+        };
+        self.warns.push(LintWarning::naming_convention_warning(
+            self.input().clone(),
+            line!() as usize,
+            ident.loc(),
+            ident.inspect(),
+            convention,
+            self.caused_by(),
+        ));
     }
 
-    pub fn lint(&mut self, _hir: &HIR) -> CompileWarnings {
-        CompileWarnings::empty()
+    /// e.g. `1: Nat`, `"a": Str`
+    fn check_type_asc(&mut self, tasc: &TypeAscription) {
+        if let Expr::Lit(lit) = tasc.expr.as_ref() {
+            let spec = tasc.spec.to_string();
+            if lit.value.class().to_string() == spec {
+                self.warns.push(LintWarning::redundant_type_asc_warning(
+                    self.input().clone(),
+                    line!() as usize,
+                    tasc.loc(),
+                    &spec,
+                    self.caused_by(),
+                ));
+            }
+        }
     }
 }
@@ -1,3 +1,5 @@
 mod lint;
+mod warning;
 
 pub use lint::Linter;
+pub use warning::*;
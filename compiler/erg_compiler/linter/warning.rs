@@ -0,0 +1,121 @@
+use erg_common::astr::AtomicStr;
+use erg_common::color::{GREEN, RESET, YELLOW};
+use erg_common::config::Input;
+use erg_common::error::{ErrorCore, ErrorKind::*, Location};
+use erg_common::switch_lang;
+
+use crate::error::{CompileWarning, CompileWarnings};
+
+pub type LintWarning = CompileWarning;
+pub type LintWarnings = CompileWarnings;
+
+impl LintWarning {
+    pub fn shadowing_warning(
+        input: Input,
+        errno: usize,
+        loc: Location,
+        name: &str,
+        caused_by: AtomicStr,
+    ) -> Self {
+        Self::new(
+            ErrorCore::new(
+                errno,
+                NameWarning,
+                loc,
+                switch_lang!(
+                    "japanese" => format!("{YELLOW}{name}{RESET}は外側のスコープの変数をシャドーイングしています"),
+                    "simplified_chinese" => format!("{YELLOW}{name}{RESET}遮蔽了外部作用域的变量"),
+                    "traditional_chinese" => format!("{YELLOW}{name}{RESET}遮蔽了外部作用域的變量"),
+                    "english" => format!("{YELLOW}{name}{RESET} shadows a variable in an outer scope"),
+                ),
+                None,
+            ),
+            input,
+            caused_by,
+        )
+    }
+
+    pub fn no_effect_proc_warning(
+        input: Input,
+        errno: usize,
+        loc: Location,
+        name: &str,
+        caused_by: AtomicStr,
+    ) -> Self {
+        Self::new(
+            ErrorCore::new(
+                errno,
+                Warning,
+                loc,
+                switch_lang!(
+                    "japanese" => format!("プロシージャ{YELLOW}{name}{RESET}には副作用がありません"),
+                    "simplified_chinese" => format!("过程{YELLOW}{name}{RESET}没有副作用"),
+                    "traditional_chinese" => format!("過程{YELLOW}{name}{RESET}沒有副作用"),
+                    "english" => format!("the procedure {YELLOW}{name}{RESET} has no side-effects"),
+                ),
+                Some(
+                    switch_lang!(
+                        "japanese" => format!("副作用がないなら、{GREEN}!{RESET}を外して関数として定義してください"),
+                        "simplified_chinese" => format!("如果没有副作用，请去掉{GREEN}!{RESET}并定义为函数"),
+                        "traditional_chinese" => format!("如果沒有副作用，請去掉{GREEN}!{RESET}並定義為函數"),
+                        "english" => format!("if it has no side-effects, remove the {GREEN}!{RESET} and define it as a function"),
+                    )
+                    .into(),
+                ),
+            ),
+            input,
+            caused_by,
+        )
+    }
+
+    pub fn redundant_type_asc_warning(
+        input: Input,
+        errno: usize,
+        loc: Location,
+        spec: &str,
+        caused_by: AtomicStr,
+    ) -> Self {
+        Self::new(
+            ErrorCore::new(
+                errno,
+                TypeWarning,
+                loc,
+                switch_lang!(
+                    "japanese" => format!("型指定{YELLOW}{spec}{RESET}は冗長です"),
+                    "simplified_chinese" => format!("类型指定{YELLOW}{spec}{RESET}是多余的"),
+                    "traditional_chinese" => format!("類型指定{YELLOW}{spec}{RESET}是多餘的"),
+                    "english" => format!("the type ascription {YELLOW}{spec}{RESET} is redundant"),
+                ),
+                None,
+            ),
+            input,
+            caused_by,
+        )
+    }
+
+    pub fn naming_convention_warning(
+        input: Input,
+        errno: usize,
+        loc: Location,
+        name: &str,
+        convention: &str,
+        caused_by: AtomicStr,
+    ) -> Self {
+        Self::new(
+            ErrorCore::new(
+                errno,
+                NameWarning,
+                loc,
+                switch_lang!(
+                    "japanese" => format!("{YELLOW}{name}{RESET}は{GREEN}{convention}{RESET}で命名すべきです"),
+                    "simplified_chinese" => format!("{YELLOW}{name}{RESET}应该使用{GREEN}{convention}{RESET}命名"),
+                    "traditional_chinese" => format!("{YELLOW}{name}{RESET}應該使用{GREEN}{convention}{RESET}命名"),
+                    "english" => format!("{YELLOW}{name}{RESET} should be named in {GREEN}{convention}{RESET}"),
+                ),
+                None,
+            ),
+            input,
+            caused_by,
+        )
+    }
+}
@@ -1,6 +1,7 @@
 //! implements `ASTLowerer`.
 //!
 //! ASTLowerer(ASTからHIRへの変換器)を実装
+use std::mem;
 
 use erg_common::astr::AtomicStr;
 use erg_common::config::ErgConfig;
@@ -19,19 +20,20 @@ use erg_parser::token::{Token, TokenKind};
 use erg_parser::Parser;
 
 use crate::ty::constructors::{
-    array_mut, array_t, free_var, func, mono, poly, proc, quant, set_mut, set_t, ty_tp,
+    array_mut, array_t, free_var, func1, mono, named_free_var, or, poly, quant, set_mut, set_t,
+    subr_t, ty_tp,
 };
-use crate::ty::free::Constraint;
+use crate::ty::free::{fresh_varname, Constraint};
 use crate::ty::typaram::TyParam;
 use crate::ty::value::{GenTypeObj, TypeKind, TypeObj, ValueObj};
-use crate::ty::{HasType, ParamTy, Type};
+use crate::ty::{HasType, ParamTy, SubrKind, SubrType, Type};
 
 use crate::context::instantiate::TyVarInstContext;
 use crate::context::{
-    ClassDefType, Context, ContextKind, OperationKind, RegistrationMode, TraitInstance,
+    ClassDefType, Context, ContextKind, OperationKind, RegistrationMode, TraitInstance, Variance,
 };
 use crate::error::{
-    CompileError, CompileErrors, LowerError, LowerErrors, LowerResult, LowerWarnings,
+    CompileError, CompileErrors, LowerError, LowerErrors, LowerResult, LowerWarning, LowerWarnings,
     SingleLowerResult,
 };
 use crate::hir;
@@ -42,6 +44,9 @@ use crate::varinfo::{Mutability, VarInfo, VarKind};
 use crate::AccessKind;
 use Visibility::*;
 
+/// (bind clauses, guard clauses) of a comprehension
+type ComprehensionClauses = (Vec<(hir::Identifier, hir::Expr)>, Vec<hir::Expr>);
+
 /// Singleton that checks types of an AST, and convert (lower) it into a HIR
 #[derive(Debug)]
 pub struct ASTLowerer {
@@ -49,6 +54,10 @@ pub struct ASTLowerer {
     pub(crate) ctx: Context,
     errs: LowerErrors,
     warns: LowerWarnings,
+    /// the error types propagated by `?` from each subroutine being lowered
+    propagated_error_ts: Vec<Type>,
+    /// whether `break`/`continue` can be used here (in the body of `for!`/`while!`)
+    in_loop: bool,
 }
 
 impl Default for ASTLowerer {
@@ -94,7 +103,7 @@ impl Runnable for ASTLowerer {
         let ast = ast_builder.build(self.input().read())?;
         let (hir, warns) = self.lower(ast, "exec").map_err(|(_, errs)| errs)?;
         if self.cfg.verbose >= 2 {
-            warns.fmt_all_stderr();
+            warns.write_all_stderr(self.cfg.output_format);
         }
         println!("{hir}");
         Ok(0)
@@ -120,9 +129,25 @@ impl ASTLowerer {
             cfg,
             errs: LowerErrors::empty(),
             warns: LowerWarnings::empty(),
+            propagated_error_ts: vec![],
+            in_loop: false,
         }
     }
 
+    /// Discards the definitions of the module to check it again (the module caches are kept)
+    pub fn reset_mod_ctx(&mut self) {
+        let mod_cache = self.ctx.mod_cache.clone().unwrap();
+        let py_mod_cache = self.ctx.py_mod_cache.clone().unwrap();
+        self.ctx = Context::new_module(
+            self.ctx.name.clone(),
+            self.cfg.clone(),
+            mod_cache,
+            py_mod_cache,
+        );
+        self.errs.clear();
+        self.warns.clear();
+    }
+
     fn return_t_check(
         &self,
         loc: Location,
@@ -198,6 +223,23 @@ impl ASTLowerer {
         Ok(lit)
     }
 
+    /// Every embedded expression is checked, but can be of any type (it will be formatted by `format`)
+    fn lower_str_interp(&mut self, interp: ast::StrInterp) -> LowerResult<hir::StrInterp> {
+        log!(info "entered {}({interp})", fn_name!());
+        let fragments = interp
+            .fragments()
+            .zip(interp.segments.iter())
+            .map(|(fragment, segment)| {
+                hir::Literal::new(ValueObj::Str(Str::rc(fragment)), segment.clone())
+            })
+            .collect();
+        let mut exprs = Vec::with_capacity(interp.exprs.len());
+        for expr in interp.exprs.into_iter() {
+            exprs.push(self.lower_expr(expr)?);
+        }
+        Ok(hir::StrInterp::new(fragments, exprs))
+    }
+
     fn lower_array(&mut self, array: ast::Array) -> LowerResult<hir::Array> {
         log!(info "entered {}({array})", fn_name!());
         match array {
@@ -205,12 +247,35 @@ impl ASTLowerer {
             ast::Array::WithLength(arr) => {
                 Ok(hir::Array::WithLength(self.lower_array_with_length(arr)?))
             }
-            other => todo!("{other}"),
+            ast::Array::Comprehension(arr) => Ok(hir::Array::Comprehension(
+                self.lower_array_comprehension(arr)?,
+            )),
+        }
+    }
+
+    /// Default values (e.g. `[x, y := 1]`) are allowed only in parameter patterns
+    fn check_default_elems(&self, elems: &ast::Args) -> LowerResult<()> {
+        if let Some(kw) = elems.kw_args().first() {
+            return Err(LowerErrors::from(LowerError::syntax_error(
+                self.cfg.input.clone(),
+                line!() as usize,
+                kw.loc(),
+                AtomicStr::arc(&self.ctx.name[..]),
+                switch_lang!(
+                    "japanese" => "デフォルト値はパターンの中でのみ使えます",
+                    "simplified_chinese" => "默认值只能在模式中使用",
+                    "traditional_chinese" => "默認值只能在模式中使用",
+                    "english" => "default values can be used only in patterns",
+                ),
+                None,
+            )));
         }
+        Ok(())
     }
 
     fn lower_normal_array(&mut self, array: ast::NormalArray) -> LowerResult<hir::NormalArray> {
         log!(info "entered {}({array})", fn_name!());
+        self.check_default_elems(&array.elems)?;
         let mut new_array = vec![];
         let (elems, _) = array.elems.into_iters();
         let mut union = Type::Never;
@@ -267,6 +332,83 @@ impl ASTLowerer {
         Ok(hir_array)
     }
 
+    fn lower_array_comprehension(
+        &mut self,
+        array: ast::ArrayComprehension,
+    ) -> LowerResult<hir::ArrayComprehension> {
+        log!(info "entered {}({array})", fn_name!());
+        self.ctx
+            .grow("<listcomp>", ContextKind::Instant, Private, None);
+        let res = self
+            .lower_comprehension_clauses(array.generators, array.guards)
+            .and_then(|(generators, guards)| {
+                let elem = self.lower_expr(*array.elem)?;
+                Ok(hir::ArrayComprehension::new(
+                    array.l_sqbr,
+                    array.r_sqbr,
+                    elem,
+                    generators,
+                    guards,
+                ))
+            });
+        self.pop_append_errs();
+        res
+    }
+
+    /// Lowers the clauses of a comprehension in the current (comprehension) scope.
+    /// Each bound variable has the element type of the iterable, and each guard must be a `Bool`.
+    fn lower_comprehension_clauses(
+        &mut self,
+        generators: Vec<(ast::Identifier, ast::Expr)>,
+        guards: Vec<ast::Expr>,
+    ) -> LowerResult<ComprehensionClauses> {
+        let mut hir_generators = Vec::with_capacity(generators.len());
+        for (ident, iterable) in generators.into_iter() {
+            let iterable = self.lower_expr(iterable)?;
+            let elem_t = if let Some(elem_t) = self.ctx.get_iter_elem_t(iterable.ref_t()) {
+                elem_t
+            } else {
+                let elem_t = named_free_var(
+                    fresh_varname().into(),
+                    self.ctx.level,
+                    Constraint::new_type_of(Type::Type),
+                );
+                let iterable_t = poly("Iterable", vec![ty_tp(elem_t.clone())]);
+                self.ctx
+                    .sub_unify(iterable.ref_t(), &iterable_t, iterable.loc(), None)?;
+                elem_t
+            };
+            if self.ctx.locals.contains_key(ident.inspect()) {
+                return Err(LowerErrors::from(LowerError::reassign_error(
+                    self.cfg.input.clone(),
+                    line!() as usize,
+                    ident.loc(),
+                    self.ctx.caused_by(),
+                    ident.inspect(),
+                )));
+            }
+            let vi = VarInfo::new(
+                elem_t.clone(),
+                Mutability::from(&ident.inspect()[..]),
+                Private,
+                VarKind::Defined(ast::DefId(get_hash(&ident))),
+                None,
+                None,
+            );
+            self.ctx.locals.insert(ident.name.clone(), vi);
+            let ident = hir::Identifier::new(ident.dot, ident.name, None, elem_t);
+            hir_generators.push((ident, iterable));
+        }
+        let mut hir_guards = Vec::with_capacity(guards.len());
+        for guard in guards.into_iter() {
+            let guard = self.lower_expr(guard)?;
+            self.ctx
+                .sub_unify(guard.ref_t(), &Type::Bool, guard.loc(), None)?;
+            hir_guards.push(guard);
+        }
+        Ok((hir_generators, hir_guards))
+    }
+
     fn gen_array_with_length_type(&self, elem: &hir::Expr, len: &ast::Expr) -> Type {
         let maybe_len = self.ctx.eval_const_expr(len);
         match maybe_len {
@@ -314,6 +456,7 @@ impl ASTLowerer {
 
     fn lower_normal_tuple(&mut self, tuple: ast::NormalTuple) -> LowerResult<hir::NormalTuple> {
         log!(info "entered {}({tuple})", fn_name!());
+        self.check_default_elems(&tuple.elems)?;
         let mut new_tuple = vec![];
         let (elems, _) = tuple.elems.into_iters();
         for elem in elems {
@@ -337,9 +480,8 @@ impl ASTLowerer {
             hir::Record::new(record.l_brace, record.r_brace, hir::RecordAttrs::empty());
         self.ctx.grow("<record>", ContextKind::Dummy, Private, None);
         for attr in record.attrs.into_iter() {
-            let attr = self.lower_def(attr).map_err(|e| {
+            let attr = self.lower_def(attr).inspect_err(|_e| {
                 self.pop_append_errs();
-                e
             })?;
             hir_record.push(attr);
         }
@@ -352,6 +494,9 @@ impl ASTLowerer {
         match set {
             ast::Set::Normal(set) => Ok(hir::Set::Normal(self.lower_normal_set(set)?)),
             ast::Set::WithLength(set) => Ok(hir::Set::WithLength(self.lower_set_with_length(set)?)),
+            ast::Set::Comprehension(set) => {
+                Ok(hir::Set::Comprehension(self.lower_set_comprehension(set)?))
+            }
         }
     }
 
@@ -434,6 +579,47 @@ impl ASTLowerer {
         Ok(hir_set)
     }
 
+    fn lower_set_comprehension(
+        &mut self,
+        set: ast::SetComprehension,
+    ) -> LowerResult<hir::SetComprehension> {
+        log!(info "entered {}({set})", fn_name!());
+        // `{I: Int | I >= 0}`
+        if set.generators.is_empty() {
+            return Err(LowerErrors::from(LowerError::feature_error(
+                self.cfg.input.clone(),
+                set.loc(),
+                "refinement types as values",
+                self.ctx.caused_by(),
+            )));
+        }
+        self.ctx
+            .grow("<setcomp>", ContextKind::Instant, Private, None);
+        let res = self
+            .lower_comprehension_clauses(set.generators, set.guards)
+            .and_then(|(generators, guards)| {
+                let elem = self.lower_expr(*set.elem)?;
+                Ok(hir::SetComprehension::new(
+                    set.l_brace,
+                    set.r_brace,
+                    elem,
+                    generators,
+                    guards,
+                ))
+            });
+        self.pop_append_errs();
+        let set = res?;
+        let sup = poly("Eq", vec![TyParam::t(set.elem.t())]);
+        // check if the element type is Eq
+        if let Err(errs) = self
+            .ctx
+            .sub_unify(set.elem.ref_t(), &sup, set.elem.loc(), None)
+        {
+            self.errs.extend(errs.into_iter());
+        }
+        Ok(set)
+    }
+
     fn gen_set_with_length_type(&mut self, elem: &hir::Expr, len: &ast::Expr) -> Type {
         let maybe_len = self.ctx.eval_const_expr(len);
         match maybe_len {
@@ -477,11 +663,46 @@ impl ASTLowerer {
         log!(info "enter {}({dict})", fn_name!());
         match dict {
             ast::Dict::Normal(set) => Ok(hir::Dict::Normal(self.lower_normal_dict(set)?)),
-            other => todo!("{other}"),
+            ast::Dict::Comprehension(dict) => Ok(hir::Dict::Comprehension(
+                self.lower_dict_comprehension(dict)?,
+            )),
             // ast::Dict::WithLength(set) => Ok(hir::Dict::WithLength(self.lower_dict_with_length(set)?)),
         }
     }
 
+    fn lower_dict_comprehension(
+        &mut self,
+        dict: ast::DictComprehension,
+    ) -> LowerResult<hir::DictComprehension> {
+        log!(info "enter {}({dict})", fn_name!());
+        self.ctx
+            .grow("<dictcomp>", ContextKind::Instant, Private, None);
+        let res = self
+            .lower_comprehension_clauses(dict.generators, dict.guards)
+            .and_then(|(generators, guards)| {
+                let key = self.lower_expr(*dict.key)?;
+                let value = self.lower_expr(*dict.value)?;
+                Ok(hir::DictComprehension::new(
+                    dict.l_brace,
+                    dict.r_brace,
+                    hir::KeyValue::new(key, value),
+                    generators,
+                    guards,
+                ))
+            });
+        self.pop_append_errs();
+        let dict = res?;
+        let sup = poly("Eq", vec![TyParam::t(dict.key.t())]);
+        // check if the key type is Eq
+        if let Err(errs) = self
+            .ctx
+            .sub_unify(dict.key.ref_t(), &sup, dict.key.loc(), None)
+        {
+            self.errs.extend(errs.into_iter());
+        }
+        Ok(dict)
+    }
+
     fn lower_normal_dict(&mut self, dict: ast::NormalDict) -> LowerResult<hir::NormalDict> {
         log!(info "enter {}({dict})", fn_name!());
         let mut union = dict! {};
@@ -565,6 +786,21 @@ impl ASTLowerer {
         log!(info "entered {}({acc})", fn_name!());
         match acc {
             ast::Accessor::Ident(ident) => {
+                if ident.is_loop_control() && !self.in_loop {
+                    return Err(LowerErrors::from(LowerError::syntax_error(
+                        self.cfg.input.clone(),
+                        line!() as usize,
+                        ident.loc(),
+                        AtomicStr::arc(&self.ctx.name[..]),
+                        switch_lang!(
+                            "japanese" => format!("{}はfor!/while!の中でのみ使えます", ident.inspect()),
+                            "simplified_chinese" => format!("{}只能在for!/while!中使用", ident.inspect()),
+                            "traditional_chinese" => format!("{}只能在for!/while!中使用", ident.inspect()),
+                            "english" => format!("{} can be used only in for!/while!", ident.inspect()),
+                        ),
+                        None,
+                    )));
+                }
                 let ident = self.lower_ident(ident)?;
                 let acc = hir::Accessor::Ident(ident);
                 Ok(acc)
@@ -594,7 +830,7 @@ impl ASTLowerer {
                 let index = self.lower_expr(*subscr.index)?;
                 // FIXME: 配列とは限らない！
                 let t = enum_unwrap!(
-                    obj.ref_t().typarams().get(0).unwrap().clone(),
+                    obj.ref_t().typarams().first().unwrap().clone(),
                     TyParam::Type
                 );
                 let acc = hir::Accessor::Subscr(hir::Subscript::new(obj, index, *t));
@@ -646,6 +882,18 @@ impl ASTLowerer {
 
     fn lower_unary(&mut self, unary: ast::UnaryOp) -> LowerResult<hir::UnaryOp> {
         log!(info "entered {}({unary})", fn_name!());
+        if unary.op.is(TokenKind::Try) {
+            return self.lower_try(unary);
+        }
+        // `*`/`**` are only allowed in parameters (`f(*args) = ...`)
+        if unary.op.is(TokenKind::PreStar) || unary.op.is(TokenKind::PreDblStar) {
+            return Err(LowerErrors::from(LowerError::feature_error(
+                self.cfg.input.clone(),
+                unary.loc(),
+                "unpacking arguments",
+                self.ctx.caused_by(),
+            )));
+        }
         let mut args = unary.args.into_iter();
         let arg = hir::PosArg::new(self.lower_expr(*args.next().unwrap())?);
         let args = [arg];
@@ -657,6 +905,33 @@ impl ASTLowerer {
         Ok(hir::UnaryOp::new(unary.op, expr, t))
     }
 
+    /// `x?` (`x: T or E`, `E <: Error`) has the type `T`, and `E` is propagated to the caller
+    fn lower_try(&mut self, unary: ast::UnaryOp) -> LowerResult<hir::UnaryOp> {
+        log!(info "entered {}({unary})", fn_name!());
+        let mut args = unary.args.into_iter();
+        let expr = self.lower_expr(*args.next().unwrap())?;
+        let (ok_t, err_t) = self.ctx.split_error_t(expr.ref_t());
+        if err_t == Type::Never && expr.ref_t() != &Type::Failure {
+            return Err(LowerErrors::from(LowerError::type_mismatch_error(
+                self.cfg.input.clone(),
+                line!() as usize,
+                expr.loc(),
+                self.ctx.caused_by(),
+                "`?`",
+                &or(expr.t(), Type::Error),
+                expr.ref_t(),
+                None,
+                None,
+            )));
+        }
+        // at the top level, the error is raised
+        if let Some(propagated) = self.propagated_error_ts.last_mut() {
+            *propagated = self.ctx.union(propagated, &err_t);
+        }
+        let sig_t = func1(expr.t(), ok_t);
+        Ok(hir::UnaryOp::new(unary.op, expr, sig_t))
+    }
+
     fn lower_call(&mut self, call: ast::Call) -> LowerResult<hir::Call> {
         log!(info "entered {}({}{}(...))", fn_name!(), call.obj, fmt_option!(call.attr_name));
         let opt_cast_to = if call.is_assert_cast() {
@@ -678,6 +953,16 @@ impl ASTLowerer {
         } else {
             None
         };
+        // the bodies of the lambdas passed to `if`, `for!`, etc. are inlined,
+        // so `?` in them returns from the enclosing subroutine
+        let inlined = call.is_control_flow();
+        // `break`/`continue` in `if`/`match` refer to the enclosing loop, but cannot escape from `with!`
+        let body_in_loop = if call.is_loop() {
+            true
+        } else {
+            self.in_loop && call.obj.get_name().map(|s| &s[..]) != Some("with!")
+        };
+        let is_for = call.is_loop() && call.obj.get_name().map(|s| &s[..]) != Some("while!");
         let (pos_args, kw_args, paren) = call.args.deconstruct();
         let mut hir_args = hir::Args::new(
             Vec::with_capacity(pos_args.len()),
@@ -686,7 +971,20 @@ impl ASTLowerer {
             paren,
         );
         for arg in pos_args.into_iter() {
-            hir_args.push_pos(hir::PosArg::new(self.lower_expr(arg.expr)?));
+            let expr = match arg.expr {
+                ast::Expr::Lambda(lambda) if inlined => {
+                    let opt_decl_t = match hir_args.pos_args.first() {
+                        Some(iterable) if is_for => self.for_body_decl_t(iterable, &lambda),
+                        _ => None,
+                    };
+                    let in_loop = mem::replace(&mut self.in_loop, body_in_loop);
+                    let lambda = self.lower_lambda(lambda, true, opt_decl_t);
+                    self.in_loop = in_loop;
+                    hir::Expr::Lambda(lambda?)
+                }
+                other => self.lower_expr(other)?,
+            };
+            hir_args.push_pos(hir::PosArg::new(expr));
         }
         for arg in kw_args.into_iter() {
             hir_args.push_kw(hir::KwArg::new(arg.keyword, self.lower_expr(arg.expr)?));
@@ -700,6 +998,11 @@ impl ASTLowerer {
             &self.cfg.input,
             &self.ctx.name,
         )?;
+        if let hir::Expr::Accessor(hir::Accessor::Ident(ident)) = &obj {
+            if ident.vis().is_private() && &ident.inspect()[..] == "match" {
+                self.warn_unreachable_match_arms(&hir_args.pos_args)?;
+            }
+        }
         let attr_name = if let Some(attr_name) = call.attr_name {
             Some(hir::Identifier::new(
                 attr_name.dot,
@@ -743,6 +1046,18 @@ impl ASTLowerer {
         Ok(call)
     }
 
+    fn warn_unreachable_match_arms(&mut self, pos_args: &[hir::PosArg]) -> LowerResult<()> {
+        for arm in self.ctx.get_unreachable_match_arms(pos_args)? {
+            self.warns.push(LowerWarning::unreachable_pattern_warning(
+                self.cfg.input.clone(),
+                line!() as usize,
+                arm.loc(),
+                self.ctx.caused_by(),
+            ));
+        }
+        Ok(())
+    }
+
     fn lower_pack(&mut self, pack: ast::DataPack) -> LowerResult<hir::Call> {
         log!(info "entered {}({pack})", fn_name!());
         let class = self.lower_expr(*pack.class)?;
@@ -775,8 +1090,35 @@ impl ASTLowerer {
         Ok(hir::Call::new(class, Some(attr_name), args, sig_t))
     }
 
+    /// `for! iterable, i => ...`: `i` has the element type of `iterable`
+    fn for_body_decl_t(&self, iterable: &hir::PosArg, body: &ast::Lambda) -> Option<SubrType> {
+        let params = &body.sig.params;
+        if params.non_defaults.len() != 1
+            || params.var_args.is_some()
+            || !params.defaults.is_empty()
+        {
+            return None;
+        }
+        let loop_var_t = self.ctx.get_loop_var_t(iterable.expr.ref_t())?;
+        Some(SubrType::new(
+            SubrKind::Proc,
+            vec![ParamTy::anonymous(loop_var_t)],
+            None,
+            vec![],
+            None,
+            Type::NoneType,
+        ))
+    }
+
     /// TODO: varargs
-    fn lower_lambda(&mut self, lambda: ast::Lambda) -> LowerResult<hir::Lambda> {
+    /// `inlined`: the body is emitted in the caller's frame (the lambdas passed to `if`, `for!`, etc.)
+    /// `opt_decl_t`: the declared type of the lambda (e.g. the body of `for!`)
+    fn lower_lambda(
+        &mut self,
+        lambda: ast::Lambda,
+        inlined: bool,
+        opt_decl_t: Option<SubrType>,
+    ) -> LowerResult<hir::Lambda> {
         log!(info "entered {}({lambda})", fn_name!());
         let is_procedural = lambda.is_procedural();
         let id = get_hash(&lambda.sig);
@@ -791,20 +1133,44 @@ impl ASTLowerer {
             .instantiate_ty_bounds(&lambda.sig.bounds, RegistrationMode::Normal)?;
         let tv_ctx = TyVarInstContext::new(self.ctx.level, bounds, &self.ctx);
         self.ctx.grow(&name, kind, Private, Some(tv_ctx));
-        if let Err(errs) = self.ctx.assign_params(&lambda.sig.params, None) {
+        let has_decl = opt_decl_t.is_some();
+        if let Err(errs) = self.ctx.assign_params(&lambda.sig.params, opt_decl_t) {
             self.errs.extend(errs.into_iter());
         }
+        // the parameters are not generalized, but fixed to the declared types
+        if has_decl {
+            for (_, vi) in self.ctx.params.iter() {
+                self.ctx.coerce(&vi.t);
+            }
+        }
         if let Err(errs) = self.ctx.preregister(&lambda.body) {
             self.errs.extend(errs.into_iter());
         }
-        let body = self.lower_block(lambda.body).map_err(|e| {
+        if !inlined {
+            self.propagated_error_ts.push(Type::Never);
+        }
+        let body = self.lower_block(lambda.body);
+        let propagated_error_t = if inlined {
+            Type::Never
+        } else {
+            self.propagated_error_ts.pop().unwrap()
+        };
+        let body = body.inspect_err(|_e| {
             self.pop_append_errs();
-            e
         })?;
+        let (var_params, kw_var_params) = self.ctx.get_var_params_t(&lambda.sig.params);
         let (non_default_params, default_params): (Vec<_>, Vec<_>) = self
             .ctx
             .params
             .iter()
+            // the elements of array/tuple/record patterns are not parameters of the lambda
+            .filter(|(_, v)| !v.kind.idx().is_some_and(|idx| idx.is_nested()))
+            .filter(|(name, _)| {
+                let name = name.as_ref().map(|n| n.inspect());
+                name.is_none()
+                    || (name != var_params.as_ref().and_then(|p| p.name())
+                        && name != kw_var_params.as_ref().and_then(|p| p.name()))
+            })
             .partition(|(_, v)| !v.kind.has_default());
         let non_default_params = non_default_params
             .into_iter()
@@ -819,16 +1185,24 @@ impl ASTLowerer {
         let bounds = self
             .ctx
             .instantiate_ty_bounds(&lambda.sig.bounds, RegistrationMode::Normal)
-            .map_err(|e| {
+            .inspect_err(|_e| {
                 self.pop_append_errs();
-                e
             })?;
         self.pop_append_errs();
-        let t = if is_procedural {
-            proc(non_default_params, None, default_params, body.t())
+        let return_t = self.ctx.union(body.ref_t(), &propagated_error_t);
+        let kind = if is_procedural {
+            SubrKind::Proc
         } else {
-            func(non_default_params, None, default_params, body.t())
+            SubrKind::Func
         };
+        let t = subr_t(
+            kind,
+            non_default_params,
+            var_params,
+            default_params,
+            kw_var_params,
+            return_t,
+        );
         let t = if bounds.is_empty() {
             t
         } else {
@@ -869,6 +1243,12 @@ impl ASTLowerer {
         }
         let kind = ContextKind::from(def.def_kind());
         let vis = def.sig.vis();
+        let decorators = match &def.sig {
+            ast::Signature::Subr(sig) => sig.decorators.clone(),
+            ast::Signature::Var(_) => vec![],
+        };
+        // `break`/`continue` cannot escape from definitions
+        let in_loop = mem::take(&mut self.in_loop);
         let res = match def.sig {
             ast::Signature::Subr(sig) => {
                 let bounds = self
@@ -883,8 +1263,20 @@ impl ASTLowerer {
                 self.lower_var_def(sig, def.body)
             }
         };
+        self.in_loop = in_loop;
         // TODO: Context上の関数に型境界情報を追加
         self.pop_append_errs();
+        // decorators are evaluated in the outer scope
+        let res = res.and_then(|mut def| {
+            if let hir::Signature::Subr(sig) = &mut def.sig {
+                let (decorators, t) = self.lower_decorators(decorators, &sig.ident, &sig.t)?;
+                sig.decorators = decorators;
+                if let Some(vi) = self.ctx.get_mut_current_scope_var(sig.ident.inspect()) {
+                    vi.t = t;
+                }
+            }
+            Ok(def)
+        });
         // remove from decls regardless of success or failure to lower
         self.ctx.decls.remove(&name);
         res
@@ -948,6 +1340,40 @@ impl ASTLowerer {
         }
     }
 
+    /// Lowers the runtime decorators of `ident: t` and returns them with the decorated type.
+    /// Each decorator is type-checked as a call (`deco(ident)`), from the bottom up.
+    fn lower_decorators(
+        &mut self,
+        decorators: Vec<ast::Decorator>,
+        ident: &hir::Identifier,
+        t: &Type,
+    ) -> LowerResult<(Vec<hir::Expr>, Type)> {
+        log!(info "entered {}({ident})", fn_name!());
+        let mut hir_decorators = vec![];
+        let mut t = t.clone();
+        for deco in decorators.into_iter().rev() {
+            if deco.is_compile_time() {
+                continue;
+            }
+            let deco = self.lower_expr(deco.into_expr())?;
+            let decorated = hir::Identifier::new(ident.dot.clone(), ident.name.clone(), None, t);
+            let args = [hir::PosArg::new(hir::Expr::Accessor(hir::Accessor::Ident(
+                decorated,
+            )))];
+            let sig_t =
+                self.ctx
+                    .get_call_t(&deco, &None, &args, &[], &self.cfg.input, &self.ctx.name)?;
+            let loc = deco.loc();
+            t = self
+                .ctx
+                .deref_tyvar(sig_t.return_t().unwrap().clone(), Variance::Covariant, loc)
+                .map_err(LowerErrors::from)?;
+            hir_decorators.push(deco);
+        }
+        hir_decorators.reverse();
+        Ok((hir_decorators, t))
+    }
+
     // NOTE: 呼ばれている間はinner scopeなので注意
     fn lower_subr_def(
         &mut self,
@@ -971,9 +1397,12 @@ impl ASTLowerer {
                 if let Err(errs) = self.ctx.preregister(&body.block) {
                     self.errs.extend(errs.into_iter());
                 }
-                match self.lower_block(body.block) {
+                self.propagated_error_ts.push(Type::Never);
+                let block = self.lower_block(body.block);
+                let propagated_error_t = self.propagated_error_ts.pop().unwrap();
+                match block {
                     Ok(block) => {
-                        let found_body_t = block.ref_t();
+                        let found_body_t = &self.ctx.union(block.ref_t(), &propagated_error_t);
                         let expect_body_t = t.return_t.as_ref();
                         if !sig.is_const() {
                             if let Err(e) = self.return_t_check(
@@ -993,7 +1422,7 @@ impl ASTLowerer {
                                 .unwrap()
                                 .assign_subr(&sig, id, found_body_t)?;
                         let ident = hir::Identifier::bare(sig.ident.dot, sig.ident.name);
-                        let sig = hir::SubrSignature::new(ident, sig.params, t);
+                        let sig = hir::SubrSignature::new(vec![], ident, sig.params, t);
                         let body = hir::DefBody::new(body.op, block, body.id);
                         Ok(hir::Def::new(hir::Signature::Subr(sig), body))
                     }
@@ -1019,9 +1448,12 @@ impl ASTLowerer {
                     .as_mut()
                     .unwrap()
                     .fake_subr_assign(&sig, Type::Failure);
-                let block = self.lower_block(body.block)?;
+                self.propagated_error_ts.push(Type::Never);
+                let block = self.lower_block(body.block);
+                self.propagated_error_ts.pop();
+                let block = block?;
                 let ident = hir::Identifier::bare(sig.ident.dot, sig.ident.name);
-                let sig = hir::SubrSignature::new(ident, sig.params, Type::Failure);
+                let sig = hir::SubrSignature::new(vec![], ident, sig.params, Type::Failure);
                 let body = hir::DefBody::new(body.op, block, body.id);
                 Ok(hir::Def::new(hir::Signature::Subr(sig), body))
             }
@@ -1123,6 +1555,12 @@ impl ASTLowerer {
             }
             self.check_collision_and_push(class);
         }
+        // NOTE: class decorators are assumed to return the class itself, so the type of the class is not changed
+        let (decorators, _) = self.lower_decorators(
+            class_def.decorators,
+            hir_def.sig.ident(),
+            hir_def.sig.ref_t(),
+        )?;
         let class = mono(hir_def.sig.ident().inspect());
         let class_ctx = self.ctx.get_nominal_type_ctx(&class).unwrap();
         let type_obj = enum_unwrap!(self.ctx.rec_get_const_obj(hir_def.sig.ident().inspect()).unwrap(), ValueObj::Type:(TypeObj::Generated:(_)));
@@ -1142,6 +1580,7 @@ impl ASTLowerer {
         };
         let require_or_sup = self.get_require_or_sup(hir_def.body.block.remove(0));
         Ok(hir::ClassDef::new(
+            decorators,
             type_obj.kind,
             hir_def.sig,
             require_or_sup,
@@ -1421,6 +1860,9 @@ impl ASTLowerer {
         log!(info "entered {}", fn_name!());
         match expr {
             ast::Expr::Lit(lit) => Ok(hir::Expr::Lit(self.lower_literal(lit)?)),
+            ast::Expr::StrInterp(interp) => {
+                Ok(hir::Expr::StrInterp(self.lower_str_interp(interp)?))
+            }
             ast::Expr::Array(arr) => Ok(hir::Expr::Array(self.lower_array(arr)?)),
             ast::Expr::Tuple(tup) => Ok(hir::Expr::Tuple(self.lower_tuple(tup)?)),
             ast::Expr::Record(rec) => Ok(hir::Expr::Record(self.lower_record(rec)?)),
@@ -1431,7 +1873,12 @@ impl ASTLowerer {
             ast::Expr::UnaryOp(unary) => Ok(hir::Expr::UnaryOp(self.lower_unary(unary)?)),
             ast::Expr::Call(call) => Ok(hir::Expr::Call(self.lower_call(call)?)),
             ast::Expr::DataPack(pack) => Ok(hir::Expr::Call(self.lower_pack(pack)?)),
-            ast::Expr::Lambda(lambda) => Ok(hir::Expr::Lambda(self.lower_lambda(lambda)?)),
+            ast::Expr::Lambda(lambda) => {
+                let in_loop = mem::take(&mut self.in_loop);
+                let lambda = self.lower_lambda(lambda, false, None);
+                self.in_loop = in_loop;
+                Ok(hir::Expr::Lambda(lambda?))
+            }
             ast::Expr::Def(def) => Ok(hir::Expr::Def(self.lower_def(def)?)),
             ast::Expr::ClassDef(defs) => Ok(hir::Expr::ClassDef(self.lower_class_def(defs)?)),
             ast::Expr::TypeAsc(tasc) => Ok(hir::Expr::TypeAsc(self.lower_type_asc(tasc)?)),
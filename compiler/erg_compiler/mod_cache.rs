@@ -1,15 +1,17 @@
 use std::borrow::Borrow;
 use std::fmt;
 use std::hash::Hash;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 use std::rc::Rc;
 
 use erg_common::dict::Dict;
 use erg_common::levenshtein::get_similar_name;
+use erg_common::set::Set;
 use erg_common::shared::Shared;
 use erg_common::Str;
 
 use crate::context::Context;
+use crate::disk_cache::CachedModule;
 use crate::hir::HIR;
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
@@ -32,6 +34,8 @@ pub struct ModuleEntry {
     id: ModId, // builtin == 0, __main__ == 1
     pub hir: Option<HIR>,
     ctx: Rc<Context>,
+    /// the on-disk cache of the module (see `disk_cache`), if it can be cached
+    pub cache: Option<CachedModule>,
 }
 
 impl fmt::Display for ModuleEntry {
@@ -50,6 +54,7 @@ impl ModuleEntry {
             id,
             hir,
             ctx: Rc::new(ctx),
+            cache: None,
         }
     }
 
@@ -58,6 +63,7 @@ impl ModuleEntry {
             id: ModId::builtin(),
             hir: None,
             ctx: Rc::new(ctx),
+            cache: None,
         }
     }
 }
@@ -65,6 +71,8 @@ impl ModuleEntry {
 #[derive(Debug, Default)]
 pub struct ModuleCache {
     cache: Dict<PathBuf, ModuleEntry>,
+    /// importer -> imported Erg modules
+    deps: Dict<PathBuf, Set<PathBuf>>,
     last_id: usize,
 }
 
@@ -82,6 +90,7 @@ impl ModuleCache {
     pub fn new() -> Self {
         Self {
             cache: Dict::new(),
+            deps: Dict::new(),
             last_id: 0,
         }
     }
@@ -114,6 +123,47 @@ impl ModuleCache {
         self.cache.remove(path)
     }
 
+    pub fn add_dependency(&mut self, importer: PathBuf, imported: PathBuf) {
+        if let Some(deps) = self.deps.get_mut(&importer) {
+            deps.insert(imported);
+        } else {
+            self.deps.insert(importer, Set::from(vec![imported]));
+        }
+    }
+
+    /// The Erg modules imported by the module at `path`
+    pub fn dependencies<Q: Eq + Hash + ?Sized>(&self, path: &Q) -> Vec<PathBuf>
+    where
+        PathBuf: Borrow<Q>,
+    {
+        self.deps
+            .get(path)
+            .map(|deps| deps.iter().cloned().collect())
+            .unwrap_or_default()
+    }
+
+    /// Removes the module at `path` and the modules which import it (transitively),
+    /// so that they will be checked again on the next import.
+    /// Returns the paths of the removed modules.
+    pub fn invalidate(&mut self, path: &Path) -> Vec<PathBuf> {
+        let mut invalidated = vec![];
+        let mut stack = vec![path.to_path_buf()];
+        while let Some(path) = stack.pop() {
+            if invalidated.contains(&path) {
+                continue;
+            }
+            self.cache.remove(&path);
+            self.deps.remove(&path);
+            for (importer, deps) in self.deps.iter() {
+                if deps.contains(&path) {
+                    stack.push(importer.clone());
+                }
+            }
+            invalidated.push(path);
+        }
+        invalidated
+    }
+
     pub fn remove_by_id(&mut self, id: ModId) -> Option<ModuleEntry> {
         if let Some(name) = self.cache.iter().find_map(|(name, ent)| {
             if ent.id == id {
@@ -157,6 +207,7 @@ impl SharedModuleCache {
         ref_.get(path)
     }
 
+    #[allow(clippy::mut_from_ref)]
     pub fn get_mut<Q: Eq + Hash + ?Sized>(&self, path: &Q) -> Option<&mut ModuleEntry>
     where
         PathBuf: Borrow<Q>,
@@ -191,6 +242,21 @@ impl SharedModuleCache {
         self.0.borrow_mut().remove(path)
     }
 
+    pub fn add_dependency(&self, importer: PathBuf, imported: PathBuf) {
+        self.0.borrow_mut().add_dependency(importer, imported);
+    }
+
+    pub fn dependencies<Q: Eq + Hash + ?Sized>(&self, path: &Q) -> Vec<PathBuf>
+    where
+        PathBuf: Borrow<Q>,
+    {
+        self.0.borrow().dependencies(path)
+    }
+
+    pub fn invalidate(&self, path: &Path) -> Vec<PathBuf> {
+        self.0.borrow_mut().invalidate(path)
+    }
+
     pub fn remove_by_id(&self, id: ModId) -> Option<ModuleEntry> {
         self.0.borrow_mut().remove_by_id(id)
     }
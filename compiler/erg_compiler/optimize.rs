@@ -1,19 +1,409 @@
-use crate::error::CompileWarnings;
-use crate::hir::HIR;
+//! defines `HIROptimizer`.
+//!
+//! HIRを最適化する
+use std::mem;
+
+use erg_common::config::ErgConfig;
+use erg_common::set::Set as HashSet;
+use erg_common::traits::{Locational, Stream};
+use erg_common::{log, Str};
+
+use erg_parser::ast;
+use erg_parser::token::{Token, TokenKind};
+
+use crate::ty::value::ValueObj;
+
+use crate::context::eval::type_from_token_kind;
+use crate::context::Context;
+use crate::error::{CompileWarning, CompileWarnings};
+use crate::hir::*;
+
+/// Folding does not generate strings longer than this (the same limit as CPython's AST optimizer)
+const MAX_FOLDED_STR_LEN: usize = 4096;
+
+fn collect_used_names(expr: &Expr, used: &mut HashSet<Str>) {
+    match expr {
+        Expr::Accessor(Accessor::Ident(ident)) => {
+            used.insert(ident.inspect().clone());
+        }
+        _ => expr.for_each_child(&mut |chunk| collect_used_names(chunk, used)),
+    }
+}
+
+/// Evaluating `expr` has no side-effects and never fails
+fn is_pure(expr: &Expr) -> bool {
+    match expr {
+        Expr::Lit(_) | Expr::Accessor(Accessor::Ident(_)) | Expr::Lambda(_) => true,
+        Expr::Array(Array::Normal(arr)) => is_pure_args(&arr.elems),
+        Expr::Tuple(Tuple::Normal(tup)) => is_pure_args(&tup.elems),
+        Expr::Set(Set::Normal(st)) => is_pure_args(&st.elems),
+        Expr::Dict(Dict::Normal(dic)) => dic
+            .kvs
+            .iter()
+            .all(|kv| is_pure(&kv.key) && is_pure(&kv.value)),
+        Expr::TypeAsc(tasc) => is_pure(&tasc.expr),
+        _ => false,
+    }
+}
+
+fn is_pure_args(args: &Args) -> bool {
+    args.var_args.is_none()
+        && args.kw_args.is_empty()
+        && args.pos_args.iter().all(|arg| is_pure(&arg.expr))
+}
+
+fn as_int(value: &ValueObj) -> Option<i128> {
+    match value {
+        ValueObj::Int(i) => Some(*i as i128),
+        ValueObj::Nat(n) => Some(*n as i128),
+        _ => None,
+    }
+}
+
+fn is_zero(value: &ValueObj) -> bool {
+    match value {
+        ValueObj::Int(i) => *i == 0,
+        ValueObj::Nat(n) => *n == 0,
+        ValueObj::Float(f) => *f == 0.0,
+        _ => false,
+    }
+}
+
+/// Returns false if the compile-time evaluation would not behave the same as Python does at runtime
+/// (e.g. Erg integers have fixed size, `1 / 0` raises `ZeroDivisionError`)
+fn is_foldable(op: TokenKind, lhs: &ValueObj, rhs: &ValueObj) -> bool {
+    use TokenKind::*;
+    if let (Some(l), Some(r)) = (as_int(lhs), as_int(rhs)) {
+        let in_range = |n: i128| (i32::MIN as i128..=i32::MAX as i128).contains(&n);
+        if !in_range(l) || !in_range(r) {
+            return false;
+        }
+        return match op {
+            Plus => in_range(l + r),
+            Minus => in_range(l - r),
+            Star => in_range(l * r),
+            Slash => r != 0,
+            // Rust rounds towards zero, but Python rounds towards negative infinity
+            FloorDiv => l >= 0 && r > 0,
+            _ => true,
+        };
+    }
+    match (op, lhs, rhs) {
+        (Slash | FloorDiv, _, rhs) => !is_zero(rhs),
+        (Star, ValueObj::Str(s), ValueObj::Nat(n)) => {
+            (s.len() as u64).saturating_mul(*n) <= MAX_FOLDED_STR_LEN as u64
+        }
+        (Plus, ValueObj::Str(l), ValueObj::Str(r)) => l.len() + r.len() <= MAX_FOLDED_STR_LEN,
+        _ => true,
+    }
+}
 
 #[derive(Debug)]
-pub struct HIROptimizer {}
+pub struct HIROptimizer {
+    cfg: ErgConfig,
+    ctx: Context,
+}
 
 impl HIROptimizer {
-    pub fn fold_constants(&mut self, mut _hir: HIR) -> HIR {
-        todo!()
+    pub fn new(cfg: ErgConfig) -> Self {
+        let ctx = Context::module(Str::ever("<module>"), cfg.copy(), None, None, 0);
+        Self { cfg, ctx }
+    }
+
+    /// Applies the passes enabled by `cfg.opt_level`
+    pub fn optimize(&mut self, hir: HIR) -> (HIR, CompileWarnings) {
+        let mut warns = CompileWarnings::empty();
+        if self.cfg.opt_level == 0 {
+            return (hir, warns);
+        }
+        log!(info "the optimization process has started.");
+        let hir = self.fold_constants(hir);
+        let (hir, mut dead_code_warns) = self.eliminate_dead_code(hir);
+        warns.append(&mut dead_code_warns);
+        let (hir, mut unused_warns) = self.eliminate_unused_variables(hir);
+        warns.append(&mut unused_warns);
+        log!(info "the optimization process has completed.");
+        (hir, warns)
+    }
+
+    /// ```erg
+    /// x = 1 + 2 * 3
+    /// ```
+    /// ↓
+    /// ```erg
+    /// x = 7
+    /// ```
+    pub fn fold_constants(&mut self, mut hir: HIR) -> HIR {
+        for chunk in hir.module.iter_mut() {
+            self.fold_expr(chunk);
+        }
+        hir
+    }
+
+    fn fold_expr(&self, expr: &mut Expr) {
+        expr.for_each_child_mut(&mut |chunk| self.fold_expr(chunk));
+        let folded = match expr {
+            Expr::BinOp(bin) => self.fold_bin(bin),
+            Expr::UnaryOp(unary) => self.fold_unary(unary),
+            _ => None,
+        };
+        if let Some(lit) = folded {
+            *expr = Expr::Lit(lit);
+        }
+    }
+
+    fn fold_bin(&self, bin: &BinOp) -> Option<Literal> {
+        use TokenKind::*;
+        let (lhs, rhs) = match (bin.lhs.as_ref(), bin.rhs.as_ref()) {
+            (Expr::Lit(lhs), Expr::Lit(rhs)) => (lhs, rhs),
+            _ => return None,
+        };
+        // operators that `Context::eval_bin` can handle
+        if !matches!(
+            bin.op.kind,
+            Plus | Minus | Star | Slash | FloorDiv | DblEq | NotEq | Less | Gre | LessEq | GreEq
+        ) || !is_foldable(bin.op.kind, &lhs.value, &rhs.value)
+        {
+            return None;
+        }
+        let expr = ast::Expr::BinOp(ast::BinOp::new(
+            bin.op.clone(),
+            ast::Expr::Lit(ast::Literal::from(lhs.token.clone())),
+            ast::Expr::Lit(ast::Literal::from(rhs.token.clone())),
+        ));
+        let value = self.ctx.eval_const_expr(&expr).ok()?;
+        Self::lit_from_value(value, &lhs.token)
+    }
+
+    fn fold_unary(&self, unary: &UnaryOp) -> Option<Literal> {
+        let lit = match unary.expr.as_ref() {
+            Expr::Lit(lit) => lit,
+            _ => return None,
+        };
+        if !matches!(
+            unary.op.kind,
+            TokenKind::PrePlus | TokenKind::PreMinus | TokenKind::PreBitNot
+        ) {
+            return None;
+        }
+        let expr = ast::Expr::UnaryOp(ast::UnaryOp::new(
+            unary.op.clone(),
+            ast::Expr::Lit(ast::Literal::from(lit.token.clone())),
+        ));
+        let value = self.ctx.eval_const_expr(&expr).ok()?;
+        Self::lit_from_value(value, &unary.op)
+    }
+
+    /// The result may be folded again, so it must be restorable from its token.
+    fn lit_from_value(value: ValueObj, loc: &Token) -> Option<Literal> {
+        let (kind, content) = match &value {
+            ValueObj::Int(i) => (TokenKind::IntLit, i.to_string()),
+            ValueObj::Nat(n) => (TokenKind::NatLit, n.to_string()),
+            ValueObj::Float(f) => (TokenKind::RatioLit, f.to_string()),
+            ValueObj::Str(s) => (TokenKind::StrLit, format!("\"{s}\"")),
+            ValueObj::Bool(b) => (TokenKind::BoolLit, if *b { "True" } else { "False" }.into()),
+            _ => return None,
+        };
+        let restored = ValueObj::from_str(type_from_token_kind(kind), Str::rc(&content))?;
+        if restored != value {
+            return None;
+        }
+        let token = Token::new(kind, content, loc.lineno, loc.col_begin);
+        Some(Literal::new(value, token))
+    }
+
+    /// ```erg
+    /// f x =
+    ///     y = 1 # removed
+    ///     x + 1
+    /// ```
+    /// Module-level variables are not removed because other modules may use them.
+    pub fn eliminate_unused_variables(&mut self, mut hir: HIR) -> (HIR, CompileWarnings) {
+        let mut warns = CompileWarnings::empty();
+        for chunk in hir.module.iter_mut() {
+            self.eliminate_unused_in_module_chunk(chunk, &mut warns);
+        }
+        (hir, warns)
+    }
+
+    fn eliminate_unused_in_module_chunk(&self, expr: &mut Expr, warns: &mut CompileWarnings) {
+        match expr {
+            Expr::Def(def) if def.sig.is_subr() => {
+                let name = def.sig.ident().inspect().clone();
+                let mut used = HashSet::new();
+                for chunk in def.body.block.iter() {
+                    collect_used_names(chunk, &mut used);
+                }
+                // these can access any local variable
+                if ["locals", "vars", "eval", "exec"]
+                    .iter()
+                    .any(|name| used.contains(*name))
+                {
+                    return;
+                }
+                self.eliminate_unused_in_block(&name, &mut def.body.block, &used, warns);
+            }
+            // linked modules (they have been checked when compiled)
+            Expr::Code(_) => {}
+            _ => expr.for_each_child_mut(&mut |chunk| {
+                self.eliminate_unused_in_module_chunk(chunk, warns)
+            }),
+        }
+    }
+
+    /// `used`: the names used in the outermost subroutine
+    fn eliminate_unused_in_block(
+        &self,
+        caused_by: &Str,
+        block: &mut Block,
+        used: &HashSet<Str>,
+        warns: &mut CompileWarnings,
+    ) {
+        let len = block.len();
+        let mut idx = 0;
+        block.ref_mut_payload().retain(|chunk| {
+            idx += 1;
+            let def = match chunk {
+                Expr::Def(def) => def,
+                _ => return true,
+            };
+            let ident = def.sig.ident();
+            if ident.vis().is_public() || used.contains(ident.inspect()) {
+                return true;
+            }
+            if !ident.inspect().starts_with('_') {
+                warns.push(CompileWarning::unused_warning(
+                    self.cfg.input.clone(),
+                    line!() as usize,
+                    ident.loc(),
+                    ident.inspect(),
+                    caused_by.into(),
+                ));
+            }
+            // decorators may have side-effects
+            let decorated = matches!(&def.sig, Signature::Subr(sig) if !sig.decorators.is_empty());
+            // the last chunk is the return value of the block
+            idx == len || decorated || !(def.sig.is_subr() || def.body.block.iter().all(is_pure))
+        });
+        for chunk in block.iter_mut() {
+            self.eliminate_unused_in_chunk(caused_by, chunk, used, warns);
+        }
+    }
+
+    fn eliminate_unused_in_chunk(
+        &self,
+        caused_by: &Str,
+        expr: &mut Expr,
+        used: &HashSet<Str>,
+        warns: &mut CompileWarnings,
+    ) {
+        match expr {
+            Expr::Def(def) if def.sig.is_subr() => {
+                let name = def.sig.ident().inspect().clone();
+                self.eliminate_unused_in_block(&name, &mut def.body.block, used, warns);
+            }
+            Expr::Def(def) => {
+                self.eliminate_unused_in_block(caused_by, &mut def.body.block, used, warns);
+            }
+            Expr::Lambda(lambda) => {
+                self.eliminate_unused_in_block(caused_by, &mut lambda.body, used, warns);
+            }
+            Expr::Compound(chunks) => {
+                self.eliminate_unused_in_block(caused_by, chunks, used, warns);
+            }
+            _ => expr.for_each_child_mut(&mut |chunk| {
+                self.eliminate_unused_in_chunk(caused_by, chunk, used, warns)
+            }),
+        }
+    }
+
+    /// ```erg
+    /// if! False, do!:
+    ///     print! "never executed"
+    /// 1 # the result is not used
+    /// ```
+    /// ↓
+    /// (removed)
+    pub fn eliminate_dead_code(&mut self, mut hir: HIR) -> (HIR, CompileWarnings) {
+        for chunk in hir.module.iter_mut() {
+            self.eliminate_dead_code_in_expr(chunk);
+        }
+        Self::remove_unused_literals(hir.module.ref_mut_payload());
+        (hir, CompileWarnings::empty())
+    }
+
+    fn eliminate_dead_code_in_expr(&self, expr: &mut Expr) {
+        expr.for_each_child_mut(&mut |chunk| self.eliminate_dead_code_in_expr(chunk));
+        if let Expr::Call(call) = expr {
+            if let Some(branch) = Self::select_const_branch(call) {
+                *expr = branch;
+            }
+        }
+        match expr {
+            Expr::Def(def) => Self::remove_unused_literals(def.body.block.ref_mut_payload()),
+            Expr::Lambda(lambda) => Self::remove_unused_literals(lambda.body.ref_mut_payload()),
+            Expr::AttrDef(attr_def) => {
+                Self::remove_unused_literals(attr_def.block.ref_mut_payload())
+            }
+            Expr::Code(chunks) | Expr::Compound(chunks) => {
+                Self::remove_unused_literals(chunks.ref_mut_payload())
+            }
+            _ => {}
+        }
     }
 
-    pub fn eliminate_unused_variables(&mut self, mut _hir: HIR) -> (HIR, CompileWarnings) {
-        todo!()
+    /// `if True, then, else` -> `then`
+    fn select_const_branch(call: &mut Call) -> Option<Expr> {
+        if call.attr_name.is_some() || call.args.var_args.is_some() || !call.args.kw_args.is_empty()
+        {
+            return None;
+        }
+        match call.obj.as_ref() {
+            Expr::Accessor(Accessor::Ident(ident))
+                if ident.vis().is_private() && matches!(&ident.inspect()[..], "if" | "if!") => {}
+            _ => return None,
+        }
+        let cond = match call.args.pos_args.first().map(|arg| &arg.expr) {
+            Some(Expr::Lit(Literal {
+                value: ValueObj::Bool(cond),
+                ..
+            })) => *cond,
+            _ => return None,
+        };
+        let idx = if cond { 1 } else { 2 };
+        if call.args.pos_args.len() <= idx {
+            // `if False, then` is `None`
+            let token = Token::new(
+                TokenKind::NoneLit,
+                "None",
+                call.obj.ln_begin().unwrap_or(0),
+                call.obj.col_begin().unwrap_or(0),
+            );
+            return Literal::try_from(token).ok().map(Expr::Lit);
+        }
+        match &mut call.args.pos_args[idx].expr {
+            // the branches of `if` are not independent scopes
+            Expr::Lambda(lambda) if lambda.params.is_empty() => {
+                let mut body = mem::replace(&mut lambda.body, Block::empty());
+                if body.len() == 1 {
+                    body.pop()
+                } else {
+                    Some(Expr::Compound(body))
+                }
+            }
+            Expr::Lambda(_) => None,
+            other => Some(mem::take(other)),
+        }
     }
 
-    pub fn eliminate_dead_code(&mut self, mut _hir: HIR) -> (HIR, CompileWarnings) {
-        todo!()
+    /// Removes literals in statement positions (e.g. left by `select_const_branch`).
+    /// The last chunk is the return value, so it is not removed.
+    fn remove_unused_literals(chunks: &mut Vec<Expr>) {
+        let len = chunks.len();
+        let mut idx = 0;
+        chunks.retain(|chunk| {
+            idx += 1;
+            idx == len || !matches!(chunk, Expr::Lit(_))
+        });
     }
 }
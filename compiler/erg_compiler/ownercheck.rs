@@ -137,6 +137,8 @@ impl OwnershipChecker {
                         .find(|(k, _)| k == kw_arg.keyword.inspect())
                     {
                         self.check_expr(&kw_arg.expr, *ownership, false);
+                    } else if let Some(ownership) = args_owns.kw_var_params.as_ref() {
+                        self.check_expr(&kw_arg.expr, *ownership, false);
                     } else {
                         todo!()
                     }
@@ -144,12 +146,24 @@ impl OwnershipChecker {
             }
             // TODO: referenced
             Expr::BinOp(binop) => {
+                // comparisons only read the operands (e.g. `while! i < 10, do!: ...`)
+                let ownership = if binop.op.kind.is_comparison_op() {
+                    Ownership::Ref
+                } else {
+                    ownership
+                };
                 self.check_expr(&binop.lhs, ownership, false);
                 self.check_expr(&binop.rhs, ownership, false);
             }
             Expr::UnaryOp(unary) => {
                 self.check_expr(&unary.expr, ownership, false);
             }
+            // the embedded values are only formatted
+            Expr::StrInterp(interp) => {
+                for expr in interp.exprs.iter() {
+                    self.check_expr(expr, Ownership::Ref, false);
+                }
+            }
             Expr::Array(array) => match array {
                 Array::Normal(arr) => {
                     for a in arr.elems.pos_args.iter() {
@@ -160,7 +174,10 @@ impl OwnershipChecker {
                     self.check_expr(&arr.elem, ownership, false);
                     self.check_expr(&arr.len, ownership, false);
                 }
-                _ => todo!(),
+                Array::Comprehension(_) => {
+                    // iterating over an object does not move it
+                    expr.for_each_child(&mut |child| self.check_expr(child, Ownership::Ref, false));
+                }
             },
             Expr::Tuple(tuple) => match tuple {
                 Tuple::Normal(arr) => {
@@ -176,7 +193,10 @@ impl OwnershipChecker {
                         self.check_expr(&kv.value, ownership, false);
                     }
                 }
-                other => todo!("{other}"),
+                hir::Dict::Comprehension(_) => {
+                    // iterating over an object does not move it
+                    expr.for_each_child(&mut |child| self.check_expr(child, Ownership::Ref, false));
+                }
             },
             Expr::Record(rec) => {
                 for def in rec.attrs.iter() {
@@ -195,6 +215,10 @@ impl OwnershipChecker {
                     self.check_expr(&st.elem, ownership, false);
                     self.check_expr(&st.len, ownership, false);
                 }
+                hir::Set::Comprehension(_) => {
+                    // iterating over an object does not move it
+                    expr.for_each_child(&mut |child| self.check_expr(child, Ownership::Ref, false));
+                }
             },
             // TODO: capturing
             Expr::Lambda(lambda) => {
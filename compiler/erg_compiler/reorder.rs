@@ -4,7 +4,9 @@ use erg_common::log;
 use erg_common::traits::{Locational, Stream};
 use erg_common::Str;
 
-use erg_parser::ast::{ClassDef, Expr, Methods, Module, PreDeclTypeSpec, TypeSpec, AST};
+use erg_parser::ast::{
+    Args, Call, ClassDef, Decorator, Expr, Methods, Module, PosArg, PreDeclTypeSpec, TypeSpec, AST,
+};
 
 use crate::error::{TyCheckError, TyCheckErrors};
 
@@ -33,24 +35,27 @@ impl Reorderer {
         let mut new = vec![];
         while let Some(chunk) = ast.module.lpop() {
             match chunk {
-                Expr::Def(def) => {
+                Expr::Def(mut def) => {
+                    // `@deco C = Class ...` has been desugared into `C = deco(Class ...)`
+                    let mut decorators = vec![];
+                    if def.body.block.len() == 1 {
+                        let expr = def.body.block.remove(0);
+                        let expr = Self::strip_class_decorators(expr, &mut decorators)
+                            .unwrap_or_else(|expr| expr);
+                        def.body.block.push(expr);
+                    }
                     match def.body.block.first().unwrap() {
-                        Expr::Call(call) => {
-                            match call.obj.get_name().map(|s| &s[..]) {
-                                // TODO: decorator
-                                Some("Class" | "Inherit" | "Inheritable") => {
-                                    self.def_root_pos_map.insert(
-                                        def.sig.ident().unwrap().inspect().clone(),
-                                        new.len(),
-                                    );
-                                    let type_def = ClassDef::new(def, vec![]);
-                                    new.push(Expr::ClassDef(type_def));
-                                }
-                                _ => {
-                                    new.push(Expr::Def(def));
-                                }
+                        Expr::Call(call) => match call.obj.get_name().map(|s| &s[..]) {
+                            Some("Class" | "Inherit" | "Inheritable") => {
+                                self.def_root_pos_map
+                                    .insert(def.sig.ident().unwrap().inspect().clone(), new.len());
+                                let type_def = ClassDef::new(decorators, def, vec![]);
+                                new.push(Expr::ClassDef(type_def));
                             }
-                        }
+                            _ => {
+                                new.push(Expr::Def(def));
+                            }
+                        },
                         _ => {
                             new.push(Expr::Def(def));
                         }
@@ -95,6 +100,46 @@ impl Reorderer {
         }
     }
 
+    /// `deco1(deco2(Class ...))` => `Class ...` (and `[deco1, deco2]` are pushed to `decorators`)
+    ///
+    /// If `expr` is not a (decorated) class definition, returns it as is.
+    fn strip_class_decorators(expr: Expr, decorators: &mut Vec<Decorator>) -> Result<Expr, Expr> {
+        let call = match expr {
+            Expr::Call(call) => call,
+            other => return Err(other),
+        };
+        match call.obj.get_name().map(|s| &s[..]) {
+            Some("Class" | "Inherit") => return Ok(Expr::Call(call)),
+            _ if call.attr_name.is_some()
+                || call.args.len() != 1
+                || call.args.pos_args().is_empty() =>
+            {
+                return Err(Expr::Call(call));
+            }
+            _ => {}
+        }
+        // `Inheritable` is applied at compile time, so it is left as is
+        let is_inheritable = call.obj.get_name().map(|s| &s[..]) == Some("Inheritable");
+        let len = decorators.len();
+        if !is_inheritable {
+            decorators.push(Decorator::new(*call.obj.clone()));
+        }
+        let (mut pos_args, kw_args, paren) = call.args.deconstruct();
+        let arg = pos_args.remove(0).expr;
+        match Self::strip_class_decorators(arg, decorators) {
+            Ok(inner) if is_inheritable => {
+                let args = Args::new(vec![PosArg::new(inner)], kw_args, paren);
+                Ok(Expr::Call(Call::new(*call.obj, None, args)))
+            }
+            Ok(inner) => Ok(inner),
+            Err(arg) => {
+                decorators.truncate(len);
+                let args = Args::new(vec![PosArg::new(arg)], kw_args, paren);
+                Err(Expr::Call(Call::new(*call.obj, call.attr_name, args)))
+            }
+        }
+    }
+
     fn link_methods(&mut self, name: Str, new: &mut Vec<Expr>, methods: Methods) {
         if let Some(pos) = self.def_root_pos_map.get(&name) {
             let mut class_def = match new.remove(*pos) {
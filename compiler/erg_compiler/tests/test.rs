@@ -1,6 +1,24 @@
+use std::path::PathBuf;
+
 use erg_common::config::ErgConfig;
+use erg_common::error::ErrorKind::*;
+use erg_common::error::{ErrorDisplay, MultiErrorDisplay};
+use erg_common::json::JsonValue;
+use erg_common::opcode::Opcode;
+use erg_common::python_util::PythonVersion;
+use erg_common::traits::{Runnable, Stream};
+
+use erg_compiler::build_hir::HIRBuilder;
 use erg_compiler::context::Context;
+use erg_compiler::linter::Linter;
 use erg_compiler::mod_cache::SharedModuleCache;
+use erg_compiler::ty::codeobj::CodeObj;
+use erg_compiler::ty::deserialize::{ByteReader, Deserializer};
+use erg_compiler::ty::disassemble::disassemble;
+use erg_compiler::ty::peephole::peephole;
+use erg_compiler::ty::value::ValueObj;
+use erg_compiler::ty::verify::{verify, verify_generated};
+use erg_compiler::Compiler;
 
 #[test]
 fn test_subtyping() -> Result<(), ()> {
@@ -56,3 +74,269 @@ fn test_dir() -> Result<(), ()> {
     }
     Ok(())
 }
+
+#[test]
+fn test_deserialize_marshal() -> Result<(), ()> {
+    // marshal.dumps((s, s, 2**40, -5, 1.5, 1+2j, b"ab", frozenset({1}), {1: 2}, [3], ..., None), 4)
+    // where s = "shared" (the second one is a reference to the first one)
+    let bytes = [
+        0xa9, 0x0c, 0xda, 0x06, 0x73, 0x68, 0x61, 0x72, 0x65, 0x64, 0x72, 0x01, 0x00, 0x00, 0x00,
+        0xec, 0x03, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x04, 0xe9, 0xfb, 0xff, 0xff,
+        0xff, 0xe7, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0xf8, 0x3f, 0xf9, 0x00, 0x00, 0x00, 0x00,
+        0x00, 0x00, 0xf0, 0x3f, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x40, 0xf3, 0x02, 0x00,
+        0x00, 0x00, 0x61, 0x62, 0x3e, 0x01, 0x00, 0x00, 0x00, 0xe9, 0x01, 0x00, 0x00, 0x00, 0x7b,
+        0x72, 0x07, 0x00, 0x00, 0x00, 0xe9, 0x02, 0x00, 0x00, 0x00, 0x30, 0x5b, 0x01, 0x00, 0x00,
+        0x00, 0xe9, 0x03, 0x00, 0x00, 0x00, 0x2e, 0x4e,
+    ];
+    let mut r = ByteReader::new(&bytes);
+    let obj = Deserializer::new()
+        .deserialize_const(&mut r, 3439)
+        .map_err(|_| ())?;
+    assert_eq!(
+        obj.to_string(),
+        r#"["shared", "shared", 1099511627776, -5, 1.5f, (1+2j), b"ab", {1}, {1: 2}, [3], Ellipsis, None]"#
+    );
+    assert!(r.is_empty());
+    // truncated input is an error, not a panic
+    for len in 0..bytes.len() {
+        let mut r = ByteReader::new(&bytes[..len]);
+        assert!(Deserializer::new().deserialize_const(&mut r, 3439).is_err());
+    }
+    Ok(())
+}
+
+#[test]
+fn test_deserialize_unsupported_version() -> Result<(), ()> {
+    let mut cfg = ErgConfig::with_main_path(PathBuf::from("tests/disassemble.er"));
+    cfg.python_ver = Some(PythonVersion::V3_11.magic_num());
+    let src = cfg.input.read();
+    let code = Compiler::new(cfg).compile(src, "exec").map_err(|_| ())?;
+    let bytes = code.into_bytes(PythonVersion::V3_11.magic_num());
+    // the magic number of Python 3.12: the opcode table of 3.11 must not be used for it
+    let err = CodeObj::from_bytes(&mut ByteReader::new(&bytes), 3531).unwrap_err();
+    assert_eq!(&err.desc[..], "unsupported pyc version 3531");
+    Ok(())
+}
+
+#[test]
+fn test_disassemble() -> Result<(), ()> {
+    for ver in [PythonVersion::V3_10, PythonVersion::V3_11] {
+        let mut cfg = ErgConfig::with_main_path(PathBuf::from("tests/disassemble.er"));
+        cfg.python_ver = Some(ver.magic_num());
+        let src = cfg.input.read();
+        let code = Compiler::new(cfg).compile(src, "exec").map_err(|_| ())?;
+        // read back the bytecode assembled for `ver`
+        let bytes = code.into_bytes(ver.magic_num());
+        let code =
+            CodeObj::from_bytes(&mut ByteReader::new(&bytes), ver.magic_num()).map_err(|_| ())?;
+        let instrs = disassemble(&code, Some(ver));
+        assert!(instrs.iter().all(|instr| instr.op.is_some()));
+        for instr in instrs.iter() {
+            if let Some(target) = instr.jump_target {
+                assert!(instrs
+                    .iter()
+                    .any(|i| i.offset == target && i.is_jump_target));
+            }
+        }
+        assert!(instrs
+            .iter()
+            .any(|i| i.opname() == "STORE_NAME" && i.argrepr == "::total"));
+        assert!(instrs
+            .iter()
+            .any(|i| i.opname() == "COMPARE_OP" && i.argrepr == "=="));
+        let lines = instrs
+            .iter()
+            .filter(|i| i.starts_line)
+            .filter_map(|i| i.line)
+            .collect::<Vec<_>>();
+        assert_eq!(lines, vec![1, 2, 3, 4, 5, 6, 7]);
+        let Some(ValueObj::Code(lambda)) =
+            code.consts.iter().find(|c| matches!(c, ValueObj::Code(_)))
+        else {
+            return Err(());
+        };
+        let instrs = disassemble(lambda, Some(ver));
+        assert!(instrs
+            .iter()
+            .any(|i| i.opname() == "LOAD_FAST" && i.argrepr == "::s"));
+        let json = code.code_info_json(Some(ver));
+        let parsed = JsonValue::parse(&json.to_string()).map_err(|_| ())?;
+        assert_eq!(
+            parsed
+                .get("code_objects")
+                .and_then(|c| c.as_array())
+                .map(|c| c.len()),
+            Some(1)
+        );
+    }
+    Ok(())
+}
+
+#[test]
+fn test_verify_bytecode() -> Result<(), ()> {
+    for ver in PythonVersion::ALL {
+        for file in ["tests/disassemble.er", "tests/verify.er"] {
+            let mut cfg = ErgConfig::with_main_path(PathBuf::from(file));
+            cfg.python_ver = Some(ver.magic_num());
+            let src = cfg.input.read();
+            let code = Compiler::new(cfg).compile(src, "exec").map_err(|_| ())?;
+            let depth = verify_generated(&code, ver.magic_num()).map_err(|_| ())?;
+            assert!(depth <= code.stacksize);
+        }
+    }
+    let ver = PythonVersion::V3_10;
+    let mut cfg = ErgConfig::with_main_path(PathBuf::from("tests/verify.er"));
+    cfg.python_ver = Some(ver.magic_num());
+    let src = cfg.input.read();
+    let code = Compiler::new(cfg).compile(src, "exec").map_err(|_| ())?;
+    let bytes = code.into_bytes(ver.magic_num());
+    let code =
+        CodeObj::from_bytes(&mut ByteReader::new(&bytes), ver.magic_num()).map_err(|_| ())?;
+    assert!(verify(&code, ver).is_ok());
+    // the stack size is too small
+    let mut broken = code.clone();
+    broken.stacksize = 1;
+    assert!(verify(&broken, ver).is_err());
+    // `LOAD_CONST` out of range
+    let mut broken = code.clone();
+    broken.code = vec![100, 255, 83, 0];
+    assert!(verify(&broken, ver).is_err());
+    // `POP_TOP` on the empty stack
+    let mut broken = code.clone();
+    broken.code = vec![1, 0, 100, 0, 83, 0];
+    assert!(verify(&broken, ver).is_err());
+    // jumping out of the code, and falling off the end
+    let mut broken = code.clone();
+    broken.code = vec![110, 5, 100, 0, 83, 0];
+    assert!(verify(&broken, ver).is_err());
+    let mut broken = code;
+    broken.code = vec![100, 0, 1, 0];
+    assert!(verify(&broken, ver).is_err());
+    Ok(())
+}
+
+#[test]
+fn test_peephole() -> Result<(), ()> {
+    for ver in PythonVersion::ALL {
+        let mut codes = vec![];
+        for opt_level in [1, 2] {
+            let mut cfg = ErgConfig::with_main_path(PathBuf::from("tests/peephole.er"));
+            cfg.python_ver = Some(ver.magic_num());
+            cfg.opt_level = opt_level;
+            let src = cfg.input.read();
+            let code = Compiler::new(cfg).compile(src, "exec").map_err(|_| ())?;
+            verify_generated(&code, ver.magic_num()).map_err(|_| ())?;
+            codes.push(code);
+        }
+        let (code, optimized) = (&codes[0], &codes[1]);
+        assert!(optimized.code.len() < code.code.len());
+        let instrs = disassemble(optimized, None);
+        assert!(instrs
+            .windows(2)
+            .all(|w| (w[0].op, w[1].op) != (Some(Opcode::LOAD_CONST), Some(Opcode::POP_TOP))));
+        assert!(instrs.iter().all(|i| i.op != Some(Opcode::BUILD_TUPLE)));
+        let tuple = ValueObj::Tuple(
+            vec![
+                ValueObj::from("a"),
+                ValueObj::Tuple(vec![ValueObj::from("b"), ValueObj::Float(1.5)].into()),
+            ]
+            .into(),
+        );
+        assert!(optimized.consts.contains(&tuple));
+        // the line numbers are kept
+        let lines = |code| {
+            disassemble(code, None)
+                .into_iter()
+                .filter(|i| i.starts_line)
+                .filter_map(|i| i.line)
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(lines(code), lines(optimized));
+    }
+    // if x: return 1 else: return 2 (via a jump to a jump), and unreachable code
+    let instrs = |instrs: &[(Opcode, u8)]| {
+        instrs
+            .iter()
+            .flat_map(|&(op, arg)| [op as u8, arg])
+            .collect::<Vec<_>>()
+    };
+    let mut code = CodeObj::empty(vec![], "<string>", "<module>", 1);
+    code.consts = vec![ValueObj::Bool(true), ValueObj::from(1), ValueObj::from(2)];
+    code.code = instrs(&[
+        (Opcode::LOAD_CONST, 0),
+        (Opcode::POP_JUMP_IF_FALSE, 4),
+        (Opcode::LOAD_CONST, 1),
+        (Opcode::RETURN_VALUE, 0),
+        (Opcode::JUMP_ABSOLUTE, 6),
+        (Opcode::LOAD_CONST, 0),
+        (Opcode::LOAD_CONST, 2),
+        (Opcode::RETURN_VALUE, 0),
+        (Opcode::LOAD_CONST, 0),
+        (Opcode::RETURN_VALUE, 0),
+    ]);
+    code.push_lnotab(8, 1);
+    code.push_lnotab(4, 1);
+    peephole(&mut code);
+    let expect = instrs(&[
+        (Opcode::LOAD_CONST, 0),
+        (Opcode::POP_JUMP_IF_FALSE, 4),
+        (Opcode::LOAD_CONST, 1),
+        (Opcode::RETURN_VALUE, 0),
+        (Opcode::LOAD_CONST, 2),
+        (Opcode::RETURN_VALUE, 0),
+    ]);
+    assert_eq!(code.code, expect);
+    let instrs = disassemble(&code, None);
+    assert_eq!(instrs[4].line, Some(3));
+    assert!(instrs[4].is_jump_target);
+    Ok(())
+}
+
+#[test]
+fn test_lint() -> Result<(), ()> {
+    let cfg = ErgConfig::with_main_path(PathBuf::from("tests/lint.er"));
+    let mut builder = HIRBuilder::new(cfg.copy());
+    let hir = builder.build(cfg.input.read(), "exec").map_err(|_| ())?;
+    let warns = Linter::new(cfg).lint(&hir);
+    let kinds = warns
+        .into_iter()
+        .map(|warn| (warn.core.kind, warn.caused_by.to_string()))
+        .collect::<Vec<_>>();
+    assert_eq!(
+        kinds,
+        vec![
+            (NameWarning, "f".to_string()),
+            (UnusedWarning, "f".to_string()),
+            (Warning, "<module>".to_string()),
+            (NameWarning, "<module>".to_string()),
+            (TypeWarning, "<module>".to_string()),
+        ]
+    );
+    Ok(())
+}
+
+#[test]
+fn test_diagnostics_output() -> Result<(), ()> {
+    let cfg = ErgConfig::with_main_path(PathBuf::from("tests/lint.er"));
+    let mut builder = HIRBuilder::new(cfg.copy());
+    let hir = builder.build(cfg.input.read(), "exec").map_err(|_| ())?;
+    let warns = Linter::new(cfg).lint(&hir);
+    let json = JsonValue::parse(&warns.first().unwrap().to_json().to_string()).map_err(|_| ())?;
+    assert_eq!(
+        json.get("kind").and_then(|k| k.as_str()),
+        Some("NameWarning")
+    );
+    assert_eq!(
+        json.path(&["loc", "ln_begin"]).and_then(|l| l.as_u64()),
+        Some(2)
+    );
+    let sarif = JsonValue::parse(&warns.to_sarif().to_string()).map_err(|_| ())?;
+    let results = sarif
+        .get("runs")
+        .and_then(|runs| runs.as_array())
+        .and_then(|runs| runs[0].get("results"))
+        .and_then(|results| results.as_array())
+        .unwrap();
+    assert_eq!(results.len(), warns.len());
+    Ok(())
+}
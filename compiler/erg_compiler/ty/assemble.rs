@@ -0,0 +1,342 @@
+//! converts the bytecode generated by `CodeGenerator` into the instruction format of each Python version.
+//!
+//! `CodeGenerator`が生成したバイトコードを各Pythonバージョンの命令形式に変換する
+//!
+//! The input is in the format of Python 3.10 (see `Opcode`), with a few exceptions:
+//! the opcodes which exist only in other versions (e.g. `PUSH_NULL`, `WITH_CLEANUP_START`) may appear,
+//! and the caller is responsible for their validity.
+use erg_common::opcode::Opcode::{self, *};
+use erg_common::opcode311;
+use erg_common::python_util::PythonVersion;
+
+use super::codeobj::CodeObj;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum JumpKind {
+    /// the argument is the offset of the target
+    /// (in 3.11+, converted into a relative jump forward or backward)
+    Absolute,
+    /// the argument is the distance from the next instruction to the target
+    Forward,
+}
+
+#[derive(Debug, Clone, Copy)]
+struct Instr {
+    op: Opcode,
+    arg: u32,
+    /// (kind, index of the target instruction)
+    jump: Option<(JumpKind, usize)>,
+}
+
+impl Instr {
+    const fn new(op: Opcode, arg: u32) -> Self {
+        Self {
+            op,
+            arg,
+            jump: None,
+        }
+    }
+}
+
+/// The result of `assemble`
+#[derive(Debug, Clone)]
+pub struct Assembled {
+    pub code: Vec<u8>,
+    /// `offsets[i]` is the offset of the instruction which was at `2 * i` before assembling
+    offsets: Vec<usize>,
+}
+
+impl Assembled {
+    /// Maps an offset of the input bytecode to the offset of the assembled one
+    pub fn map_offset(&self, offset: usize) -> usize {
+        self.offsets
+            .get(offset / 2)
+            .copied()
+            .unwrap_or(self.code.len())
+    }
+}
+
+/// Instruction offsets in the input bytecode (with EXTENDED_ARG merged): `(offset, op, arg, next offset)`
+pub(crate) fn decode(code: &[u8]) -> Vec<(usize, Opcode, u32, usize)> {
+    let mut instrs = vec![];
+    let (mut start, mut ext) = (0, 0u32);
+    for (i, pair) in code.chunks(2).enumerate() {
+        let op = Opcode::from(pair[0]);
+        let arg = (ext << 8) | pair.get(1).copied().unwrap_or(0) as u32;
+        if op == EXTENDED_ARG {
+            ext = arg;
+            continue;
+        }
+        instrs.push((start, op, arg, i * 2 + 2));
+        start = i * 2 + 2;
+        ext = 0;
+    }
+    instrs
+}
+
+/// The offset of the jump target of an input instruction, `next` is the offset of the next instruction
+pub(crate) fn jump_target(op: Opcode, arg: u32, next: usize) -> Option<usize> {
+    match op {
+        JUMP_ABSOLUTE | POP_JUMP_IF_FALSE | POP_JUMP_IF_TRUE | JUMP_IF_FALSE_OR_POP
+        | JUMP_IF_TRUE_OR_POP => Some(arg as usize * 2),
+        JUMP_FORWARD | FOR_ITER | SETUP_WITH => Some(next + arg as usize * 2),
+        _ => None,
+    }
+}
+
+/// The localsplus index of the cell/free variable `idx` (the index of `co_cellvars + co_freevars`)
+fn deref_index(code: &CodeObj, idx: usize) -> usize {
+    let extra_cells = code
+        .cellvars
+        .iter()
+        .filter(|cell| !code.varnames.contains(cell))
+        .collect::<Vec<_>>();
+    if let Some(cell) = code.cellvars.get(idx) {
+        code.varnames
+            .iter()
+            .position(|v| v == cell)
+            .unwrap_or_else(|| {
+                code.varnames.len() + extra_cells.iter().position(|c| c == &cell).unwrap()
+            })
+    } else {
+        code.varnames.len() + extra_cells.len() + (idx - code.cellvars.len())
+    }
+}
+
+/// Converts an instruction of the input into that of `ver`, `target` is the input offset of the jump target
+fn lower(code: &CodeObj, ver: PythonVersion, op: Opcode, arg: u32, target: usize) -> Vec<Instr> {
+    let jump = |op, kind| Instr {
+        op,
+        arg: 0,
+        jump: Some((kind, target)),
+    };
+    if ver < PythonVersion::V3_11 {
+        return match op {
+            JUMP_ABSOLUTE | POP_JUMP_IF_FALSE | POP_JUMP_IF_TRUE | JUMP_IF_FALSE_OR_POP
+            | JUMP_IF_TRUE_OR_POP => vec![jump(op, JumpKind::Absolute)],
+            JUMP_FORWARD | FOR_ITER | SETUP_WITH => vec![jump(op, JumpKind::Forward)],
+            // a local variable captured by an inner function lives in the cell
+            LOAD_FAST | STORE_FAST => {
+                let cell = code
+                    .varnames
+                    .get(arg as usize)
+                    .and_then(|name| code.cellvars.iter().position(|cell| cell == name));
+                match (op, cell) {
+                    (LOAD_FAST, Some(cell)) => vec![Instr::new(LOAD_DEREF, cell as u32)],
+                    (_, Some(cell)) => vec![Instr::new(STORE_DEREF, cell as u32)],
+                    _ => vec![Instr::new(op, arg)],
+                }
+            }
+            _ => vec![Instr::new(op, arg)],
+        };
+    }
+    match op {
+        DUP_TOP => vec![Instr::new(COPY, 1)],
+        DUP_TOP2 => vec![Instr::new(COPY, 2), Instr::new(COPY, 2)],
+        ROT_TWO => vec![Instr::new(SWAP, 2)],
+        ROT_THREE => vec![Instr::new(SWAP, 3), Instr::new(SWAP, 2)],
+        ROT_FOUR => vec![
+            Instr::new(SWAP, 4),
+            Instr::new(SWAP, 3),
+            Instr::new(SWAP, 2),
+        ],
+        CALL_FUNCTION | CALL_METHOD | CALL_FUNCTION_KW => {
+            vec![Instr::new(PRECALL, arg), Instr::new(CALL, arg)]
+        }
+        // the direction is resolved in `assemble`
+        JUMP_ABSOLUTE => vec![jump(JUMP_FORWARD, JumpKind::Absolute)],
+        POP_JUMP_IF_FALSE => vec![jump(POP_JUMP_FORWARD_IF_FALSE, JumpKind::Absolute)],
+        POP_JUMP_IF_TRUE => vec![jump(POP_JUMP_FORWARD_IF_TRUE, JumpKind::Absolute)],
+        JUMP_IF_FALSE_OR_POP | JUMP_IF_TRUE_OR_POP | JUMP_FORWARD | FOR_ITER => {
+            vec![jump(op, JumpKind::Forward)]
+        }
+        // there are no blocks in 3.11 (exceptions are handled with the exception table)
+        POP_BLOCK => vec![],
+        LOAD_GLOBAL => vec![Instr::new(LOAD_GLOBAL, arg << 1)],
+        LOAD_CLOSURE | LOAD_DEREF | STORE_DEREF => {
+            vec![Instr::new(op, deref_index(code, arg as usize) as u32)]
+        }
+        // cell variables are stored in the same slots as the local variables
+        LOAD_FAST | STORE_FAST
+            if code
+                .varnames
+                .get(arg as usize)
+                .is_some_and(|name| code.cellvars.contains(name)) =>
+        {
+            let op = if op == LOAD_FAST {
+                LOAD_DEREF
+            } else {
+                STORE_DEREF
+            };
+            vec![Instr::new(op, arg)]
+        }
+        other => {
+            if let Some(nb) = opcode311::binary_op_arg(other) {
+                vec![Instr::new(BINARY_OP, nb as u32)]
+            } else {
+                vec![Instr::new(other, arg)]
+            }
+        }
+    }
+}
+
+/// 3.11: `MAKE_CELL`s, `COPY_FREE_VARS` and `RESUME`
+fn prologue(code: &CodeObj) -> Vec<Instr> {
+    let mut instrs = vec![];
+    let mut cells = (0..code.cellvars.len())
+        .map(|i| deref_index(code, i))
+        .collect::<Vec<_>>();
+    cells.sort_unstable();
+    for cell in cells {
+        instrs.push(Instr::new(MAKE_CELL, cell as u32));
+    }
+    if !code.freevars.is_empty() {
+        instrs.push(Instr::new(COPY_FREE_VARS, code.freevars.len() as u32));
+    }
+    instrs.push(Instr::new(RESUME, 0));
+    instrs
+}
+
+pub(crate) const fn ext_len(arg: u32) -> usize {
+    match arg {
+        0..=0xFF => 0,
+        0x100..=0xFFFF => 1,
+        0x1_0000..=0xFF_FFFF => 2,
+        _ => 3,
+    }
+}
+
+/// The size of the instruction in code units (2 bytes)
+fn instr_units(instr: &Instr, ver: PythonVersion) -> usize {
+    let caches = if ver >= PythonVersion::V3_11 {
+        opcode311::cache_entries(instr.op)
+    } else {
+        0
+    };
+    ext_len(instr.arg) + 1 + caches
+}
+
+/// Converts the bytecode of `code` into that of `ver`.
+/// Jump arguments are recalculated, and `EXTENDED_ARG`s and `CACHE`s are inserted as needed.
+pub fn assemble(code: &CodeObj, ver: PythonVersion) -> Assembled {
+    let decoded = decode(&code.code);
+    let mut instrs = if ver >= PythonVersion::V3_11 {
+        prologue(code)
+    } else {
+        vec![]
+    };
+    // `first[i]`: the index in `instrs` of the first instruction lowered from the input at `2 * i`
+    let mut first = vec![usize::MAX; code.code.len() / 2 + 1];
+    for &(start, op, arg, next) in decoded.iter() {
+        first[start / 2] = instrs.len();
+        let target = jump_target(op, arg, next).unwrap_or(0);
+        instrs.append(&mut lower(code, ver, op, arg, target));
+    }
+    // EXTENDED_ARGs in the input and instructions lowered to nothing belong to the next instruction
+    let end = code.code.len() / 2;
+    first[end] = instrs.len();
+    for i in (0..end).rev() {
+        if first[i] == usize::MAX {
+            first[i] = first[i + 1];
+        }
+    }
+    for instr in instrs.iter_mut() {
+        if let Some((_, target)) = instr.jump.as_mut() {
+            *target = first[(*target / 2).min(end)];
+        }
+    }
+    // The sizes of jumps depend on the offsets, which depend on the sizes. Iterate until they converge
+    let mut units = instrs
+        .iter()
+        .map(|instr| instr_units(instr, ver))
+        .collect::<Vec<_>>();
+    let mut offsets;
+    loop {
+        offsets = Vec::with_capacity(instrs.len() + 1);
+        let mut offset = 0;
+        for u in units.iter() {
+            offsets.push(offset);
+            offset += u;
+        }
+        offsets.push(offset);
+        let mut changed = false;
+        for (i, instr) in instrs.iter_mut().enumerate() {
+            if let Some((kind, target)) = instr.jump {
+                let next = offsets[i + 1];
+                let target_offset = offsets[target];
+                let (op, dist) = match kind {
+                    JumpKind::Absolute if ver >= PythonVersion::V3_11 => {
+                        // JUMP_FORWARD -> JUMP_BACKWARD, etc.
+                        if target_offset < next {
+                            let op = match instr.op {
+                                POP_JUMP_FORWARD_IF_FALSE | POP_JUMP_BACKWARD_IF_FALSE => {
+                                    POP_JUMP_BACKWARD_IF_FALSE
+                                }
+                                POP_JUMP_FORWARD_IF_TRUE | POP_JUMP_BACKWARD_IF_TRUE => {
+                                    POP_JUMP_BACKWARD_IF_TRUE
+                                }
+                                _ => JUMP_BACKWARD,
+                            };
+                            (op, next - target_offset)
+                        } else {
+                            let op = match instr.op {
+                                POP_JUMP_FORWARD_IF_FALSE | POP_JUMP_BACKWARD_IF_FALSE => {
+                                    POP_JUMP_FORWARD_IF_FALSE
+                                }
+                                POP_JUMP_FORWARD_IF_TRUE | POP_JUMP_BACKWARD_IF_TRUE => {
+                                    POP_JUMP_FORWARD_IF_TRUE
+                                }
+                                _ => JUMP_FORWARD,
+                            };
+                            (op, target_offset - next)
+                        }
+                    }
+                    JumpKind::Absolute => (instr.op, target_offset),
+                    JumpKind::Forward => (instr.op, target_offset.saturating_sub(next)),
+                };
+                // Python 3.7 ~ 3.9 count the arguments in bytes
+                let arg = if ver < PythonVersion::V3_10 {
+                    dist * 2
+                } else {
+                    dist
+                } as u32;
+                instr.op = op;
+                instr.arg = arg;
+                let new_units = instr_units(instr, ver);
+                if new_units != units[i] {
+                    units[i] = new_units;
+                    changed = true;
+                }
+            }
+        }
+        if !changed {
+            break;
+        }
+    }
+    let mut bytes = Vec::with_capacity(offsets[instrs.len()] * 2);
+    for instr in instrs.iter() {
+        let byte = instr.op.encode(ver).unwrap_or(instr.op as u8);
+        let arg = if instr.op.take_arg_in(ver) {
+            instr.arg
+        } else {
+            0
+        };
+        for i in (1..=ext_len(arg)).rev() {
+            bytes.push(EXTENDED_ARG.encode(ver).unwrap());
+            bytes.push((arg >> (8 * i)) as u8);
+        }
+        bytes.push(byte);
+        bytes.push(arg as u8);
+        if ver >= PythonVersion::V3_11 {
+            for _ in 0..opcode311::cache_entries(instr.op) {
+                bytes.push(CACHE.encode(ver).unwrap());
+                bytes.push(0);
+            }
+        }
+    }
+    let offsets = first.iter().map(|&i| offsets[i] * 2).collect();
+    Assembled {
+        code: bytes,
+        offsets,
+    }
+}
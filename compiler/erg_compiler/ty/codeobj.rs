@@ -4,17 +4,20 @@ use std::fs::File;
 use std::io::{BufReader, Read, Write as _};
 use std::path::Path;
 
+use erg_common::error::Location;
 use erg_common::impl_display_from_debug;
-use erg_common::opcode::Opcode;
-use erg_common::python_util::detect_magic_number;
+use erg_common::json::JsonValue;
+use erg_common::python_util::{detect_magic_number, PythonVersion};
 use erg_common::serialize::*;
 use erg_common::Str;
 
-use super::deserialize::{DeserializeResult, Deserializer};
+use super::assemble::{assemble, Assembled};
+use super::deserialize::{ByteReader, DeserializeError, DeserializeResult, Deserializer};
+use super::disassemble::disassemble;
 use super::value::ValueObj;
-use super::{HasType, Type, TypePair};
+use super::{HasType, Type};
 
-pub fn consts_into_bytes(consts: Vec<ValueObj>) -> Vec<u8> {
+pub fn consts_into_bytes(consts: Vec<ValueObj>, python_ver: u32) -> Vec<u8> {
     let mut tuple = vec![];
     if consts.len() > u8::MAX as usize {
         tuple.push(DataTypePrefix::Tuple as u8);
@@ -24,7 +27,7 @@ pub fn consts_into_bytes(consts: Vec<ValueObj>) -> Vec<u8> {
         tuple.push(consts.len() as u8);
     }
     for obj in consts {
-        tuple.append(&mut obj.into_bytes());
+        tuple.append(&mut obj.into_bytes(python_ver));
     }
     tuple
 }
@@ -94,8 +97,442 @@ impl CodeObjFlags {
     }
 }
 
+/// Appends a `(sdelta, ldelta)` pair to a classic `lnotab`, splitting deltas out of range
+fn push_lnotab_entry(lnotab: &mut Vec<u8>, mut sdelta: usize, mut ldelta: i64) {
+    if sdelta == 0 && ldelta == 0 {
+        return;
+    }
+    while sdelta > u8::MAX as usize {
+        lnotab.push(u8::MAX);
+        lnotab.push(0);
+        sdelta -= u8::MAX as usize;
+    }
+    loop {
+        let chunk = ldelta.clamp(i8::MIN as i64, i8::MAX as i64);
+        lnotab.push(sdelta as u8);
+        lnotab.push(chunk as i8 as u8);
+        sdelta = 0;
+        ldelta -= chunk;
+        if ldelta == 0 {
+            break;
+        }
+    }
+}
+
+/// Decodes a classic `lnotab` into `(bytecode offset, line number)` pairs
+pub(crate) fn lnotab_to_lines(lnotab: &[u8], firstlineno: u32) -> Vec<(usize, i64)> {
+    let (mut addr, mut line) = (0, firstlineno as i64);
+    let mut lines = vec![(addr, line)];
+    for pair in lnotab.chunks(2) {
+        addr += pair[0] as usize;
+        line += pair.get(1).copied().unwrap_or(0) as i8 as i64;
+        match lines.last_mut() {
+            Some(last) if last.0 == addr => last.1 = line,
+            _ => lines.push((addr, line)),
+        }
+    }
+    lines
+}
+
+pub(crate) fn lines_to_lnotab(lines: &[(usize, i64)], firstlineno: u32) -> Vec<u8> {
+    let mut lnotab = vec![];
+    let (mut prev_addr, mut prev_line) = (0, firstlineno as i64);
+    for &(addr, line) in lines.iter() {
+        if line != prev_line {
+            push_lnotab_entry(&mut lnotab, addr - prev_addr, line - prev_line);
+            (prev_addr, prev_line) = (addr, line);
+        }
+    }
+    lnotab
+}
+
+/// Encodes `(bytecode offset, line number)` pairs into `co_linetable` (Python 3.10).
+/// See Objects/lnotab_notes.txt in CPython 3.10 for details
+fn lines_to_linetable(lines: &[(usize, i64)], firstlineno: u32, code_len: usize) -> Vec<u8> {
+    let mut table = vec![];
+    let mut prev_line = firstlineno as i64;
+    for (i, &(start, line)) in lines.iter().enumerate() {
+        let end = lines
+            .get(i + 1)
+            .map(|(addr, _)| *addr)
+            .unwrap_or(code_len)
+            .max(start);
+        let mut sdelta = end - start;
+        let mut ldelta = line - prev_line;
+        while ldelta.abs() > i8::MAX as i64 {
+            let chunk = ldelta.signum() * i8::MAX as i64;
+            table.push(0);
+            table.push(chunk as i8 as u8);
+            ldelta -= chunk;
+        }
+        loop {
+            let chunk = sdelta.min(254);
+            table.push(chunk as u8);
+            table.push(ldelta as i8 as u8);
+            sdelta -= chunk;
+            ldelta = 0;
+            if sdelta == 0 {
+                break;
+            }
+        }
+        prev_line = line;
+    }
+    table
+}
+
+/// The inverse of `lines_to_linetable` (ranges without line numbers belong to the previous line)
+fn linetable_to_lines(table: &[u8], firstlineno: u32) -> Vec<(usize, i64)> {
+    let (mut addr, mut line) = (0, firstlineno as i64);
+    let mut lines: Vec<(usize, i64)> = vec![];
+    for pair in table.chunks(2) {
+        let ldelta = pair.get(1).copied().unwrap_or(0) as i8;
+        if ldelta != i8::MIN {
+            line += ldelta as i64;
+        }
+        if pair[0] != 0 {
+            if lines.last().map(|(_, l)| *l != line).unwrap_or(true) {
+                lines.push((addr, line));
+            }
+            addr += pair[0] as usize;
+        }
+    }
+    lines
+}
+
+/// Variable-length integers of the location/exception tables (6-bit chunks, `0x40` means "continued")
+fn push_varint(table: &mut Vec<u8>, mut val: u64) {
+    while val >= 0x40 {
+        table.push(0x40 | (val & 0x3F) as u8);
+        val >>= 6;
+    }
+    table.push(val as u8);
+}
+
+fn push_svarint(table: &mut Vec<u8>, val: i64) {
+    let val = if val < 0 {
+        (val.unsigned_abs() << 1) | 1
+    } else {
+        (val as u64) << 1
+    };
+    push_varint(table, val);
+}
+
+fn read_varint<I: Iterator<Item = u8>>(iter: &mut I) -> u64 {
+    let (mut val, mut shift) = (0, 0);
+    for byte in iter {
+        val |= ((byte & 0x3F) as u64) << shift;
+        shift += 6;
+        if byte & 0x40 == 0 {
+            break;
+        }
+    }
+    val
+}
+
+fn read_svarint<I: Iterator<Item = u8>>(iter: &mut I) -> i64 {
+    let val = read_varint(iter);
+    if val & 1 != 0 {
+        -((val >> 1) as i64)
+    } else {
+        (val >> 1) as i64
+    }
+}
+
+/// Encodes `(bytecode offset, line number)` pairs into `co_linetable` (Python 3.11, the location table).
+/// Columns are not recorded. See Objects/locations.md in CPython 3.11 for details
+fn lines_to_location_table(lines: &[(usize, i64)], firstlineno: u32, code_len: usize) -> Vec<u8> {
+    // PY_CODE_LOCATION_INFO_NO_COLUMNS
+    const NO_COLUMNS: u8 = 13;
+    let mut table = vec![];
+    let mut prev_line = firstlineno as i64;
+    for (i, &(start, line)) in lines.iter().enumerate() {
+        let end = lines
+            .get(i + 1)
+            .map(|(addr, _)| *addr)
+            .unwrap_or(code_len)
+            .max(start);
+        let mut units = (end - start) / 2;
+        if units == 0 {
+            continue;
+        }
+        let mut ldelta = line - prev_line;
+        while units > 0 {
+            let len = units.min(8);
+            table.push(0x80 | (NO_COLUMNS << 3) | (len - 1) as u8);
+            push_svarint(&mut table, ldelta);
+            units -= len;
+            ldelta = 0;
+        }
+        prev_line = line;
+    }
+    table
+}
+
+/// The inverse of `lines_to_location_table` (columns are discarded)
+fn location_table_to_lines(table: &[u8], firstlineno: u32) -> Vec<(usize, i64)> {
+    let (mut addr, mut line) = (0, firstlineno as i64);
+    let mut lines: Vec<(usize, i64)> = vec![];
+    let mut iter = table.iter().copied();
+    while let Some(head) = iter.next() {
+        let code = (head >> 3) & 0xF;
+        let len = (head & 7) as usize + 1;
+        let has_line = match code {
+            // PY_CODE_LOCATION_INFO_NONE
+            15 => false,
+            // PY_CODE_LOCATION_INFO_LONG: line delta, end line delta, column + 1, end column + 1
+            14 => {
+                line += read_svarint(&mut iter);
+                for _ in 0..3 {
+                    read_varint(&mut iter);
+                }
+                true
+            }
+            13 => {
+                line += read_svarint(&mut iter);
+                true
+            }
+            // PY_CODE_LOCATION_INFO_ONE_LINE0..2: the line delta is `code - 10`, followed by 2 columns
+            10..=12 => {
+                line += code as i64 - 10;
+                iter.next();
+                iter.next();
+                true
+            }
+            // short forms (on the same line), followed by a column byte
+            _ => {
+                iter.next();
+                true
+            }
+        };
+        if has_line && lines.last().map(|(_, l)| *l != line).unwrap_or(true) {
+            lines.push((addr, line));
+        }
+        addr += len * 2;
+    }
+    lines
+}
+
+/// Converts `co_lnotab`/`co_linetable` of `python_ver` into a classic `lnotab`
+pub(crate) fn lnotab_from_line_table(table: Vec<u8>, firstlineno: u32, python_ver: u32) -> Vec<u8> {
+    if PythonVersion::from_checked_magic_num(python_ver) >= PythonVersion::V3_11 {
+        lines_to_lnotab(&location_table_to_lines(&table, firstlineno), firstlineno)
+    } else if python_ver >= 3430 {
+        lines_to_lnotab(&linetable_to_lines(&table, firstlineno), firstlineno)
+    } else {
+        table
+    }
+}
+
+/// An entry of the exception table (Python 3.11+):
+/// exceptions raised in `[start, end)` are handled at `target`
+/// after the stack is popped to `depth` (and the offset of the raising instruction is pushed if `lasti`)
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct ExceptionTableEntry {
+    pub start: usize,
+    pub end: usize,
+    pub target: usize,
+    pub depth: u32,
+    pub lasti: bool,
+}
+
+impl ExceptionTableEntry {
+    pub const fn new(start: usize, end: usize, target: usize, depth: u32, lasti: bool) -> Self {
+        Self {
+            start,
+            end,
+            target,
+            depth,
+            lasti,
+        }
+    }
+
+    pub fn to_json(&self) -> JsonValue {
+        JsonValue::object(vec![
+            ("start", self.start.into()),
+            ("end", self.end.into()),
+            ("target", self.target.into()),
+            ("depth", self.depth.into()),
+            ("lasti", self.lasti.into()),
+        ])
+    }
+}
+
+/// Entries of the exception table must be sorted and must not overlap.
+/// `entries` may be nested (e.g. `with!` in `with!`), in which case the innermost one takes precedence.
+fn flatten_exception_table(
+    entries: &[ExceptionTableEntry],
+    code_len: usize,
+) -> Vec<ExceptionTableEntry> {
+    let mut flattened: Vec<ExceptionTableEntry> = vec![];
+    for unit in (0..code_len).step_by(2) {
+        let entry = if let Some(entry) = entries
+            .iter()
+            .filter(|entry| entry.start <= unit && unit < entry.end)
+            .min_by_key(|entry| entry.end - entry.start)
+        {
+            entry
+        } else {
+            continue;
+        };
+        match flattened.last_mut() {
+            Some(last)
+                if last.end == unit
+                    && (last.target, last.depth, last.lasti)
+                        == (entry.target, entry.depth, entry.lasti) =>
+            {
+                last.end = unit + 2;
+            }
+            _ => flattened.push(ExceptionTableEntry::new(
+                unit,
+                unit + 2,
+                entry.target,
+                entry.depth,
+                entry.lasti,
+            )),
+        }
+    }
+    flattened
+}
+
+/// Exception table items are big-endian, unlike the location table
+fn push_exception_item(table: &mut Vec<u8>, val: usize, msb: u8) {
+    let mut shift = 0;
+    while shift + 6 < usize::BITS && val >> (shift + 6) != 0 {
+        shift += 6;
+    }
+    let mut msb = msb;
+    while shift > 0 {
+        table.push(0x40 | msb | ((val >> shift) & 0x3F) as u8);
+        msb = 0;
+        shift -= 6;
+    }
+    table.push(msb | (val & 0x3F) as u8);
+}
+
+fn read_exception_item<I: Iterator<Item = u8>>(iter: &mut I) -> Option<usize> {
+    let mut byte = iter.next()?;
+    let mut val = (byte & 0x3F) as usize;
+    while byte & 0x40 != 0 {
+        byte = iter.next()?;
+        val = (val << 6) | (byte & 0x3F) as usize;
+    }
+    Some(val)
+}
+
+/// Encodes into `co_exceptiontable` (Python 3.11, offsets are in code units).
+/// See Objects/exception_handling_notes.txt in CPython 3.11 for details
+fn exception_table_into_bytes(entries: &[ExceptionTableEntry]) -> Vec<u8> {
+    let mut table = vec![];
+    for entry in entries.iter() {
+        push_exception_item(&mut table, entry.start / 2, 0x80);
+        push_exception_item(&mut table, (entry.end - entry.start) / 2, 0);
+        push_exception_item(&mut table, entry.target / 2, 0);
+        push_exception_item(
+            &mut table,
+            ((entry.depth as usize) << 1) | entry.lasti as usize,
+            0,
+        );
+    }
+    table
+}
+
+pub(crate) fn exception_table_from_bytes(table: &[u8]) -> Vec<ExceptionTableEntry> {
+    let mut entries = vec![];
+    let mut iter = table.iter().copied();
+    while let (Some(start), Some(size), Some(target), Some(depth_lasti)) = (
+        read_exception_item(&mut iter),
+        read_exception_item(&mut iter),
+        read_exception_item(&mut iter),
+        read_exception_item(&mut iter),
+    ) {
+        entries.push(ExceptionTableEntry::new(
+            start * 2,
+            (start + size) * 2,
+            target * 2,
+            (depth_lasti >> 1) as u32,
+            depth_lasti & 1 != 0,
+        ));
+    }
+    entries
+}
+
+/// `co_localsplusnames` and `co_localspluskinds` (Python 3.11+)
+pub(crate) fn localsplus(
+    varnames: &[Str],
+    cellvars: &[Str],
+    freevars: &[Str],
+) -> (Vec<Str>, Vec<u8>) {
+    const LOCAL: u8 = 0x20;
+    const CELL: u8 = 0x40;
+    const FREE: u8 = 0x80;
+    let mut names = varnames.to_vec();
+    let mut kinds = varnames
+        .iter()
+        .map(|name| {
+            if cellvars.contains(name) {
+                LOCAL | CELL
+            } else {
+                LOCAL
+            }
+        })
+        .collect::<Vec<_>>();
+    for cell in cellvars.iter().filter(|cell| !varnames.contains(cell)) {
+        names.push(cell.clone());
+        kinds.push(CELL);
+    }
+    for free in freevars.iter() {
+        names.push(free.clone());
+        kinds.push(FREE);
+    }
+    (names, kinds)
+}
+
+/// The inverse of `localsplus`: `(varnames, cellvars, freevars)`
+pub(crate) fn split_localsplus(names: Vec<Str>, kinds: &[u8]) -> (Vec<Str>, Vec<Str>, Vec<Str>) {
+    let (mut varnames, mut cellvars, mut freevars) = (vec![], vec![], vec![]);
+    for (name, kind) in names.into_iter().zip(kinds.iter()) {
+        if kind & 0x80 != 0 {
+            freevars.push(name);
+            continue;
+        }
+        if kind & 0x40 != 0 {
+            cellvars.push(name.clone());
+        }
+        if kind & 0x20 != 0 {
+            varnames.push(name);
+        }
+    }
+    (varnames, cellvars, freevars)
+}
+
+/// A range of bytecode `[start, end)` generated from the Erg expression at `loc`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct CodeSpan {
+    pub start: usize,
+    pub end: usize,
+    pub loc: Location,
+}
+
+impl CodeSpan {
+    pub const fn new(start: usize, end: usize, loc: Location) -> Self {
+        Self { start, end, loc }
+    }
+
+    pub fn to_json(&self) -> JsonValue {
+        JsonValue::object(vec![
+            ("start", self.start.into()),
+            ("end", self.end.into()),
+            ("loc", self.loc.to_json()),
+        ])
+    }
+}
+
 /// Implementation of `PyCodeObject`, see Include/cpython/code.h in CPython for details.
 ///
+/// `code` generated by `CodeGenerator` is in the format of Python 3.10 (see `Opcode`),
+/// and is converted into that of the target version when serialized (`into_bytes`).
+/// `code` read by `from_bytes` is left in the format of the version of the file.
+///
 /// 各属性をErg側のObjに変換すると遅くなりそうなので、アクサスされたときのみ変換して提供する
 #[derive(Clone, PartialEq, Eq, Hash)]
 pub struct CodeObj {
@@ -113,12 +550,19 @@ pub struct CodeObj {
     pub cellvars: Vec<Str>,    // names used in the inner function (closure)
     pub filename: Str,
     pub name: Str,
+    /// serialized only in Python 3.11+ (in older versions, `MAKE_FUNCTION` takes a qualified name)
+    pub qualname: Str,
     pub firstlineno: u32,
     // lnotab (line number table): see Object/lnotab_notes.txt in CPython for details
     // e.g. +12bytes, +3line -> [.., 0x1C, 0x03, ..]
     // ([sdelta, ldelta, sdelta, ldelta, ..])
     // if delta > 255 -> [255, 0, 255-delta, ...]
+    // (converted to/from `co_linetable` for Python 3.10+)
     pub lnotab: Vec<u8>,
+    /// Python 3.11+
+    pub exception_table: Vec<ExceptionTableEntry>,
+    /// Erg source locations of the bytecode (not serialized, see `source_map`)
+    pub spans: Vec<CodeSpan>,
 }
 
 impl HasType for CodeObj {
@@ -165,8 +609,11 @@ impl Default for CodeObj {
             cellvars: Vec::new(),
             filename: "<dummy>".into(),
             name: "<dummy>".into(),
+            qualname: "<dummy>".into(),
             firstlineno: 1,
             lnotab: Vec::new(),
+            exception_table: Vec::new(),
+            spans: Vec::new(),
         }
     }
 }
@@ -178,6 +625,7 @@ impl CodeObj {
         name: T,
         firstlineno: u32,
     ) -> Self {
+        let name = name.into();
         Self {
             argcount: params.len() as u32,
             posonlyargcount: 0,
@@ -192,91 +640,169 @@ impl CodeObj {
             freevars: Vec::new(),
             cellvars: Vec::new(),
             filename: filename.into(),
-            name: name.into(),
+            qualname: name.clone(),
+            name,
             firstlineno,
             lnotab: Vec::with_capacity(4),
+            exception_table: Vec::new(),
+            spans: Vec::new(),
         }
     }
 
     pub fn from_pyc<P: AsRef<Path>>(path: P) -> DeserializeResult<Self> {
+        Self::from_pyc_with_ver(path).map(|(code, _)| code)
+    }
+
+    /// Returns the code object and the magic number of the .pyc file
+    pub fn from_pyc_with_ver<P: AsRef<Path>>(path: P) -> DeserializeResult<(Self, u32)> {
         let mut f = BufReader::new(File::open(path)?);
-        let v = &mut Vec::with_capacity(16);
-        f.read_to_end(v)?;
-        let python_ver = get_magic_num_from_bytes(&Deserializer::consume::<4>(v));
-        let _padding = Deserializer::deserialize_u32(v);
-        let _timestamp = Deserializer::deserialize_u32(v);
-        let _padding = Deserializer::deserialize_u32(v);
-        let code = Self::from_bytes(v, python_ver)?;
-        Ok(code)
-    }
-
-    pub fn from_bytes(v: &mut Vec<u8>, python_ver: u32) -> DeserializeResult<Self> {
-        let mut des = Deserializer::new();
-        let argcount = Deserializer::deserialize_u32(v);
-        let posonlyargcount = if python_ver >= 3413 {
-            Deserializer::deserialize_u32(v)
-        } else {
-            0
-        };
-        let kwonlyargcount = Deserializer::deserialize_u32(v);
-        let nlocals = Deserializer::deserialize_u32(v);
-        let stacksize = Deserializer::deserialize_u32(v);
-        let flags = Deserializer::deserialize_u32(v);
-        let code = des.deserialize_bytes(v)?;
-        let consts = des.deserialize_const_vec(v, python_ver)?;
-        let names = des.deserialize_str_vec(v, python_ver)?;
-        let varnames = des.deserialize_str_vec(v, python_ver)?;
-        let freevars = des.deserialize_str_vec(v, python_ver)?;
-        let cellvars = des.deserialize_str_vec(v, python_ver)?;
-        let filename = des.deserialize_str(v, python_ver)?;
-        let name = des.deserialize_str(v, python_ver)?;
-        let firstlineno = Deserializer::deserialize_u32(v);
-        let lnotab = des.deserialize_bytes(v)?;
-        Ok(CodeObj {
-            argcount,
-            posonlyargcount,
-            kwonlyargcount,
-            nlocals,
-            stacksize,
-            flags,
-            code,
-            consts,
-            names,
-            varnames,
-            freevars,
-            cellvars,
-            filename,
-            name,
-            firstlineno,
-            lnotab,
-        })
+        let mut bytes = Vec::with_capacity(16);
+        f.read_to_end(&mut bytes)?;
+        let mut r = ByteReader::new(&bytes);
+        let python_ver = get_magic_num_from_bytes(&r.take_array::<4>()?);
+        let _padding = r.read_u32()?;
+        let _timestamp = r.read_u32()?;
+        let _padding = r.read_u32()?;
+        Ok((Self::from_bytes(&mut r, python_ver)?, python_ver))
     }
 
+    /// `r` starts with the type prefix of the code object.
+    /// NOTE: The bytecode is left in the format of `python_ver`
+    pub fn from_bytes(r: &mut ByteReader, python_ver: u32) -> DeserializeResult<Self> {
+        match Deserializer::new().deserialize_const(r, python_ver)? {
+            ValueObj::Code(code) => Ok(*code),
+            other => Err(DeserializeError::type_error(&Type::Code, &other.class())),
+        }
+    }
+
+    /// Serializes the code object in the format of `python_ver`.
+    /// The bytecode is assembled for the version (see `assemble`)
     pub fn into_bytes(self, python_ver: u32) -> Vec<u8> {
+        let ver = PythonVersion::from_checked_magic_num(python_ver);
+        let asm = assemble(&self, ver);
+        let lnotab = self.line_table(&asm, python_ver);
         let mut bytes = vec![DataTypePrefix::Code as u8];
         bytes.append(&mut self.argcount.to_le_bytes().to_vec());
         if python_ver >= 3413 {
             bytes.append(&mut self.posonlyargcount.to_le_bytes().to_vec());
         }
         bytes.append(&mut self.kwonlyargcount.to_le_bytes().to_vec());
-        bytes.append(&mut self.nlocals.to_le_bytes().to_vec());
+        if ver < PythonVersion::V3_11 {
+            bytes.append(&mut self.nlocals.to_le_bytes().to_vec());
+        }
         bytes.append(&mut self.stacksize.to_le_bytes().to_vec());
         bytes.append(&mut self.flags.to_le_bytes().to_vec());
         // co_code is represented as PyStrObject (Not Ascii, Unicode)
-        bytes.append(&mut raw_string_into_bytes(self.code));
-        bytes.append(&mut consts_into_bytes(self.consts)); // write as PyTupleObject
+        bytes.append(&mut raw_string_into_bytes(asm.code.clone()));
+        bytes.append(&mut consts_into_bytes(self.consts, python_ver)); // write as PyTupleObject
         bytes.append(&mut strs_into_bytes(self.names));
-        bytes.append(&mut strs_into_bytes(self.varnames));
-        bytes.append(&mut strs_into_bytes(self.freevars));
-        bytes.append(&mut strs_into_bytes(self.cellvars));
+        if ver >= PythonVersion::V3_11 {
+            let (names, kinds) = localsplus(&self.varnames, &self.cellvars, &self.freevars);
+            bytes.append(&mut strs_into_bytes(names));
+            bytes.append(&mut raw_string_into_bytes(kinds));
+        } else {
+            bytes.append(&mut strs_into_bytes(self.varnames));
+            bytes.append(&mut strs_into_bytes(self.freevars));
+            bytes.append(&mut strs_into_bytes(self.cellvars));
+        }
         bytes.append(&mut str_into_bytes(self.filename, false));
         bytes.append(&mut str_into_bytes(self.name, true));
+        if ver >= PythonVersion::V3_11 {
+            bytes.append(&mut str_into_bytes(self.qualname, false));
+        }
         bytes.append(&mut self.firstlineno.to_le_bytes().to_vec());
         // lnotab is represented as PyStrObject
-        bytes.append(&mut raw_string_into_bytes(self.lnotab));
+        bytes.append(&mut raw_string_into_bytes(lnotab));
+        if ver >= PythonVersion::V3_11 {
+            let entries = self
+                .exception_table
+                .iter()
+                .map(|entry| {
+                    ExceptionTableEntry::new(
+                        asm.map_offset(entry.start),
+                        asm.map_offset(entry.end),
+                        asm.map_offset(entry.target),
+                        entry.depth,
+                        entry.lasti,
+                    )
+                })
+                .collect::<Vec<_>>();
+            let entries = flatten_exception_table(&entries, asm.code.len());
+            bytes.append(&mut raw_string_into_bytes(exception_table_into_bytes(
+                &entries,
+            )));
+        }
         bytes
     }
 
+    /// `lnotab` in the format of `python_ver`, with the offsets of the assembled bytecode
+    fn line_table(&self, asm: &Assembled, python_ver: u32) -> Vec<u8> {
+        let mut lines: Vec<(usize, i64)> = vec![];
+        for (addr, line) in lnotab_to_lines(&self.lnotab, self.firstlineno) {
+            // the prologue of 3.11 (e.g. `RESUME`) belongs to the first line
+            let addr = if addr == 0 { 0 } else { asm.map_offset(addr) };
+            match lines.last_mut() {
+                Some(last) if last.0 == addr => last.1 = line,
+                _ => lines.push((addr, line)),
+            }
+        }
+        if PythonVersion::from_checked_magic_num(python_ver) >= PythonVersion::V3_11 {
+            lines_to_location_table(&lines, self.firstlineno, asm.code.len())
+        } else if python_ver >= 3430 {
+            lines_to_linetable(&lines, self.firstlineno, asm.code.len())
+        } else {
+            lines_to_lnotab(&lines, self.firstlineno)
+        }
+    }
+
+    /// Appends a line number entry: the bytecode from `lasti + sdelta` is on `lineno + ldelta`
+    pub fn push_lnotab(&mut self, sdelta: usize, ldelta: i64) {
+        push_lnotab_entry(&mut self.lnotab, sdelta, ldelta);
+    }
+
+    /// Maps bytecode offsets (of `python_ver`) of this and nested code objects to Erg source locations:
+    /// `{"file", "codes": [{"name", "firstlineno", "spans": [{"start", "end", "loc"}]}]}`
+    pub fn source_map(&self, python_ver: u32) -> JsonValue {
+        let mut codes = vec![];
+        self.collect_spans(
+            &mut codes,
+            PythonVersion::from_checked_magic_num(python_ver),
+        );
+        JsonValue::object(vec![
+            ("file", self.filename.to_string().into()),
+            ("codes", codes.into()),
+        ])
+    }
+
+    fn collect_spans(&self, codes: &mut Vec<JsonValue>, ver: PythonVersion) {
+        let asm = assemble(self, ver);
+        codes.push(JsonValue::object(vec![
+            ("name", self.name.to_string().into()),
+            ("firstlineno", self.firstlineno.into()),
+            (
+                "spans",
+                self.spans
+                    .iter()
+                    .map(|span| {
+                        let (start, end) = (asm.map_offset(span.start), asm.map_offset(span.end));
+                        CodeSpan::new(start, end, span.loc).to_json()
+                    })
+                    .collect::<Vec<_>>()
+                    .into(),
+            ),
+        ]));
+        for cons in self.consts.iter() {
+            if let ValueObj::Code(c) = cons {
+                c.collect_spans(codes, ver);
+            }
+        }
+    }
+
+    pub fn dump_source_map<P: AsRef<Path>>(&self, path: P, python_ver: u32) -> std::io::Result<()> {
+        let mut file = File::create(path)?;
+        file.write_all(self.source_map(python_ver).to_string().as_bytes())
+    }
+
     pub fn dump_as_pyc<P: AsRef<Path>>(
         self,
         path: P,
@@ -324,7 +850,7 @@ impl CodeObj {
             tables += "Freevars:\n";
         }
         for (i, freevar) in self.freevars.iter().enumerate() {
-            writeln!(tables, "   {}: {}\n", i, freevar).unwrap();
+            writeln!(tables, "   {}: {}", i, freevar).unwrap();
         }
         tables
     }
@@ -338,167 +864,145 @@ impl CodeObj {
         writeln!(attrs, "Kw-only arguments: {}", self.kwonlyargcount).unwrap();
         writeln!(attrs, "Number of locals:  {}", self.nlocals).unwrap();
         writeln!(attrs, "Stack size:        {}", self.stacksize).unwrap();
-        let mut flagged = "".to_string();
-        for i in 0..32 {
-            if (self.flags & (1 << i)) != 0 {
-                let flag: CodeObjFlags = 2u32.pow(i).into();
-                write!(flagged, "{:?}, ", flag).unwrap();
-            }
-        }
-        flagged.pop();
-        flagged.pop();
-        writeln!(attrs, "Flags:             {}", flagged).unwrap();
+        writeln!(attrs, "Flags:             {}", self.flag_names().join(", ")).unwrap();
         attrs
     }
 
-    fn instr_info(&self) -> String {
-        let mut lnotab_iter = self.lnotab.iter();
-        let mut code_iter = self.code.iter();
-        let mut idx = 0;
-        let mut line_offset = 0;
-        let mut lineno = self.firstlineno as u8;
-        let mut sdelta = lnotab_iter.next().unwrap_or(&0);
-        let mut ldelta = lnotab_iter.next().unwrap_or(&0);
-        let mut instrs = "".to_string();
-        writeln!(instrs, "lnotab: {:?}", self.lnotab).unwrap();
-        if *sdelta != 0 {
-            writeln!(instrs, "{}:", lineno).unwrap();
-        }
-        loop {
-            if *sdelta == line_offset {
-                line_offset = 0;
-                lineno += ldelta;
-                writeln!(instrs, "{}:", lineno).unwrap();
-                sdelta = lnotab_iter.next().unwrap_or(&0);
-                ldelta = lnotab_iter.next().unwrap_or(&0);
+    fn flag_names(&self) -> Vec<String> {
+        (0..32)
+            .filter(|i| (self.flags & (1 << i)) != 0)
+            .map(|i| format!("{:?}", CodeObjFlags::from(1u32 << i)))
+            .collect()
+    }
+
+    /// Instructions in the format of `dis`: `line  >>  offset  opname  arg  (argrepr)`
+    fn instr_info(&self, ver: Option<PythonVersion>) -> String {
+        let instrs = disassemble(self, ver);
+        let lineno_width = instrs
+            .iter()
+            .filter_map(|instr| instr.line)
+            .map(|line| line.to_string().len())
+            .max()
+            .unwrap_or(0)
+            .max(3);
+        let offset_width = self.code.len().to_string().len().max(4);
+        let mut info = "".to_string();
+        for (i, instr) in instrs.iter().enumerate() {
+            if instr.starts_line && i > 0 {
+                info.push('\n');
             }
-            if let (Some(op), Some(arg)) = (code_iter.next(), code_iter.next()) {
-                let op = Opcode::from(*op);
-                let s_op = op.to_string();
-                write!(instrs, "{:>15} {:<25}", idx, s_op).unwrap();
-                match op {
-                    Opcode::COMPARE_OP => {
-                        let op = match arg {
-                            0 => "<",
-                            1 => "<=",
-                            2 => "==",
-                            3 => "!=",
-                            4 => ">",
-                            5 => ">=",
-                            _ => "?",
-                        };
-                        write!(instrs, "{} ({})", arg, op).unwrap();
-                    }
-                    Opcode::STORE_NAME
-                    | Opcode::LOAD_NAME
-                    | Opcode::STORE_GLOBAL
-                    | Opcode::LOAD_GLOBAL
-                    | Opcode::STORE_ATTR
-                    | Opcode::LOAD_ATTR
-                    | Opcode::LOAD_METHOD
-                    | Opcode::IMPORT_NAME
-                    | Opcode::IMPORT_FROM => {
-                        write!(
-                            instrs,
-                            "{} ({})",
-                            arg,
-                            self.names.get(*arg as usize).unwrap()
-                        )
-                        .unwrap();
-                    }
-                    Opcode::STORE_DEREF | Opcode::LOAD_DEREF => {
-                        write!(
-                            instrs,
-                            "{} ({})",
-                            arg,
-                            self.freevars.get(*arg as usize).unwrap()
-                        )
-                        .unwrap();
-                    }
-                    Opcode::LOAD_CLOSURE => {
-                        write!(
-                            instrs,
-                            "{} ({})",
-                            arg,
-                            self.cellvars.get(*arg as usize).unwrap()
-                        )
-                        .unwrap();
-                    }
-                    Opcode::STORE_FAST | Opcode::LOAD_FAST => {
-                        write!(
-                            instrs,
-                            "{} ({})",
-                            arg,
-                            self.varnames.get(*arg as usize).unwrap()
-                        )
-                        .unwrap();
-                    }
-                    Opcode::LOAD_CONST => {
-                        write!(
-                            instrs,
-                            "{} ({})",
-                            arg,
-                            self.consts.get(*arg as usize).unwrap()
-                        )
-                        .unwrap();
-                    }
-                    Opcode::FOR_ITER => {
-                        write!(instrs, "{} (to {})", arg, idx + arg * 2 + 2).unwrap();
-                    }
-                    Opcode::JUMP_FORWARD => {
-                        write!(instrs, "{} (to {})", arg, idx + arg * 2 + 2).unwrap();
-                    }
-                    Opcode::SETUP_WITH => {
-                        write!(instrs, "{} (to {})", arg, idx + arg * 2 + 2).unwrap();
-                    }
-                    Opcode::JUMP_ABSOLUTE => {
-                        write!(instrs, "{} (to {})", arg, arg * 2).unwrap();
-                    }
-                    Opcode::POP_JUMP_IF_FALSE | Opcode::POP_JUMP_IF_TRUE => {
-                        write!(instrs, "{} (to {})", arg, arg * 2).unwrap();
-                    }
-                    Opcode::MAKE_FUNCTION => {
-                        let flag = match arg {
-                            8 => "(closure)",
-                            // TODO:
-                            _ => "",
-                        };
-                        write!(instrs, "{} {}", arg, flag).unwrap();
-                    }
-                    // Ergでは引数で型キャストする
-                    Opcode::BINARY_ADD
-                    | Opcode::BINARY_SUBTRACT
-                    | Opcode::BINARY_MULTIPLY
-                    | Opcode::BINARY_TRUE_DIVIDE => {
-                        write!(instrs, "{} ({:?})", arg, TypePair::from(*arg)).unwrap();
-                    }
-                    other if other.take_arg() => {
-                        write!(instrs, "{}", arg).unwrap();
-                    }
-                    _ => {}
+            let line = match instr.line {
+                Some(line) if instr.starts_line => line.to_string(),
+                _ => "".to_string(),
+            };
+            let label = if instr.is_jump_target { ">>" } else { "  " };
+            let mut fields = format!(
+                "{line:>lineno_width$}     {label} {:>offset_width$} {:<20}",
+                instr.offset,
+                instr.opname(),
+            );
+            if let Some(arg) = instr.arg {
+                write!(fields, " {arg:>5}").unwrap();
+                if !instr.argrepr.is_empty() {
+                    write!(fields, " ({})", instr.argrepr).unwrap();
                 }
-                instrs.push('\n');
-                idx += 2;
-                line_offset += 2;
-            } else {
-                break;
             }
+            writeln!(info, "{}", fields.trim_end()).unwrap();
+        }
+        if !self.exception_table.is_empty() {
+            info += "ExceptionTable:\n";
         }
-        instrs
+        for entry in self.exception_table.iter() {
+            // `end` is the offset of the last instruction covered (as `dis` shows)
+            writeln!(
+                info,
+                "  {} to {} -> {} [{}]{}",
+                entry.start,
+                entry.end.saturating_sub(2),
+                entry.target,
+                entry.depth,
+                if entry.lasti { " lasti" } else { "" }
+            )
+            .unwrap();
+        }
+        info
     }
 
-    pub fn code_info(&self) -> String {
+    /// Disassembles this and nested code objects like `dis.dis`.
+    /// `ver` is the format of the bytecode (`None` for the output of `CodeGenerator`)
+    pub fn code_info(&self, ver: Option<PythonVersion>) -> String {
         let mut info = "".to_string();
         writeln!(info, "Disassembly of {:?}:", self).unwrap();
         info += &self.attrs_info();
         info += &self.tables_info();
-        info += &self.instr_info();
+        info += &self.instr_info(ver);
         info.push('\n');
         for cons in self.consts.iter() {
             if let ValueObj::Code(c) = cons {
-                info += &c.code_info();
+                info += &c.code_info(ver);
             }
         }
         info
     }
+
+    /// The JSON version of `code_info`
+    pub fn code_info_json(&self, ver: Option<PythonVersion>) -> JsonValue {
+        let strs = |strs: &[Str]| {
+            strs.iter()
+                .map(|s| s.to_string())
+                .collect::<Vec<_>>()
+                .into()
+        };
+        JsonValue::object(vec![
+            ("name", self.name.to_string().into()),
+            ("qualname", self.qualname.to_string().into()),
+            ("filename", self.filename.to_string().into()),
+            ("firstlineno", self.firstlineno.into()),
+            ("argcount", self.argcount.into()),
+            ("posonlyargcount", self.posonlyargcount.into()),
+            ("kwonlyargcount", self.kwonlyargcount.into()),
+            ("nlocals", self.nlocals.into()),
+            ("stacksize", self.stacksize.into()),
+            ("flags", self.flag_names().into()),
+            (
+                "consts",
+                self.consts
+                    .iter()
+                    .map(|obj| obj.to_string())
+                    .collect::<Vec<_>>()
+                    .into(),
+            ),
+            ("names", strs(&self.names)),
+            ("varnames", strs(&self.varnames)),
+            ("cellvars", strs(&self.cellvars)),
+            ("freevars", strs(&self.freevars)),
+            (
+                "instructions",
+                disassemble(self, ver)
+                    .iter()
+                    .map(|instr| instr.to_json())
+                    .collect::<Vec<_>>()
+                    .into(),
+            ),
+            (
+                "exception_table",
+                self.exception_table
+                    .iter()
+                    .map(|entry| entry.to_json())
+                    .collect::<Vec<_>>()
+                    .into(),
+            ),
+            (
+                "code_objects",
+                self.consts
+                    .iter()
+                    .filter_map(|cons| match cons {
+                        ValueObj::Code(c) => Some(c.code_info_json(ver)),
+                        _ => None,
+                    })
+                    .collect::<Vec<_>>()
+                    .into(),
+            ),
+        ])
+    }
 }
@@ -162,6 +162,7 @@ pub fn subr_t(
     non_default_params: Vec<ParamTy>,
     var_params: Option<ParamTy>,
     default_params: Vec<ParamTy>,
+    kw_var_params: Option<ParamTy>,
     return_t: Type,
 ) -> Type {
     Type::Subr(SubrType::new(
@@ -169,6 +170,7 @@ pub fn subr_t(
         non_default_params,
         var_params,
         default_params,
+        kw_var_params,
         return_t,
     ))
 }
@@ -184,6 +186,7 @@ pub fn func(
         non_default_params,
         var_params,
         default_params,
+        None,
         return_t,
     ))
 }
@@ -231,6 +234,7 @@ pub fn proc(
         non_default_params,
         var_params,
         default_params,
+        None,
         return_t,
     ))
 }
@@ -265,6 +269,7 @@ pub fn fn_met(
         non_default_params,
         var_params,
         default_params,
+        None,
         return_t,
     ))
 }
@@ -300,6 +305,7 @@ pub fn pr_met(
         non_default_params,
         var_params,
         default_params,
+        None,
         return_t,
     ))
 }
@@ -7,15 +7,19 @@ use erg_common::cache::CacheSet;
 use erg_common::config::{ErgConfig, Input};
 use erg_common::dict::Dict;
 use erg_common::error::{ErrorCore, ErrorKind, Location};
-use erg_common::serialize::DataTypePrefix;
+use erg_common::json::JsonValue;
+use erg_common::python_util::PythonVersion;
+use erg_common::serialize::{DataTypePrefix, FLAG_REF};
 use erg_common::{fn_name, switch_lang};
 use erg_common::{RcArray, Str};
 
-use super::codeobj::CodeObj;
+use super::codeobj::{
+    exception_table_from_bytes, lnotab_from_line_table, split_localsplus, CodeObj,
+};
 use super::constructors::array_t;
 use super::typaram::TyParam;
 use super::value::ValueObj;
-use super::{HasType, Type};
+use super::Type;
 
 #[derive(Debug)]
 pub struct DeserializeError {
@@ -74,6 +78,34 @@ impl DeserializeError {
         )
     }
 
+    pub fn invalid_ref_error(idx: u32) -> Self {
+        Self::new(
+            0,
+            fn_name!(),
+            switch_lang!(
+                "japanese" => format!("不正な参照です: {idx}"),
+                "simplified_chinese" => format!("无效的引用: {idx}"),
+                "traditional_chinese" => format!("無效的引用: {idx}"),
+                "english" => format!("invalid reference: {idx}"),
+            ),
+        )
+    }
+
+    /// The opcodes and the layout of code objects differ between versions,
+    /// so a .pyc of an unsupported version cannot be read with another version's table
+    pub fn unsupported_version_error(python_ver: u32) -> Self {
+        Self::new(
+            0,
+            fn_name!(),
+            switch_lang!(
+                "japanese" => format!("サポートされていないバージョンの.pycファイルです: {python_ver}"),
+                "simplified_chinese" => format!("不支持的pyc版本: {python_ver}"),
+                "traditional_chinese" => format!("不支援的pyc版本: {python_ver}"),
+                "english" => format!("unsupported pyc version {python_ver}"),
+            ),
+        )
+    }
+
     pub fn type_error(expect: &Type, found: &Type) -> Self {
         Self::new(
             0,
@@ -102,11 +134,63 @@ impl DeserializeError {
 
 pub type DeserializeResult<T> = Result<T, DeserializeError>;
 
+/// A cursor over serialized bytes.
+/// Reading past the end is reported as `DeserializeError` (not a panic)
+#[derive(Debug, Clone)]
+pub struct ByteReader<'a> {
+    bytes: &'a [u8],
+}
+
+impl<'a> ByteReader<'a> {
+    pub const fn new(bytes: &'a [u8]) -> Self {
+        Self { bytes }
+    }
+
+    pub const fn remaining(&self) -> usize {
+        self.bytes.len()
+    }
+
+    pub const fn is_empty(&self) -> bool {
+        self.bytes.is_empty()
+    }
+
+    pub fn peek(&self) -> DeserializeResult<u8> {
+        self.bytes
+            .first()
+            .copied()
+            .ok_or_else(DeserializeError::file_broken_error)
+    }
+
+    pub fn take(&mut self, len: usize) -> DeserializeResult<&'a [u8]> {
+        if self.bytes.len() < len {
+            return Err(DeserializeError::file_broken_error());
+        }
+        let (taken, rest) = self.bytes.split_at(len);
+        self.bytes = rest;
+        Ok(taken)
+    }
+
+    pub fn take_array<const LEN: usize>(&mut self) -> DeserializeResult<[u8; LEN]> {
+        // `take` guarantees the length
+        Ok(self.take(LEN)?.try_into().unwrap())
+    }
+
+    pub fn read_u8(&mut self) -> DeserializeResult<u8> {
+        Ok(self.take_array::<1>()?[0])
+    }
+
+    pub fn read_u32(&mut self) -> DeserializeResult<u32> {
+        Ok(u32::from_le_bytes(self.take_array::<4>()?))
+    }
+}
+
 #[derive(Default)]
 pub struct Deserializer {
     str_cache: CacheSet<str>,
     arr_cache: CacheSet<[ValueObj]>,
     _dict_cache: CacheSet<Dict<ValueObj, ValueObj>>,
+    /// the objects flagged with `FLAG_REF`, in order of appearance
+    refs: Vec<ValueObj>,
 }
 
 impl Deserializer {
@@ -115,6 +199,7 @@ impl Deserializer {
             str_cache: CacheSet::new(),
             arr_cache: CacheSet::new(),
             _dict_cache: CacheSet::new(),
+            refs: Vec::new(),
         }
     }
 
@@ -125,16 +210,27 @@ impl Deserializer {
             eprintln!("{:?} is not a filename", cfg.input);
             process::exit(1);
         };
-        match CodeObj::from_pyc(&filename) {
-            Ok(codeobj) => {
-                println!("{}", codeobj.code_info());
+        match CodeObj::from_pyc_with_ver(&filename) {
+            Ok((codeobj, python_ver)) => {
+                // `deserialize_code` has checked the version
+                let ver = Some(PythonVersion::from_checked_magic_num(python_ver));
+                if cfg.output_format == "json" {
+                    let json = JsonValue::object(vec![
+                        ("python", python_ver.into()),
+                        ("code", codeobj.code_info_json(ver)),
+                    ]);
+                    println!("{json}");
+                } else {
+                    println!("{}", codeobj.code_info(ver));
+                }
             }
             Err(e) => {
                 eprintln!(
                     "failed to deserialize {}: {}",
                     filename.to_string_lossy(),
                     e.desc
-                )
+                );
+                process::exit(1);
             }
         }
     }
@@ -147,105 +243,175 @@ impl Deserializer {
         ValueObj::Array(self.arr_cache.get(arr))
     }
 
-    pub fn vec_to_bytes<const LEN: usize>(vector: Vec<u8>) -> [u8; LEN] {
-        let mut arr = [0u8; LEN];
-        for (arr_elem, vec_elem) in arr.iter_mut().zip(vector.iter()) {
-            *arr_elem = *vec_elem;
+    /// NOTE: Erg has no arbitrary-precision integer constants,
+    /// so integers out of the range of `Int` and `Nat` are approximated as `Float`
+    fn int_into_const(i: i128) -> ValueObj {
+        if let Ok(i) = i32::try_from(i) {
+            ValueObj::Int(i)
+        } else if let Ok(n) = u64::try_from(i) {
+            ValueObj::Nat(n)
+        } else {
+            ValueObj::Float(i as f64)
+        }
+    }
+
+    /// `len: i32` + 15-bit digits (little endian). The sign of `len` is the sign of the number
+    fn deserialize_long(r: &mut ByteReader) -> DeserializeResult<ValueObj> {
+        let len = r.read_u32()? as i32;
+        let digits = r
+            .take(len.unsigned_abs() as usize * 2)?
+            .chunks_exact(2)
+            .map(|digit| u16::from_le_bytes([digit[0], digit[1]]))
+            .collect::<Vec<_>>();
+        let sign = if len < 0 { -1 } else { 1 };
+        // 15 * 8 = 120 bits fit in `i128`
+        if digits.len() <= 8 {
+            let abs = digits
+                .iter()
+                .rev()
+                .fold(0i128, |acc, digit| (acc << 15) | *digit as i128);
+            Ok(Self::int_into_const(sign * abs))
+        } else {
+            let abs = digits
+                .iter()
+                .rev()
+                .fold(0f64, |acc, digit| acc * 32768.0 + *digit as f64);
+            Ok(ValueObj::Float(sign as f64 * abs))
         }
-        arr
     }
 
-    pub fn consume<const LEN: usize>(v: &mut Vec<u8>) -> [u8; LEN] {
-        Self::vec_to_bytes::<LEN>(v.drain(..LEN).collect::<Vec<_>>())
+    /// `len: u8` + ASCII representation of the float
+    fn deserialize_text_float(r: &mut ByteReader) -> DeserializeResult<f64> {
+        let len = r.read_u8()?;
+        let bytes = r.take(len as usize)?.to_vec();
+        String::from_utf8(bytes)?
+            .parse::<f64>()
+            .map_err(|_| DeserializeError::file_broken_error())
     }
 
-    pub fn deserialize_u32(v: &mut Vec<u8>) -> u32 {
-        u32::from_le_bytes(Self::consume::<4>(v))
+    fn deserialize_elems(
+        &mut self,
+        r: &mut ByteReader,
+        len: usize,
+        python_ver: u32,
+    ) -> DeserializeResult<Vec<ValueObj>> {
+        // each element takes at least 1 byte, so a broken `len` does not cause a huge allocation
+        let mut elems = Vec::with_capacity(len.min(r.remaining()));
+        for _ in 0..len {
+            elems.push(self.deserialize_const(r, python_ver)?);
+        }
+        Ok(elems)
     }
 
     pub fn deserialize_const(
         &mut self,
-        v: &mut Vec<u8>,
+        r: &mut ByteReader,
         python_ver: u32,
     ) -> DeserializeResult<ValueObj> {
-        match DataTypePrefix::from(v.remove(0)) {
+        let prefix = r.read_u8()?;
+        // The index is reserved before the contents are read (as CPython does),
+        // since the contents (e.g. elements of a tuple) may be flagged too
+        let ref_idx = if prefix & FLAG_REF != 0 {
+            self.refs.push(ValueObj::None);
+            Some(self.refs.len() - 1)
+        } else {
+            None
+        };
+        let obj = self.deserialize_const_body(DataTypePrefix::from(prefix), r, python_ver)?;
+        if let Some(idx) = ref_idx {
+            self.refs[idx] = obj.clone();
+        }
+        Ok(obj)
+    }
+
+    fn deserialize_const_body(
+        &mut self,
+        prefix: DataTypePrefix,
+        r: &mut ByteReader,
+        python_ver: u32,
+    ) -> DeserializeResult<ValueObj> {
+        match prefix {
             DataTypePrefix::Int32 => {
-                let bytes = Self::consume::<4>(v);
+                let bytes = r.take_array::<4>()?;
                 Ok(ValueObj::Int(i32::from_le_bytes(bytes)))
             }
+            DataTypePrefix::Int64 => {
+                let bytes = r.take_array::<8>()?;
+                Ok(Self::int_into_const(i64::from_le_bytes(bytes) as i128))
+            }
+            DataTypePrefix::Long => Self::deserialize_long(r),
             DataTypePrefix::BinFloat => {
-                let bytes = Self::consume::<8>(v);
+                let bytes = r.take_array::<8>()?;
                 Ok(ValueObj::Float(f64::from_le_bytes(bytes)))
             }
+            DataTypePrefix::Float => Ok(ValueObj::Float(Self::deserialize_text_float(r)?)),
+            DataTypePrefix::BinComplex => {
+                let re = f64::from_le_bytes(r.take_array::<8>()?);
+                let im = f64::from_le_bytes(r.take_array::<8>()?);
+                Ok(ValueObj::Complex(re, im))
+            }
+            DataTypePrefix::Complex => {
+                let re = Self::deserialize_text_float(r)?;
+                let im = Self::deserialize_text_float(r)?;
+                Ok(ValueObj::Complex(re, im))
+            }
             DataTypePrefix::ShortAscii | DataTypePrefix::ShortAsciiInterned => {
-                let len = v.remove(0);
-                let bytes = v.drain(..len as usize).collect();
+                let len = r.read_u8()?;
+                let bytes = r.take(len as usize)?.to_vec();
                 Ok(self.get_cached_str(&String::from_utf8(bytes)?))
             }
-            DataTypePrefix::Str | DataTypePrefix::Unicode => {
-                let len = Self::deserialize_u32(v);
-                let bytes = v.drain(..len as usize).collect();
+            DataTypePrefix::Ascii
+            | DataTypePrefix::AsciiInterned
+            | DataTypePrefix::Unicode
+            | DataTypePrefix::Interned => {
+                let len = r.read_u32()?;
+                let bytes = r.take(len as usize)?.to_vec();
                 Ok(self.get_cached_str(&String::from_utf8(bytes)?))
             }
+            DataTypePrefix::Str => {
+                let len = r.read_u32()?;
+                Ok(ValueObj::Bytes(r.take(len as usize)?.into()))
+            }
             DataTypePrefix::True => Ok(ValueObj::Bool(true)),
             DataTypePrefix::False => Ok(ValueObj::Bool(false)),
             DataTypePrefix::SmallTuple => {
-                let len = v.remove(0);
-                let mut arr = Vec::with_capacity(len as usize);
-                for _ in 0..len {
-                    arr.push(self.deserialize_const(v, python_ver)?);
-                }
+                let len = r.read_u8()?;
+                let arr = self.deserialize_elems(r, len as usize, python_ver)?;
                 Ok(self.get_cached_arr(&arr))
             }
-            DataTypePrefix::Tuple => {
-                let len = Self::deserialize_u32(v);
-                let mut arr = Vec::with_capacity(len as usize);
-                for _ in 0..len {
-                    arr.push(self.deserialize_const(v, python_ver)?);
-                }
+            DataTypePrefix::Tuple | DataTypePrefix::List => {
+                let len = r.read_u32()?;
+                let arr = self.deserialize_elems(r, len as usize, python_ver)?;
                 Ok(self.get_cached_arr(&arr))
             }
-            DataTypePrefix::Code => {
-                let argcount = Self::deserialize_u32(v);
-                let posonlyargcount = if python_ver >= 3413 {
-                    Self::deserialize_u32(v)
-                } else {
-                    0
-                };
-                let kwonlyargcount = Self::deserialize_u32(v);
-                let nlocals = Self::deserialize_u32(v);
-                let stacksize = Self::deserialize_u32(v);
-                let flags = Self::deserialize_u32(v);
-                let code = self.deserialize_bytes(v)?;
-                let consts = self.deserialize_const_vec(v, python_ver)?;
-                let names = self.deserialize_str_vec(v, python_ver)?;
-                let varnames = self.deserialize_str_vec(v, python_ver)?;
-                let freevars = self.deserialize_str_vec(v, python_ver)?;
-                let cellvars = self.deserialize_str_vec(v, python_ver)?;
-                let filename = self.deserialize_str(v, python_ver)?;
-                let name = self.deserialize_str(v, python_ver)?;
-                let firstlineno = Self::deserialize_u32(v);
-                let lnotab = self.deserialize_bytes(v)?;
-                Ok(ValueObj::from(CodeObj {
-                    argcount,
-                    posonlyargcount,
-                    kwonlyargcount,
-                    nlocals,
-                    stacksize,
-                    flags,
-                    code,
-                    consts,
-                    names,
-                    varnames,
-                    freevars,
-                    cellvars,
-                    filename,
-                    name,
-                    firstlineno,
-                    lnotab,
-                }))
+            DataTypePrefix::Set | DataTypePrefix::FrozenSet => {
+                let len = r.read_u32()?;
+                let elems = self.deserialize_elems(r, len as usize, python_ver)?;
+                Ok(ValueObj::Set(elems.into_iter().collect()))
+            }
+            DataTypePrefix::Dict => {
+                let mut dict = Dict::new();
+                loop {
+                    if DataTypePrefix::from(r.peek()?) == DataTypePrefix::Null {
+                        r.read_u8()?;
+                        break;
+                    }
+                    let key = self.deserialize_const(r, python_ver)?;
+                    let value = self.deserialize_const(r, python_ver)?;
+                    dict.insert(key, value);
+                }
+                Ok(ValueObj::Dict(dict))
+            }
+            DataTypePrefix::Ref => {
+                let idx = r.read_u32()?;
+                self.refs
+                    .get(idx as usize)
+                    .cloned()
+                    .ok_or_else(|| DeserializeError::invalid_ref_error(idx))
             }
+            DataTypePrefix::Code => Ok(ValueObj::from(self.deserialize_code(r, python_ver)?)),
             DataTypePrefix::None => Ok(ValueObj::None),
+            DataTypePrefix::Ellipsis => Ok(ValueObj::Ellipsis),
             other => Err(DeserializeError::new(
                 0,
                 fn_name!(),
@@ -259,25 +425,101 @@ impl Deserializer {
         }
     }
 
+    /// deserializes a code object (after the type prefix)
+    pub fn deserialize_code(
+        &mut self,
+        r: &mut ByteReader,
+        python_ver: u32,
+    ) -> DeserializeResult<CodeObj> {
+        let ver = PythonVersion::from_magic_num(python_ver)
+            .ok_or_else(|| DeserializeError::unsupported_version_error(python_ver))?;
+        let argcount = r.read_u32()?;
+        let posonlyargcount = if python_ver >= 3413 { r.read_u32()? } else { 0 };
+        let kwonlyargcount = r.read_u32()?;
+        let nlocals = if ver < PythonVersion::V3_11 {
+            r.read_u32()?
+        } else {
+            0
+        };
+        let stacksize = r.read_u32()?;
+        let flags = r.read_u32()?;
+        let code = self.deserialize_bytes(r, python_ver)?;
+        let consts = self.deserialize_const_vec(r, python_ver)?;
+        let names = self.deserialize_str_vec(r, python_ver)?;
+        let (varnames, freevars, cellvars) = if ver >= PythonVersion::V3_11 {
+            let localsplusnames = self.deserialize_str_vec(r, python_ver)?;
+            let localspluskinds = self.deserialize_bytes(r, python_ver)?;
+            let (varnames, cellvars, freevars) =
+                split_localsplus(localsplusnames, &localspluskinds);
+            (varnames, freevars, cellvars)
+        } else {
+            let varnames = self.deserialize_str_vec(r, python_ver)?;
+            let freevars = self.deserialize_str_vec(r, python_ver)?;
+            let cellvars = self.deserialize_str_vec(r, python_ver)?;
+            (varnames, freevars, cellvars)
+        };
+        let filename = self.deserialize_str(r, python_ver)?;
+        let name = self.deserialize_str(r, python_ver)?;
+        let qualname = if ver >= PythonVersion::V3_11 {
+            self.deserialize_str(r, python_ver)?
+        } else {
+            name.clone()
+        };
+        let firstlineno = r.read_u32()?;
+        let lnotab = self.deserialize_bytes(r, python_ver)?;
+        let lnotab = lnotab_from_line_table(lnotab, firstlineno, python_ver);
+        let exception_table = if ver >= PythonVersion::V3_11 {
+            exception_table_from_bytes(&self.deserialize_bytes(r, python_ver)?)
+        } else {
+            Vec::new()
+        };
+        let nlocals = if ver >= PythonVersion::V3_11 {
+            varnames.len() as u32
+        } else {
+            nlocals
+        };
+        Ok(CodeObj {
+            argcount,
+            posonlyargcount,
+            kwonlyargcount,
+            nlocals,
+            stacksize,
+            flags,
+            code,
+            consts,
+            names,
+            varnames,
+            freevars,
+            cellvars,
+            filename,
+            name,
+            qualname,
+            firstlineno,
+            lnotab,
+            exception_table,
+            spans: Vec::new(),
+        })
+    }
+
     pub fn deserialize_const_vec(
         &mut self,
-        v: &mut Vec<u8>,
+        r: &mut ByteReader,
         python_ver: u32,
     ) -> DeserializeResult<Vec<ValueObj>> {
-        match self.deserialize_const(v, python_ver)? {
+        match self.deserialize_const(r, python_ver)? {
             ValueObj::Array(arr) => Ok(arr.to_vec()),
-            other => Err(DeserializeError::type_error(&Type::Str, other.ref_t())),
+            other => Err(DeserializeError::type_error(&Type::Str, &other.class())),
         }
     }
 
     pub fn deserialize_const_array(
         &mut self,
-        v: &mut Vec<u8>,
+        r: &mut ByteReader,
         python_ver: u32,
     ) -> DeserializeResult<RcArray<ValueObj>> {
-        match self.deserialize_const(v, python_ver)? {
+        match self.deserialize_const(r, python_ver)? {
             ValueObj::Array(arr) => Ok(arr),
-            other => Err(DeserializeError::type_error(&Type::Str, other.ref_t())),
+            other => Err(DeserializeError::type_error(&Type::Str, &other.class())),
         }
     }
 
@@ -288,16 +530,16 @@ impl Deserializer {
     pub fn try_into_str(&mut self, c: ValueObj) -> DeserializeResult<Str> {
         match c {
             ValueObj::Str(s) => Ok(s),
-            other => Err(DeserializeError::type_error(&Type::Str, other.ref_t())),
+            other => Err(DeserializeError::type_error(&Type::Str, &other.class())),
         }
     }
 
     pub fn deserialize_str_vec(
         &mut self,
-        v: &mut Vec<u8>,
+        r: &mut ByteReader,
         python_ver: u32,
     ) -> DeserializeResult<Vec<Str>> {
-        match self.deserialize_const(v, python_ver)? {
+        match self.deserialize_const(r, python_ver)? {
             ValueObj::Array(arr) => {
                 let mut strs = Vec::with_capacity(arr.len());
                 for c in arr.iter().cloned() {
@@ -307,21 +549,31 @@ impl Deserializer {
             }
             other => Err(DeserializeError::type_error(
                 &array_t(Type::Str, TyParam::erased(Type::Nat)),
-                other.ref_t(),
+                &other.class(),
             )),
         }
     }
 
-    pub fn deserialize_str(&mut self, v: &mut Vec<u8>, python_ver: u32) -> DeserializeResult<Str> {
-        match self.deserialize_const(v, python_ver)? {
+    pub fn deserialize_str(
+        &mut self,
+        r: &mut ByteReader,
+        python_ver: u32,
+    ) -> DeserializeResult<Str> {
+        match self.deserialize_const(r, python_ver)? {
             ValueObj::Str(s) => Ok(s),
-            other => Err(DeserializeError::type_error(&Type::Str, other.ref_t())),
+            other => Err(DeserializeError::type_error(&Type::Str, &other.class())),
         }
     }
 
-    pub fn deserialize_bytes(&self, v: &mut Vec<u8>) -> DeserializeResult<Vec<u8>> {
-        if DataTypePrefix::from(v.remove(0)) != DataTypePrefix::Str {
-            return Err(DeserializeError::new(
+    /// NOTE: bytes may be referred by `Ref` (e.g. an empty line table)
+    pub fn deserialize_bytes(
+        &mut self,
+        r: &mut ByteReader,
+        python_ver: u32,
+    ) -> DeserializeResult<Vec<u8>> {
+        match self.deserialize_const(r, python_ver)? {
+            ValueObj::Bytes(bytes) => Ok(bytes.to_vec()),
+            _ => Err(DeserializeError::new(
                 0,
                 fn_name!(),
                 switch_lang!(
@@ -330,9 +582,7 @@ impl Deserializer {
                     "traditional_chinese" => "未能加載字節",
                     "english" => "failed to load bytes",
                 ),
-            ));
+            )),
         }
-        let len = Self::deserialize_u32(v);
-        Ok(v.drain(0..len as usize).collect())
     }
 }
@@ -0,0 +1,280 @@
+//! disassembles the bytecode of `CodeObj` (like `dis` in Python).
+//!
+//! `CodeObj`のバイトコードを逆アセンブルする(Pythonの`dis`相当)
+//!
+//! The bytecode is in the format of `ver` (e.g. read from a .pyc file),
+//! or in the format of `CodeGenerator` if `ver` is `None` (see `Opcode`).
+use erg_common::json::JsonValue;
+use erg_common::opcode::Opcode::{self, *};
+use erg_common::python_util::PythonVersion;
+use erg_common::Str;
+
+use super::codeobj::{lnotab_to_lines, localsplus, CodeObj};
+use super::TypePair;
+
+/// `cmp_op` in `opcode.py`
+const COMPARE_OPS: [&str; 6] = ["<", "<=", "==", "!=", ">", ">="];
+
+/// `_nb_ops` in `opcode.py` (Python 3.11+)
+const BINARY_OPS: [&str; 26] = [
+    "+", "&", "//", "<<", "@", "*", "%", "|", "**", ">>", "-", "/", "^", "+=", "&=", "//=", "<<=",
+    "@=", "*=", "%=", "|=", "**=", ">>=", "-=", "/=", "^=",
+];
+
+/// `MAKE_FUNCTION_FLAGS` in `dis.py`
+const MAKE_FUNCTION_FLAGS: [&str; 4] = ["defaults", "kwdefaults", "annotations", "closure"];
+
+/// `FORMAT_VALUE_CONVERTERS` in `dis.py`
+const FORMAT_VALUE_CONVERTERS: [&str; 4] = ["", "str", "repr", "ascii"];
+
+/// A disassembled instruction
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Instruction {
+    pub offset: usize,
+    /// `None` if the opcode is unknown (in the version)
+    pub op: Option<Opcode>,
+    /// the opcode number
+    pub byte: u8,
+    /// `None` if the opcode takes no argument. Preceding `EXTENDED_ARG`s are merged
+    pub arg: Option<u32>,
+    /// human-readable description of the argument (e.g. the name, the constant)
+    pub argrepr: String,
+    pub jump_target: Option<usize>,
+    pub line: Option<i64>,
+    pub starts_line: bool,
+    pub is_jump_target: bool,
+}
+
+impl Instruction {
+    pub fn opname(&self) -> String {
+        match self.op {
+            Some(op) => op.to_string(),
+            None => format!("<{}>", self.byte),
+        }
+    }
+
+    pub fn to_json(&self) -> JsonValue {
+        JsonValue::object(vec![
+            ("offset", self.offset.into()),
+            ("opname", self.opname().into()),
+            ("opcode", self.byte.into()),
+            ("arg", self.arg.into()),
+            ("argrepr", self.argrepr.clone().into()),
+            ("jump_target", self.jump_target.into()),
+            ("line", self.line.into()),
+            ("starts_line", self.starts_line.into()),
+            ("is_jump_target", self.is_jump_target.into()),
+        ])
+    }
+}
+
+fn decode_op(byte: u8, ver: Option<PythonVersion>) -> Option<Opcode> {
+    match ver {
+        Some(ver) => Opcode::decode(byte, ver),
+        None => Opcode::from_u8(byte),
+    }
+}
+
+fn takes_arg(op: Option<Opcode>, byte: u8, ver: Option<PythonVersion>) -> bool {
+    match (op, ver) {
+        // the operands are cast by the argument (see `TypePair`)
+        (Some(BINARY_ADD | BINARY_SUBTRACT | BINARY_MULTIPLY | BINARY_TRUE_DIVIDE), None) => true,
+        (Some(op), None) => op.take_arg(),
+        // `HAVE_ARGUMENT` is 90 in all versions
+        _ => byte >= 90,
+    }
+}
+
+/// The offset of the jump target, `next` is the offset of the next instruction (excluding `CACHE`s)
+fn jump_target(op: Opcode, arg: u32, next: usize, ver: Option<PythonVersion>) -> Option<usize> {
+    let arg = arg as usize;
+    match ver {
+        Some(PythonVersion::V3_11) => match op {
+            JUMP_FORWARD
+            | FOR_ITER
+            | JUMP_IF_FALSE_OR_POP
+            | JUMP_IF_TRUE_OR_POP
+            | POP_JUMP_FORWARD_IF_FALSE
+            | POP_JUMP_FORWARD_IF_TRUE
+            | POP_JUMP_FORWARD_IF_NONE
+            | POP_JUMP_FORWARD_IF_NOT_NONE
+            | SEND => Some(next + arg * 2),
+            JUMP_BACKWARD
+            | JUMP_BACKWARD_NO_INTERRUPT
+            | POP_JUMP_BACKWARD_IF_FALSE
+            | POP_JUMP_BACKWARD_IF_TRUE
+            | POP_JUMP_BACKWARD_IF_NONE
+            | POP_JUMP_BACKWARD_IF_NOT_NONE => Some(next.saturating_sub(arg * 2)),
+            _ => None,
+        },
+        // Python 3.7 ~ 3.9 count the arguments in bytes
+        Some(ver) if ver < PythonVersion::V3_10 => match op {
+            JUMP_ABSOLUTE
+            | POP_JUMP_IF_FALSE
+            | POP_JUMP_IF_TRUE
+            | JUMP_IF_FALSE_OR_POP
+            | JUMP_IF_TRUE_OR_POP
+            | JUMP_IF_NOT_EXC_MATCH => Some(arg),
+            JUMP_FORWARD | FOR_ITER | SETUP_WITH | SETUP_FINALLY | SETUP_ASYNC_WITH => {
+                Some(next + arg)
+            }
+            _ => None,
+        },
+        _ => match op {
+            JUMP_ABSOLUTE
+            | POP_JUMP_IF_FALSE
+            | POP_JUMP_IF_TRUE
+            | JUMP_IF_FALSE_OR_POP
+            | JUMP_IF_TRUE_OR_POP
+            | JUMP_IF_NOT_EXC_MATCH => Some(arg * 2),
+            JUMP_FORWARD | FOR_ITER | SETUP_WITH | SETUP_FINALLY | SETUP_ASYNC_WITH => {
+                Some(next + arg * 2)
+            }
+            _ => None,
+        },
+    }
+}
+
+fn flags_repr(arg: u32, names: &[&str]) -> String {
+    names
+        .iter()
+        .enumerate()
+        .filter(|(i, _)| arg & (1 << i) != 0)
+        .map(|(_, name)| *name)
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+fn name_repr(names: &[Str], idx: u32) -> String {
+    names
+        .get(idx as usize)
+        .map(|name| name.to_string())
+        .unwrap_or_default()
+}
+
+/// The names of the cell and free variables (indexed by `LOAD_DEREF`, etc.)
+fn deref_names(code: &CodeObj, ver: Option<PythonVersion>) -> Vec<Str> {
+    if ver.is_some_and(|ver| ver >= PythonVersion::V3_11) {
+        localsplus(&code.varnames, &code.cellvars, &code.freevars).0
+    } else {
+        [&code.cellvars[..], &code.freevars[..]].concat()
+    }
+}
+
+fn arg_repr(
+    code: &CodeObj,
+    op: Opcode,
+    arg: u32,
+    ver: Option<PythonVersion>,
+    deref_names: &[Str],
+) -> String {
+    let is_311 = ver.is_some_and(|ver| ver >= PythonVersion::V3_11);
+    match op {
+        LOAD_CONST | KW_NAMES => code
+            .consts
+            .get(arg as usize)
+            .map(|obj| obj.to_string())
+            .unwrap_or_default(),
+        // the lowest bit indicates whether `NULL` is pushed before the global
+        LOAD_GLOBAL if is_311 => {
+            let name = name_repr(&code.names, arg >> 1);
+            if arg & 1 != 0 {
+                format!("NULL + {name}")
+            } else {
+                name
+            }
+        }
+        STORE_NAME | DELETE_NAME | STORE_ATTR | DELETE_ATTR | STORE_GLOBAL | LOAD_NAME
+        | LOAD_ATTR | IMPORT_NAME | IMPORT_FROM | LOAD_GLOBAL | LOAD_METHOD => {
+            name_repr(&code.names, arg)
+        }
+        // in 3.11+, the local variables are in `localsplus` (`varnames` comes first)
+        LOAD_FAST | STORE_FAST | DELETE_FAST if is_311 => name_repr(deref_names, arg),
+        LOAD_FAST | STORE_FAST | DELETE_FAST => name_repr(&code.varnames, arg),
+        LOAD_CLOSURE | LOAD_DEREF | STORE_DEREF | MAKE_CELL => name_repr(deref_names, arg),
+        COMPARE_OP => COMPARE_OPS
+            .get(arg as usize)
+            .map(|op| op.to_string())
+            .unwrap_or_default(),
+        BINARY_OP => BINARY_OPS
+            .get(arg as usize)
+            .map(|op| op.to_string())
+            .unwrap_or_default(),
+        MAKE_FUNCTION => flags_repr(arg, &MAKE_FUNCTION_FLAGS),
+        FORMAT_VALUE => {
+            let conv = FORMAT_VALUE_CONVERTERS[(arg & 3) as usize];
+            match (conv, arg & 4 != 0) {
+                ("", true) => "with format".to_string(),
+                (conv, true) => format!("{conv}, with format"),
+                (conv, false) => conv.to_string(),
+            }
+        }
+        BINARY_ADD | BINARY_SUBTRACT | BINARY_MULTIPLY | BINARY_TRUE_DIVIDE if ver.is_none() => {
+            format!("{:?}", TypePair::from(arg as u8))
+        }
+        _ => "".to_string(),
+    }
+}
+
+/// Disassembles `code` (nested code objects are not included).
+/// `CACHE`s of Python 3.11 are skipped, and `EXTENDED_ARG`s are kept as instructions (as `dis` does)
+pub fn disassemble(code: &CodeObj, ver: Option<PythonVersion>) -> Vec<Instruction> {
+    let deref_names = deref_names(code, ver);
+    let lines = lnotab_to_lines(&code.lnotab, code.firstlineno);
+    let mut instrs: Vec<Instruction> = vec![];
+    let mut ext = 0u32;
+    // the index of the first entry of `lines` after the current instruction
+    let mut next_line = 0;
+    for (i, pair) in code.code.chunks(2).enumerate() {
+        let offset = i * 2;
+        let byte = pair[0];
+        let op = decode_op(byte, ver);
+        if op == Some(CACHE) && ver.is_some_and(|ver| ver >= PythonVersion::V3_11) {
+            continue;
+        }
+        let raw_arg = (ext << 8) | pair.get(1).copied().unwrap_or(0) as u32;
+        ext = if op == Some(EXTENDED_ARG) { raw_arg } else { 0 };
+        let arg = takes_arg(op, byte, ver).then_some(raw_arg);
+        let (argrepr, jump) = match (op, arg) {
+            (Some(op), Some(arg)) => match jump_target(op, arg, offset + 2, ver) {
+                Some(target) => (format!("to {target}"), Some(target)),
+                None => (arg_repr(code, op, arg, ver, &deref_names), None),
+            },
+            _ => ("".to_string(), None),
+        };
+        while lines
+            .get(next_line)
+            .is_some_and(|(addr, _)| *addr <= offset)
+        {
+            next_line += 1;
+        }
+        // the prologue instructions have no line number (which `lnotab` cannot represent)
+        let line = if matches!(op, Some(MAKE_CELL | COPY_FREE_VARS | GEN_START)) {
+            None
+        } else {
+            next_line.checked_sub(1).map(|i| lines[i].1)
+        };
+        let starts_line =
+            line.is_some() && instrs.last().map(|prev| prev.line != line).unwrap_or(true);
+        instrs.push(Instruction {
+            offset,
+            op,
+            byte,
+            arg,
+            argrepr,
+            jump_target: jump,
+            line,
+            starts_line,
+            is_jump_target: false,
+        });
+    }
+    let targets = instrs
+        .iter()
+        .filter_map(|instr| instr.jump_target)
+        .chain(code.exception_table.iter().map(|entry| entry.target))
+        .collect::<Vec<_>>();
+    for instr in instrs.iter_mut() {
+        instr.is_jump_target = targets.contains(&instr.offset);
+    }
+    instrs
+}
@@ -1,14 +1,18 @@
 //! defines `Type` (type kind).
 //!
 //! Type(コンパイラ等で使われる「型」を表現する)を定義する
-#![allow(clippy::derive_hash_xor_eq)]
+#![allow(clippy::derived_hash_with_manual_eq)]
 #![allow(clippy::large_enum_variant)]
+pub mod assemble;
 pub mod codeobj;
 pub mod constructors;
 pub mod deserialize;
+pub mod disassemble;
 pub mod free;
+pub mod peephole;
 pub mod typaram;
 pub mod value;
+pub mod verify;
 
 use std::fmt;
 use std::ops::{Range, RangeInclusive};
@@ -506,7 +510,12 @@ impl HasLevel for Predicate {
             | Self::GreaterEqual { rhs, .. }
             | Self::LessEqual { rhs, .. }
             | Self::NotEqual { rhs, .. } => rhs.level(),
-            Self::And(_lhs, _rhs) | Self::Or(_lhs, _rhs) | Self::Not(_lhs, _rhs) => todo!(),
+            Self::And(lhs, rhs) | Self::Or(lhs, rhs) | Self::Not(lhs, rhs) => {
+                match (lhs.level(), rhs.level()) {
+                    (Some(l), Some(r)) => Some(l.max(r)),
+                    (l, r) => l.or(r),
+                }
+            }
         }
     }
 
@@ -575,6 +584,14 @@ impl Predicate {
         matches!(self, Self::Equal { .. })
     }
 
+    /// `A or (B or C)` => `{A, B, C}`
+    pub fn split_or(self) -> Set<Predicate> {
+        match self {
+            Self::Or(lhs, rhs) => lhs.split_or().concat(rhs.split_or()),
+            other => set! {other},
+        }
+    }
+
     pub fn subject(&self) -> Option<&str> {
         match self {
             Self::Equal { lhs, .. }
@@ -821,7 +838,7 @@ pub struct SubrType {
     pub non_default_params: Vec<ParamTy>,
     pub var_params: Option<Box<ParamTy>>,
     pub default_params: Vec<ParamTy>,
-    // var_kw_params: Option<(Str, Box<Type>)>,
+    pub kw_var_params: Option<Box<ParamTy>>,
     pub return_t: Box<Type>,
 }
 
@@ -855,6 +872,16 @@ impl LimitedDisplay for SubrType {
             write!(f, ", {} := ", pt.name().unwrap())?;
             pt.typ().limited_fmt(f, limit - 1)?;
         }
+        if let Some(kw_var_params) = &self.kw_var_params {
+            if !self.non_default_params.is_empty()
+                || self.var_params.is_some()
+                || !self.default_params.is_empty()
+            {
+                write!(f, ", ")?;
+            }
+            write!(f, "**")?;
+            kw_var_params.typ().limited_fmt(f, limit - 1)?;
+        }
         write!(f, ") {} ", self.kind.arrow())?;
         self.return_t.limited_fmt(f, limit - 1)
     }
@@ -866,6 +893,7 @@ impl SubrType {
         non_default_params: Vec<ParamTy>,
         var_params: Option<ParamTy>,
         default_params: Vec<ParamTy>,
+        kw_var_params: Option<ParamTy>,
         return_t: Type,
     ) -> Self {
         Self {
@@ -873,6 +901,7 @@ impl SubrType {
             non_default_params,
             var_params: var_params.map(Box::new),
             default_params,
+            kw_var_params: kw_var_params.map(Box::new),
             return_t: Box::new(return_t),
         }
     }
@@ -890,6 +919,11 @@ impl SubrType {
                 .default_params
                 .iter()
                 .any(|pt| pt.typ().contains_tvar(name))
+            || self
+                .kw_var_params
+                .as_ref()
+                .map(|pt| pt.typ().contains_tvar(name))
+                .unwrap_or(false)
             || self.return_t.contains_tvar(name)
     }
 
@@ -901,6 +935,11 @@ impl SubrType {
                 .map(|pt| pt.typ().has_qvar())
                 .unwrap_or(false)
             || self.default_params.iter().any(|pt| pt.typ().has_qvar())
+            || self
+                .kw_var_params
+                .as_ref()
+                .map(|pt| pt.typ().has_qvar())
+                .unwrap_or(false)
             || self.return_t.has_qvar()
     }
 
@@ -919,6 +958,11 @@ impl SubrType {
                 .iter()
                 .map(|pt| TyParam::t(pt.typ().clone()))
                 .collect(),
+            self.kw_var_params
+                .as_ref()
+                .map(|pt| TyParam::t(pt.typ().clone()))
+                .into_iter()
+                .collect(),
         ]
         .concat()
     }
@@ -1094,6 +1138,7 @@ pub struct ArgsOwnership {
     pub non_defaults: Vec<Ownership>,
     pub var_params: Option<Ownership>,
     pub defaults: Vec<(Str, Ownership)>,
+    pub kw_var_params: Option<Ownership>,
 }
 
 impl fmt::Display for ArgsOwnership {
@@ -1111,6 +1156,9 @@ impl fmt::Display for ArgsOwnership {
         for (name, o) in self.defaults.iter() {
             write!(f, ", {name} := {o:?}")?;
         }
+        if let Some(o) = self.kw_var_params.as_ref() {
+            write!(f, ", **{o:?}")?;
+        }
         write!(f, ")")?;
         Ok(())
     }
@@ -1121,16 +1169,18 @@ impl ArgsOwnership {
         non_defaults: Vec<Ownership>,
         var_params: Option<Ownership>,
         defaults: Vec<(Str, Ownership)>,
+        kw_var_params: Option<Ownership>,
     ) -> Self {
         Self {
             non_defaults,
             var_params,
             defaults,
+            kw_var_params,
         }
     }
 }
 
-#[derive(Debug, Clone, Hash)]
+#[derive(Debug, Clone, Hash, Default)]
 pub enum Type {
     /* Monomorphic (builtin) types */
     Obj, // {=}
@@ -1200,7 +1250,8 @@ pub enum Type {
         args: Vec<TyParam>,
     }, // e.g. Ts.__getitem__(N)
     FreeVar(FreeTyVar), // a reference to the type of other expression, see docs/compiler/inference.md
-    Failure,            // indicates a failure of type inference and behaves as `Never`.
+    #[default]
+    Failure, // indicates a failure of type inference and behaves as `Never`.
     Untyped,            // e.g. the type of `match`
     /// used to represent `TyParam` is not initialized (see `erg_compiler::context::instantiate_tp`)
     Uninited,
@@ -1435,12 +1486,6 @@ impl LimitedDisplay for Type {
     }
 }
 
-impl Default for Type {
-    fn default() -> Self {
-        Self::Failure
-    }
-}
-
 impl From<Range<TyParam>> for Type {
     fn from(r: Range<TyParam>) -> Self {
         int_interval(IntervalOp::RightOpen, r.start, r.end)
@@ -1574,6 +1619,9 @@ impl HasLevel for Type {
                 for pt in subr.default_params.iter() {
                     pt.typ().update_level(level);
                 }
+                if let Some(pt) = subr.kw_var_params.as_ref() {
+                    pt.typ().update_level(level);
+                }
                 subr.return_t.update_level(level);
             }
             Self::And(lhs, rhs) | Self::Or(lhs, rhs) | Self::Not(lhs, rhs) => {
@@ -1635,6 +1683,9 @@ impl HasLevel for Type {
                 for pt in subr.default_params.iter() {
                     pt.typ().lift();
                 }
+                if let Some(pt) = subr.kw_var_params.as_ref() {
+                    pt.typ().lift();
+                }
                 subr.return_t.lift();
             }
             Self::And(lhs, rhs) | Self::Or(lhs, rhs) | Self::Not(lhs, rhs) => {
@@ -1740,6 +1791,7 @@ impl Type {
             Self::FreeVar(fv) if fv.is_linked() => fv.crack().is_procedural(),
             Self::Callable { .. } => true,
             Self::Subr(subr) if subr.kind == SubrKind::Proc => true,
+            Self::Quantified(quant) => quant.unbound_callable.is_procedural(),
             Self::Refinement(refine) =>
                 refine.t.is_procedural() || refine.preds.iter().any(|pred|
                     matches!(pred, Predicate::Equal{ rhs, .. } if pred.mentions(&refine.var) && rhs.qual_name().map(|n| n.ends_with('!')).unwrap_or(false))
@@ -1863,7 +1915,8 @@ impl Type {
                     };
                     d_args.push((d_param.name().unwrap().clone(), ownership));
                 }
-                ArgsOwnership::new(nd_args, var_args, d_args)
+                let kw_var_args = subr.kw_var_params.as_ref().map(|t| t.typ().ownership());
+                ArgsOwnership::new(nd_args, var_args, d_args, kw_var_args)
             }
             _ => todo!(),
         }
@@ -1938,7 +1991,7 @@ impl Type {
         match self {
             Self::Mono(name) | Self::Poly { name, .. } => {
                 let name = name.split("::").last().unwrap_or(name);
-                let name = name.split('.').last().unwrap_or(name);
+                let name = name.split('.').next_back().unwrap_or(name);
                 Str::rc(name)
             }
             _ => self.qual_name(),
@@ -2056,6 +2109,11 @@ impl Type {
                         .map(|pt| pt.typ().is_cachable())
                         .unwrap_or(false)
                     && subr.default_params.iter().all(|pt| pt.typ().is_cachable())
+                    && subr
+                        .kw_var_params
+                        .as_ref()
+                        .map(|pt| pt.typ().is_cachable())
+                        .unwrap_or(true)
                     && subr.return_t.is_cachable()
             }
             Self::Record(r) => r.values().all(|t| t.is_cachable()),
@@ -2106,6 +2164,11 @@ impl Type {
                         .default_params
                         .iter()
                         .any(|pt| pt.typ().has_unbound_var())
+                    || subr
+                        .kw_var_params
+                        .as_ref()
+                        .map(|pt| pt.typ().has_unbound_var())
+                        .unwrap_or(false)
                     || subr.return_t.has_unbound_var()
             }
             Self::Record(r) => r.values().any(|t| t.has_unbound_var()),
@@ -2139,6 +2202,7 @@ impl Type {
                 subr.non_default_params.len()
                     + subr.var_params.as_ref().map(|_| 1).unwrap_or(0)
                     + subr.default_params.len()
+                    + subr.kw_var_params.as_ref().map(|_| 1).unwrap_or(0)
                     + 1,
             ),
             Self::Callable { param_ts, .. } => Some(param_ts.len() + 1),
@@ -2219,6 +2283,18 @@ impl Type {
         }
     }
 
+    pub fn kw_var_args(&self) -> Option<&ParamTy> {
+        match self {
+            Self::FreeVar(fv) if fv.is_linked() => unsafe { fv.as_ptr().as_ref() }
+                .unwrap()
+                .linked()
+                .and_then(|t| t.kw_var_args()),
+            Self::Refinement(refine) => refine.t.kw_var_args(),
+            Self::Subr(SubrType { kw_var_params, .. }) => kw_var_params.as_deref(),
+            _ => None,
+        }
+    }
+
     pub fn return_t(&self) -> Option<&Type> {
         match self {
             Self::FreeVar(fv) if fv.is_linked() => unsafe { fv.as_ptr().as_ref() }
@@ -2263,13 +2339,17 @@ impl Type {
         match self {
             Self::FreeVar(fv) if fv.is_linked() => fv.crack().derefine(),
             Self::FreeVar(fv) => {
-                let name = fv.get_unbound_name().unwrap();
-                let level = fv.level().unwrap();
-                let (sub, sup) = fv.get_bound_types().unwrap();
+                let (Some(level), Some((sub, sup))) = (fv.level(), fv.get_bound_types()) else {
+                    return self.clone();
+                };
                 let cyclicity = fv.cyclicity();
                 let constraint =
                     Constraint::new_sandwiched(sub.derefine(), sup.derefine(), cyclicity);
-                Self::FreeVar(Free::new_named_unbound(name, level, constraint))
+                if let Some(name) = fv.get_unbound_name() {
+                    Self::FreeVar(Free::new_named_unbound(name, level, constraint))
+                } else {
+                    Self::FreeVar(Free::new_unbound(level, constraint))
+                }
             }
             Self::Refinement(refine) => refine.t.as_ref().clone(),
             Self::Poly { name, params } => {
@@ -2619,8 +2699,8 @@ impl TypePair {
             {
                 Self::ProcProc
             }
-            (Type::Refinement(refine), r) => Self::new(&*refine.t, r),
-            (l, Type::Refinement(refine)) => Self::new(l, &*refine.t),
+            (Type::Refinement(refine), r) => Self::new(&refine.t, r),
+            (l, Type::Refinement(refine)) => Self::new(l, &refine.t),
             (_, _) => Self::Others,
         }
     }
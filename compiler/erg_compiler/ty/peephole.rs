@@ -0,0 +1,395 @@
+//! applies peephole optimizations to the bytecode generated by `CodeGenerator` (`opt_level` >= 2).
+//!
+//! `CodeGenerator`が生成したバイトコードに覗き穴最適化を施す
+//!
+//! The passes work on the format of `CodeGenerator` (see `assemble`), so they do not depend on the target version.
+//! The jump arguments, `lnotab`, the exception table and the source spans are recalculated afterwards.
+use erg_common::opcode::Opcode::{self, *};
+
+use super::assemble::{decode, ext_len, jump_target};
+use super::codeobj::{lines_to_lnotab, lnotab_to_lines, CodeObj, CodeSpan, ExceptionTableEntry};
+use super::value::ValueObj;
+
+#[derive(Debug, Clone, Copy)]
+struct Instr {
+    op: Opcode,
+    arg: u32,
+    /// the index of the target instruction
+    target: Option<usize>,
+}
+
+/// the argument is the distance from the next instruction (so these cannot jump backward)
+fn is_forward_jump(op: Opcode) -> bool {
+    matches!(op, JUMP_FORWARD | FOR_ITER | SETUP_WITH)
+}
+
+fn is_unconditional_jump(op: Opcode) -> bool {
+    matches!(op, JUMP_ABSOLUTE | JUMP_FORWARD)
+}
+
+/// The execution never falls through to the next instruction
+fn is_terminal(op: Opcode) -> bool {
+    is_unconditional_jump(op) || matches!(op, RETURN_VALUE | RAISE_VARARGS | RERAISE)
+}
+
+/// Immutable objects which can be elements of a constant tuple
+fn is_foldable(obj: &ValueObj) -> bool {
+    match obj {
+        ValueObj::Tuple(tup) => tup.iter().all(is_foldable),
+        ValueObj::Int(_)
+        | ValueObj::Nat(_)
+        | ValueObj::Float(_)
+        | ValueObj::Complex(_, _)
+        | ValueObj::Str(_)
+        | ValueObj::Bytes(_)
+        | ValueObj::Bool(_)
+        | ValueObj::None
+        | ValueObj::Ellipsis => true,
+        _ => false,
+    }
+}
+
+/// The bytecode of a code object, in which the offsets are replaced with instruction indices
+/// (`instrs.len()` is the end of the code). Removed instructions are replaced with `NOP`s until `remove_nops`
+#[derive(Debug)]
+struct Peephole {
+    instrs: Vec<Instr>,
+    /// `(index, line number)`
+    lines: Vec<(usize, i64)>,
+    exception_table: Vec<ExceptionTableEntry>,
+    spans: Vec<CodeSpan>,
+}
+
+impl Peephole {
+    fn new(code: &CodeObj) -> Self {
+        let decoded = decode(&code.code);
+        // `index[i]`: the index of the instruction which contains the offset `2 * i`
+        let mut index = vec![decoded.len(); code.code.len() / 2 + 1];
+        for (i, &(start, _, _, next)) in decoded.iter().enumerate() {
+            index[start / 2..next / 2].fill(i);
+        }
+        let idx = |offset: usize| index[(offset / 2).min(index.len() - 1)];
+        let instrs = decoded
+            .iter()
+            .map(|&(_, op, arg, next)| Instr {
+                op,
+                arg,
+                target: jump_target(op, arg, next).map(idx),
+            })
+            .collect();
+        let lines = lnotab_to_lines(&code.lnotab, code.firstlineno)
+            .into_iter()
+            .map(|(addr, line)| (idx(addr), line))
+            .collect();
+        let exception_table = code
+            .exception_table
+            .iter()
+            .map(|entry| {
+                ExceptionTableEntry::new(
+                    idx(entry.start),
+                    idx(entry.end),
+                    idx(entry.target),
+                    entry.depth,
+                    entry.lasti,
+                )
+            })
+            .collect();
+        let spans = code
+            .spans
+            .iter()
+            .map(|span| CodeSpan::new(idx(span.start), idx(span.end), span.loc))
+            .collect();
+        Self {
+            instrs,
+            lines,
+            exception_table,
+            spans,
+        }
+    }
+
+    /// `is_target[i]`: whether the instruction `i` is jumped to (including the exception handlers)
+    fn jump_targets(&self) -> Vec<bool> {
+        let mut is_target = vec![false; self.instrs.len() + 1];
+        for target in self
+            .instrs
+            .iter()
+            .filter_map(|instr| instr.target)
+            .chain(self.exception_table.iter().map(|entry| entry.target))
+        {
+            is_target[target] = true;
+        }
+        is_target
+    }
+
+    fn remove(&mut self, i: usize) {
+        self.instrs[i] = Instr {
+            op: NOP,
+            arg: 0,
+            target: None,
+        };
+    }
+
+    /// `LOAD_CONST 1; LOAD_CONST 2; BUILD_TUPLE 2` -> `LOAD_CONST (1, 2)`
+    fn fold_tuples(&mut self, consts: &mut Vec<ValueObj>) -> bool {
+        let is_target = self.jump_targets();
+        let mut changed = false;
+        for i in 0..self.instrs.len() {
+            let Instr { op, arg, .. } = self.instrs[i];
+            let len = arg as usize;
+            if op != BUILD_TUPLE || len > i {
+                continue;
+            }
+            let start = i - len;
+            // jumping into the middle of the sequence
+            if (start + 1..=i).any(|j| is_target[j]) {
+                continue;
+            }
+            let elems = self.instrs[start..i]
+                .iter()
+                .map(|instr| {
+                    (instr.op == LOAD_CONST)
+                        .then(|| consts.get(instr.arg as usize))
+                        .flatten()
+                        .filter(|obj| is_foldable(obj))
+                        .cloned()
+                })
+                .collect::<Option<Vec<_>>>();
+            let Some(elems) = elems else {
+                continue;
+            };
+            let tuple = ValueObj::Tuple(elems.into());
+            let idx = consts.iter().position(|c| c == &tuple).unwrap_or_else(|| {
+                consts.push(tuple);
+                consts.len() - 1
+            });
+            for j in start..=i {
+                self.remove(j);
+            }
+            // the first instruction is kept, which may be a jump target
+            self.instrs[start] = Instr {
+                op: LOAD_CONST,
+                arg: idx as u32,
+                target: None,
+            };
+            changed = true;
+        }
+        changed
+    }
+
+    /// `LOAD_CONST x; POP_TOP` -> (nothing)
+    fn remove_const_pops(&mut self) -> bool {
+        let is_target = self.jump_targets();
+        let mut changed = false;
+        let len = self.instrs.len();
+        for (i, &is_target) in is_target.iter().enumerate().take(len).skip(1) {
+            let (prev, instr) = (self.instrs[i - 1], self.instrs[i]);
+            if (prev.op, instr.op) == (LOAD_CONST, POP_TOP) && !is_target {
+                self.remove(i - 1);
+                self.remove(i);
+                changed = true;
+            }
+        }
+        changed
+    }
+
+    /// Jumps to an unconditional jump are redirected to its target, and unconditional jumps to the next instruction are removed
+    fn thread_jumps(&mut self) -> bool {
+        let mut changed = false;
+        for i in 0..self.instrs.len() {
+            let Some(mut target) = self.instrs[i].target else {
+                continue;
+            };
+            // the loop count is limited because of infinite loops (e.g. `JUMP_ABSOLUTE` to itself)
+            for _ in 0..self.instrs.len() {
+                match self.instrs.get(target) {
+                    Some(next) if is_unconditional_jump(next.op) && next.target != Some(target) => {
+                        target = next.target.unwrap();
+                    }
+                    _ => break,
+                }
+            }
+            let instr = &mut self.instrs[i];
+            if instr.target != Some(target) {
+                if target > i || !is_forward_jump(instr.op) {
+                    instr.target = Some(target);
+                    changed = true;
+                } else if instr.op == JUMP_FORWARD {
+                    instr.op = JUMP_ABSOLUTE;
+                    instr.target = Some(target);
+                    changed = true;
+                }
+            }
+            if is_unconditional_jump(self.instrs[i].op) && self.instrs[i].target == Some(i + 1) {
+                self.remove(i);
+                changed = true;
+            }
+        }
+        changed
+    }
+
+    /// Removes the instructions which are never executed (e.g. after `RETURN_VALUE`)
+    fn remove_unreachable(&mut self) -> bool {
+        let mut reachable = vec![false; self.instrs.len() + 1];
+        let mut stack = vec![0];
+        stack.extend(self.exception_table.iter().map(|entry| entry.target));
+        while let Some(i) = stack.pop() {
+            if i >= self.instrs.len() || reachable[i] {
+                continue;
+            }
+            reachable[i] = true;
+            let instr = self.instrs[i];
+            if !is_terminal(instr.op) {
+                stack.push(i + 1);
+            }
+            if let Some(target) = instr.target {
+                stack.push(target);
+            }
+        }
+        let mut changed = false;
+        for (instr, reachable) in self.instrs.iter_mut().zip(reachable) {
+            if !reachable && instr.op != NOP {
+                instr.op = NOP;
+                instr.target = None;
+                changed = true;
+            }
+        }
+        changed
+    }
+
+    /// Removes `NOP`s. The references to them are moved to the next instruction
+    fn remove_nops(&mut self) {
+        // `new_index[i]`: the number of instructions kept before `i`
+        let mut new_index = Vec::with_capacity(self.instrs.len() + 1);
+        let mut kept = 0;
+        for instr in self.instrs.iter() {
+            new_index.push(kept);
+            if instr.op != NOP {
+                kept += 1;
+            }
+        }
+        new_index.push(kept);
+        self.instrs.retain(|instr| instr.op != NOP);
+        for instr in self.instrs.iter_mut() {
+            if let Some(target) = instr.target.as_mut() {
+                *target = new_index[*target];
+            }
+        }
+        for (idx, _) in self.lines.iter_mut() {
+            *idx = new_index[*idx];
+        }
+        for entry in self.exception_table.iter_mut() {
+            entry.start = new_index[entry.start];
+            entry.end = new_index[entry.end];
+            entry.target = new_index[entry.target];
+        }
+        for span in self.spans.iter_mut() {
+            span.start = new_index[span.start];
+            span.end = new_index[span.end];
+        }
+    }
+
+    /// Writes the instructions back to `code` (`EXTENDED_ARG`s are inserted as needed)
+    fn write_to(self, code: &mut CodeObj) {
+        // The sizes of jumps depend on the offsets, which depend on the sizes. Iterate until they converge
+        let mut instrs = self.instrs;
+        let mut units = instrs
+            .iter()
+            .map(|instr| ext_len(instr.arg) + 1)
+            .collect::<Vec<_>>();
+        let mut offsets;
+        loop {
+            offsets = Vec::with_capacity(instrs.len() + 1);
+            let mut offset = 0;
+            for u in units.iter() {
+                offsets.push(offset);
+                offset += u;
+            }
+            offsets.push(offset);
+            let mut changed = false;
+            for (i, instr) in instrs.iter_mut().enumerate() {
+                if let Some(target) = instr.target {
+                    instr.arg = if is_forward_jump(instr.op) {
+                        offsets[target] - offsets[i + 1]
+                    } else {
+                        offsets[target]
+                    } as u32;
+                    let new_units = ext_len(instr.arg) + 1;
+                    if new_units != units[i] {
+                        units[i] = new_units;
+                        changed = true;
+                    }
+                }
+            }
+            if !changed {
+                break;
+            }
+        }
+        let mut bytes = Vec::with_capacity(offsets[instrs.len()] * 2);
+        for instr in instrs.iter() {
+            for i in (1..=ext_len(instr.arg)).rev() {
+                bytes.push(EXTENDED_ARG as u8);
+                bytes.push((instr.arg >> (8 * i)) as u8);
+            }
+            bytes.push(instr.op as u8);
+            bytes.push(instr.arg as u8);
+        }
+        let addr = |idx: usize| offsets[idx] * 2;
+        let mut lines: Vec<(usize, i64)> = vec![];
+        for (idx, line) in self.lines {
+            let addr = addr(idx);
+            // the lines of the removed code at the end
+            if addr != 0 && addr >= bytes.len() {
+                continue;
+            }
+            match lines.last_mut() {
+                Some(last) if last.0 == addr => last.1 = line,
+                _ => lines.push((addr, line)),
+            }
+        }
+        code.lnotab = lines_to_lnotab(&lines, code.firstlineno);
+        code.exception_table = self
+            .exception_table
+            .into_iter()
+            .map(|entry| {
+                ExceptionTableEntry::new(
+                    addr(entry.start),
+                    addr(entry.end),
+                    addr(entry.target),
+                    entry.depth,
+                    entry.lasti,
+                )
+            })
+            .collect();
+        code.spans = self
+            .spans
+            .into_iter()
+            .map(|span| CodeSpan::new(addr(span.start), addr(span.end), span.loc))
+            .collect();
+        code.code = bytes;
+    }
+}
+
+/// Optimizes the bytecode of `code` and the nested code objects:
+/// * `LOAD_CONST`/`POP_TOP` pairs are removed
+/// * jumps to unconditional jumps are redirected to the final targets
+/// * tuples of constants are folded into a constant
+/// * unreachable instructions (e.g. after `RETURN_VALUE`) are removed
+pub fn peephole(code: &mut CodeObj) {
+    for cons in code.consts.iter_mut() {
+        if let ValueObj::Code(inner) = cons {
+            peephole(inner);
+        }
+    }
+    let mut p = Peephole::new(code);
+    loop {
+        let mut changed = p.fold_tuples(&mut code.consts);
+        changed |= p.remove_const_pops();
+        changed |= p.thread_jumps();
+        changed |= p.remove_unreachable();
+        p.remove_nops();
+        if !changed {
+            break;
+        }
+    }
+    p.write_to(code);
+}
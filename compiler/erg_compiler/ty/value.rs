@@ -122,7 +122,10 @@ pub enum ValueObj {
     Int(i32),
     Nat(u64),
     Float(f64),
+    /// `real + imag j` (only loaded from .pyc files)
+    Complex(f64, f64),
     Str(Str),
+    Bytes(Rc<[u8]>),
     Bool(bool),
     Array(Rc<[ValueObj]>),
     Set(Set<ValueObj>),
@@ -167,7 +170,22 @@ impl fmt::Debug for ValueObj {
                     write!(f, "{fl}f")
                 }
             }
+            Self::Complex(re, im) => write!(f, "({re}{im:+}j)"),
             Self::Str(s) => write!(f, "\"{s}\""),
+            Self::Bytes(bytes) => {
+                write!(f, "b\"")?;
+                for b in bytes.iter() {
+                    match *b {
+                        b'"' | b'\\' => write!(f, "\\{}", *b as char)?,
+                        b'\n' => write!(f, "\\n")?,
+                        b'\t' => write!(f, "\\t")?,
+                        b'\r' => write!(f, "\\r")?,
+                        0x20..=0x7E => write!(f, "{}", *b as char)?,
+                        _ => write!(f, "\\x{b:02x}")?,
+                    }
+                }
+                write!(f, "\"")
+            }
             Self::Bool(b) => {
                 if *b {
                     write!(f, "True")
@@ -239,7 +257,12 @@ impl Hash for ValueObj {
             Self::Nat(n) => n.hash(state),
             // TODO:
             Self::Float(f) => f.to_bits().hash(state),
+            Self::Complex(re, im) => {
+                re.to_bits().hash(state);
+                im.to_bits().hash(state);
+            }
             Self::Str(s) => s.hash(state),
+            Self::Bytes(bytes) => bytes.hash(state),
             Self::Bool(b) => b.hash(state),
             Self::Array(arr) => arr.hash(state),
             Self::Dict(dict) => dict.hash(state),
@@ -464,7 +487,7 @@ impl ValueObj {
         }
     }
 
-    pub fn into_bytes(self) -> Vec<u8> {
+    pub fn into_bytes(self, python_ver: u32) -> Vec<u8> {
         match self {
             Self::Int(i) => [vec![DataTypePrefix::Int32 as u8], i.to_le_bytes().to_vec()].concat(),
             // TODO: Natとしてシリアライズ
@@ -478,7 +501,14 @@ impl ValueObj {
                 f.to_le_bytes().to_vec(),
             ]
             .concat(),
+            Self::Complex(re, im) => [
+                vec![DataTypePrefix::BinComplex as u8],
+                re.to_le_bytes().to_vec(),
+                im.to_le_bytes().to_vec(),
+            ]
+            .concat(),
             Self::Str(s) => str_into_bytes(s, false),
+            Self::Bytes(bytes) => raw_string_into_bytes(bytes.to_vec()),
             Self::Bool(true) => vec![DataTypePrefix::True as u8],
             Self::Bool(false) => vec![DataTypePrefix::False as u8],
             // TODO: SmallTuple
@@ -487,7 +517,7 @@ impl ValueObj {
                 bytes.push(DataTypePrefix::Tuple as u8);
                 bytes.append(&mut (arr.len() as u32).to_le_bytes().to_vec());
                 for obj in arr.iter().cloned() {
-                    bytes.append(&mut obj.into_bytes());
+                    bytes.append(&mut obj.into_bytes(python_ver));
                 }
                 bytes
             }
@@ -496,14 +526,14 @@ impl ValueObj {
                 bytes.push(DataTypePrefix::Tuple as u8);
                 bytes.append(&mut (tup.len() as u32).to_le_bytes().to_vec());
                 for obj in tup.iter().cloned() {
-                    bytes.append(&mut obj.into_bytes());
+                    bytes.append(&mut obj.into_bytes(python_ver));
                 }
                 bytes
             }
             Self::None => {
                 vec![DataTypePrefix::None as u8]
             }
-            Self::Code(c) => c.into_bytes(3425),
+            Self::Code(c) => c.into_bytes(python_ver),
             // Dict
             other => {
                 panic!(
@@ -524,7 +554,9 @@ impl ValueObj {
             Self::Int(_) => Type::Int,
             Self::Nat(_) => Type::Nat,
             Self::Float(_) => Type::Float,
+            Self::Complex(_, _) => mono("Complex"),
             Self::Str(_) => Type::Str,
+            Self::Bytes(_) => mono("Bytes"),
             Self::Bool(_) => Type::Bool,
             // TODO: Zero
             Self::Array(arr) => array_t(
@@ -583,7 +615,7 @@ impl ValueObj {
             // REVIEW: 等しいとみなしてよいのか?
             (Self::Inf, Self::Inf) | (Self::NegInf, Self::NegInf) => Some(Ordering::Equal),
             (Self::Mut(m), other) => m.borrow().try_cmp(other),
-            (self_, Self::Mut(m)) => self_.try_cmp(&*m.borrow()),
+            (self_, Self::Mut(m)) => self_.try_cmp(&m.borrow()),
             /* (Self::PlusEpsilon(l), r) => l.try_cmp(r)
                 .map(|o| if matches!(o, Ordering::Equal) { Ordering::Less } else { o }),
             (l, Self::PlusEpsilon(r)) => l.try_cmp(r)
@@ -611,10 +643,10 @@ impl ValueObj {
             (Self::Float(l), Self::Float(r)) => Some(Self::Float(l + r)),
             (Self::Int(l), Self::Nat(r)) => Some(Self::from(l + r as i32)),
             (Self::Nat(l), Self::Int(r)) => Some(Self::Int(l as i32 + r)),
-            (Self::Float(l), Self::Nat(r)) => Some(Self::Float(l - r as f64)),
-            (Self::Int(l), Self::Float(r)) => Some(Self::Float(l as f64 - r)),
-            (Self::Nat(l), Self::Float(r)) => Some(Self::Float(l as f64 - r)),
-            (Self::Float(l), Self::Int(r)) => Some(Self::Float(l - r as f64)),
+            (Self::Float(l), Self::Nat(r)) => Some(Self::Float(l + r as f64)),
+            (Self::Int(l), Self::Float(r)) => Some(Self::Float(l as f64 + r)),
+            (Self::Nat(l), Self::Float(r)) => Some(Self::Float(l as f64 + r)),
+            (Self::Float(l), Self::Int(r)) => Some(Self::Float(l + r as f64)),
             (Self::Str(l), Self::Str(r)) => Some(Self::Str(Str::from(format!("{}{}", l, r)))),
             (inf @ (Self::Inf | Self::NegInf), _) | (_, inf @ (Self::Inf | Self::NegInf)) => {
                 Some(inf)
@@ -634,7 +666,7 @@ impl ValueObj {
     pub fn try_sub(self, other: Self) -> Option<Self> {
         match (self, other) {
             (Self::Int(l), Self::Int(r)) => Some(Self::Int(l - r)),
-            (Self::Nat(l), Self::Nat(r)) => Some(Self::Int((l - r) as i32)),
+            (Self::Nat(l), Self::Nat(r)) => Some(Self::Int(l as i32 - r as i32)),
             (Self::Float(l), Self::Float(r)) => Some(Self::Float(l - r)),
             (Self::Int(l), Self::Nat(r)) => Some(Self::from(l - r as i32)),
             (Self::Nat(l), Self::Int(r)) => Some(Self::from(l as i32 - r)),
@@ -938,17 +970,13 @@ pub mod value_set {
         if !is_homogeneous(set) {
             return None;
         }
-        set.iter()
-            .max_by(|x, y| x.try_cmp(y).unwrap())
-            .map(Clone::clone)
+        set.iter().max_by(|x, y| x.try_cmp(y).unwrap()).cloned()
     }
 
     pub fn min(set: &Set<ValueObj>) -> Option<ValueObj> {
         if !is_homogeneous(set) {
             return None;
         }
-        set.iter()
-            .min_by(|x, y| x.try_cmp(y).unwrap())
-            .map(Clone::clone)
+        set.iter().min_by(|x, y| x.try_cmp(y).unwrap()).cloned()
     }
 }
@@ -0,0 +1,379 @@
+//! verifies the bytecode of `CodeObj` (the stack depth, the jump targets and the operand indices).
+//!
+//! `CodeObj`のバイトコードを検証する(スタックの深さ、ジャンプ先、オペランドのインデックス)
+//!
+//! CPython does not verify the bytecode, so these mistakes cause a segfault or a `SystemError` at runtime.
+use erg_common::astr::AtomicStr;
+use erg_common::error::{ErrorCore, ErrorKind, Location};
+use erg_common::opcode::Opcode::{self, *};
+use erg_common::python_util::PythonVersion;
+use erg_common::{fn_name, switch_lang};
+
+use super::codeobj::{localsplus, CodeObj, CodeObjFlags};
+use super::deserialize::ByteReader;
+use super::disassemble::{disassemble, Instruction};
+use super::value::ValueObj;
+
+#[derive(Debug)]
+pub struct BytecodeError {
+    pub errno: usize,
+    pub caused_by: AtomicStr,
+    pub desc: AtomicStr,
+}
+
+impl From<BytecodeError> for ErrorCore {
+    fn from(err: BytecodeError) -> Self {
+        ErrorCore::new(
+            err.errno,
+            ErrorKind::BytecodeError,
+            Location::Unknown,
+            err.desc,
+            Option::<AtomicStr>::None,
+        )
+    }
+}
+
+/// e.g. `<module>:12 (LOAD_CONST)`
+fn instr_loc(code: &CodeObj, instr: &Instruction) -> String {
+    format!("{}:{} ({})", code.name, instr.offset, instr.opname())
+}
+
+impl BytecodeError {
+    pub fn new<S: Into<AtomicStr>, T: Into<AtomicStr>>(
+        errno: usize,
+        caused_by: S,
+        desc: T,
+    ) -> Self {
+        Self {
+            errno,
+            caused_by: caused_by.into(),
+            desc: desc.into(),
+        }
+    }
+
+    pub fn unknown_opcode_error(code: &CodeObj, instr: &Instruction) -> Self {
+        let loc = instr_loc(code, instr);
+        Self::new(
+            0,
+            fn_name!(),
+            switch_lang!(
+                "japanese" => format!("{loc}: 未知のオペコードです"),
+                "simplified_chinese" => format!("{loc}: 未知的操作码"),
+                "traditional_chinese" => format!("{loc}: 未知的操作碼"),
+                "english" => format!("{loc}: unknown opcode"),
+            ),
+        )
+    }
+
+    pub fn index_out_of_range_error(
+        code: &CodeObj,
+        instr: &Instruction,
+        table: &str,
+        idx: u32,
+        len: usize,
+    ) -> Self {
+        let loc = instr_loc(code, instr);
+        Self::new(
+            0,
+            fn_name!(),
+            switch_lang!(
+                "japanese" => format!("{loc}: インデックス{idx}は{table}の範囲外です(要素数: {len})"),
+                "simplified_chinese" => format!("{loc}: 索引{idx}超出{table}的范围（元素数: {len}）"),
+                "traditional_chinese" => format!("{loc}: 索引{idx}超出{table}的範圍（元素數: {len}）"),
+                "english" => format!("{loc}: the index {idx} is out of range of {table} (len: {len})"),
+            ),
+        )
+    }
+
+    pub fn invalid_jump_target_error(code: &CodeObj, instr: &Instruction, target: usize) -> Self {
+        let loc = instr_loc(code, instr);
+        Self::new(
+            0,
+            fn_name!(),
+            switch_lang!(
+                "japanese" => format!("{loc}: ジャンプ先{target}は命令の先頭ではありません"),
+                "simplified_chinese" => format!("{loc}: 跳转目标{target}不是指令的开头"),
+                "traditional_chinese" => format!("{loc}: 跳轉目標{target}不是指令的開頭"),
+                "english" => format!("{loc}: the jump target {target} is not the start of an instruction"),
+            ),
+        )
+    }
+
+    pub fn stack_underflow_error(code: &CodeObj, instr: &Instruction, depth: u32) -> Self {
+        let loc = instr_loc(code, instr);
+        Self::new(
+            0,
+            fn_name!(),
+            switch_lang!(
+                "japanese" => format!("{loc}: スタックが空です(深さ: {depth})"),
+                "simplified_chinese" => format!("{loc}: 堆栈下溢（深度: {depth}）"),
+                "traditional_chinese" => format!("{loc}: 堆棧下溢（深度: {depth}）"),
+                "english" => format!("{loc}: stack underflow (depth: {depth})"),
+            ),
+        )
+    }
+
+    pub fn inconsistent_depth_error(
+        code: &CodeObj,
+        instr: &Instruction,
+        expect: u32,
+        found: u32,
+    ) -> Self {
+        let loc = instr_loc(code, instr);
+        Self::new(
+            0,
+            fn_name!(),
+            switch_lang!(
+                "japanese" => format!("{loc}: スタックの深さが経路によって異なります({expect}と{found})"),
+                "simplified_chinese" => format!("{loc}: 堆栈深度因路径而异（{expect}和{found}）"),
+                "traditional_chinese" => format!("{loc}: 堆棧深度因路徑而異（{expect}和{found}）"),
+                "english" => format!("{loc}: the stack depth differs between paths ({expect} and {found})"),
+            ),
+        )
+    }
+
+    pub fn fall_off_error(code: &CodeObj) -> Self {
+        let name = &code.name;
+        Self::new(
+            0,
+            fn_name!(),
+            switch_lang!(
+                "japanese" => format!("{name}: 実行がコードの末尾を越えます"),
+                "simplified_chinese" => format!("{name}: 执行越过了代码的末尾"),
+                "traditional_chinese" => format!("{name}: 執行越過了代碼的末尾"),
+                "english" => format!("{name}: the execution falls off the end of the code"),
+            ),
+        )
+    }
+
+    pub fn stacksize_error(code: &CodeObj, instr: &Instruction, depth: u32) -> Self {
+        let loc = instr_loc(code, instr);
+        let stacksize = code.stacksize;
+        Self::new(
+            0,
+            fn_name!(),
+            switch_lang!(
+                "japanese" => format!("{loc}: スタックの深さ{depth}がstacksize({stacksize})を超えています"),
+                "simplified_chinese" => format!("{loc}: 堆栈深度{depth}超过了stacksize（{stacksize}）"),
+                "traditional_chinese" => format!("{loc}: 堆棧深度{depth}超過了stacksize（{stacksize}）"),
+                "english" => format!("{loc}: the stack depth {depth} exceeds the stacksize ({stacksize})"),
+            ),
+        )
+    }
+}
+
+pub type BytecodeResult<T> = Result<T, BytecodeError>;
+
+/// The index of the instruction at `offset`.
+/// Jumping into the middle of `EXTENDED_ARG`s (or into `CACHE`s) is invalid
+fn instr_index(instrs: &[Instruction], offset: usize) -> Option<usize> {
+    let idx = instrs
+        .binary_search_by_key(&offset, |instr| instr.offset)
+        .ok()?;
+    if idx > 0 && instrs[idx - 1].op == Some(EXTENDED_ARG) {
+        None
+    } else {
+        Some(idx)
+    }
+}
+
+/// The instructions after which the execution does not continue
+fn is_terminal(op: Opcode) -> bool {
+    matches!(
+        op,
+        RETURN_VALUE
+            | RAISE_VARARGS
+            | RERAISE
+            | JUMP_FORWARD
+            | JUMP_ABSOLUTE
+            | JUMP_BACKWARD
+            | JUMP_BACKWARD_NO_INTERRUPT
+    )
+}
+
+/// Checks the opcodes, the indices of `co_consts`, `co_names`, etc., and the jump targets
+fn check_operands(
+    code: &CodeObj,
+    instrs: &[Instruction],
+    ver: PythonVersion,
+) -> BytecodeResult<()> {
+    let is_311 = ver >= PythonVersion::V3_11;
+    // in 3.11+, the local, cell and free variables are in `localsplus`
+    let (locals_len, derefs_len) = if is_311 {
+        let len = localsplus(&code.varnames, &code.cellvars, &code.freevars)
+            .0
+            .len();
+        (len, len)
+    } else {
+        (
+            code.varnames.len(),
+            code.cellvars.len() + code.freevars.len(),
+        )
+    };
+    for instr in instrs.iter() {
+        let Some(op) = instr.op else {
+            return Err(BytecodeError::unknown_opcode_error(code, instr));
+        };
+        if let Some(target) = instr.jump_target {
+            if instr_index(instrs, target).is_none() {
+                return Err(BytecodeError::invalid_jump_target_error(
+                    code, instr, target,
+                ));
+            }
+        }
+        let Some(arg) = instr.arg else {
+            continue;
+        };
+        let (table, idx, len) = match op {
+            LOAD_CONST | KW_NAMES => ("co_consts", arg, code.consts.len()),
+            LOAD_GLOBAL if is_311 => ("co_names", arg >> 1, code.names.len()),
+            STORE_NAME | DELETE_NAME | STORE_ATTR | DELETE_ATTR | STORE_GLOBAL | LOAD_NAME
+            | LOAD_ATTR | IMPORT_NAME | IMPORT_FROM | LOAD_GLOBAL | LOAD_METHOD => {
+                ("co_names", arg, code.names.len())
+            }
+            LOAD_FAST | STORE_FAST | DELETE_FAST => ("co_varnames", arg, locals_len),
+            LOAD_CLOSURE | LOAD_DEREF | STORE_DEREF | MAKE_CELL => {
+                ("co_cellvars + co_freevars", arg, derefs_len)
+            }
+            _ => continue,
+        };
+        if idx as usize >= len {
+            return Err(BytecodeError::index_out_of_range_error(
+                code, instr, table, idx, len,
+            ));
+        }
+    }
+    if let Some(instr) = instrs.last() {
+        for entry in code.exception_table.iter() {
+            if instr_index(instrs, entry.target).is_none() {
+                return Err(BytecodeError::invalid_jump_target_error(
+                    code,
+                    instr,
+                    entry.target,
+                ));
+            }
+        }
+    }
+    Ok(())
+}
+
+fn apply_effect(
+    code: &CodeObj,
+    instr: &Instruction,
+    depth: u32,
+    effect: i32,
+) -> BytecodeResult<u32> {
+    let new_depth = depth as i64 + effect as i64;
+    if new_depth < 0 {
+        Err(BytecodeError::stack_underflow_error(code, instr, depth))
+    } else {
+        Ok(new_depth as u32)
+    }
+}
+
+/// Simulates the stack effects along all the paths of the code,
+/// and returns the stack depth before each instruction (`None` if unreachable).
+/// The depth at an instruction must be the same in all paths to it.
+fn simulate(
+    code: &CodeObj,
+    instrs: &[Instruction],
+    ver: PythonVersion,
+) -> BytecodeResult<Vec<Option<u32>>> {
+    let mut depths: Vec<Option<u32>> = vec![None; instrs.len()];
+    // in 3.10+, generators start with the sent value (popped by `GEN_START` or `POP_TOP`)
+    let is_generator = [
+        CodeObjFlags::Generator,
+        CodeObjFlags::Coroutine,
+        CodeObjFlags::AsyncGenerator,
+    ]
+    .iter()
+    .any(|flag| flag.is_in(code.flags));
+    let start_depth = (is_generator && ver >= PythonVersion::V3_10) as u32;
+    // (index of the instruction, depth)
+    let mut pending = vec![(0, start_depth)];
+    while let Some((mut idx, mut depth)) = pending.pop() {
+        loop {
+            let Some(instr) = instrs.get(idx) else {
+                return Err(BytecodeError::fall_off_error(code));
+            };
+            match depths[idx] {
+                Some(prev) if prev == depth => break,
+                // until 3.7, `END_FINALLY` pops a variable number of values
+                // (CPython takes the maximum depth of the paths)
+                Some(prev) if ver <= PythonVersion::V3_7 && depth < prev => break,
+                Some(prev) if ver > PythonVersion::V3_7 => {
+                    return Err(BytecodeError::inconsistent_depth_error(
+                        code, instr, prev, depth,
+                    ));
+                }
+                _ => depths[idx] = Some(depth),
+            }
+            let Some(op) = instr.op else {
+                return Err(BytecodeError::unknown_opcode_error(code, instr));
+            };
+            let arg = instr.arg.unwrap_or(0);
+            // the stack is popped to `entry.depth`, and the exception (and `lasti`) are pushed
+            for entry in code
+                .exception_table
+                .iter()
+                .filter(|entry| entry.start <= instr.offset && instr.offset < entry.end)
+            {
+                let target = instr_index(instrs, entry.target).unwrap();
+                pending.push((target, entry.depth + entry.lasti as u32 + 1));
+            }
+            if let Some(target) = instr.jump_target {
+                let Some(effect) = op.stack_effect(arg, true, ver) else {
+                    return Err(BytecodeError::unknown_opcode_error(code, instr));
+                };
+                let target_depth = apply_effect(code, instr, depth, effect)?;
+                pending.push((instr_index(instrs, target).unwrap(), target_depth));
+            }
+            if is_terminal(op) {
+                break;
+            }
+            let Some(effect) = op.stack_effect(arg, false, ver) else {
+                return Err(BytecodeError::unknown_opcode_error(code, instr));
+            };
+            depth = match apply_effect(code, instr, depth, effect) {
+                Ok(depth) => depth,
+                // in 3.7, `END_FINALLY` pops `None` (the normal path) or 6 values (the exception path)
+                Err(_) if op == END_FINALLY && ver <= PythonVersion::V3_7 => 0,
+                Err(err) => return Err(err),
+            };
+            idx += 1;
+        }
+    }
+    Ok(depths)
+}
+
+/// Verifies `code` and the nested code objects in the format of `ver` (e.g. read from a .pyc file),
+/// and returns the maximum stack depth of `code`.
+pub fn verify(code: &CodeObj, ver: PythonVersion) -> BytecodeResult<u32> {
+    for cons in code.consts.iter() {
+        if let ValueObj::Code(inner) = cons {
+            verify(inner, ver)?;
+        }
+    }
+    let instrs = disassemble(code, Some(ver));
+    check_operands(code, &instrs, ver)?;
+    let depths = simulate(code, &instrs, ver)?;
+    let mut max_depth = 0;
+    for (instr, depth) in instrs.iter().zip(depths) {
+        let Some(depth) = depth else {
+            continue;
+        };
+        if depth > code.stacksize {
+            return Err(BytecodeError::stacksize_error(code, instr, depth));
+        }
+        max_depth = max_depth.max(depth);
+    }
+    Ok(max_depth)
+}
+
+/// Verifies the code object generated by `CodeGenerator` as it is written into a .pyc file of `python_ver`
+pub fn verify_generated(code: &CodeObj, python_ver: u32) -> BytecodeResult<u32> {
+    let bytes = code.clone().into_bytes(python_ver);
+    let code = CodeObj::from_bytes(&mut ByteReader::new(&bytes), python_ver)
+        .map_err(|err| BytecodeError::new(err.errno, err.caused_by, err.desc))?;
+    verify(&code, PythonVersion::from_checked_magic_num(python_ver))
+}
@@ -4,7 +4,6 @@ use std::fmt;
 use std::fmt::Write as _;
 
 use erg_common::error::Location;
-use erg_common::set::Set as HashSet;
 use erg_common::traits::{Locational, NestedDisplay, Stream};
 use erg_common::vis::{Field, Visibility};
 use erg_common::{
@@ -15,7 +14,7 @@ use erg_common::{
 };
 use erg_common::{fmt_vec_split_with, Str};
 
-use crate::token::{Token, TokenKind};
+use crate::token::{Token, TokenCategory, TokenKind};
 
 pub fn fmt_lines<'a, T: NestedDisplay + 'a>(
     mut iter: impl Iterator<Item = &'a T>,
@@ -71,6 +70,59 @@ impl Literal {
     }
 }
 
+/// String interpolation
+/// e.g. `"Hello, \{name}!"`
+///
+/// `segments` are `StrInterpLeft`, `StrInterpMid`s and `StrInterpRight`,
+/// and `exprs[i]` is placed between `segments[i]` and `segments[i + 1]`.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct StrInterp {
+    pub segments: Vec<Token>,
+    pub exprs: Vec<Expr>,
+}
+
+impl NestedDisplay for StrInterp {
+    fn fmt_nest(&self, f: &mut fmt::Formatter<'_>, _level: usize) -> fmt::Result {
+        for (segment, expr) in self.segments.iter().zip(self.exprs.iter()) {
+            write!(f, "{}{expr}", segment.content)?;
+        }
+        write!(f, "{}", self.segments.last().unwrap().content)
+    }
+}
+
+impl_display_from_nested!(StrInterp);
+
+impl Locational for StrInterp {
+    fn loc(&self) -> Location {
+        Location::concat(
+            self.segments.first().unwrap(),
+            self.segments.last().unwrap(),
+        )
+    }
+}
+
+impl StrInterp {
+    pub fn new(segments: Vec<Token>, exprs: Vec<Expr>) -> Self {
+        debug_assert_eq!(segments.len(), exprs.len() + 1);
+        Self { segments, exprs }
+    }
+
+    /// The contents of the segments without the delimiters (`"`, `\{`, `}`)
+    pub fn fragments(&self) -> impl Iterator<Item = &str> {
+        self.segments.iter().map(|segment| {
+            let content = &segment.content[..];
+            let content = content
+                .strip_suffix("\\{")
+                .or_else(|| content.strip_suffix('"'))
+                .unwrap_or(content);
+            content
+                .strip_prefix('"')
+                .or_else(|| content.strip_prefix('}'))
+                .unwrap_or(content)
+        })
+    }
+}
+
 #[derive(Clone, Debug, PartialEq, Eq, Hash)]
 pub struct PosArg {
     pub expr: Expr,
@@ -144,9 +196,15 @@ impl Locational for Args {
     fn loc(&self) -> Location {
         if let Some((l, r)) = &self.paren {
             Location::concat(l, r)
+        } else if !self.kw_args.is_empty() {
+            Location::concat(self.kw_args.first().unwrap(), self.kw_args.last().unwrap())
+        } else if !self.pos_args.is_empty() {
+            Location::concat(
+                self.pos_args.first().unwrap(),
+                self.pos_args.last().unwrap(),
+            )
         } else {
-            // TODO: kw, var_args
-            Location::concat(&self.pos_args[0], self.pos_args.last().unwrap())
+            Location::Unknown
         }
     }
 }
@@ -233,7 +291,7 @@ impl Args {
 
     pub fn get_left_or_key(&self, key: &str) -> Option<&Expr> {
         if !self.pos_args.is_empty() {
-            self.pos_args.get(0).map(|a| &a.expr)
+            self.pos_args.first().map(|a| &a.expr)
         } else {
             self.kw_args.iter().find_map(|a| {
                 if &a.keyword.content[..] == key {
@@ -505,18 +563,25 @@ pub struct ArrayComprehension {
     pub guards: Vec<Expr>,
 }
 
+/// `i <- xs; j <- ys; i + j > 0`
+fn fmt_comprehension_clauses(generators: &[(Identifier, Expr)], guards: &[Expr]) -> String {
+    let mut clauses = String::new();
+    for (name, gen) in generators.iter() {
+        write!(clauses, "{name} <- {gen}; ").unwrap();
+    }
+    for guard in guards.iter() {
+        write!(clauses, "{guard}; ").unwrap();
+    }
+    clauses.trim_end_matches("; ").to_string()
+}
+
 impl NestedDisplay for ArrayComprehension {
     fn fmt_nest(&self, f: &mut fmt::Formatter<'_>, _level: usize) -> fmt::Result {
-        let mut generators = String::new();
-        for (name, gen) in self.generators.iter() {
-            write!(generators, "{} <- {}, ", name, gen).unwrap();
-        }
         write!(
             f,
-            "[{}| {}{}]",
+            "[{} | {}]",
             self.elem,
-            generators,
-            fmt_vec(&self.guards)
+            fmt_comprehension_clauses(&self.generators, &self.guards)
         )
     }
 }
@@ -642,16 +707,23 @@ impl NormalDict {
 
 #[derive(Clone, Debug, PartialEq, Eq, Hash)]
 pub struct DictComprehension {
-    l_brace: Token,
-    r_brace: Token,
-    pub attrs: Args,
-    guards: Vec<Expr>,
+    pub l_brace: Token,
+    pub r_brace: Token,
+    pub key: Box<Expr>,
+    pub value: Box<Expr>,
+    pub generators: Vec<(Identifier, Expr)>,
+    pub guards: Vec<Expr>,
 }
 
-// TODO:
 impl NestedDisplay for DictComprehension {
     fn fmt_nest(&self, f: &mut fmt::Formatter<'_>, _level: usize) -> fmt::Result {
-        write!(f, "{{{} | {}}}", self.attrs, fmt_vec(&self.guards))
+        write!(
+            f,
+            "{{{}: {} | {}}}",
+            self.key,
+            self.value,
+            fmt_comprehension_clauses(&self.generators, &self.guards)
+        )
     }
 }
 
@@ -659,11 +731,19 @@ impl_display_from_nested!(DictComprehension);
 impl_locational!(DictComprehension, l_brace, r_brace);
 
 impl DictComprehension {
-    pub fn new(l_brace: Token, r_brace: Token, attrs: Args, guards: Vec<Expr>) -> Self {
+    pub fn new(
+        l_brace: Token,
+        r_brace: Token,
+        kv: KeyValue,
+        generators: Vec<(Identifier, Expr)>,
+        guards: Vec<Expr>,
+    ) -> Self {
         Self {
             l_brace,
             r_brace,
-            attrs,
+            key: Box::new(kv.key),
+            value: Box::new(kv.value),
+            generators,
             guards,
         }
     }
@@ -848,16 +928,57 @@ impl SetWithLength {
     }
 }
 
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct SetComprehension {
+    pub l_brace: Token,
+    pub r_brace: Token,
+    pub elem: Box<Expr>,
+    pub generators: Vec<(Identifier, Expr)>,
+    pub guards: Vec<Expr>,
+}
+
+impl NestedDisplay for SetComprehension {
+    fn fmt_nest(&self, f: &mut fmt::Formatter<'_>, _level: usize) -> fmt::Result {
+        write!(
+            f,
+            "{{{} | {}}}",
+            self.elem,
+            fmt_comprehension_clauses(&self.generators, &self.guards)
+        )
+    }
+}
+
+impl_display_from_nested!(SetComprehension);
+impl_locational!(SetComprehension, l_brace, r_brace);
+
+impl SetComprehension {
+    pub fn new(
+        l_brace: Token,
+        r_brace: Token,
+        elem: Expr,
+        generators: Vec<(Identifier, Expr)>,
+        guards: Vec<Expr>,
+    ) -> Self {
+        Self {
+            l_brace,
+            r_brace,
+            elem: Box::new(elem),
+            generators,
+            guards,
+        }
+    }
+}
+
 #[derive(Debug, Clone, PartialEq, Eq, Hash)]
 pub enum Set {
     Normal(NormalSet),
     WithLength(SetWithLength),
-    // Comprehension(SetComprehension),
+    Comprehension(SetComprehension),
 }
 
-impl_nested_display_for_enum!(Set; Normal, WithLength);
-impl_display_for_enum!(Set; Normal, WithLength);
-impl_locational_for_enum!(Set; Normal, WithLength);
+impl_nested_display_for_enum!(Set; Normal, WithLength, Comprehension);
+impl_display_for_enum!(Set; Normal, WithLength, Comprehension);
+impl_locational_for_enum!(Set; Normal, WithLength, Comprehension);
 
 #[derive(Clone, Debug, PartialEq, Eq, Hash)]
 pub struct BinOp {
@@ -908,7 +1029,12 @@ impl_display_from_nested!(UnaryOp);
 
 impl Locational for UnaryOp {
     fn loc(&self) -> Location {
-        Location::concat(&self.op, self.args[0].as_ref())
+        // a postfix operator (`x?`) follows the operand
+        if self.op.category_is(TokenCategory::PostfixOp) {
+            Location::concat(self.args[0].as_ref(), &self.op)
+        } else {
+            Location::concat(&self.op, self.args[0].as_ref())
+        }
     }
 }
 
@@ -947,7 +1073,19 @@ impl_display_from_nested!(Call);
 
 impl Locational for Call {
     fn loc(&self) -> Location {
-        Location::concat(self.obj.as_ref(), &self.args)
+        // the arguments of a desugared pipeline call (`x |> f`) may precede the callee
+        let args_end = (self.args.ln_end(), self.args.col_end());
+        if let Some(attr_name) = self.attr_name.as_ref() {
+            if args_end > (attr_name.ln_end(), attr_name.col_end()) {
+                Location::concat(self.obj.as_ref(), &self.args)
+            } else {
+                Location::concat(self.obj.as_ref(), attr_name)
+            }
+        } else if args_end > (self.obj.ln_end(), self.obj.col_end()) {
+            Location::concat(self.obj.as_ref(), &self.args)
+        } else {
+            self.obj.loc()
+        }
     }
 }
 
@@ -967,6 +1105,27 @@ impl Call {
             .unwrap_or(false)
     }
 
+    /// `if`, `for!`, `match`, etc.: the bodies of the lambdas passed to them are inlined
+    pub fn is_control_flow(&self) -> bool {
+        self.obj
+            .get_name()
+            .map(|s| {
+                matches!(
+                    &s[..],
+                    "if" | "if!" | "for" | "for!" | "while!" | "match" | "match!" | "with!"
+                )
+            })
+            .unwrap_or(false)
+    }
+
+    /// `for!`, `while!`: `break` and `continue` in the bodies of the lambdas passed to them refer to the loop
+    pub fn is_loop(&self) -> bool {
+        self.obj
+            .get_name()
+            .map(|s| matches!(&s[..], "for" | "for!" | "while!"))
+            .unwrap_or(false)
+    }
+
     pub fn is_assert_cast(&self) -> bool {
         self.obj
             .get_name()
@@ -1700,6 +1859,51 @@ impl SetTypeSpec {
     }
 }
 
+/// e.g. `{I: Int | I >= 0}`, `{I: Int | I >= 0; I != 3}`
+///
+/// Multiple predicates (separated by `;`) are joined by `and`.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct RefinementTypeSpec {
+    pub l_brace: Token,
+    pub r_brace: Token,
+    pub var: Token,
+    pub typ: Box<TypeSpec>,
+    pub preds: Vec<ConstExpr>,
+}
+
+impl fmt::Display for RefinementTypeSpec {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{{{}: {} | ", self.var.content, self.typ)?;
+        for (i, pred) in self.preds.iter().enumerate() {
+            if i != 0 {
+                write!(f, "; ")?;
+            }
+            write!(f, "{pred}")?;
+        }
+        write!(f, "}}")
+    }
+}
+
+impl_locational!(RefinementTypeSpec, l_brace, r_brace);
+
+impl RefinementTypeSpec {
+    pub fn new(
+        l_brace: Token,
+        r_brace: Token,
+        var: Token,
+        typ: TypeSpec,
+        preds: Vec<ConstExpr>,
+    ) -> Self {
+        Self {
+            l_brace,
+            r_brace,
+            var,
+            typ: Box::new(typ),
+            preds,
+        }
+    }
+}
+
 /// * Array: `[Int; 3]`, `[Int, Ratio, Complex]`, etc.
 /// * Dict: `[Str: Str]`, etc.
 /// * And (Intersection type): Add and Sub and Mul (== Num), etc.
@@ -1707,6 +1911,7 @@ impl SetTypeSpec {
 /// * Or (Union type): Int or None (== Option Int), etc.
 /// * Enum: `{0, 1}` (== Binary), etc.
 /// * Range: 1..12, 0.0<..1.0, etc.
+/// * Refinement: {I: Int | I >= 0}, etc.
 /// * Record: {.into_s: Self.() -> Str }, etc.
 /// * Subr: Int -> Int, Int => None, T.(X) -> Int, etc.
 /// * TypeApp: F|...|
@@ -1728,6 +1933,7 @@ pub enum TypeSpec {
         lhs: ConstExpr,
         rhs: ConstExpr,
     },
+    Refinement(RefinementTypeSpec),
     // Record(),
     Subr(SubrTypeSpec),
     TypeApp {
@@ -1748,6 +1954,7 @@ impl fmt::Display for TypeSpec {
             Self::Tuple(tys) => write!(f, "({})", fmt_vec(tys)),
             Self::Enum(elems) => write!(f, "{{{elems}}}"),
             Self::Interval { op, lhs, rhs } => write!(f, "{lhs}{}{rhs}", op.inspect()),
+            Self::Refinement(refine) => write!(f, "{refine}"),
             Self::Subr(s) => write!(f, "{s}"),
             Self::TypeApp { spec, args } => write!(f, "{spec}{args}"),
         }
@@ -1767,6 +1974,7 @@ impl Locational for TypeSpec {
             Self::Tuple(tys) => Location::concat(tys.first().unwrap(), tys.last().unwrap()),
             Self::Enum(set) => set.loc(),
             Self::Interval { lhs, rhs, .. } => Location::concat(lhs, rhs),
+            Self::Refinement(refine) => refine.loc(),
             Self::Subr(s) => s.loc(),
             Self::TypeApp { spec, args } => Location::concat(spec.as_ref(), args),
         }
@@ -1888,6 +2096,19 @@ impl Decorator {
     pub fn into_expr(self) -> Expr {
         self.0
     }
+
+    /// Compile-time decorators (`Override`, `Impl`, etc.) only affect type checking and are not applied at runtime
+    pub fn is_compile_time(&self) -> bool {
+        match &self.0 {
+            Expr::Accessor(Accessor::Ident(ident)) => {
+                matches!(&ident.inspect()[..], "Override" | "Final" | "Inheritable")
+            }
+            Expr::Call(call) => {
+                matches!(call.obj.get_name().map(|s| &s[..]), Some("Impl" | "Attach"))
+            }
+            _ => false,
+        }
+    }
 }
 
 /// symbol as a left value
@@ -1959,6 +2180,12 @@ impl VarName {
             .unwrap_or(false)
     }
 
+    /// `break` and `continue` are reserved for the loop control
+    #[inline]
+    pub fn is_loop_control(&self) -> bool {
+        matches!(&self.0.content[..], "break" | "continue")
+    }
+
     pub const fn token(&self) -> &Token {
         &self.0
     }
@@ -2049,6 +2276,10 @@ impl Identifier {
     pub fn is_procedural(&self) -> bool {
         self.name.is_procedural()
     }
+
+    pub fn is_loop_control(&self) -> bool {
+        self.vis().is_private() && self.name.is_loop_control()
+    }
 }
 
 #[derive(Clone, Debug, PartialEq, Eq, Hash)]
@@ -2467,7 +2698,7 @@ impl ParamRecordAttrs {
 #[derive(Clone, Debug, PartialEq, Eq, Hash)]
 pub struct ParamRecordPattern {
     l_brace: Token,
-    pub(crate) elems: ParamRecordAttrs,
+    pub elems: ParamRecordAttrs,
     r_brace: Token,
 }
 
@@ -2615,8 +2846,11 @@ impl ParamSignature {
 #[derive(Clone, Debug, PartialEq, Eq, Hash)]
 pub struct Params {
     pub non_defaults: Vec<ParamSignature>,
+    /// `*args`
     pub var_args: Option<Box<ParamSignature>>,
     pub defaults: Vec<ParamSignature>,
+    /// `**kwargs`
+    pub kw_var_args: Option<Box<ParamSignature>>,
     pub parens: Option<(Token, Token)>,
 }
 
@@ -2624,10 +2858,11 @@ impl fmt::Display for Params {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         write!(
             f,
-            "({}, {}, {})",
+            "({}, {}, {}, {})",
             fmt_vec(&self.non_defaults),
-            fmt_option!(pre "...", &self.var_args),
-            fmt_vec(&self.defaults)
+            fmt_option!(pre "*", &self.var_args),
+            fmt_vec(&self.defaults),
+            fmt_option!(pre "**", &self.kw_var_args),
         )
     }
 }
@@ -2647,6 +2882,8 @@ impl Locational for Params {
             }
         } else if !self.defaults.is_empty() {
             Location::concat(&self.defaults[0], self.defaults.last().unwrap())
+        } else if let Some(kw_var_args) = &self.kw_var_args {
+            kw_var_args.loc()
         } else {
             panic!()
         }
@@ -2657,6 +2894,7 @@ type RawParams = (
     Vec<ParamSignature>,
     Option<Box<ParamSignature>>,
     Vec<ParamSignature>,
+    Option<Box<ParamSignature>>,
     Option<(Token, Token)>,
 );
 
@@ -2671,12 +2909,19 @@ impl Params {
             non_defaults,
             var_args: var_args.map(Box::new),
             defaults,
+            kw_var_args: None,
             parens,
         }
     }
 
     pub fn deconstruct(self) -> RawParams {
-        (self.non_defaults, self.var_args, self.defaults, self.parens)
+        (
+            self.non_defaults,
+            self.var_args,
+            self.defaults,
+            self.kw_var_args,
+            self.parens,
+        )
     }
 
     #[inline]
@@ -2693,7 +2938,7 @@ impl Params {
 /// 引数を取るならTypeでもSubr扱い
 #[derive(Clone, Debug, PartialEq, Eq, Hash)]
 pub struct SubrSignature {
-    pub decorators: HashSet<Decorator>,
+    pub decorators: Vec<Decorator>,
     pub ident: Identifier,
     pub bounds: TypeBoundSpecs,
     pub params: Params,
@@ -2739,7 +2984,7 @@ impl Locational for SubrSignature {
 
 impl SubrSignature {
     pub const fn new(
-        decorators: HashSet<Decorator>,
+        decorators: Vec<Decorator>,
         ident: Identifier,
         bounds: TypeBoundSpecs,
         params: Params,
@@ -3102,6 +3347,7 @@ impl Methods {
 
 #[derive(Debug, Clone, PartialEq, Eq, Hash)]
 pub struct ClassDef {
+    pub decorators: Vec<Decorator>,
     pub def: Def,
     pub methods_list: Vec<Methods>,
 }
@@ -3122,8 +3368,9 @@ impl_display_from_nested!(ClassDef);
 impl_locational!(ClassDef, def);
 
 impl ClassDef {
-    pub const fn new(def: Def, methods: Vec<Methods>) -> Self {
+    pub const fn new(decorators: Vec<Decorator>, def: Def, methods: Vec<Methods>) -> Self {
         Self {
+            decorators,
             def,
             methods_list: methods,
         }
@@ -3134,6 +3381,7 @@ impl ClassDef {
 #[derive(Debug, Clone, PartialEq, Eq, Hash)]
 pub enum Expr {
     Lit(Literal),
+    StrInterp(StrInterp),
     Accessor(Accessor),
     Array(Array),
     Tuple(Tuple),
@@ -3151,9 +3399,9 @@ pub enum Expr {
     ClassDef(ClassDef),
 }
 
-impl_nested_display_for_chunk_enum!(Expr; Lit, Accessor, Array, Tuple, Dict, Set, Record, BinOp, UnaryOp, Call, DataPack, Lambda, TypeAsc, Def, Methods, ClassDef);
+impl_nested_display_for_chunk_enum!(Expr; Lit, StrInterp, Accessor, Array, Tuple, Dict, Set, Record, BinOp, UnaryOp, Call, DataPack, Lambda, TypeAsc, Def, Methods, ClassDef);
 impl_display_from_nested!(Expr);
-impl_locational_for_enum!(Expr; Lit, Accessor, Array, Tuple, Dict, Set, Record, BinOp, UnaryOp, Call, DataPack, Lambda, TypeAsc, Def, Methods, ClassDef);
+impl_locational_for_enum!(Expr; Lit, StrInterp, Accessor, Array, Tuple, Dict, Set, Record, BinOp, UnaryOp, Call, DataPack, Lambda, TypeAsc, Def, Methods, ClassDef);
 
 impl Expr {
     pub fn is_match_call(&self) -> bool {
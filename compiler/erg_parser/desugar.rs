@@ -8,15 +8,16 @@
 use erg_common::set::Set;
 use erg_common::traits::{Locational, Stream};
 use erg_common::Str;
-use erg_common::{enum_unwrap, get_hash, log, set};
+use erg_common::{enum_unwrap, get_hash, log};
 
 use crate::ast::{
     Accessor, Args, Array, ArrayComprehension, ArrayWithLength, BinOp, Block, Call, DataPack, Def,
-    DefBody, DefId, Dict, Expr, Identifier, KeyValue, KwArg, Lambda, LambdaSignature, Literal,
-    Methods, Module, NormalArray, NormalDict, NormalRecord, NormalSet, NormalTuple, ParamPattern,
-    ParamSignature, Params, PosArg, Record, RecordAttrs, Set as astSet, SetWithLength,
-    ShortenedRecord, Signature, SubrSignature, Tuple, TypeAscription, TypeBoundSpecs, TypeSpec,
-    UnaryOp, VarName, VarPattern, VarRecordAttr, VarSignature,
+    DefBody, DefId, Dict, DictComprehension, Expr, Identifier, KeyValue, KwArg, Lambda,
+    LambdaSignature, Literal, Methods, Module, NormalArray, NormalDict, NormalRecord, NormalSet,
+    NormalTuple, ParamPattern, ParamSignature, Params, PosArg, Record, RecordAttrs, Set as astSet,
+    SetComprehension, SetWithLength, ShortenedRecord, Signature, StrInterp, SubrSignature, Tuple,
+    TypeAscription, TypeBoundSpecs, TypeSpec, UnaryOp, VarName, VarPattern, VarRecordAttr,
+    VarSignature,
 };
 use crate::token::{Token, TokenKind};
 
@@ -52,12 +53,27 @@ impl Desugarer {
         let module = self.desugar_multiple_pattern_def(module);
         let module = self.desugar_pattern(module);
         let module = Self::desugar_shortened_record(module);
+        let module = Self::desugar_pipeline(module);
         let module = Self::desugar_acc(module);
         log!(info "AST (desugared):\n{module}");
         log!(info "the desugaring process has completed.");
         module
     }
 
+    /// Desugars the bind and guard clauses of a comprehension
+    fn desugar_clauses(
+        desugar: &impl Fn(Expr) -> Expr,
+        generators: Vec<(Identifier, Expr)>,
+        guards: Vec<Expr>,
+    ) -> (Vec<(Identifier, Expr)>, Vec<Expr>) {
+        let generators = generators
+            .into_iter()
+            .map(|(ident, gen)| (ident, desugar(gen)))
+            .collect();
+        let guards = guards.into_iter().map(desugar).collect();
+        (generators, guards)
+    }
+
     fn perform_desugar(desugar: impl Fn(Expr) -> Expr, expr: Expr) -> Expr {
         match expr {
             Expr::Record(record) => match record {
@@ -81,12 +97,16 @@ impl Desugarer {
             }
             Expr::Array(array) => match array {
                 Array::Normal(arr) => {
-                    let (elems, _, _) = arr.elems.deconstruct();
+                    let (elems, kw_elems, _) = arr.elems.deconstruct();
                     let elems = elems
                         .into_iter()
                         .map(|elem| PosArg::new(desugar(elem.expr)))
                         .collect();
-                    let elems = Args::new(elems, vec![], None);
+                    let kw_elems = kw_elems
+                        .into_iter()
+                        .map(|elem| KwArg::new(elem.keyword, elem.t_spec, desugar(elem.expr)))
+                        .collect();
+                    let elems = Args::new(elems, kw_elems, None);
                     let arr = NormalArray::new(arr.l_sqbr, arr.r_sqbr, elems);
                     Expr::Array(Array::Normal(arr))
                 }
@@ -98,12 +118,8 @@ impl Desugarer {
                 }
                 Array::Comprehension(arr) => {
                     let elem = desugar(*arr.elem);
-                    let generators = arr
-                        .generators
-                        .into_iter()
-                        .map(|(ident, gen)| (ident, desugar(gen)))
-                        .collect();
-                    let guards = arr.guards.into_iter().map(desugar).collect();
+                    let (generators, guards) =
+                        Self::desugar_clauses(&desugar, arr.generators, arr.guards);
                     let arr =
                         ArrayComprehension::new(arr.l_sqbr, arr.r_sqbr, elem, generators, guards);
                     Expr::Array(Array::Comprehension(arr))
@@ -111,12 +127,16 @@ impl Desugarer {
             },
             Expr::Tuple(tuple) => match tuple {
                 Tuple::Normal(tup) => {
-                    let (elems, _, paren) = tup.elems.deconstruct();
+                    let (elems, kw_elems, paren) = tup.elems.deconstruct();
                     let elems = elems
                         .into_iter()
                         .map(|elem| PosArg::new(desugar(elem.expr)))
                         .collect();
-                    let new_tup = Args::new(elems, vec![], paren);
+                    let kw_elems = kw_elems
+                        .into_iter()
+                        .map(|elem| KwArg::new(elem.keyword, elem.t_spec, desugar(elem.expr)))
+                        .collect();
+                    let new_tup = Args::new(elems, kw_elems, paren);
                     let tup = NormalTuple::new(new_tup);
                     Expr::Tuple(Tuple::Normal(tup))
                 }
@@ -138,6 +158,14 @@ impl Desugarer {
                     let set = SetWithLength::new(set.l_brace, set.r_brace, elem, len);
                     Expr::Set(astSet::WithLength(set))
                 }
+                astSet::Comprehension(set) => {
+                    let elem = desugar(*set.elem);
+                    let (generators, guards) =
+                        Self::desugar_clauses(&desugar, set.generators, set.guards);
+                    let set =
+                        SetComprehension::new(set.l_brace, set.r_brace, elem, generators, guards);
+                    Expr::Set(astSet::Comprehension(set))
+                }
             },
             Expr::Dict(dict) => match dict {
                 Dict::Normal(dic) => {
@@ -153,8 +181,25 @@ impl Desugarer {
                     let tup = NormalDict::new(dic.l_brace, dic.r_brace, new_kvs);
                     Expr::Dict(Dict::Normal(tup))
                 }
-                _ => todo!("dict comprehension"),
+                Dict::Comprehension(dic) => {
+                    let key = desugar(*dic.key);
+                    let value = desugar(*dic.value);
+                    let (generators, guards) =
+                        Self::desugar_clauses(&desugar, dic.generators, dic.guards);
+                    let dict = DictComprehension::new(
+                        dic.l_brace,
+                        dic.r_brace,
+                        KeyValue::new(key, value),
+                        generators,
+                        guards,
+                    );
+                    Expr::Dict(Dict::Comprehension(dict))
+                }
             },
+            Expr::StrInterp(interp) => {
+                let exprs = interp.exprs.into_iter().map(desugar).collect();
+                Expr::StrInterp(StrInterp::new(interp.segments, exprs))
+            }
             Expr::BinOp(binop) => {
                 let mut args = binop.args.into_iter();
                 let lhs = desugar(*args.next().unwrap());
@@ -258,7 +303,7 @@ impl Desugarer {
                                 ParamSignature::new(ParamPattern::VarName(param), None, None);
                             let params = Params::new(vec![param], None, vec![], None);
                             let sig = Signature::Subr(SubrSignature::new(
-                                set! {},
+                                vec![],
                                 name,
                                 TypeBoundSpecs::empty(),
                                 params,
@@ -523,6 +568,61 @@ impl Desugarer {
         }
     }
 
+    /// `x |> f` -> `f(x)`
+    /// `x |> g(y)` -> `g(x, y)`
+    /// `(x, y) |> g` -> `g((x, y))`
+    /// `x |>.method(y)` -> `x.method(y)`
+    fn desugar_pipeline(mut module: Module) -> Module {
+        let mut new = Module::with_capacity(module.len());
+        while let Some(chunk) = module.lpop() {
+            new.push(Self::rec_desugar_pipeline(chunk));
+        }
+        new
+    }
+
+    fn rec_desugar_pipeline(expr: Expr) -> Expr {
+        match expr {
+            Expr::BinOp(binop) if binop.op.is(TokenKind::Pipe) => {
+                let mut args = binop.args.into_iter();
+                let lhs = Self::rec_desugar_pipeline(*args.next().unwrap());
+                let rhs = Self::rec_desugar_pipeline(*args.next().unwrap());
+                Self::desugar_pipeline_inner(lhs, rhs)
+            }
+            expr => Self::perform_desugar(Self::rec_desugar_pipeline, expr),
+        }
+    }
+
+    fn desugar_pipeline_inner(lhs: Expr, rhs: Expr) -> Expr {
+        match rhs {
+            // x |>.method
+            Expr::Accessor(Accessor::Ident(method)) if method.dot.is_some() => {
+                let args = Args::new(vec![], vec![], None);
+                Expr::Call(Call::new(lhs, Some(method), args))
+            }
+            Expr::Call(call) => match *call.obj {
+                // x |>.method(y)
+                Expr::Accessor(Accessor::Ident(method))
+                    if method.dot.is_some() && call.attr_name.is_none() =>
+                {
+                    Expr::Call(Call::new(lhs, Some(method), call.args))
+                }
+                // x |> g(y)
+                obj => {
+                    let (pos_args, kw_args, paren) = call.args.deconstruct();
+                    let mut new_pos_args = vec![PosArg::new(lhs)];
+                    new_pos_args.extend(pos_args);
+                    let args = Args::new(new_pos_args, kw_args, paren);
+                    Expr::Call(Call::new(obj, call.attr_name, args))
+                }
+            },
+            // x |> f
+            callee => {
+                let args = Args::new(vec![PosArg::new(lhs)], vec![], None);
+                Expr::Call(Call::new(callee, None, args))
+            }
+        }
+    }
+
     /// `{x; y}` -> `{x = x; y = y}`
     fn desugar_shortened_record(mut module: Module) -> Module {
         let mut new = Module::with_capacity(module.len());
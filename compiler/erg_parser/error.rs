@@ -124,6 +124,21 @@ impl LexError {
             hint,
         ))
     }
+
+    pub fn reserved_name_error(errno: usize, loc: Location, name: &str) -> Self {
+        Self::new(ErrorCore::new(
+            errno,
+            SyntaxError,
+            loc,
+            switch_lang!(
+                "japanese" => format!("{RED}{name}{RESET}は予約されているため、変数名として使えません"),
+                "simplified_chinese" => format!("{RED}{name}{RESET}是保留字，不能用作变量名"),
+                "traditional_chinese" => format!("{RED}{name}{RESET}是保留字，不能用作變量名"),
+                "english" => format!("{RED}{name}{RESET} is reserved and cannot be used as a variable name"),
+            ),
+            None,
+        ))
+    }
 }
 
 pub type LexResult<T> = Result<T, LexError>;
@@ -79,6 +79,8 @@ pub struct Lexer /*<'a>*/ {
     lineno_token_starts: usize,
     /// 0-origin, indicates the column number in which the token appears
     col_token_starts: usize,
+    /// the nesting levels of `{}` in each (nested) string interpolation `\{...}`
+    str_interp_depths: Vec<usize>,
 }
 
 impl Lexer /*<'a>*/ {
@@ -92,6 +94,7 @@ impl Lexer /*<'a>*/ {
             prev_token: Token::new(TokenKind::BOF, "", 0, 0),
             lineno_token_starts: 0,
             col_token_starts: 0,
+            str_interp_depths: vec![],
         }
     }
 
@@ -106,6 +109,7 @@ impl Lexer /*<'a>*/ {
             prev_token: Token::new(TokenKind::BOF, "", 0, 0),
             lineno_token_starts: 0,
             col_token_starts: 0,
+            str_interp_depths: vec![],
         }
     }
 
@@ -215,6 +219,10 @@ impl Lexer /*<'a>*/ {
     // +, -, * etc. may be pre/bin
     // and, or, is, isnot, in, notin, as, dot, cross may be bin/function
     const fn is_bin_position(&self) -> Option<bool> {
+        // `}` of `\{...}` is an REnclosure, but the next operator is unary: `"\{x}\{-y}"`
+        if let StrInterpMid = self.prev_token.kind {
+            return Some(false);
+        }
         match self.prev_token.category() {
             // unary: `[ +`, `= +`, `+ +`, `, +`, `:: +`
             TokenCategory::LEnclosure
@@ -225,8 +233,10 @@ impl Lexer /*<'a>*/ {
             | TokenCategory::DefOp
             | TokenCategory::LambdaOp
             | TokenCategory::BOF => Some(false),
-            // bin: `] +`, `1 +`, `true and[true]`
-            TokenCategory::REnclosure | TokenCategory::Literal => Some(true),
+            // bin: `] +`, `1 +`, `true and[true]`, `x? +`
+            TokenCategory::REnclosure | TokenCategory::Literal | TokenCategory::PostfixOp => {
+                Some(true)
+            }
             // bin: `fn +1`
             // NOTE: if semantic analysis shows `fn` is a function, should this be rewritten to be unary?
             TokenCategory::Symbol => Some(true),
@@ -371,7 +381,8 @@ impl Lexer /*<'a>*/ {
                 ),
                 None,
             )))
-        } else if self.prev_token.is(Newline) {
+        } else if self.prev_token.is(Newline) || (self.prev_token.is(Dedent) && !spaces.is_empty())
+        {
             self.lex_indent_dedent(spaces)
         } else {
             self.col_token_starts += spaces.len();
@@ -434,7 +445,11 @@ impl Lexer /*<'a>*/ {
             Ordering::Greater => {
                 if is_valid_dedent {
                     let dedent = self.emit_token(Dedent, "");
-                    self.indent_stack.pop();
+                    let indent_len = self.indent_stack.pop().unwrap_or(0);
+                    // dedent multiple levels at once: re-read the spaces to emit the remaining dedents
+                    if sum_indent - indent_len > spaces.len() {
+                        self.cursor -= spaces.len();
+                    }
                     Some(Ok(dedent))
                 } else {
                     let invalid_dedent = self.emit_token(Dedent, "");
@@ -624,7 +639,17 @@ impl Lexer /*<'a>*/ {
     }
 
     fn lex_str(&mut self) -> LexResult<Token> {
-        let mut s = "\"".to_string();
+        self.lex_str_segment("\"".to_string(), StrLit, StrInterpLeft)
+    }
+
+    /// Lexes a string until `"` (emits `closed_kind`) or `\{` (emits `interp_kind`).
+    /// `s` is the beginning of the token (`"`, or `}` after an interpolation).
+    fn lex_str_segment(
+        &mut self,
+        mut s: String,
+        closed_kind: TokenKind,
+        interp_kind: TokenKind,
+    ) -> LexResult<Token> {
         while let Some(c) = self.peek_cur_ch() {
             match c {
                 '\n' => {
@@ -651,7 +676,7 @@ impl Lexer /*<'a>*/ {
                 }
                 '"' => {
                     s.push(self.consume().unwrap());
-                    let token = self.emit_token(StrLit, &s);
+                    let token = self.emit_token(closed_kind, &s);
                     return Ok(token);
                 }
                 _ => {
@@ -666,6 +691,13 @@ impl Lexer /*<'a>*/ {
                             '"' => s.push('"'),
                             't' => s.push_str("    "), // tab is invalid, so changed into 4 whitespace
                             '\\' => s.push('\\'),
+                            // the beginning of an interpolation
+                            '{' => {
+                                s.push_str("\\{");
+                                let token = self.emit_token(interp_kind, &s);
+                                self.str_interp_depths.push(0);
+                                return Ok(token);
+                            }
                             _ => {
                                 let token = self.emit_token(Illegal, &format!("\\{next_c}"));
                                 return Err(LexError::syntax_error(
@@ -838,8 +870,24 @@ impl Iterator for Lexer /*<'a>*/ {
             Some(')') => self.accept(RParen, ")"),
             Some('[') => self.accept(LSqBr, "["),
             Some(']') => self.accept(RSqBr, "]"),
-            Some('{') => self.accept(LBrace, "{"),
-            Some('}') => self.accept(RBrace, "}"),
+            Some('{') => {
+                if let Some(depth) = self.str_interp_depths.last_mut() {
+                    *depth += 1;
+                }
+                self.accept(LBrace, "{")
+            }
+            Some('}') => match self.str_interp_depths.last_mut() {
+                // the end of an interpolation
+                Some(0) => {
+                    self.str_interp_depths.pop();
+                    Some(self.lex_str_segment("}".to_string(), StrInterpRight, StrInterpMid))
+                }
+                Some(depth) => {
+                    *depth -= 1;
+                    self.accept(RBrace, "}")
+                }
+                None => self.accept(RBrace, "}"),
+            },
             Some('<') => match self.peek_cur_ch() {
                 Some('.') => {
                     self.consume();
@@ -949,6 +997,10 @@ impl Iterator for Lexer /*<'a>*/ {
                     self.consume();
                     self.accept(BitOr, "||")
                 }
+                Some('>') => {
+                    self.consume();
+                    self.accept(Pipe, "|>")
+                }
                 _ => self.accept(VBar, "|"),
             },
             Some('^') => {
@@ -1024,12 +1076,19 @@ impl Iterator for Lexer /*<'a>*/ {
             Some('*') => match self.peek_cur_ch() {
                 Some('*') => {
                     self.consume();
-                    self.accept(Pow, "**")
+                    // `f(**kwargs)`
+                    let kind = match self.is_bin_position() {
+                        Some(false) => PreDblStar,
+                        _ => Pow,
+                    };
+                    self.accept(kind, "**")
                 }
                 _ => {
                     let kind = match self.is_bin_position() {
                         Some(true) => Star,
-                        _ => {
+                        // `f(*args)`
+                        Some(false) => PreStar,
+                        None => {
                             let token = self.emit_token(Illegal, "*");
                             return Some(Err(LexError::simple_syntax_error(0, token.loc())));
                         }
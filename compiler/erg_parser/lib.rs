@@ -1,6 +1,7 @@
 //! Implements `Parser` for Erg. `Parser` parses the source code to generate `AST`,
 //! and performs type checking and other optimizations if necessary.
 #![allow(clippy::large_enum_variant)]
+#![allow(clippy::result_large_err)]
 extern crate erg_common;
 
 pub mod ast;
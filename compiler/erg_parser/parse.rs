@@ -9,12 +9,11 @@ use erg_common::config::ErgConfig;
 use erg_common::config::Input;
 use erg_common::error::Location;
 use erg_common::option_enum_unwrap;
-use erg_common::set::Set as HashSet;
 use erg_common::str::Str;
 use erg_common::traits::Runnable;
 use erg_common::traits::{Locational, Stream};
 use erg_common::{
-    caused_by, debug_power_assert, enum_unwrap, fn_name, impl_locational_for_enum, log, set,
+    caused_by, debug_power_assert, enum_unwrap, fn_name, impl_locational_for_enum, log,
     switch_lang, switch_unreachable,
 };
 
@@ -51,6 +50,9 @@ enum PosOrKwArg {
     Kw(KwArg),
 }
 
+/// (bind clauses, guard clauses) of a comprehension
+type ComprehensionClauses = (Vec<(Identifier, Expr)>, Vec<Expr>);
+
 pub enum ArrayInner {
     Normal(Args),
     WithLength(PosArg, Expr),
@@ -360,11 +362,11 @@ impl Parser {
     }
 
     #[inline]
-    fn opt_reduce_decorators(&mut self) -> ParseResult<HashSet<Decorator>> {
+    fn opt_reduce_decorators(&mut self) -> ParseResult<Vec<Decorator>> {
         debug_call_info!(self);
-        let mut decs = set![];
+        let mut decs = vec![];
         while let Some(deco) = self.opt_reduce_decorator().map_err(|_| self.stack_dec())? {
-            decs.insert(deco);
+            decs.push(deco);
             if self.cur_is(Newline) {
                 self.skip();
             } else {
@@ -442,11 +444,16 @@ impl Parser {
                 return Ok(ArrayInner::WithLength(elems.remove_pos(0), len));
             }
             Some(vbar) if vbar.is(VBar) => {
-                let err = ParseError::feature_error(line!() as usize, vbar.loc(), "comprehension");
-                self.lpop();
-                self.errs.push(err);
+                self.skip();
+                let (generators, guards) = self
+                    .try_reduce_comprehension_clauses()
+                    .map_err(|_| self.stack_dec())?;
                 self.level -= 1;
-                return Err(());
+                return Ok(ArrayInner::Comprehension {
+                    elem: elems.remove_pos(0),
+                    generators,
+                    guards,
+                });
             }
             Some(t) if t.category_is(TC::REnclosure) || t.is(Comma) => {}
             Some(_) => {
@@ -470,7 +477,21 @@ impl Parser {
                         self.errs.push(err);
                         return Err(());
                     }
-                    elems.push_pos(self.try_reduce_elem().map_err(|_| self.stack_dec())?);
+                    match self.try_reduce_arg(false).map_err(|_| self.stack_dec())? {
+                        PosOrKwArg::Pos(arg) if elems.kw_is_empty() => {
+                            elems.push_pos(arg);
+                        }
+                        PosOrKwArg::Pos(arg) => {
+                            self.level -= 1;
+                            let err = ParseError::simple_syntax_error(line!() as usize, arg.loc());
+                            self.errs.push(err);
+                            return Err(());
+                        }
+                        // `[x, y := 1]` (only valid as a parameter pattern)
+                        PosOrKwArg::Kw(arg) => {
+                            elems.push_kw(arg);
+                        }
+                    }
                 }
                 Some(t) if t.category_is(TC::REnclosure) => {
                     break;
@@ -502,11 +523,116 @@ impl Parser {
         }
     }
 
+    /// The clauses of a comprehension (after `|`): `(name <- iterable;)+ (predicate;)*`
+    fn try_reduce_comprehension_clauses(&mut self) -> ParseResult<ComprehensionClauses> {
+        debug_call_info!(self);
+        let mut generators = vec![];
+        let mut guards = vec![];
+        loop {
+            match self.peek() {
+                Some(t) if t.is(Symbol) && self.nth_is(1, Inclusion) => {
+                    if !guards.is_empty() {
+                        let loc = t.loc();
+                        self.level -= 1;
+                        let err = ParseError::syntax_error(
+                            line!() as usize,
+                            loc,
+                            switch_lang!(
+                                "japanese" => "束縛節はガード節の前に置く必要があります",
+                                "simplified_chinese" => "绑定子句必须放在守卫子句之前",
+                                "traditional_chinese" => "綁定子句必須放在守衛子句之前",
+                                "english" => "bind clauses must precede guard clauses",
+                            ),
+                            None,
+                        );
+                        self.errs.push(err);
+                        return Err(());
+                    }
+                    let ident = Identifier::new(None, VarName::new(self.lpop()));
+                    self.skip(); // <-
+                    let iterable = self
+                        .try_reduce_expr(false, false, false)
+                        .map_err(|_| self.stack_dec())?;
+                    generators.push((ident, iterable));
+                }
+                Some(t) if !generators.is_empty() && !t.category_is(TC::REnclosure) => {
+                    let guard = self
+                        .try_reduce_expr(false, false, false)
+                        .map_err(|_| self.stack_dec())?;
+                    guards.push(guard);
+                }
+                Some(t) => {
+                    let loc = t.loc();
+                    self.level -= 1;
+                    let err = ParseError::syntax_error(
+                        line!() as usize,
+                        loc,
+                        switch_lang!(
+                            "japanese" => "内包表記には束縛節(`name <- iterable`)が必要です",
+                            "simplified_chinese" => "推导式需要绑定子句(`name <- iterable`)",
+                            "traditional_chinese" => "推導式需要綁定子句(`name <- iterable`)",
+                            "english" => "a comprehension requires a bind clause (`name <- iterable`)",
+                        ),
+                        None,
+                    );
+                    self.errs.push(err);
+                    return Err(());
+                }
+                None => switch_unreachable!(),
+            }
+            match self.peek() {
+                Some(t) if t.is(Semi) => {
+                    self.skip();
+                }
+                Some(t) if t.category_is(TC::REnclosure) => {
+                    break;
+                }
+                _ => {
+                    self.level -= 1;
+                    let err = self.skip_and_throw_syntax_err(caused_by!());
+                    self.errs.push(err);
+                    return Err(());
+                }
+            }
+        }
+        self.level -= 1;
+        Ok((generators, guards))
+    }
+
+    /// The predicates of a refinement type (after `|`): `(predicate;)+`
+    fn try_reduce_refinement_preds(&mut self) -> ParseResult<Vec<Expr>> {
+        debug_call_info!(self);
+        let mut preds = vec![];
+        loop {
+            let pred = self
+                .try_reduce_expr(false, false, false)
+                .map_err(|_| self.stack_dec())?;
+            preds.push(pred);
+            match self.peek() {
+                Some(t) if t.is(Semi) => {
+                    self.skip();
+                }
+                Some(t) if t.category_is(TC::REnclosure) => {
+                    break;
+                }
+                _ => {
+                    self.level -= 1;
+                    let err = self.skip_and_throw_syntax_err(caused_by!());
+                    self.errs.push(err);
+                    return Err(());
+                }
+            }
+        }
+        self.level -= 1;
+        Ok(preds)
+    }
+
     fn opt_reduce_args(&mut self, in_type_args: bool) -> Option<ParseResult<Args>> {
         // debug_call_info!(self);
         match self.peek() {
             Some(t)
                 if t.category_is(TC::Literal)
+                    || t.is(StrInterpLeft)
                     || t.is(Symbol)
                     || t.category_is(TC::UnaryOp)
                     || t.is(LParen)
@@ -585,7 +711,8 @@ impl Parser {
                         self.errs.push(err);
                         return Err(());
                     }
-                    if !args.kw_is_empty() {
+                    // `**kwargs` can follow keyword arguments (`f(x, y := 1, **kwargs) = ...`)
+                    if !args.kw_is_empty() && !self.cur_is(PreDblStar) {
                         args.push_kw(
                             self.try_reduce_kw_arg(in_type_args)
                                 .map_err(|_| self.stack_dec())?,
@@ -907,6 +1034,30 @@ impl Parser {
                     let expr = Expr::TypeAsc(TypeAscription::new(lhs, op, t_spec));
                     stack.push(ExprOrOp::Expr(expr));
                 }
+                Some(op) if op.is(Pipe) => {
+                    let op_prec = op.kind.precedence();
+                    if stack.len() >= 2 {
+                        while let Some(ExprOrOp::Op(prev_op)) = stack.get(stack.len() - 2) {
+                            if prev_op.kind.precedence() >= op_prec {
+                                let rhs = enum_unwrap!(stack.pop(), Some:(ExprOrOp::Expr:(_)));
+                                let prev_op = enum_unwrap!(stack.pop(), Some:(ExprOrOp::Op:(_)));
+                                let lhs = enum_unwrap!(stack.pop(), Some:(ExprOrOp::Expr:(_)));
+                                let bin = BinOp::new(prev_op, lhs, rhs);
+                                stack.push(ExprOrOp::Expr(Expr::BinOp(bin)));
+                            } else {
+                                break;
+                            }
+                            if stack.len() <= 1 {
+                                break;
+                            }
+                        }
+                    }
+                    stack.push(ExprOrOp::Op(self.lpop()));
+                    stack.push(ExprOrOp::Expr(
+                        self.try_reduce_pipe_rhs(false, in_brace)
+                            .map_err(|_| self.stack_dec())?,
+                    ));
+                }
                 Some(op) if op.category_is(TC::BinOp) => {
                     let op_prec = op.kind.precedence();
                     if stack.len() >= 2 {
@@ -1068,6 +1219,18 @@ impl Parser {
                     let acc = Accessor::subscr(obj, index, r_sqbr);
                     stack.push(ExprOrOp::Expr(Expr::Accessor(acc)));
                 }
+                Some(t) if t.is(Try) => {
+                    let op = self.lpop();
+                    let obj = if let Some(ExprOrOp::Expr(expr)) = stack.pop() {
+                        expr
+                    } else {
+                        self.level -= 1;
+                        let err = self.skip_and_throw_syntax_err(caused_by!());
+                        self.errs.push(err);
+                        return Err(());
+                    };
+                    stack.push(ExprOrOp::Expr(Expr::UnaryOp(UnaryOp::new(op, obj))));
+                }
                 Some(t) if t.is(Comma) && winding => {
                     let first_elem = enum_unwrap!(stack.pop(), Some:(ExprOrOp::Expr:(_)));
                     let tup = self
@@ -1173,6 +1336,30 @@ impl Parser {
                     let expr = Expr::TypeAsc(TypeAscription::new(lhs, op, t_spec));
                     stack.push(ExprOrOp::Expr(expr));
                 }
+                Some(op) if op.is(Pipe) => {
+                    let op_prec = op.kind.precedence();
+                    if stack.len() >= 2 {
+                        while let Some(ExprOrOp::Op(prev_op)) = stack.get(stack.len() - 2) {
+                            if prev_op.kind.precedence() >= op_prec {
+                                let rhs = enum_unwrap!(stack.pop(), Some:(ExprOrOp::Expr:(_)));
+                                let prev_op = enum_unwrap!(stack.pop(), Some:(ExprOrOp::Op:(_)));
+                                let lhs = enum_unwrap!(stack.pop(), Some:(ExprOrOp::Expr:(_)));
+                                let bin = BinOp::new(prev_op, lhs, rhs);
+                                stack.push(ExprOrOp::Expr(Expr::BinOp(bin)));
+                            } else {
+                                break;
+                            }
+                            if stack.len() <= 1 {
+                                break;
+                            }
+                        }
+                    }
+                    stack.push(ExprOrOp::Op(self.lpop()));
+                    stack.push(ExprOrOp::Expr(
+                        self.try_reduce_pipe_rhs(in_type_args, in_brace)
+                            .map_err(|_| self.stack_dec())?,
+                    ));
+                }
                 Some(op) if op.category_is(TC::BinOp) => {
                     let op_prec = op.kind.precedence();
                     if stack.len() >= 2 {
@@ -1234,6 +1421,18 @@ impl Parser {
                         }
                     }
                 }
+                Some(t) if t.is(Try) => {
+                    let op = self.lpop();
+                    let obj = if let Some(ExprOrOp::Expr(expr)) = stack.pop() {
+                        expr
+                    } else {
+                        self.level -= 1;
+                        let err = self.skip_and_throw_syntax_err(caused_by!());
+                        self.errs.push(err);
+                        return Err(());
+                    };
+                    stack.push(ExprOrOp::Expr(Expr::UnaryOp(UnaryOp::new(op, obj))));
+                }
                 Some(t) if t.is(Comma) && winding => {
                     let first_elem = enum_unwrap!(stack.pop(), Some:(ExprOrOp::Expr:(_)));
                     let tup = self
@@ -1290,6 +1489,44 @@ impl Parser {
         }
     }
 
+    /// The right-hand side of `|>`: a callee (`f`, `g(y)`) or a method (`.method(y)`)
+    fn try_reduce_pipe_rhs(&mut self, in_type_args: bool, in_brace: bool) -> ParseResult<Expr> {
+        debug_call_info!(self);
+        match self.peek() {
+            Some(t) if t.is(Dot) => {
+                let vis = self.lpop();
+                let symbol = self.lpop();
+                if !symbol.is(Symbol) {
+                    self.restore(symbol);
+                    self.level -= 1;
+                    let err = self.skip_and_throw_syntax_err(caused_by!());
+                    self.errs.push(err);
+                    return Err(());
+                }
+                let ident = Identifier::new(Some(vis), VarName::new(symbol));
+                let method = Expr::Accessor(Accessor::Ident(ident));
+                let expr = if let Some(args) = self
+                    .opt_reduce_args(in_type_args)
+                    .transpose()
+                    .map_err(|_| self.stack_dec())?
+                {
+                    Expr::Call(Call::new(method, None, args))
+                } else {
+                    method
+                };
+                self.level -= 1;
+                Ok(expr)
+            }
+            _ => {
+                let expr = self
+                    .try_reduce_bin_lhs(in_type_args, in_brace)
+                    .map_err(|_| self.stack_dec())?;
+                self.level -= 1;
+                Ok(expr)
+            }
+        }
+    }
+
     /// "LHS" is the smallest unit that can be the left-hand side of an BinOp.
     /// e.g. Call, Name, UnaryOp, Lambda
     fn try_reduce_bin_lhs(&mut self, in_type_args: bool, in_brace: bool) -> ParseResult<Expr> {
@@ -1301,6 +1538,11 @@ impl Parser {
                 self.level -= 1;
                 Ok(Expr::Lit(lit))
             }
+            Some(t) if t.is(StrInterpLeft) => {
+                let interp = self.try_reduce_str_interp().map_err(|_| self.stack_dec())?;
+                self.level -= 1;
+                Ok(Expr::StrInterp(interp))
+            }
             Some(t) if t.is(AtSign) => {
                 let decos = self.opt_reduce_decorators()?;
                 let expr = self.try_reduce_chunk(false, in_brace)?;
@@ -1321,7 +1563,8 @@ impl Parser {
                     }
                     Signature::Var(var) => {
                         let mut last = def.body.block.pop().unwrap();
-                        for deco in decos.into_iter() {
+                        // decorators are applied from the bottom up
+                        for deco in decos.into_iter().rev() {
                             last = Expr::Call(Call::new(
                                 deco.into_expr(),
                                 None,
@@ -1355,6 +1598,14 @@ impl Parser {
                     self.level -= 1;
                     return Ok(Expr::Tuple(unit));
                 }
+                // `(x := 1) -> ...`
+                if self.cur_is(Symbol) && self.nth_is(1, Walrus) {
+                    let tup = self
+                        .try_reduce_default_params_tuple(lparen)
+                        .map_err(|_| self.stack_dec())?;
+                    self.level -= 1;
+                    return Ok(Expr::Tuple(tup));
+                }
                 let mut expr = self
                     .try_reduce_expr(true, false, false)
                     .map_err(|_| self.stack_dec())?;
@@ -1536,7 +1787,7 @@ impl Parser {
                     let call = Call::new(receiver, attr_name, args);
                     obj = Expr::Call(call);
                 }
-                Some(t) if t.is(VBar) && !in_type_args => {
+                Some(t) if t.is(VBar) && !in_type_args && obj.col_end() == t.col_begin() => {
                     let type_args = self
                         .try_reduce_type_app_args()
                         .map_err(|_| self.stack_dec())?;
@@ -1578,7 +1829,7 @@ impl Parser {
             ArrayInner::Normal(mut elems) => {
                 let elems = if elems
                     .pos_args()
-                    .get(0)
+                    .first()
                     .map(|pos| match &pos.expr {
                         Expr::Tuple(tup) => tup.paren().is_none(),
                         _ => false,
@@ -1594,15 +1845,13 @@ impl Parser {
             ArrayInner::WithLength(elem, len) => {
                 Array::WithLength(ArrayWithLength::new(l_sqbr, r_sqbr, elem, len))
             }
-            ArrayInner::Comprehension { .. } => {
-                self.level -= 1;
-                self.errs.push(ParseError::feature_error(
-                    line!() as usize,
-                    Location::concat(&l_sqbr, &r_sqbr),
-                    "array comprehension",
-                ));
-                return Err(());
-            }
+            ArrayInner::Comprehension {
+                elem,
+                generators,
+                guards,
+            } => Array::Comprehension(ArrayComprehension::new(
+                l_sqbr, r_sqbr, elem.expr, generators, guards,
+            )),
         };
         self.level -= 1;
         Ok(arr)
@@ -1689,11 +1938,27 @@ impl Parser {
             }
             // Dict
             other if self.cur_is(Colon) => {
-                let dict = self
-                    .try_reduce_normal_dict(l_brace, other)
+                let container = self
+                    .try_reduce_dict(l_brace, other)
                     .map_err(|_| self.stack_dec())?;
                 self.level -= 1;
-                Ok(BraceContainer::Dict(Dict::Normal(dict)))
+                Ok(container)
+            }
+            other if self.cur_is(VBar) => {
+                self.skip();
+                let (generators, guards) = self
+                    .try_reduce_comprehension_clauses()
+                    .map_err(|_| self.stack_dec())?;
+                let r_brace = self.lpop();
+                if !r_brace.is(RBrace) {
+                    self.level -= 1;
+                    self.errs
+                        .push(ParseError::simple_syntax_error(0, r_brace.loc()));
+                    return Err(());
+                }
+                let set = SetComprehension::new(l_brace, r_brace, other, generators, guards);
+                self.level -= 1;
+                Ok(BraceContainer::Set(Set::Comprehension(set)))
             }
             other => {
                 let set = self
@@ -1829,18 +2094,70 @@ impl Parser {
         }
     }
 
-    fn try_reduce_normal_dict(
-        &mut self,
-        l_brace: Token,
-        first_key: Expr,
-    ) -> ParseResult<NormalDict> {
+    /// Reduces a dict, or a refinement type (e.g. `{I: Int | I >= 0}`)
+    fn try_reduce_dict(&mut self, l_brace: Token, first_key: Expr) -> ParseResult<BraceContainer> {
         debug_call_info!(self);
         assert!(self.cur_is(Colon));
-        self.skip();
+        let colon = self.lpop();
         let value = self
             .try_reduce_chunk(false, false)
             .map_err(|_| self.stack_dec())?;
-        let mut kvs = vec![KeyValue::new(first_key, value)];
+        // `{I: Int | I >= 0}` has no bind clauses (`name <- iterable`)
+        if self.cur_is(VBar)
+            && matches!(first_key, Expr::Accessor(Accessor::Ident(_)))
+            && !(self.nth_is(1, Symbol) && self.nth_is(2, Inclusion))
+        {
+            self.skip();
+            let t_spec = Self::expr_to_type_spec(value).map_err(|e| {
+                self.level -= 1;
+                self.errs.push(e);
+            })?;
+            let preds = self
+                .try_reduce_refinement_preds()
+                .map_err(|_| self.stack_dec())?;
+            let r_brace = self.lpop();
+            if !r_brace.is(RBrace) {
+                self.level -= 1;
+                self.errs
+                    .push(ParseError::simple_syntax_error(0, r_brace.loc()));
+                return Err(());
+            }
+            let elem = Expr::TypeAsc(TypeAscription::new(first_key, colon, t_spec));
+            let set = SetComprehension::new(l_brace, r_brace, elem, vec![], preds);
+            self.level -= 1;
+            return Ok(BraceContainer::Set(Set::Comprehension(set)));
+        }
+        let kv = KeyValue::new(first_key, value);
+        if self.cur_is(VBar) {
+            self.skip();
+            let (generators, guards) = self
+                .try_reduce_comprehension_clauses()
+                .map_err(|_| self.stack_dec())?;
+            let r_brace = self.lpop();
+            if !r_brace.is(RBrace) {
+                self.level -= 1;
+                self.errs
+                    .push(ParseError::simple_syntax_error(0, r_brace.loc()));
+                return Err(());
+            }
+            let dict = DictComprehension::new(l_brace, r_brace, kv, generators, guards);
+            self.level -= 1;
+            return Ok(BraceContainer::Dict(Dict::Comprehension(dict)));
+        }
+        let dict = self
+            .try_reduce_normal_dict(l_brace, kv)
+            .map_err(|_| self.stack_dec())?;
+        self.level -= 1;
+        Ok(BraceContainer::Dict(Dict::Normal(dict)))
+    }
+
+    fn try_reduce_normal_dict(
+        &mut self,
+        l_brace: Token,
+        first: KeyValue,
+    ) -> ParseResult<NormalDict> {
+        debug_call_info!(self);
+        let mut kvs = vec![first];
         loop {
             match self.peek() {
                 Some(t) if t.is(Comma) => {
@@ -1963,7 +2280,7 @@ impl Parser {
                         break;
                     }
                     match self.try_reduce_arg(false).map_err(|_| self.stack_dec())? {
-                        PosOrKwArg::Pos(arg) => match arg.expr {
+                        PosOrKwArg::Pos(arg) if args.kw_is_empty() => match arg.expr {
                             Expr::Tuple(Tuple::Normal(tup)) if tup.elems.paren.is_none() => {
                                 args.extend_pos(tup.elems.into_iters().0);
                             }
@@ -1971,12 +2288,17 @@ impl Parser {
                                 args.push_pos(PosArg::new(other));
                             }
                         },
-                        PosOrKwArg::Kw(arg) => {
+                        // positional elements cannot follow the default ones (e.g. `x := 1, y`)
+                        PosOrKwArg::Pos(arg) => {
                             self.level -= 1;
                             let err = ParseError::simple_syntax_error(line!() as usize, arg.loc());
                             self.errs.push(err);
                             return Err(());
                         }
+                        // `x, y := 1` (only valid as a parameter pattern)
+                        PosOrKwArg::Kw(arg) => {
+                            args.push_kw(arg);
+                        }
                     }
                 }
                 _ => {
@@ -1989,6 +2311,36 @@ impl Parser {
         Ok(tup)
     }
 
+    /// `(x := 1, y := 2)`: a tuple consisting only of default values (only valid as the parameters of a lambda)
+    fn try_reduce_default_params_tuple(&mut self, lparen: Token) -> ParseResult<Tuple> {
+        debug_call_info!(self);
+        let mut args = Args::new(vec![], vec![], None);
+        loop {
+            let kw = self
+                .try_reduce_kw_arg(false)
+                .map_err(|_| self.stack_dec())?;
+            args.push_kw(kw);
+            match self.peek() {
+                Some(t) if t.is(Comma) => {
+                    self.skip();
+                }
+                Some(t) if t.is(RParen) => {
+                    let rparen = self.lpop();
+                    args.paren = Some((lparen, rparen));
+                    break;
+                }
+                _ => {
+                    self.level -= 1;
+                    let err = self.skip_and_throw_syntax_err(caused_by!());
+                    self.errs.push(err);
+                    return Err(());
+                }
+            }
+        }
+        self.level -= 1;
+        Ok(Tuple::Normal(NormalTuple::new(args)))
+    }
+
     #[inline]
     fn try_reduce_lit(&mut self) -> ParseResult<Literal> {
         debug_call_info!(self);
@@ -2003,6 +2355,36 @@ impl Parser {
         }
     }
 
+    /// `"a\{x}b\{y}c"`: StrInterpLeft Expr StrInterpMid Expr StrInterpRight
+    fn try_reduce_str_interp(&mut self) -> ParseResult<StrInterp> {
+        debug_call_info!(self);
+        let mut segments = vec![self.lpop()];
+        let mut exprs = vec![];
+        loop {
+            let expr = self
+                .try_reduce_expr(false, false, false)
+                .map_err(|_| self.stack_dec())?;
+            exprs.push(expr);
+            match self.peek() {
+                Some(t) if t.is(StrInterpMid) => {
+                    segments.push(self.lpop());
+                }
+                Some(t) if t.is(StrInterpRight) => {
+                    segments.push(self.lpop());
+                    break;
+                }
+                _ => {
+                    self.level -= 1;
+                    let err = self.skip_and_throw_syntax_err(caused_by!());
+                    self.errs.push(err);
+                    return Err(());
+                }
+            }
+        }
+        self.level -= 1;
+        Ok(StrInterp::new(segments, exprs))
+    }
+
     /// Call: F(x) -> SubrSignature: F(x)
     fn convert_rhs_to_sig(&mut self, rhs: Expr) -> ParseResult<Signature> {
         debug_call_info!(self);
@@ -2073,6 +2455,16 @@ impl Parser {
         debug_call_info!(self);
         match accessor {
             Accessor::Ident(ident) => {
+                if ident.is_loop_control() {
+                    self.level -= 1;
+                    let err = ParseError::reserved_name_error(
+                        line!() as usize,
+                        ident.loc(),
+                        ident.inspect(),
+                    );
+                    self.errs.push(err);
+                    return Err(());
+                }
                 let pat = if &ident.inspect()[..] == "_" {
                     VarPattern::Discard(ident.name.into_token())
                 } else {
@@ -2253,7 +2645,7 @@ impl Parser {
         let params = self
             .convert_args_to_params(call.args)
             .map_err(|_| self.stack_dec())?;
-        let sig = SubrSignature::new(set! {}, ident, bounds, params, None);
+        let sig = SubrSignature::new(vec![], ident, bounds, params, None);
         self.level -= 1;
         Ok(sig)
     }
@@ -2264,6 +2656,13 @@ impl Parser {
     ) -> ParseResult<(Identifier, TypeBoundSpecs)> {
         debug_call_info!(self);
         let (ident, bounds) = match accessor {
+            Accessor::Ident(ident) if ident.is_loop_control() => {
+                self.level -= 1;
+                let err =
+                    ParseError::reserved_name_error(line!() as usize, ident.loc(), ident.inspect());
+                self.errs.push(err);
+                return Err(());
+            }
             Accessor::Ident(ident) => (ident, TypeBoundSpecs::empty()),
             Accessor::TypeApp(t_app) => {
                 let sig = self
@@ -2334,22 +2733,91 @@ impl Parser {
         let (pos_args, kw_args, parens) = args.deconstruct();
         let mut params = Params::new(vec![], None, vec![], parens);
         for (i, arg) in pos_args.into_iter().enumerate() {
-            let nd_param = self
-                .convert_pos_arg_to_non_default_param(arg, i == 0)
+            self.push_pos_arg_to_params(&mut params, arg, i == 0)
                 .map_err(|_| self.stack_dec())?;
-            params.non_defaults.push(nd_param);
         }
-        // TODO: varargs
         for arg in kw_args.into_iter() {
-            let d_param = self
-                .convert_kw_arg_to_default_param(arg)
+            self.push_kw_arg_to_params(&mut params, arg)
                 .map_err(|_| self.stack_dec())?;
-            params.defaults.push(d_param);
         }
         self.level -= 1;
         Ok(params)
     }
 
+    /// `x`, `*args` or `**kwargs`
+    fn push_pos_arg_to_params(
+        &mut self,
+        params: &mut Params,
+        arg: PosArg,
+        allow_self: bool,
+    ) -> ParseResult<()> {
+        debug_call_info!(self);
+        let var_param = match arg.expr {
+            Expr::UnaryOp(unary) if unary.op.is(PreStar) || unary.op.is(PreDblStar) => {
+                let op = unary.op;
+                let expr = *unary.args.into_iter().next().unwrap();
+                Some((op, expr))
+            }
+            expr => {
+                let loc = expr.loc();
+                let nd_param = self
+                    .convert_rhs_to_param(expr, allow_self)
+                    .map_err(|_| self.stack_dec())?;
+                // non-default parameters cannot follow `*args`/`**kwargs`
+                if params.var_args.is_some() || params.kw_var_args.is_some() {
+                    self.level -= 1;
+                    let err = ParseError::simple_syntax_error(line!() as usize, loc);
+                    self.errs.push(err);
+                    return Err(());
+                }
+                params.non_defaults.push(nd_param);
+                None
+            }
+        };
+        if let Some((op, expr)) = var_param {
+            let loc = Location::concat(&op, &expr);
+            let param = self
+                .convert_rhs_to_param(expr, false)
+                .map_err(|_| self.stack_dec())?;
+            let slot = if op.is(PreStar) && params.kw_var_args.is_none() {
+                &mut params.var_args
+            } else {
+                &mut params.kw_var_args
+            };
+            // `*args` must be one, and `**kwargs` must be the last one
+            if slot.is_some() || !matches!(param.pat, ParamPattern::VarName(_)) {
+                self.level -= 1;
+                let err = ParseError::simple_syntax_error(line!() as usize, loc);
+                self.errs.push(err);
+                return Err(());
+            }
+            *slot = Some(Box::new(param));
+        }
+        self.level -= 1;
+        Ok(())
+    }
+
+    /// `x := 1`
+    fn push_kw_arg_to_params(&mut self, params: &mut Params, arg: KwArg) -> ParseResult<()> {
+        debug_call_info!(self);
+        // `**kwargs` must be the last parameter
+        if let Some(kw_var_args) = params.kw_var_args.as_ref() {
+            let kw_var_args_pos = (kw_var_args.ln_begin(), kw_var_args.col_begin());
+            if (arg.ln_begin(), arg.col_begin()) > kw_var_args_pos {
+                self.level -= 1;
+                let err = ParseError::simple_syntax_error(line!() as usize, arg.loc());
+                self.errs.push(err);
+                return Err(());
+            }
+        }
+        let d_param = self
+            .convert_kw_arg_to_default_param(arg)
+            .map_err(|_| self.stack_dec())?;
+        params.defaults.push(d_param);
+        self.level -= 1;
+        Ok(())
+    }
+
     fn convert_pos_arg_to_non_default_param(
         &mut self,
         arg: PosArg,
@@ -2377,6 +2845,16 @@ impl Parser {
                     self.errs.push(err);
                     return Err(());
                 }
+                if ident.name.is_loop_control() {
+                    self.level -= 1;
+                    let err = ParseError::reserved_name_error(
+                        line!() as usize,
+                        ident.loc(),
+                        ident.inspect(),
+                    );
+                    self.errs.push(err);
+                    return Err(());
+                }
                 // FIXME deny: public
                 let pat = ParamPattern::VarName(ident.name);
                 let param = ParamSignature::new(pat, None, None);
@@ -2407,6 +2885,17 @@ impl Parser {
                 self.level -= 1;
                 Ok(param)
             }
+            // `{x = (x := 1)}`
+            Expr::Tuple(Tuple::Normal(tup))
+                if tup.elems.pos_args().is_empty() && tup.elems.kw_args().len() == 1 =>
+            {
+                let (_, mut kw_args, _) = tup.elems.deconstruct();
+                let param = self
+                    .convert_kw_arg_to_default_param(kw_args.remove(0))
+                    .map_err(|_| self.stack_dec())?;
+                self.level -= 1;
+                Ok(param)
+            }
             Expr::Tuple(tuple) => {
                 let tuple_pat = self
                     .convert_tuple_to_param_tuple_pat(tuple)
@@ -2461,7 +2950,14 @@ impl Parser {
 
     fn convert_kw_arg_to_default_param(&mut self, arg: KwArg) -> ParseResult<ParamSignature> {
         debug_call_info!(self);
-        let pat = ParamPattern::VarName(VarName::new(arg.keyword));
+        let name = VarName::new(arg.keyword);
+        if name.is_loop_control() {
+            self.level -= 1;
+            let err = ParseError::reserved_name_error(line!() as usize, name.loc(), name.inspect());
+            self.errs.push(err);
+            return Err(());
+        }
+        let pat = ParamPattern::VarName(name);
         let expr = Self::validate_const_expr(arg.expr).map_err(|e| self.errs.push(e))?;
         let param = ParamSignature::new(pat, arg.t_spec, Some(expr));
         self.level -= 1;
@@ -2472,11 +2968,16 @@ impl Parser {
         debug_call_info!(self);
         match array {
             Array::Normal(arr) => {
+                let (elems, kw_elems, _) = arr.elems.deconstruct();
                 let mut params = vec![];
-                for arg in arr.elems.into_iters().0 {
+                for arg in elems.into_iter() {
                     params.push(self.convert_pos_arg_to_non_default_param(arg, false)?);
                 }
-                let params = Params::new(params, None, vec![], None);
+                let mut defaults = vec![];
+                for arg in kw_elems.into_iter() {
+                    defaults.push(self.convert_kw_arg_to_default_param(arg)?);
+                }
+                let params = Params::new(params, None, defaults, None);
                 self.level -= 1;
                 Ok(ParamArrayPattern::new(arr.l_sqbr, params, arr.r_sqbr))
             }
@@ -2503,10 +3004,8 @@ impl Parser {
                     let lhs =
                         option_enum_unwrap!(lhs.pat, VarPattern::Ident).unwrap_or_else(|| todo!());
                     assert_eq!(attr.body.block.len(), 1);
-                    let rhs = option_enum_unwrap!(attr.body.block.remove(0), Expr::Accessor)
-                        .unwrap_or_else(|| todo!());
                     let rhs = self
-                        .convert_accessor_to_param_sig(rhs)
+                        .convert_rhs_to_param(attr.body.block.remove(0), false)
                         .map_err(|_| self.stack_dec())?;
                     pats.push(ParamRecordAttr::new(lhs, rhs));
                 }
@@ -2533,11 +3032,15 @@ impl Parser {
         match tuple {
             Tuple::Normal(tup) => {
                 let mut params = vec![];
-                let (elems, _, parens) = tup.elems.deconstruct();
+                let (elems, kw_elems, parens) = tup.elems.deconstruct();
                 for arg in elems.into_iter() {
                     params.push(self.convert_pos_arg_to_non_default_param(arg, false)?);
                 }
-                let params = Params::new(params, None, vec![], parens);
+                let mut defaults = vec![];
+                for arg in kw_elems.into_iter() {
+                    defaults.push(self.convert_kw_arg_to_default_param(arg)?);
+                }
+                let params = Params::new(params, None, defaults, parens);
                 self.level -= 1;
                 Ok(ParamTuplePattern::new(params))
             }
@@ -2602,6 +3105,14 @@ impl Parser {
                 self.level -= 1;
                 Ok(sig)
             }
+            // `(*args) -> ...`
+            Expr::UnaryOp(unary) if unary.op.is(PreStar) || unary.op.is(PreDblStar) => {
+                let mut params = Params::new(vec![], None, vec![], None);
+                self.push_pos_arg_to_params(&mut params, PosArg::new(Expr::UnaryOp(unary)), false)
+                    .map_err(|_| self.stack_dec())?;
+                self.level -= 1;
+                Ok(LambdaSignature::new(params, None, TypeBoundSpecs::empty()))
+            }
             other => {
                 self.level -= 1;
                 let err = ParseError::simple_syntax_error(line!() as usize, other.loc());
@@ -2614,6 +3125,13 @@ impl Parser {
     fn convert_accessor_to_param_sig(&mut self, accessor: Accessor) -> ParseResult<ParamSignature> {
         debug_call_info!(self);
         match accessor {
+            Accessor::Ident(ident) if ident.name.is_loop_control() => {
+                self.level -= 1;
+                let err =
+                    ParseError::reserved_name_error(line!() as usize, ident.loc(), ident.inspect());
+                self.errs.push(err);
+                Err(())
+            }
             Accessor::Ident(ident) => {
                 let pat = if &ident.name.inspect()[..] == "_" {
                     ParamPattern::Discard(ident.name.into_token())
@@ -2639,16 +3157,12 @@ impl Parser {
                 let (pos_args, kw_args, paren) = tup.elems.deconstruct();
                 let mut params = Params::new(vec![], None, vec![], paren);
                 for (i, arg) in pos_args.into_iter().enumerate() {
-                    let param = self
-                        .convert_pos_arg_to_non_default_param(arg, i == 0)
+                    self.push_pos_arg_to_params(&mut params, arg, i == 0)
                         .map_err(|_| self.stack_dec())?;
-                    params.non_defaults.push(param);
                 }
                 for arg in kw_args {
-                    let param = self
-                        .convert_kw_arg_to_default_param(arg)
+                    self.push_kw_arg_to_params(&mut params, arg)
                         .map_err(|_| self.stack_dec())?;
-                    params.defaults.push(param);
                 }
                 self.level -= 1;
                 Ok(params)
@@ -2662,7 +3176,7 @@ impl Parser {
     ) -> ParseResult<LambdaSignature> {
         debug_call_info!(self);
         let sig = self
-            .convert_rhs_to_param(*tasc.expr, true)
+            .convert_type_asc_to_param_pattern(tasc, true)
             .map_err(|_| self.stack_dec())?;
         self.level -= 1;
         Ok(LambdaSignature::new(
@@ -2700,7 +3214,18 @@ impl Parser {
                     "???",
                 )),
             },
-            // TODO: App, Record, BinOp, UnaryOp,
+            Expr::BinOp(bin) => {
+                let mut args = bin.args.into_iter();
+                let lhs = Self::validate_const_expr(*args.next().unwrap())?;
+                let rhs = Self::validate_const_expr(*args.next().unwrap())?;
+                Ok(ConstExpr::BinOp(ConstBinOp::new(bin.op, lhs, rhs)))
+            }
+            Expr::UnaryOp(unary) => {
+                let mut args = unary.args.into_iter();
+                let arg = Self::validate_const_expr(*args.next().unwrap())?;
+                Ok(ConstExpr::UnaryOp(ConstUnaryOp::new(unary.op, arg)))
+            }
+            // TODO: App, Record,
             other => Err(ParseError::syntax_error(
                 line!() as usize,
                 other.loc(),
@@ -2846,6 +3371,40 @@ impl Parser {
                 let len = Self::validate_const_expr(*set.len)?;
                 Ok(SetTypeSpec::new(t_spec, len))
             }
+            Set::Comprehension(set) => {
+                let err = ParseError::simple_syntax_error(line!() as usize, set.loc());
+                Err(err)
+            }
+        }
+    }
+
+    /// `{I: Int | I >= 0}` (a set comprehension without bind clauses) => refinement type
+    fn set_comprehension_to_refinement_type_spec(
+        set: SetComprehension,
+    ) -> Result<RefinementTypeSpec, ParseError> {
+        let loc = set.loc();
+        match *set.elem {
+            Expr::TypeAsc(tasc) if set.generators.is_empty() => {
+                let var = match *tasc.expr {
+                    Expr::Accessor(Accessor::Ident(ident)) => ident.name.into_token(),
+                    other => {
+                        let err = ParseError::simple_syntax_error(line!() as usize, other.loc());
+                        return Err(err);
+                    }
+                };
+                let mut preds = vec![];
+                for pred in set.guards.into_iter() {
+                    preds.push(Self::validate_const_expr(pred)?);
+                }
+                Ok(RefinementTypeSpec::new(
+                    set.l_brace,
+                    set.r_brace,
+                    var,
+                    tasc.t_spec,
+                    preds,
+                ))
+            }
+            _ => Err(ParseError::simple_syntax_error(line!() as usize, loc)),
         }
     }
 
@@ -2864,6 +3423,11 @@ impl Parser {
                 let array = Self::array_to_array_type_spec(array)?;
                 Ok(TypeSpec::Array(array))
             }
+            // e.g. `{I: Int | I >= 0}`
+            Expr::Set(Set::Comprehension(set)) => {
+                let refine = Self::set_comprehension_to_refinement_type_spec(set)?;
+                Ok(TypeSpec::Refinement(refine))
+            }
             Expr::Set(set) => {
                 let set = Self::set_to_set_type_spec(set)?;
                 Ok(TypeSpec::Set(set))
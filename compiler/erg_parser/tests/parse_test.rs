@@ -26,6 +26,36 @@ fn parse_hello_world() -> Result<(), ParserRunnerErrors> {
     expect_success("tests/hello_world.er")
 }
 
+#[test]
+fn parse_pipeline() -> Result<(), ParserRunnerErrors> {
+    expect_success("tests/pipeline.er")
+}
+
+#[test]
+fn parse_try() -> Result<(), ParserRunnerErrors> {
+    expect_success("tests/try.er")
+}
+
+#[test]
+fn parse_refinement() -> Result<(), ParserRunnerErrors> {
+    expect_success("tests/refinement.er")
+}
+
+#[test]
+fn parse_default_param() -> Result<(), ParserRunnerErrors> {
+    expect_success("tests/default_param.er")
+}
+
+#[test]
+fn parse_varargs() -> Result<(), ParserRunnerErrors> {
+    expect_success("tests/varargs.er")
+}
+
+#[test]
+fn parse_varargs_err() -> Result<(), ParserRunnerErrors> {
+    expect_failure("tests/failed_varargs.er")
+}
+
 #[test]
 fn parse_simple_if() -> Result<(), ParserRunnerErrors> {
     expect_success("tests/simple_if.er")
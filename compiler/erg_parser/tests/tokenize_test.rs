@@ -13,6 +13,10 @@ const FILE1: &str = "tests/test1_basic_syntax.er";
 const FILE2: &str = "tests/test2_advanced_syntax.er";
 const FILE3: &str = "tests/test3_literal_syntax.er";
 const FILE4: &str = "tests/multi_line_str_literal.er";
+const FILE5: &str = "tests/str_interp.er";
+const FILE6: &str = "tests/pipeline.er";
+const FILE7: &str = "tests/try.er";
+const FILE8: &str = "tests/loop.er";
 
 #[test]
 fn test_lexer_for_basic() -> ParseResult<()> {
@@ -403,6 +407,138 @@ line break\"\"\"",
     Ok(())
 }
 
+#[test]
+fn test_lexer_for_str_interp() -> ParseResult<()> {
+    let mut lexer = Lexer::new(Input::File(FILE5.into()));
+    let newline = "\n";
+    let token_array = vec![
+        (Symbol, "print!"),
+        (StrInterpLeft, "\"a\\{"),
+        (Symbol, "x"),
+        (StrInterpMid, "}b\\{"),
+        (LBrace, "{"),
+        (NatLit, "1"),
+        (RBrace, "}"),
+        (StrInterpRight, "}c\""),
+        (Newline, newline),
+        (EOF, ""),
+    ];
+    let mut tok: Token;
+    for (id, i) in token_array.into_iter().enumerate() {
+        print!("{id:>03}: ");
+        tok = lexer.next().unwrap().unwrap();
+        assert_eq!(tok, Token::from_str(i.0, i.1));
+        println!("{tok}");
+    }
+    Ok(())
+}
+
+#[test]
+fn test_lexer_for_pipeline() -> ParseResult<()> {
+    let mut lexer = Lexer::new(Input::File(FILE6.into()));
+    let newline = "\n";
+    let token_array = vec![
+        (Symbol, "x"),
+        (Pipe, "|>"),
+        (Symbol, "f"),
+        (Pipe, "|>"),
+        (Dot, "."),
+        (Symbol, "g"),
+        (LParen, "("),
+        (NatLit, "1"),
+        (RParen, ")"),
+        (Newline, newline),
+        (EOF, ""),
+    ];
+    let mut tok: Token;
+    for (id, i) in token_array.into_iter().enumerate() {
+        print!("{id:>03}: ");
+        tok = lexer.next().unwrap().unwrap();
+        assert_eq!(tok, Token::from_str(i.0, i.1));
+        println!("{tok}");
+    }
+    Ok(())
+}
+
+#[test]
+fn test_lexer_for_try() -> ParseResult<()> {
+    let mut lexer = Lexer::new(Input::File(FILE7.into()));
+    let newline = "\n";
+    let token_array = vec![
+        (Symbol, "y"),
+        (Equal, "="),
+        (Symbol, "f"),
+        (LParen, "("),
+        (Symbol, "x"),
+        (RParen, ")"),
+        (Try, "?"),
+        (Plus, "+"),
+        (NatLit, "1"),
+        (Newline, newline),
+        (EOF, ""),
+    ];
+    let mut tok: Token;
+    for (id, i) in token_array.into_iter().enumerate() {
+        print!("{id:>03}: ");
+        tok = lexer.next().unwrap().unwrap();
+        assert_eq!(tok, Token::from_str(i.0, i.1));
+        println!("{tok}");
+    }
+    Ok(())
+}
+
+#[test]
+fn test_lexer_for_loop() -> ParseResult<()> {
+    let mut lexer = Lexer::new(Input::File(FILE8.into()));
+    let newline = "\n";
+    let token_array = vec![
+        (Symbol, "for!"),
+        (Symbol, "xs"),
+        (Comma, ","),
+        (Symbol, "x"),
+        (ProcArrow, "=>"),
+        (Newline, newline),
+        (Indent, "    "),
+        (Symbol, "while!"),
+        (Symbol, "x"),
+        (Comma, ","),
+        (Symbol, "do!"),
+        (Colon, ":"),
+        (Newline, newline),
+        (Indent, "    "),
+        (Symbol, "f!"),
+        (LParen, "("),
+        (RParen, ")"),
+        (Newline, newline),
+        (Symbol, "if!"),
+        (Symbol, "x"),
+        (Comma, ","),
+        (Symbol, "do!"),
+        (Colon, ":"),
+        (Newline, newline),
+        (Indent, "    "),
+        (Symbol, "break"),
+        (Newline, newline),
+        // dedent two levels at once
+        (Dedent, ""),
+        (Dedent, ""),
+        (Symbol, "g!"),
+        (LParen, "("),
+        (RParen, ")"),
+        (Newline, newline),
+        (Dedent, ""),
+        (EOF, ""),
+    ];
+    let mut tok: Token;
+    for (id, i) in token_array.into_iter().enumerate() {
+        print!("{id:>03}: ");
+        tok = lexer.next().unwrap().unwrap();
+        assert_eq!(tok, Token::from_str(i.0, i.1));
+        println!("{tok}");
+    }
+    Ok(())
+}
+
 #[test]
 fn tesop_te_prec() {
     assert_eq!(Mod.precedence(), Some(170));
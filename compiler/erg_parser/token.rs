@@ -25,6 +25,12 @@ pub enum TokenKind {
     RatioLit,
     BoolLit,
     StrLit,
+    /// e.g. `"abc\{` (the beginning of an interpolated string)
+    StrInterpLeft,
+    /// e.g. `}abc\{`
+    StrInterpMid,
+    /// e.g. `}abc"`
+    StrInterpRight,
     NoneLit,
     NoImplLit,
     EllipsisLit,
@@ -35,6 +41,10 @@ pub enum TokenKind {
     PreMinus,
     /// ~ (unary)
     PreBitNot,
+    /// `*` (unary, variable-length positional parameters)
+    PreStar,
+    /// `**` (unary, variable-length keyword parameters)
+    PreDblStar,
     // PreAmp,    // & (unary)
     // PreAt,     // @ (unary)
     /// ! (unary)
@@ -214,15 +224,19 @@ impl TokenKind {
             Symbol => TokenCategory::Symbol,
             NatLit | IntLit | RatioLit | StrLit | BoolLit | NoneLit | EllipsisLit | NoImplLit
             | InfLit => TokenCategory::Literal,
-            PrePlus | PreMinus | PreBitNot | Mutate | RefOp | RefMutOp => TokenCategory::UnaryOp,
+            PrePlus | PreMinus | PreBitNot | PreStar | PreDblStar | Mutate | RefOp | RefMutOp => {
+                TokenCategory::UnaryOp
+            }
             Try => TokenCategory::PostfixOp,
             Comma | Colon | DblColon | SupertypeOf | SubtypeOf | Dot | Pipe | Walrus
             | Inclusion => TokenCategory::SpecialBinOp,
             Equal => TokenCategory::DefOp,
             FuncArrow | ProcArrow => TokenCategory::LambdaOp,
             Semi | Newline => TokenCategory::Separator,
-            LParen | LBrace | LSqBr | Indent => TokenCategory::LEnclosure,
-            RParen | RBrace | RSqBr | Dedent => TokenCategory::REnclosure,
+            LParen | LBrace | LSqBr | Indent | StrInterpLeft => TokenCategory::LEnclosure,
+            RParen | RBrace | RSqBr | Dedent | StrInterpMid | StrInterpRight => {
+                TokenCategory::REnclosure
+            }
             Caret | Amper => TokenCategory::Reserved,
             AtSign => TokenCategory::AtSign,
             VBar => TokenCategory::VBar,
@@ -239,6 +253,7 @@ impl TokenKind {
             Dot | DblColon => 200,                                    // .
             Pow => 190,                                               // **
             PrePlus | PreMinus | PreBitNot | RefOp | RefMutOp => 180, // (unary) + - * ~ ref ref!
+            PreStar | PreDblStar => 180,                              // (unary) * **
             Star | Slash | FloorDiv | Mod | CrossOp | DotOp => 170,   // * / // % cross dot
             Plus | Minus => 160,                                      // + -
             Shl | Shr => 150,                                         // << >>
@@ -249,6 +264,7 @@ impl TokenKind {
             Less | Gre | LessEq | GreEq | DblEq | NotEq | InOp | NotInOp | IsOp | IsNotOp => 90, // < > <= >= == != in notin is isnot
             AndOp => 80,                             // and
             OrOp => 70,                              // or
+            Pipe => 65,                              // |>
             FuncArrow | ProcArrow | Inclusion => 60, // -> => <-
             Colon | SupertypeOf | SubtypeOf => 50,   // : :> <:
             Comma => 40,                             // ,
@@ -270,6 +286,10 @@ impl TokenKind {
     pub const fn is_range_op(&self) -> bool {
         matches!(self, Closed | LeftOpen | RightOpen | Open)
     }
+
+    pub const fn is_comparison_op(&self) -> bool {
+        matches!(self, Less | Gre | LessEq | GreEq | DblEq | NotEq)
+    }
 }
 
 impl fmt::Display for TokenKind {
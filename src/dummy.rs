@@ -1,18 +1,20 @@
-use std::fs::remove_file;
+use std::fs::{read_to_string, remove_file};
 use std::io::{Read, Write};
 use std::net::{Ipv4Addr, SocketAddrV4, TcpListener, TcpStream};
 use std::thread::sleep;
 use std::time::Duration;
 
-use erg_common::config::ErgConfig;
-use erg_common::python_util::{exec_pyc, spawn_py};
+use erg_common::config::{ErgConfig, Input};
+use erg_common::error::{ErrorCore, ErrorKind, Location};
+use erg_common::json::JsonValue;
+use erg_common::python_util::{exec_py_with_args, spawn_py};
 use erg_common::traits::Runnable;
 
 use erg_compiler::hir::Expr;
 use erg_compiler::ty::HasType;
 
 use erg_compiler::error::{CompileError, CompileErrors};
-use erg_compiler::Compiler;
+use erg_compiler::{source_map_path, Compiler};
 
 pub type EvalError = CompileError;
 pub type EvalErrors = CompileErrors;
@@ -50,7 +52,7 @@ impl Runnable for DummyVM {
                 println!("Connecting to the REPL server...");
             }
             loop {
-                match TcpStream::connect(&addr) {
+                match TcpStream::connect(addr) {
                     Ok(stream) => {
                         stream
                             .set_read_timeout(Some(Duration::from_secs(cfg.py_server_timeout)))
@@ -76,19 +78,10 @@ impl Runnable for DummyVM {
     }
 
     fn finish(&mut self) {
-        if let Some(stream) = &mut self.stream {
-            stream.write_all("exit".as_bytes()).unwrap();
-            let mut buf = [0; 1024];
-            match stream.read(&mut buf) {
-                Result::Ok(n) => {
-                    let s = std::str::from_utf8(&buf[..n]).unwrap();
-                    if s.contains("closed") {
-                        println!("The REPL server is closed.");
-                    }
-                }
-                Result::Err(e) => {
-                    panic!("{}", format!("Read error: {e}"));
-                }
+        if self.stream.is_some() {
+            let reply = self.send_order("exit");
+            if reply.get("kind").and_then(|k| k.as_str()) == Some("closed") {
+                println!("The REPL server is closed.");
             }
         }
     }
@@ -104,7 +97,7 @@ impl Runnable for DummyVM {
             .input
             .filename()
             .split('/')
-            .last()
+            .next_back()
             .unwrap()
             .replace(".er", ".pyc");
         self.compiler
@@ -112,8 +105,25 @@ impl Runnable for DummyVM {
         if self.cfg().dump_as_pyc {
             return Ok(0);
         }
-        let code = exec_pyc(&filename);
-        remove_file(&filename).unwrap();
+        let map_path = source_map_path(&filename);
+        let report_path = format!("{filename}.report");
+        let code = exec_py_with_args(
+            include_str!("scripts/exec_pyc.py"),
+            &[&filename, &report_path],
+        );
+        let source_map = read_to_string(&map_path)
+            .ok()
+            .and_then(|map| JsonValue::parse(&map).ok());
+        let _ = remove_file(&filename);
+        let _ = remove_file(&map_path);
+        if let Ok(report) = read_to_string(&report_path) {
+            let _ = remove_file(&report_path);
+            let report = JsonValue::parse(&report)
+                .unwrap_or_else(|e| panic!("invalid exception report: {e}"));
+            return Err(EvalErrors::from(
+                self.map_exception(&report, source_map.as_ref()),
+            ));
+        }
         Ok(code.unwrap_or(1))
     }
 
@@ -121,30 +131,39 @@ impl Runnable for DummyVM {
         let last = self
             .compiler
             .eval_compile_and_dump_as_pyc("o.pyc", src, "eval")?;
-        let mut res = match self.stream.as_mut().unwrap().write("load".as_bytes()) {
-            Result::Ok(_) => {
-                let mut buf = [0; 1024];
-                match self.stream.as_mut().unwrap().read(&mut buf) {
-                    Result::Ok(n) => {
-                        let s = std::str::from_utf8(&buf[..n]).unwrap();
-                        if s == "[Exception] SystemExit" {
-                            return Err(EvalErrors::from(EvalError::system_exit()));
-                        }
-                        s.to_string()
-                    }
-                    Result::Err(e) => {
-                        self.finish();
-                        panic!("{}", format!("Read error: {e}"));
-                    }
+        let reply = self.send_order("load");
+        let field = |key: &str| reply.get(key).and_then(|v| v.as_str()).unwrap_or("");
+        eprint!("{}", field("stderr"));
+        let mut res = field("stdout").trim_end_matches('\n').to_string();
+        match field("kind") {
+            "ok" => {}
+            "exit" => {
+                print!("{}", res);
+                return Err(EvalErrors::from(EvalError::system_exit()));
+            }
+            "exception" => {
+                if !res.is_empty() {
+                    println!("{res}");
                 }
+                let exc = reply.get("exception").unwrap();
+                let exc_field = |key: &str| exc.get(key).and_then(|v| v.as_str()).unwrap_or("");
+                return Err(EvalErrors::from(runtime_error(
+                    self.input().clone(),
+                    exc_field("type"),
+                    exc_field("message"),
+                    exc_field("traceback"),
+                )));
             }
-            Result::Err(e) => {
+            other => {
                 self.finish();
-                panic!("{}", format!("Sending error: {e}"))
+                panic!("unexpected reply from the REPL server: {other}");
             }
-        };
-        if res.ends_with("None") {
-            res.truncate(res.len() - 5);
+        }
+        if let Some(result) = reply.get("result").and_then(|v| v.as_str()) {
+            if !res.is_empty() {
+                res.push('\n');
+            }
+            res.push_str(result);
         }
         if self.cfg().show_type {
             res.push_str(": ");
@@ -165,16 +184,159 @@ impl Runnable for DummyVM {
     }
 }
 
+impl DummyVM {
+    /// Converts an exception report of `scripts/exec_pyc.py` into a runtime error
+    /// that points at the innermost frame of the executed module.
+    /// Other frames are shown in the hint, like Python's traceback.
+    fn map_exception(&self, report: &JsonValue, source_map: Option<&JsonValue>) -> EvalError {
+        let module = self.input().enclosed_name();
+        let frames = report
+            .get("frames")
+            .and_then(|frames| frames.as_array())
+            .map(|frames| &frames[..])
+            .unwrap_or(&[]);
+        let frame_loc = |frame: &JsonValue| {
+            if str_field(frame, "filename") != module {
+                return None;
+            }
+            source_map
+                .and_then(|map| {
+                    lookup_source_map(
+                        map,
+                        str_field(frame, "name"),
+                        usize_field(frame, "firstlineno"),
+                        usize_field(frame, "lasti"),
+                    )
+                })
+                .or_else(|| {
+                    Some(usize_field(frame, "lineno"))
+                        .filter(|ln| *ln != 0)
+                        .map(Location::Line)
+                })
+        };
+        let innermost = frames
+            .iter()
+            .rev()
+            .find_map(|frame| frame_loc(frame).map(|loc| (frame, loc)));
+        let (frame, loc) = if let Some(innermost) = innermost {
+            innermost
+        } else {
+            return runtime_error(
+                self.input().clone(),
+                str_field(report, "type"),
+                str_field(report, "message"),
+                str_field(report, "traceback"),
+            );
+        };
+        let hint = if frames.len() > 1 {
+            let traceback = frames
+                .iter()
+                .map(|frame| {
+                    let lineno = frame_loc(frame)
+                        .and_then(|loc| loc.ln_begin())
+                        .unwrap_or_else(|| usize_field(frame, "lineno"));
+                    format!(
+                        "File \"{}\", line {lineno}, in {}",
+                        str_field(frame, "filename"),
+                        str_field(frame, "name")
+                    )
+                })
+                .collect::<Vec<_>>()
+                .join("\n");
+            Some(format!("Traceback (most recent call last):\n{traceback}").into())
+        } else {
+            None
+        };
+        EvalError::new(
+            ErrorCore::new(
+                0,
+                ErrorKind::from(str_field(report, "type")),
+                loc,
+                str_field(report, "message").to_string(),
+                hint,
+            ),
+            self.input().clone(),
+            str_field(frame, "name").to_string().into(),
+        )
+    }
+
+    /// Sends `order` to the REPL server and returns the reply.
+    /// Both messages are prefixed with their length (4-byte big-endian).
+    fn send_order(&mut self, order: &str) -> JsonValue {
+        let stream = self.stream.as_mut().unwrap();
+        let mut msg = (order.len() as u32).to_be_bytes().to_vec();
+        msg.extend(order.as_bytes());
+        if let Err(e) = stream.write_all(&msg) {
+            panic!("Sending error: {e}");
+        }
+        let mut len = [0; 4];
+        let mut buf = vec![];
+        let res = stream.read_exact(&mut len).and_then(|_| {
+            buf.resize(u32::from_be_bytes(len) as usize, 0);
+            stream.read_exact(&mut buf)
+        });
+        if let Err(e) = res {
+            panic!("Read error: {e}");
+        }
+        let reply = String::from_utf8_lossy(&buf);
+        JsonValue::parse(&reply)
+            .unwrap_or_else(|e| panic!("invalid reply from the REPL server: {e}"))
+    }
+}
+
+fn str_field<'a>(json: &'a JsonValue, key: &str) -> &'a str {
+    json.get(key).and_then(|v| v.as_str()).unwrap_or("")
+}
+
+fn usize_field(json: &JsonValue, key: &str) -> usize {
+    json.get(key).and_then(|v| v.as_u64()).unwrap_or(0) as usize
+}
+
+/// Returns the location of the narrowest span containing `lasti`
+/// in the code object `name` (defined at `firstlineno`)
+fn lookup_source_map(
+    source_map: &JsonValue,
+    name: &str,
+    firstlineno: usize,
+    lasti: usize,
+) -> Option<Location> {
+    let code = source_map.get("codes")?.as_array()?.iter().find(|code| {
+        str_field(code, "name") == name && usize_field(code, "firstlineno") == firstlineno
+    })?;
+    code.get("spans")?
+        .as_array()?
+        .iter()
+        .filter(|span| usize_field(span, "start") <= lasti && lasti < usize_field(span, "end"))
+        .min_by_key(|span| usize_field(span, "end") - usize_field(span, "start"))
+        .and_then(|span| span.get("loc"))
+        .map(Location::from_json)
+        .filter(|loc| *loc != Location::Unknown)
+}
+
+/// Converts a Python exception into a runtime error
+/// (the kind is `UserError` if `exc_type` is not a builtin exception)
+fn runtime_error(input: Input, exc_type: &str, message: &str, traceback: &str) -> EvalError {
+    let desc = if traceback.is_empty() {
+        message.to_string()
+    } else {
+        format!("{message}\n{}", traceback.trim_end())
+    };
+    EvalError::new(
+        ErrorCore::new(0, ErrorKind::from(exc_type), Location::Unknown, desc, None),
+        input,
+        "".into(),
+    )
+}
+
 fn find_available_port() -> u16 {
     const DEFAULT_PORT: u16 = 8736;
-    TcpListener::bind(SocketAddrV4::new(Ipv4Addr::LOCALHOST, DEFAULT_PORT))
-        .is_ok()
-        .then_some(DEFAULT_PORT)
-        .unwrap_or_else(|| {
-            let socket = SocketAddrV4::new(Ipv4Addr::LOCALHOST, 0);
-            TcpListener::bind(socket)
-                .and_then(|listener| listener.local_addr())
-                .map(|sock_addr| sock_addr.port())
-                .expect("No free port found.")
-        })
+    if TcpListener::bind(SocketAddrV4::new(Ipv4Addr::LOCALHOST, DEFAULT_PORT)).is_ok() {
+        DEFAULT_PORT
+    } else {
+        let socket = SocketAddrV4::new(Ipv4Addr::LOCALHOST, 0);
+        TcpListener::bind(socket)
+            .and_then(|listener| listener.local_addr())
+            .map(|sock_addr| sock_addr.port())
+            .expect("No free port found.")
+    }
 }
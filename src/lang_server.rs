@@ -0,0 +1,873 @@
+//! implements a language server for Erg (LSP over stdio).
+//!
+//! Erg用の言語サーバー(標準入出力上のLSP)を実装する
+use std::io::{stdin, stdout, BufRead, BufReader, Write};
+use std::panic::{catch_unwind, AssertUnwindSafe};
+use std::path::{Path, PathBuf};
+
+use erg_common::color::strip_escapes;
+use erg_common::config::{ErgConfig, Input};
+use erg_common::dict::Dict;
+use erg_common::error::{ErrorDisplay, Location};
+use erg_common::json::JsonValue;
+use erg_common::traits::{Locational, Stream};
+use erg_common::Str;
+
+use erg_parser::ast::{ParamPattern, Params};
+
+use erg_compiler::build_hir::HIRBuilder;
+use erg_compiler::context::Context;
+use erg_compiler::error::CompileErrors;
+use erg_compiler::hir::{Accessor, Args, Block, Def, Expr, Identifier, Signature, HIR};
+use erg_compiler::mod_cache::SharedModuleCache;
+use erg_compiler::ty::{HasType, Type};
+use erg_compiler::varinfo::VarInfo;
+
+const PARSE_ERROR: i64 = -32700;
+const METHOD_NOT_FOUND: i64 = -32601;
+const SERVER_NOT_INITIALIZED: i64 = -32002;
+
+// LSP constants
+const DIAG_ERROR: u8 = 1;
+const DIAG_WARNING: u8 = 2;
+const COMPLETION_METHOD: u8 = 2;
+const COMPLETION_FUNCTION: u8 = 3;
+const COMPLETION_VARIABLE: u8 = 6;
+const COMPLETION_CLASS: u8 = 7;
+const COMPLETION_MODULE: u8 = 9;
+const COMPLETION_CONSTANT: u8 = 21;
+
+/// The result of checking a file: the (maybe partial) HIR and the module context
+struct Analysis {
+    hir: Option<HIR>,
+    ctx: Option<Context>,
+}
+
+/// A name found while walking the HIR
+#[derive(Debug, Clone)]
+struct Symbol {
+    name: Str,
+    t: Type,
+    loc: Location,
+    def_loc: Option<Location>,
+}
+
+/// Finds the symbol at the given position, tracking the definitions in the enclosing scopes.
+/// `ln` is 1-origin and `col` is 0-origin (the same as `Location`).
+struct SymbolFinder<'a> {
+    ln: usize,
+    col: usize,
+    ctx: Option<&'a Context>,
+    scopes: Vec<Vec<Symbol>>,
+    found: Option<Symbol>,
+    /// names visible at the position (for completion)
+    visible: Option<Vec<Symbol>>,
+}
+
+impl<'a> SymbolFinder<'a> {
+    fn new(ln: usize, col: usize, ctx: Option<&'a Context>) -> Self {
+        Self {
+            ln,
+            col,
+            ctx,
+            scopes: vec![],
+            found: None,
+            visible: None,
+        }
+    }
+
+    fn contains(&self, loc: Location) -> bool {
+        match (loc.ln_begin(), loc.col_begin(), loc.ln_end(), loc.col_end()) {
+            (Some(lb), Some(cb), Some(le), Some(ce)) => {
+                (lb, cb) <= (self.ln, self.col) && (self.ln, self.col) <= (le, ce)
+            }
+            (Some(lb), _, Some(le), _) => lb <= self.ln && self.ln <= le,
+            _ => false,
+        }
+    }
+
+    fn lookup(&self, name: &str) -> Option<Location> {
+        for scope in self.scopes.iter().rev() {
+            if let Some(sym) = scope.iter().rev().find(|sym| &sym.name[..] == name) {
+                return Some(sym.loc);
+            }
+        }
+        let ctx = self.ctx?;
+        let (var_name, _) = ctx.get_var_info(name).ok()?;
+        match var_name.loc() {
+            Location::Unknown => None,
+            loc => Some(loc),
+        }
+    }
+
+    fn found(&mut self, name: &Str, t: &Type, loc: Location, def_loc: Option<Location>) {
+        if self.found.is_none() && self.contains(loc) {
+            self.found = Some(Symbol {
+                name: name.clone(),
+                t: t.clone(),
+                loc,
+                def_loc,
+            });
+        }
+    }
+
+    fn push_scope(&mut self, loc: Location, syms: Vec<Symbol>) {
+        self.scopes.push(syms);
+        if self.contains(loc) {
+            self.visible = Some(self.scopes.iter().flatten().cloned().collect());
+        }
+    }
+
+    fn collect_defs(block: &[Expr]) -> Vec<Symbol> {
+        let mut syms = vec![];
+        for chunk in block.iter() {
+            let sig = match chunk {
+                Expr::Def(def) => &def.sig,
+                Expr::ClassDef(class_def) => &class_def.sig,
+                _ => continue,
+            };
+            let ident = sig.ident();
+            syms.push(Symbol {
+                name: ident.inspect().clone(),
+                t: sig.t(),
+                loc: ident.name.loc(),
+                def_loc: Some(ident.name.loc()),
+            });
+        }
+        syms
+    }
+
+    fn collect_params(params: &Params, subr_t: &Type) -> Vec<Symbol> {
+        let param_ts = subr_t
+            .non_default_params()
+            .into_iter()
+            .flatten()
+            .chain(subr_t.default_params().into_iter().flatten())
+            .map(|pt| pt.typ().clone())
+            .collect::<Vec<_>>();
+        params
+            .non_defaults
+            .iter()
+            .chain(params.defaults.iter())
+            .enumerate()
+            .filter_map(|(i, param)| match &param.pat {
+                ParamPattern::VarName(name)
+                | ParamPattern::Ref(name)
+                | ParamPattern::RefMut(name) => Some(Symbol {
+                    name: name.inspect().clone(),
+                    t: param_ts.get(i).cloned().unwrap_or(Type::Obj),
+                    loc: name.loc(),
+                    def_loc: Some(name.loc()),
+                }),
+                _ => None,
+            })
+            .collect()
+    }
+
+    fn visit_module(&mut self, hir: &HIR) {
+        let syms = Self::collect_defs(hir.module.ref_payload());
+        self.scopes.push(syms);
+        self.visible = Some(self.scopes.iter().flatten().cloned().collect());
+        for chunk in hir.module.iter() {
+            self.visit_expr(chunk);
+        }
+    }
+
+    fn visit_block(&mut self, block: &Block, params: Vec<Symbol>) {
+        let mut syms = params;
+        syms.extend(Self::collect_defs(block.ref_payload()));
+        let loc = if block.is_empty() {
+            Location::Unknown
+        } else {
+            block.loc()
+        };
+        self.push_scope(loc, syms);
+        for chunk in block.iter() {
+            self.visit_expr(chunk);
+        }
+        self.scopes.pop();
+    }
+
+    fn visit_args(&mut self, args: &Args) {
+        for arg in args.pos_args.iter() {
+            self.visit_expr(&arg.expr);
+        }
+        if let Some(var_args) = &args.var_args {
+            self.visit_expr(&var_args.expr);
+        }
+        for arg in args.kw_args.iter() {
+            self.visit_expr(&arg.expr);
+        }
+    }
+
+    fn visit_def(&mut self, def: &Def) {
+        let ident = def.sig.ident();
+        let name_loc = ident.name.loc();
+        self.found(ident.inspect(), &def.sig.t(), name_loc, Some(name_loc));
+        let params = match &def.sig {
+            Signature::Subr(sig) => {
+                let params = Self::collect_params(&sig.params, &sig.t);
+                for param in params.iter() {
+                    self.found(&param.name, &param.t, param.loc, param.def_loc);
+                }
+                params
+            }
+            Signature::Var(_) => vec![],
+        };
+        self.visit_block(&def.body.block, params);
+    }
+
+    /// The bound variables are visible in the whole comprehension
+    fn visit_comprehension(
+        &mut self,
+        loc: Location,
+        generators: &[(Identifier, Expr)],
+        guards: &[Expr],
+        elems: &[&Expr],
+    ) {
+        let mut syms = vec![];
+        for (ident, _) in generators.iter() {
+            let loc = ident.name.loc();
+            self.found(ident.inspect(), ident.ref_t(), loc, Some(loc));
+            syms.push(Symbol {
+                name: ident.inspect().clone(),
+                t: ident.t(),
+                loc,
+                def_loc: Some(loc),
+            });
+        }
+        self.push_scope(loc, syms);
+        for (_, iterable) in generators.iter() {
+            self.visit_expr(iterable);
+        }
+        for expr in guards.iter().chain(elems.iter().copied()) {
+            self.visit_expr(expr);
+        }
+        self.scopes.pop();
+    }
+
+    fn visit_acc(&mut self, acc: &Accessor) {
+        match acc {
+            Accessor::Ident(ident) => {
+                let loc = ident.name.loc();
+                if self.found.is_none() && self.contains(loc) {
+                    let def_loc = self.lookup(ident.inspect());
+                    self.found(ident.inspect(), ident.ref_t(), loc, def_loc);
+                }
+            }
+            Accessor::Attr(attr) => {
+                self.visit_expr(&attr.obj);
+                let loc = attr.ident.name.loc();
+                self.found(attr.ident.inspect(), attr.ref_t(), loc, None);
+            }
+            Accessor::TupleAttr(attr) => {
+                self.visit_expr(&attr.obj);
+            }
+            Accessor::Subscr(_) => {}
+        }
+    }
+
+    fn visit_expr(&mut self, expr: &Expr) {
+        if self.found.is_some() {
+            return;
+        }
+        match expr {
+            Expr::Accessor(acc) => self.visit_acc(acc),
+            Expr::Def(def) => self.visit_def(def),
+            Expr::ClassDef(class_def) => {
+                let ident = class_def.sig.ident();
+                let loc = ident.name.loc();
+                self.found(ident.inspect(), &class_def.sig.t(), loc, Some(loc));
+                self.visit_expr(&class_def.require_or_sup);
+                self.visit_block(&class_def.methods, vec![]);
+            }
+            Expr::AttrDef(attr_def) => {
+                self.visit_acc(&attr_def.attr);
+                self.visit_block(&attr_def.block, vec![]);
+            }
+            Expr::Lambda(lambda) => {
+                let params = Self::collect_params(&lambda.params, &lambda.t);
+                for param in params.iter() {
+                    self.found(&param.name, &param.t, param.loc, param.def_loc);
+                }
+                self.visit_block(&lambda.body, params);
+            }
+            Expr::Call(call) => {
+                self.visit_expr(&call.obj);
+                if let Some(attr_name) = &call.attr_name {
+                    let loc = attr_name.name.loc();
+                    self.found(attr_name.inspect(), &call.sig_t, loc, None);
+                }
+                self.visit_args(&call.args);
+            }
+            Expr::BinOp(bin) => {
+                self.visit_expr(&bin.lhs);
+                self.visit_expr(&bin.rhs);
+            }
+            Expr::UnaryOp(unary) => self.visit_expr(&unary.expr),
+            Expr::StrInterp(interp) => {
+                for expr in interp.exprs.iter() {
+                    self.visit_expr(expr);
+                }
+            }
+            Expr::Array(arr) => match arr {
+                erg_compiler::hir::Array::Normal(arr) => self.visit_args(&arr.elems),
+                erg_compiler::hir::Array::WithLength(arr) => {
+                    self.visit_expr(&arr.elem);
+                    self.visit_expr(&arr.len);
+                }
+                erg_compiler::hir::Array::Comprehension(arr) => {
+                    self.visit_comprehension(arr.loc(), &arr.generators, &arr.guards, &[&arr.elem]);
+                }
+            },
+            Expr::Tuple(erg_compiler::hir::Tuple::Normal(tup)) => self.visit_args(&tup.elems),
+            Expr::Set(set) => match set {
+                erg_compiler::hir::Set::Normal(set) => self.visit_args(&set.elems),
+                erg_compiler::hir::Set::WithLength(set) => {
+                    self.visit_expr(&set.elem);
+                    self.visit_expr(&set.len);
+                }
+                erg_compiler::hir::Set::Comprehension(set) => {
+                    self.visit_comprehension(set.loc(), &set.generators, &set.guards, &[&set.elem]);
+                }
+            },
+            Expr::Dict(dict) => match dict {
+                erg_compiler::hir::Dict::Normal(dict) => {
+                    for kv in dict.kvs.iter() {
+                        self.visit_expr(&kv.key);
+                        self.visit_expr(&kv.value);
+                    }
+                }
+                erg_compiler::hir::Dict::Comprehension(dict) => {
+                    let elems = [dict.key.as_ref(), dict.value.as_ref()];
+                    self.visit_comprehension(dict.loc(), &dict.generators, &dict.guards, &elems);
+                }
+            },
+            Expr::Record(rec) => {
+                for attr in rec.attrs.iter() {
+                    self.visit_def(attr);
+                }
+            }
+            Expr::TypeAsc(tasc) => self.visit_expr(&tasc.expr),
+            Expr::Code(block) | Expr::Compound(block) => {
+                for chunk in block.iter() {
+                    self.visit_expr(chunk);
+                }
+            }
+            Expr::Decl(decl) => {
+                let ident = decl.sig.ident();
+                let loc = ident.name.loc();
+                self.found(ident.inspect(), &decl.t, loc, Some(loc));
+            }
+            Expr::Lit(_) => {}
+        }
+    }
+}
+
+/// Erg Language Server
+///
+/// The editor and the server communicate with JSON-RPC messages (with `Content-Length` headers) over stdio.
+/// Type information is obtained by checking the file with `HIRBuilder`.
+pub struct ErgLanguageServer {
+    cfg: ErgConfig,
+    initialized: bool,
+    shutdown_requested: bool,
+    /// K: URI, V: source text
+    files: Dict<String, String>,
+    analyses: Dict<String, Analysis>,
+}
+
+impl ErgLanguageServer {
+    pub fn new(cfg: ErgConfig) -> Self {
+        Self {
+            cfg,
+            initialized: false,
+            shutdown_requested: false,
+            files: Dict::new(),
+            analyses: Dict::new(),
+        }
+    }
+
+    pub fn run(&mut self) {
+        let stdin = stdin();
+        let mut reader = BufReader::new(stdin.lock());
+        while let Some(msg) = read_message(&mut reader) {
+            match JsonValue::parse(&msg) {
+                Ok(msg) => {
+                    if let Some(code) = self.dispatch(&msg) {
+                        std::process::exit(code);
+                    }
+                }
+                Err(err) => {
+                    self.send_error(JsonValue::Null, PARSE_ERROR, &err.to_string());
+                }
+            }
+        }
+    }
+
+    /// Returns the exit code if the server should stop
+    fn dispatch(&mut self, msg: &JsonValue) -> Option<i32> {
+        let method = msg.get("method").and_then(|m| m.as_str()).unwrap_or("");
+        let id = msg.get("id").cloned();
+        let params = msg.get("params").cloned().unwrap_or(JsonValue::Null);
+        if !self.initialized && method != "initialize" && method != "exit" {
+            if let Some(id) = id {
+                self.send_error(id, SERVER_NOT_INITIALIZED, "the server is not initialized");
+            }
+            return None;
+        }
+        match (method, id) {
+            ("initialize", Some(id)) => {
+                self.initialized = true;
+                self.send_result(id, Self::capabilities());
+            }
+            ("shutdown", Some(id)) => {
+                self.shutdown_requested = true;
+                self.send_result(id, JsonValue::Null);
+            }
+            ("exit", _) => {
+                return Some(if self.shutdown_requested { 0 } else { 1 });
+            }
+            ("textDocument/didOpen", None) => {
+                if let (Some(uri), Some(text)) = (
+                    params
+                        .path(&["textDocument", "uri"])
+                        .and_then(|u| u.as_str()),
+                    params
+                        .path(&["textDocument", "text"])
+                        .and_then(|t| t.as_str()),
+                ) {
+                    self.files.insert(uri.to_string(), text.to_string());
+                    self.check_file(uri);
+                }
+            }
+            ("textDocument/didChange", None) => {
+                let uri = params
+                    .path(&["textDocument", "uri"])
+                    .and_then(|u| u.as_str());
+                // the server requires full synchronization, so the last change is the whole text
+                let text = params
+                    .get("contentChanges")
+                    .and_then(|changes| changes.as_array())
+                    .and_then(|changes| changes.last())
+                    .and_then(|change| change.get("text"))
+                    .and_then(|t| t.as_str());
+                if let (Some(uri), Some(text)) = (uri, text) {
+                    self.files.insert(uri.to_string(), text.to_string());
+                    self.check_file(uri);
+                }
+            }
+            ("textDocument/didSave", None) => {
+                if let Some(uri) = params
+                    .path(&["textDocument", "uri"])
+                    .and_then(|u| u.as_str())
+                {
+                    self.check_file(uri);
+                }
+            }
+            ("textDocument/didClose", None) => {
+                if let Some(uri) = params
+                    .path(&["textDocument", "uri"])
+                    .and_then(|u| u.as_str())
+                {
+                    self.files.remove(uri);
+                    self.analyses.remove(uri);
+                }
+            }
+            ("textDocument/hover", Some(id)) => {
+                let res = self.hover(&params).unwrap_or(JsonValue::Null);
+                self.send_result(id, res);
+            }
+            ("textDocument/definition", Some(id)) => {
+                let res = self.definition(&params).unwrap_or(JsonValue::Null);
+                self.send_result(id, res);
+            }
+            ("textDocument/completion", Some(id)) => {
+                let res = self.completion(&params).unwrap_or(JsonValue::Null);
+                self.send_result(id, res);
+            }
+            (_, Some(id)) => {
+                self.send_error(id, METHOD_NOT_FOUND, &format!("unknown method: {method}"));
+            }
+            // unknown notifications are ignored
+            (_, None) => {}
+        }
+        None
+    }
+
+    fn capabilities() -> JsonValue {
+        JsonValue::object(vec![
+            (
+                "capabilities",
+                JsonValue::object(vec![
+                    // 1: full synchronization
+                    ("textDocumentSync", 1.into()),
+                    ("hoverProvider", true.into()),
+                    ("definitionProvider", true.into()),
+                    (
+                        "completionProvider",
+                        JsonValue::object(vec![("triggerCharacters", vec!["."].into())]),
+                    ),
+                ]),
+            ),
+            (
+                "serverInfo",
+                JsonValue::object(vec![
+                    ("name", "erg".into()),
+                    ("version", env!("CARGO_PKG_VERSION").into()),
+                ]),
+            ),
+        ])
+    }
+
+    fn check_file(&mut self, uri: &str) {
+        let Some(src) = self.files.get(uri).cloned() else {
+            return;
+        };
+        let path = uri_to_path(uri);
+        let cfg = ErgConfig {
+            input: Input::File(path.clone()),
+            // warnings are published as diagnostics
+            verbose: 0,
+            ..self.cfg.copy()
+        };
+        // the compiler may panic with an unimplemented feature, but the server must not die
+        let res = catch_unwind(AssertUnwindSafe(|| {
+            let mut builder = HIRBuilder::new_with_cache(
+                cfg,
+                "<module>",
+                SharedModuleCache::new(),
+                SharedModuleCache::new(),
+            );
+            let (hir, errs, warns) = match builder.build_with_warns(src.clone(), "exec") {
+                Ok((hir, warns)) => (Some(hir), CompileErrors::empty(), warns),
+                Err((hir, errs)) => (hir, errs, CompileErrors::empty()),
+            };
+            let ctx = builder.pop_mod_ctx();
+            (hir, ctx, errs, warns)
+        }));
+        let diags = match res {
+            Ok((hir, ctx, errs, warns)) => {
+                let mut diags = vec![];
+                for err in errs.iter() {
+                    diags.push(diagnostic(err, &path, &src, DIAG_ERROR));
+                }
+                for warn in warns.iter() {
+                    diags.push(diagnostic(warn, &path, &src, DIAG_WARNING));
+                }
+                self.analyses.insert(
+                    uri.to_string(),
+                    Analysis {
+                        hir,
+                        ctx: Some(ctx),
+                    },
+                );
+                diags
+            }
+            Err(_) => {
+                // keep the last analysis for hovering and completion
+                vec![JsonValue::object(vec![
+                    ("range", lsp_range(Location::Unknown, "")),
+                    ("severity", DIAG_ERROR.into()),
+                    ("source", "erg".into()),
+                    (
+                        "message",
+                        "the compiler crashed while checking this file".into(),
+                    ),
+                ])]
+            }
+        };
+        self.send_notification(
+            "textDocument/publishDiagnostics",
+            JsonValue::object(vec![
+                ("uri", uri.into()),
+                ("diagnostics", JsonValue::Array(diags)),
+            ]),
+        );
+    }
+
+    /// Returns the URI and the position as `(ln, col)` (1-origin line, 0-origin column in chars)
+    fn position(&self, params: &JsonValue) -> Option<(String, usize, usize)> {
+        let uri = params.path(&["textDocument", "uri"])?.as_str()?;
+        let line = params.path(&["position", "line"])?.as_u64()? as usize;
+        let col = params.path(&["position", "character"])?.as_u64()? as usize;
+        let src = self.files.get(uri).map_or("", |src| &src[..]);
+        let col = utf16_to_char_col(nth_line(src, line + 1), col);
+        Some((uri.to_string(), line + 1, col))
+    }
+
+    fn find_symbol(&self, uri: &str, ln: usize, col: usize) -> Option<Symbol> {
+        let analysis = self.analyses.get(uri)?;
+        let mut finder = SymbolFinder::new(ln, col, analysis.ctx.as_ref());
+        finder.visit_module(analysis.hir.as_ref()?);
+        finder.found
+    }
+
+    fn hover(&self, params: &JsonValue) -> Option<JsonValue> {
+        let (uri, ln, col) = self.position(params)?;
+        let sym = self.find_symbol(&uri, ln, col)?;
+        let value = format!("```erg\n{}: {}\n```", sym.name, sym.t);
+        Some(JsonValue::object(vec![
+            (
+                "contents",
+                JsonValue::object(vec![("kind", "markdown".into()), ("value", value.into())]),
+            ),
+            ("range", lsp_range(sym.loc, self.files.get(&uri)?)),
+        ]))
+    }
+
+    fn definition(&self, params: &JsonValue) -> Option<JsonValue> {
+        let (uri, ln, col) = self.position(params)?;
+        let sym = self.find_symbol(&uri, ln, col)?;
+        let def_loc = sym.def_loc?;
+        let range = lsp_range(def_loc, self.files.get(&uri)?);
+        Some(JsonValue::object(vec![
+            ("uri", uri.into()),
+            ("range", range),
+        ]))
+    }
+
+    fn completion(&self, params: &JsonValue) -> Option<JsonValue> {
+        let (uri, ln, col) = self.position(params)?;
+        let analysis = self.analyses.get(&uri)?;
+        let ctx = analysis.ctx.as_ref()?;
+        let line = nth_line(self.files.get(&uri)?, ln);
+        let before_cursor = line.chars().take(col).collect::<String>();
+        // strip the name being typed
+        let before_word =
+            before_cursor.trim_end_matches(|c: char| c.is_alphanumeric() || c == '_' || c == '!');
+        let mut items = vec![];
+        if let Some(receiver) = before_word.strip_suffix('.') {
+            let receiver = receiver
+                .rsplit(|c: char| !(c.is_alphanumeric() || c == '_' || c == '!'))
+                .next()
+                .unwrap_or("");
+            // The receiver's definition comes from the file being edited, so the column is not important.
+            let receiver_t = analysis.hir.as_ref().and_then(|hir| {
+                let mut finder = SymbolFinder::new(ln, col, Some(ctx));
+                finder.visit_module(hir);
+                finder
+                    .visible
+                    .unwrap_or_default()
+                    .into_iter()
+                    .rev()
+                    .find(|sym| &sym.name[..] == receiver)
+                    .map(|sym| sym.t)
+            });
+            let members = if let Some(t) = receiver_t.as_ref() {
+                ctx.get_type_members(t)
+            } else if let Some(recv_ctx) = ctx.get_receiver_ctx(receiver) {
+                recv_ctx.type_dir()
+            } else {
+                vec![]
+            };
+            for (name, vi) in members {
+                items.push(completion_item(name.inspect(), vi, true));
+            }
+        } else {
+            if let Some(hir) = analysis.hir.as_ref() {
+                let mut finder = SymbolFinder::new(ln, col, Some(ctx));
+                finder.visit_module(hir);
+                for sym in finder.visible.unwrap_or_default() {
+                    items.push(JsonValue::object(vec![
+                        ("label", sym.name.to_string().into()),
+                        ("kind", completion_kind(&sym.name, &sym.t, false).into()),
+                        ("detail", sym.t.to_string().into()),
+                    ]));
+                }
+            }
+            for (name, vi) in ctx.dir() {
+                items.push(completion_item(name.inspect(), vi, false));
+            }
+        }
+        let mut labels = erg_common::set::Set::new();
+        items.retain(|item| {
+            let label = item.get("label").and_then(|l| l.as_str()).unwrap_or("");
+            // private (double-underscored) names are not shown
+            !label.starts_with("__") && labels.insert(label.to_string())
+        });
+        Some(JsonValue::Array(items))
+    }
+
+    fn send(&self, msg: JsonValue) {
+        let body = msg.to_string();
+        let mut out = stdout().lock();
+        write!(out, "Content-Length: {}\r\n\r\n{body}", body.len()).unwrap();
+        out.flush().unwrap();
+    }
+
+    fn send_result(&self, id: JsonValue, result: JsonValue) {
+        self.send(JsonValue::object(vec![
+            ("jsonrpc", "2.0".into()),
+            ("id", id),
+            ("result", result),
+        ]));
+    }
+
+    fn send_error(&self, id: JsonValue, code: i64, msg: &str) {
+        self.send(JsonValue::object(vec![
+            ("jsonrpc", "2.0".into()),
+            ("id", id),
+            (
+                "error",
+                JsonValue::object(vec![("code", code.into()), ("message", msg.into())]),
+            ),
+        ]));
+    }
+
+    fn send_notification(&self, method: &str, params: JsonValue) {
+        self.send(JsonValue::object(vec![
+            ("jsonrpc", "2.0".into()),
+            ("method", method.into()),
+            ("params", params),
+        ]));
+    }
+}
+
+/// Reads a JSON-RPC message with the `Content-Length` header.
+/// Returns `None` if the input is closed.
+fn read_message<R: BufRead>(reader: &mut R) -> Option<String> {
+    let mut len = None;
+    loop {
+        let mut line = String::new();
+        if reader.read_line(&mut line).ok()? == 0 {
+            return None;
+        }
+        let line = line.trim_end();
+        if line.is_empty() {
+            if len.is_some() {
+                break;
+            }
+            continue;
+        }
+        if let Some(n) = line.strip_prefix("Content-Length:") {
+            len = n.trim().parse::<usize>().ok();
+        }
+    }
+    let mut buf = vec![0; len?];
+    reader.read_exact(&mut buf).ok()?;
+    Some(String::from_utf8_lossy(&buf).to_string())
+}
+
+/// `ln` is 1-origin
+fn nth_line(src: &str, ln: usize) -> &str {
+    src.lines().nth(ln.saturating_sub(1)).unwrap_or("")
+}
+
+/// LSP counts the columns in UTF-16 code units, but Erg counts them in chars
+fn utf16_to_char_col(line: &str, col: usize) -> usize {
+    let mut units = 0;
+    for (i, c) in line.chars().enumerate() {
+        if units >= col {
+            return i;
+        }
+        units += c.len_utf16();
+    }
+    line.chars().count()
+}
+
+fn char_to_utf16_col(line: &str, col: usize) -> usize {
+    line.chars().take(col).map(char::len_utf16).sum()
+}
+
+fn lsp_pos(ln: usize, col: usize, src: &str) -> JsonValue {
+    let col = char_to_utf16_col(nth_line(src, ln), col);
+    JsonValue::object(vec![
+        ("line", ln.saturating_sub(1).into()),
+        ("character", col.into()),
+    ])
+}
+
+fn lsp_range(loc: Location, src: &str) -> JsonValue {
+    let (start, end) = match (loc.ln_begin(), loc.col_begin(), loc.ln_end(), loc.col_end()) {
+        (Some(lb), Some(cb), Some(le), Some(ce)) => (lsp_pos(lb, cb, src), lsp_pos(le, ce, src)),
+        // the whole lines
+        (Some(lb), _, Some(le), _) => (lsp_pos(lb, 0, src), lsp_pos(le + 1, 0, src)),
+        _ => (lsp_pos(1, 0, src), lsp_pos(1, 0, src)),
+    };
+    JsonValue::object(vec![("start", start), ("end", end)])
+}
+
+fn diagnostic<E: ErrorDisplay>(err: &E, path: &Path, src: &str, severity: u8) -> JsonValue {
+    let core = err.core();
+    let mut msg = format!("{}: {}", core.kind, strip_escapes(&core.desc));
+    if let Some(hint) = &core.hint {
+        msg.push_str(&format!("\nhint: {}", strip_escapes(hint)));
+    }
+    // errors in the other modules are reported at the beginning of the file
+    let loc = match err.input() {
+        Input::File(file) if !same_file(file, path) => {
+            msg = format!("{}: {msg}", file.display());
+            Location::Unknown
+        }
+        _ => core.loc,
+    };
+    JsonValue::object(vec![
+        ("range", lsp_range(loc, src)),
+        ("severity", severity.into()),
+        ("code", core.errno.into()),
+        ("source", "erg".into()),
+        ("message", msg.into()),
+    ])
+}
+
+fn completion_kind(name: &str, t: &Type, is_attr: bool) -> u8 {
+    if t.is_callable() {
+        if is_attr {
+            COMPLETION_METHOD
+        } else {
+            COMPLETION_FUNCTION
+        }
+    } else if matches!(t, Type::ClassType) {
+        COMPLETION_CLASS
+    } else if t.qual_name().contains("Module") {
+        COMPLETION_MODULE
+    } else if name.chars().next().is_some_and(|c| c.is_uppercase()) {
+        COMPLETION_CONSTANT
+    } else {
+        COMPLETION_VARIABLE
+    }
+}
+
+fn completion_item(name: &str, vi: &VarInfo, is_attr: bool) -> JsonValue {
+    JsonValue::object(vec![
+        ("label", name.into()),
+        ("kind", completion_kind(name, &vi.t, is_attr).into()),
+        ("detail", vi.t.to_string().into()),
+    ])
+}
+
+fn same_file(lhs: &Path, rhs: &Path) -> bool {
+    match (lhs.canonicalize(), rhs.canonicalize()) {
+        (Ok(l), Ok(r)) => l == r,
+        _ => lhs == rhs,
+    }
+}
+
+/// `file:///home/user/a%20b.er` -> `/home/user/a b.er`
+pub fn uri_to_path(uri: &str) -> PathBuf {
+    let path = uri.strip_prefix("file://").unwrap_or(uri);
+    // `file:///C:/...` on Windows
+    let path = if cfg!(windows) {
+        path.trim_start_matches('/')
+    } else {
+        path
+    };
+    let bytes = path.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        if bytes[i] == b'%' && i + 2 < bytes.len() {
+            let hi = (bytes[i + 1] as char).to_digit(16);
+            let lo = (bytes[i + 2] as char).to_digit(16);
+            if let (Some(hi), Some(lo)) = (hi, lo) {
+                decoded.push((hi * 16 + lo) as u8);
+                i += 3;
+                continue;
+            }
+        }
+        decoded.push(bytes[i]);
+        i += 1;
+    }
+    PathBuf::from(String::from_utf8_lossy(&decoded).to_string())
+}
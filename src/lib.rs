@@ -1,3 +1,4 @@
 extern crate erg_common;
 extern crate erg_compiler;
 pub mod dummy;
+pub mod lang_server;
@@ -13,14 +13,21 @@ use erg_parser::lex::LexerRunner;
 use erg_parser::ParserRunner;
 
 use erg_compiler::build_hir::HIRBuilder;
+use erg_compiler::build_package::PackageBuilder;
+use erg_compiler::linter::Linter;
 use erg_compiler::lower::ASTLowerer;
 use erg_compiler::ty::deserialize::Deserializer;
 use erg_compiler::Compiler;
 
 use erg::dummy::DummyVM;
+use erg::lang_server::ErgLanguageServer;
 
 fn run() {
     let cfg = ErgConfig::parse();
+    if cfg.watch && cfg.mode != "check" {
+        eprintln!("--watch is only available in check mode");
+        process::exit(1);
+    }
     match cfg.mode {
         "lex" => {
             LexerRunner::run(cfg);
@@ -31,18 +38,30 @@ fn run() {
         "lower" => {
             ASTLowerer::run(cfg);
         }
+        "check" if cfg.watch => {
+            HIRBuilder::new(cfg).watch();
+        }
         "check" => {
             HIRBuilder::run(cfg);
         }
+        "lint" => {
+            Linter::run(cfg);
+        }
         "compile" => {
             Compiler::run(cfg);
         }
+        "build" => {
+            PackageBuilder::run(cfg);
+        }
         "exec" => {
             DummyVM::run(cfg);
         }
         "read" => {
             Deserializer::run(cfg);
         }
+        "server" => {
+            ErgLanguageServer::new(cfg).run();
+        }
         other => {
             eprintln!("invalid mode: {other}");
             process::exit(1);
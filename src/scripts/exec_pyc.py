@@ -0,0 +1,45 @@
+# Append __ to all variables to prevent name collisions in exec
+# All strings must be quoted by single quotes to prevent shell interpretation
+#
+# Usage: python -c <this script> <pyc> <report>
+# Executes <pyc> and, if an exception is not caught, writes a JSON report to <report>:
+# {'type', 'message', 'frames': [{'filename', 'name', 'firstlineno', 'lineno', 'lasti'}], 'traceback'}
+import json as __json
+import marshal as __marshal
+import sys as __sys
+import traceback as __traceback
+
+__pyc, __report = __sys.argv[1], __sys.argv[2]
+__sys.argv = [__pyc]
+# skip the header (magic number, flags, timestamp and size)
+with open(__pyc, 'rb') as __f:
+    __f.read(16)
+    __code = __marshal.loads(__f.read())
+
+try:
+    exec(__code, {'__name__': '__main__'})
+except SystemExit:
+    raise
+except BaseException as __e:
+    # the first frame is this script
+    __tb = __e.__traceback__.tb_next
+    __frames = []
+    while __tb is not None:
+        __co = __tb.tb_frame.f_code
+        __frames.append({
+            'filename': __co.co_filename,
+            'name': __co.co_name,
+            'firstlineno': __co.co_firstlineno,
+            'lineno': __tb.tb_lineno,
+            'lasti': __tb.tb_lasti,
+        })
+        __tb = __tb.tb_next
+    with open(__report, 'w') as __f:
+        __json.dump({
+            'type': type(__e).__name__,
+            'message': str(__e),
+            'frames': __frames,
+            'traceback': ''.join(__traceback.format_exception(type(__e), __e, __e.__traceback__.tb_next)),
+        }, __f)
+    __sys.stdout.flush()
+    __sys.exit(1)
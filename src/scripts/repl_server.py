@@ -1,9 +1,16 @@
 # Append __ to all variables to prevent name collisions in exec
 # All strings must be quoted by single quotes to prevent shell interpretation
+#
+# Protocol: every message is a 4-byte big-endian length followed by a UTF-8 payload.
+# DummyVM sends an order (load | exit) and this server replies with a JSON object:
+# {'kind': 'ok' | 'exception' | 'exit' | 'closed' | 'unknown', 'stdout', 'stderr', 'result', 'exception'}
 import socket as __socket
 import sys as __sys
-import importlib as __importlib
 import io as __io
+import json as __json
+import marshal as __marshal
+import struct as __struct
+import traceback as __traceback
 
 __server_socket = __socket.socket()
 # DummyVM will replace this __PORT__ with free port
@@ -11,32 +18,63 @@ __server_socket.bind(('127.0.0.1', __PORT__))
 __server_socket.listen(1)
 (__client_socket, __client_address) = __server_socket.accept()
 
-__already_loaded = False
-__res = ''
+# the namespace shared by all inputs
+__globals = {'__name__': '__main__'}
+
+def __recv_exact(n):
+    buf = b''
+    while len(buf) < n:
+        chunk = __client_socket.recv(n - len(buf))
+        if not chunk:
+            raise ConnectionError('the connection was closed')
+        buf += chunk
+    return buf
+
+def __recv():
+    (length,) = __struct.unpack('>I', __recv_exact(4))
+    return __recv_exact(length).decode()
+
+def __send(**kwargs):
+    payload = __json.dumps(kwargs).encode()
+    __client_socket.sendall(__struct.pack('>I', len(payload)) + payload)
+
+def __load():
+    # skip the header (magic number, flags, timestamp and size)
+    with open('o.pyc', 'rb') as f:
+        f.read(16)
+        code = __marshal.loads(f.read())
+    (stdout, stderr) = (__io.StringIO(), __io.StringIO())
+    (__sys.stdout, __sys.stderr) = (stdout, stderr)
+    res = {'kind': 'ok', 'result': None, 'exception': None}
+    try:
+        value = eval(code, __globals)
+        if value is not None:
+            res['result'] = repr(value)
+    except SystemExit as e:
+        res['kind'] = 'exit'
+        res['result'] = repr(e.code)
+    except BaseException as e:
+        res['kind'] = 'exception'
+        # the first frame is this function
+        tb = e.__traceback__.tb_next
+        res['exception'] = {
+            'type': type(e).__name__,
+            'message': str(e),
+            'traceback': ''.join(__traceback.format_exception(type(e), e, tb)),
+        }
+    finally:
+        (__sys.stdout, __sys.stderr) = (__sys.__stdout__, __sys.__stderr__)
+    __send(stdout=stdout.getvalue(), stderr=stderr.getvalue(), **res)
 
 while True:
-    __order = __client_socket.recv(1024).decode()
+    __order = __recv()
     if __order == 'quit' or __order == 'exit':
-        __client_socket.send('closed'.encode())
+        __send(kind='closed')
         break
     elif __order == 'load':
-        __sys.stdout = __io.StringIO()
-        try:
-            if __already_loaded:
-                __res = str(exec('__importlib.reload(o)'))
-            else:
-                __res = str(exec('import o'))
-        except SystemExit:
-            __client_socket.send('[Exception] SystemExit'.encode())
-            continue
-        except e:
-            __res = str(e)
-        __already_loaded = True
-        __out = __sys.stdout.getvalue()[:-1]
-        __res = __out + '\n' + __res
-        __client_socket.send(__res.encode())
+        __load()
     else:
-        __client_socket.send('unknown operation'.encode())
+        __send(kind='unknown')
 
 __client_socket.close()
 __server_socket.close()
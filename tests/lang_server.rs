@@ -0,0 +1,155 @@
+use std::io::Write;
+use std::path::PathBuf;
+use std::process::{Command, Stdio};
+
+use erg_common::json::JsonValue;
+
+use erg::lang_server::uri_to_path;
+
+const URI: &str = "file:///tmp/erg_lang_server_test.er";
+
+fn request(id: u32, method: &str, params: JsonValue) -> String {
+    let msg = JsonValue::object(vec![
+        ("jsonrpc", "2.0".into()),
+        ("id", id.into()),
+        ("method", method.into()),
+        ("params", params),
+    ]);
+    frame(msg)
+}
+
+fn notification(method: &str, params: JsonValue) -> String {
+    let msg = JsonValue::object(vec![
+        ("jsonrpc", "2.0".into()),
+        ("method", method.into()),
+        ("params", params),
+    ]);
+    frame(msg)
+}
+
+fn frame(msg: JsonValue) -> String {
+    let body = msg.to_string();
+    format!("Content-Length: {}\r\n\r\n{body}", body.len())
+}
+
+/// Splits the server output into the messages
+fn parse_messages(mut out: &str) -> Vec<JsonValue> {
+    let mut msgs = vec![];
+    while let Some(rest) = out.strip_prefix("Content-Length: ") {
+        let (len, rest) = rest.split_once("\r\n\r\n").unwrap();
+        let len = len.parse::<usize>().unwrap();
+        msgs.push(JsonValue::parse(&rest[..len]).unwrap());
+        out = &rest[len..];
+    }
+    assert!(out.is_empty(), "unexpected output: {out}");
+    msgs
+}
+
+fn response(msgs: &[JsonValue], id: u64) -> &JsonValue {
+    msgs.iter()
+        .find(|msg| msg.get("id").and_then(JsonValue::as_u64) == Some(id))
+        .and_then(|msg| msg.get("result"))
+        .unwrap_or_else(|| panic!("no response for the request {id}"))
+}
+
+#[test]
+fn lang_server_initialize_and_hover() {
+    // the position of `s` in the second line is 10 in chars, but 11 in UTF-16 code units
+    let src = "s = \"abc\"\nt = (\"😀\", s)\n";
+    let text_document = JsonValue::object(vec![("uri", URI.into())]);
+    let mut input = request(
+        1,
+        "initialize",
+        JsonValue::object(vec![("capabilities", JsonValue::object::<&str>(vec![]))]),
+    );
+    input += &notification("initialized", JsonValue::object::<&str>(vec![]));
+    input += &notification(
+        "textDocument/didOpen",
+        JsonValue::object(vec![(
+            "textDocument",
+            JsonValue::object(vec![
+                ("uri", URI.into()),
+                ("languageId", "erg".into()),
+                ("version", 1u32.into()),
+                ("text", src.into()),
+            ]),
+        )]),
+    );
+    input += &request(
+        2,
+        "textDocument/hover",
+        JsonValue::object(vec![
+            ("textDocument", text_document),
+            (
+                "position",
+                JsonValue::object(vec![("line", 1u32.into()), ("character", 11u32.into())]),
+            ),
+        ]),
+    );
+    input += &request(3, "shutdown", JsonValue::Null);
+    input += &notification("exit", JsonValue::Null);
+
+    let mut server = Command::new(env!("CARGO_BIN_EXE_erg"))
+        .args(["--mode", "server"])
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .spawn()
+        .unwrap();
+    server
+        .stdin
+        .take()
+        .unwrap()
+        .write_all(input.as_bytes())
+        .unwrap();
+    let out = server.wait_with_output().unwrap();
+    assert!(out.status.success());
+    let msgs = parse_messages(std::str::from_utf8(&out.stdout).unwrap());
+
+    let init = response(&msgs, 1);
+    assert_eq!(
+        init.path(&["capabilities", "hoverProvider"])
+            .and_then(JsonValue::as_bool),
+        Some(true)
+    );
+    assert_eq!(
+        init.path(&["serverInfo", "name"])
+            .and_then(JsonValue::as_str),
+        Some("erg")
+    );
+
+    let diags = msgs
+        .iter()
+        .find(|msg| {
+            msg.get("method").and_then(JsonValue::as_str) == Some("textDocument/publishDiagnostics")
+        })
+        .and_then(|msg| msg.path(&["params", "diagnostics"]))
+        .and_then(JsonValue::as_array)
+        .unwrap();
+    assert!(diags.is_empty(), "{diags:?}");
+
+    let hover = response(&msgs, 2);
+    let value = hover
+        .path(&["contents", "value"])
+        .and_then(JsonValue::as_str)
+        .unwrap();
+    assert!(value.starts_with("```erg\ns: "), "{value}");
+    let start = hover.path(&["range", "start"]).unwrap();
+    assert_eq!(start.get("line").and_then(JsonValue::as_u64), Some(1));
+    assert_eq!(start.get("character").and_then(JsonValue::as_u64), Some(11));
+
+    assert_eq!(response(&msgs, 3), &JsonValue::Null);
+}
+
+#[cfg(unix)]
+#[test]
+fn lang_server_uri_to_path() {
+    assert_eq!(
+        uri_to_path("file:///tmp/a%20b%E3%81%82.er"),
+        PathBuf::from("/tmp/a bあ.er")
+    );
+    // `%` followed by a multibyte char or a truncated escape is kept as it is
+    assert_eq!(
+        uri_to_path("file:///tmp/%あ%8"),
+        PathBuf::from("/tmp/%あ%8")
+    );
+}
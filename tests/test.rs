@@ -1,8 +1,25 @@
-use std::path::PathBuf;
+use std::env::{self, temp_dir};
+use std::fs::{create_dir_all, metadata, read, remove_dir_all, remove_file, write};
+use std::io::{Read, Write};
+use std::net::{TcpListener, TcpStream};
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use std::thread;
+use std::time::Duration;
 
 use erg_common::config::ErgConfig;
-use erg_common::error::MultiErrorDisplay;
-use erg_common::traits::Runnable;
+use erg_common::error::{ErrorKind, MultiErrorDisplay};
+use erg_common::json::JsonValue;
+use erg_common::python_util::{detect_magic_number, which_python, PythonVersion};
+use erg_common::serialize::get_magic_num_from_bytes;
+use erg_common::traits::{Runnable, Stream};
+use erg_common::watch::FileWatcher;
+
+use erg_compiler::build_hir::HIRBuilder;
+use erg_compiler::build_package::PackageBuilder;
+use erg_compiler::disk_cache::{cache_path, code_cache_path, CachedModule};
+use erg_compiler::mod_cache::SharedModuleCache;
+use erg_compiler::{source_map_path, Compiler};
 
 use erg::dummy::DummyVM;
 
@@ -16,11 +33,251 @@ fn exec_assert_cast() -> Result<(), ()> {
     expect_success("examples/assert_cast.er")
 }
 
+#[test]
+fn exec_build_package() -> Result<(), ()> {
+    let out_dir = temp_dir().join("erg_build_pkg");
+    let _ = remove_dir_all(&out_dir);
+    let mut cfg = ErgConfig::with_main_path(PathBuf::from("examples/build_pkg"));
+    cfg.output_dir = Some(out_dir.clone());
+    let mut builder = PackageBuilder::new(cfg);
+    let pyc_paths = match builder.build() {
+        Ok(paths) => paths,
+        Err(errs) => {
+            errs.fmt_all_stderr();
+            return Err(());
+        }
+    };
+    // dependencies come first
+    let names = pyc_paths
+        .iter()
+        .map(|path| path.strip_prefix(out_dir.canonicalize().unwrap()).unwrap())
+        .collect::<Vec<_>>();
+    assert_eq!(
+        names,
+        [
+            Path::new("util.pyc"),
+            Path::new("sub/geom.pyc"),
+            Path::new("main.pyc")
+        ]
+    );
+    let out = Command::new(which_python())
+        .args(["-c", "import main"])
+        .current_dir(&out_dir)
+        .output()
+        .unwrap();
+    remove_dir_all(&out_dir).unwrap();
+    if out.status.success() && out.stdout == b"12\n42\n" {
+        Ok(())
+    } else {
+        println!("{}", String::from_utf8_lossy(&out.stderr));
+        Err(())
+    }
+}
+
+#[test]
+fn check_invalidate_module() -> Result<(), ()> {
+    let root = Path::new("examples/build_pkg").canonicalize().unwrap();
+    let util = root.join("util.er");
+    let geom = root.join("sub/geom.er");
+    let mod_cache = SharedModuleCache::new();
+    let cfg = ErgConfig {
+        use_cache: false,
+        ..ErgConfig::with_main_path(root.join("main.er"))
+    };
+    let check = || {
+        let mut builder = HIRBuilder::new_with_cache(
+            cfg.copy(),
+            "<module>",
+            mod_cache.clone(),
+            SharedModuleCache::new(),
+        );
+        builder
+            .build(builder.input().read(), "exec")
+            .map_err(|(_, errs)| errs.fmt_all_stderr())
+    };
+    check()?;
+    // the modules imported by `sub/geom` are kept
+    mod_cache.invalidate(&geom);
+    assert!(mod_cache.get(&geom).is_none());
+    assert!(mod_cache.get(&util).is_some());
+    check()?;
+    // `sub/geom` is invalidated since it imports `util`
+    mod_cache.invalidate(&util);
+    assert!(mod_cache.get(&util).is_none());
+    assert!(mod_cache.get(&geom).is_none());
+    Ok(())
+}
+
+#[test]
+fn check_watch_invalidates_importers() -> Result<(), ()> {
+    let dir = temp_dir().join("erg_watch");
+    let _ = remove_dir_all(&dir);
+    create_dir_all(&dir).unwrap();
+    let dir = dir.canonicalize().unwrap();
+    let (a, b) = (dir.join("a.er"), dir.join("b.er"));
+    write(dir.join("main.er"), "a = import \"a\"\ny: Int = a.y\n").unwrap();
+    write(&a, "b = import \"b\"\n.y = b.x + 1\n").unwrap();
+    write(&b, ".x: Int = 1\n").unwrap();
+    let cfg = ErgConfig {
+        use_cache: false,
+        ..ErgConfig::with_main_path(dir.join("main.er"))
+    };
+    let mod_cache = SharedModuleCache::new();
+    let mut builder =
+        HIRBuilder::new_with_cache(cfg, "<module>", mod_cache.clone(), SharedModuleCache::new());
+    let res = builder.build(builder.input().read(), "exec");
+    let ok = res.map_err(|(_, errs)| errs.fmt_all_stderr()).is_ok();
+    let mut watched = builder.watched_files(&mod_cache);
+    watched.sort();
+    assert_eq!(watched, vec![a.clone(), b.clone(), dir.join("main.er")]);
+    // `b` is modified while `wait_for_changes` is polling
+    let modify = thread::spawn({
+        let b = b.clone();
+        move || {
+            thread::sleep(Duration::from_millis(500));
+            write(&b, ".x: Str = \"a\"\n").unwrap();
+        }
+    });
+    let mut watcher = FileWatcher::new(Duration::from_millis(50));
+    let mut invalidated = builder.wait_for_changes(&mut watcher);
+    modify.join().unwrap();
+    invalidated.sort();
+    // `a` (and `main`) import `b` transitively, so they are also checked again
+    assert_eq!(invalidated, watched);
+    assert!(mod_cache.get(&a).is_none() && mod_cache.get(&b).is_none());
+    let mut builder = HIRBuilder::new_with_cache(
+        builder.cfg().copy(),
+        "<module>",
+        mod_cache.clone(),
+        SharedModuleCache::new(),
+    );
+    let rechecked = builder.build(builder.input().read(), "exec");
+    remove_dir_all(&dir).unwrap();
+    if ok && rechecked.is_err() {
+        Ok(())
+    } else {
+        Err(())
+    }
+}
+
+#[test]
+fn exec_watch_is_rejected() {
+    let out = Command::new(env!("CARGO_BIN_EXE_erg"))
+        .args(["--mode", "exec", "--watch", "examples/helloworld.er"])
+        .output()
+        .unwrap();
+    assert_eq!(out.status.code(), Some(1));
+    let stderr = String::from_utf8_lossy(&out.stderr);
+    assert!(stderr.contains("--watch is only available in check mode"));
+}
+
+/// Talks to `scripts/repl_server.py` in the same way as `DummyVM`:
+/// every message is prefixed with its length (4-byte big-endian)
+#[test]
+fn exec_repl_server_protocol() {
+    fn send(stream: &mut TcpStream, order: &str) -> JsonValue {
+        let mut msg = (order.len() as u32).to_be_bytes().to_vec();
+        msg.extend(order.as_bytes());
+        stream.write_all(&msg).unwrap();
+        let mut len = [0; 4];
+        stream.read_exact(&mut len).unwrap();
+        let mut buf = vec![0; u32::from_be_bytes(len) as usize];
+        stream.read_exact(&mut buf).unwrap();
+        JsonValue::parse(std::str::from_utf8(&buf).unwrap()).unwrap()
+    }
+    let field = |reply: &JsonValue, key: &str| {
+        reply
+            .get(key)
+            .and_then(JsonValue::as_str)
+            .unwrap_or("")
+            .to_string()
+    };
+
+    let dir = temp_dir().join("erg_repl_server");
+    let _ = remove_dir_all(&dir);
+    create_dir_all(&dir).unwrap();
+    let port = TcpListener::bind("127.0.0.1:0")
+        .unwrap()
+        .local_addr()
+        .unwrap()
+        .port();
+    let code = include_str!("../src/scripts/repl_server.py").replace("__PORT__", &port.to_string());
+    let mut server = Command::new(which_python())
+        .arg("-c")
+        .arg(code)
+        .current_dir(&dir)
+        .spawn()
+        .unwrap();
+    let mut stream = loop {
+        match TcpStream::connect(("127.0.0.1", port)) {
+            Ok(stream) => break stream,
+            Err(_) => thread::sleep(Duration::from_millis(100)),
+        }
+    };
+    stream
+        .set_read_timeout(Some(Duration::from_secs(10)))
+        .unwrap();
+    // the compiler keeps the definitions of the previous inputs, as the server does
+    let mut compiler = Compiler::new(ErgConfig::default());
+    let mut eval = |src: &str| {
+        compiler
+            .eval_compile_and_dump_as_pyc(dir.join("o.pyc"), src.to_string(), "eval")
+            .map_err(|errs| errs.fmt_all_stderr())
+            .unwrap();
+        send(&mut stream, "load")
+    };
+
+    let reply = eval("x = 1");
+    assert_eq!(field(&reply, "kind"), "ok");
+    let reply = eval("print! x + 1");
+    assert_eq!(field(&reply, "stdout"), "2\n");
+    let reply = eval("x + 2");
+    assert_eq!(field(&reply, "result"), "3");
+    let reply = eval("x / 0");
+    assert_eq!(field(&reply, "kind"), "exception");
+    let exc = reply.get("exception").unwrap();
+    assert_eq!(field(exc, "type"), "ZeroDivisionError");
+    // the session continues after an exception
+    let reply = eval("x");
+    assert_eq!(field(&reply, "result"), "1");
+    let reply = send(&mut stream, "unknown order");
+    assert_eq!(field(&reply, "kind"), "unknown");
+
+    let reply = send(&mut stream, "exit");
+    assert_eq!(field(&reply, "kind"), "closed");
+    // the server closes the connection and exits
+    assert_eq!(stream.read(&mut [0; 1]).unwrap(), 0);
+    assert!(server.wait().unwrap().success());
+    remove_dir_all(&dir).unwrap();
+}
+
 #[test]
 fn exec_class() -> Result<(), ()> {
     expect_success("examples/class.er")
 }
 
+#[test]
+fn exec_comprehension() -> Result<(), ()> {
+    expect_success("examples/comprehension.er")
+}
+
+#[test]
+fn exec_decorator() -> Result<(), ()> {
+    expect_success("examples/decorator.er")
+}
+
+#[test]
+fn exec_decorator_err() -> Result<(), ()> {
+    expect_failure("tests/decorator.er")
+}
+
+#[test]
+fn exec_decorator_python_versions() -> Result<(), ()> {
+    // `functools.cache` is not available before Python 3.9
+    expect_compile_result("examples/decorator.er", PythonVersion::V3_8, false)?;
+    expect_compile_result("examples/decorator.er", PythonVersion::V3_9, true)
+}
+
 #[test]
 fn exec_dict() -> Result<(), ()> {
     expect_success("examples/dict.er")
@@ -61,11 +318,86 @@ fn exec_infer_trait() -> Result<(), ()> {
     expect_success("tests/infer_trait.er")
 }
 
+#[test]
+fn exec_loop() -> Result<(), ()> {
+    expect_success("examples/loop.er")
+}
+
+#[test]
+fn exec_loop_err() -> Result<(), ()> {
+    expect_errors("tests/loop.er", 5)
+}
+
+#[test]
+fn exec_while_cond() -> Result<(), ()> {
+    expect_errors("tests/while_cond.er", 1)
+}
+
 #[test]
 fn exec_move_check() -> Result<(), ()> {
     expect_failure("examples/move_check.er")
 }
 
+#[test]
+fn exec_optimize() -> Result<(), ()> {
+    expect_success("tests/optimize.er")
+}
+
+#[test]
+fn exec_import_cache() -> Result<(), ()> {
+    let path = Path::new("examples/simple_math.er").canonicalize().unwrap();
+    let python_ver = detect_magic_number();
+    let decls_path = cache_path(&path);
+    let code_path = code_cache_path(&path, python_ver, 1);
+    let _ = remove_file(&decls_path);
+    let _ = remove_file(&code_path);
+    expect_success("examples/import_cache.er")?;
+    let mut cached = CachedModule::load(&path, None).unwrap();
+    assert!(cached.load_code(&path, python_ver, 1).is_some());
+    let modified = |path: &Path| metadata(path).unwrap().modified().unwrap();
+    let (decls_modified, code_modified) = (modified(&decls_path), modified(&code_path));
+    // uses the cache (the cache files are not rewritten)
+    expect_success("examples/import_cache.er")?;
+    assert_eq!(modified(&decls_path), decls_modified);
+    assert_eq!(modified(&code_path), code_modified);
+    Ok(())
+}
+
+#[test]
+fn exec_pattern() -> Result<(), ()> {
+    expect_success("examples/pattern.er")
+}
+
+#[test]
+fn exec_pattern_err() -> Result<(), ()> {
+    expect_errors("tests/pattern.er", 2)
+}
+
+#[test]
+fn exec_pattern_default_err() -> Result<(), ()> {
+    expect_errors("tests/pattern_default.er", 1)
+}
+
+#[test]
+fn exec_pipeline() -> Result<(), ()> {
+    expect_success("examples/pipeline.er")
+}
+
+#[test]
+fn exec_pipeline_err() -> Result<(), ()> {
+    expect_failure("tests/pipeline.er")
+}
+
+#[test]
+fn exec_try() -> Result<(), ()> {
+    expect_success("examples/try.er")
+}
+
+#[test]
+fn exec_try_err() -> Result<(), ()> {
+    expect_errors("tests/try.er", 3)
+}
+
 #[test]
 fn exec_pyimport() -> Result<(), ()> {
     expect_end_with("examples/pyimport.er", 111)
@@ -79,7 +411,7 @@ fn exec_quantified() -> Result<(), ()> {
 #[test]
 fn exec_rec() -> Result<(), ()> {
     // this script is valid but the current code generating process has a bug.
-    expect_end_with("tests/rec.er", 1)
+    expect_runtime_error("tests/rec.er", ErrorKind::NameError, 2)
 }
 
 #[test]
@@ -87,6 +419,16 @@ fn exec_record() -> Result<(), ()> {
     expect_success("examples/record.er")
 }
 
+#[test]
+fn exec_refinement() -> Result<(), ()> {
+    expect_success("examples/refinement.er")
+}
+
+#[test]
+fn exec_refinement_err() -> Result<(), ()> {
+    expect_errors("tests/refinement.er", 2)
+}
+
 #[test]
 fn exec_set() -> Result<(), ()> {
     expect_failure("examples/set.er")
@@ -97,6 +439,11 @@ fn exec_side_effect() -> Result<(), ()> {
     expect_failure("examples/side_effect.er")
 }
 
+#[test]
+fn exec_str_interp() -> Result<(), ()> {
+    expect_success("examples/str_interp.er")
+}
+
 #[test]
 fn exec_subtyping() -> Result<(), ()> {
     expect_failure("tests/subtyping.er")
@@ -122,56 +469,269 @@ fn exec_use_py() -> Result<(), ()> {
     expect_success("examples/use_py.er")
 }
 
+#[test]
+fn exec_runtime_error() -> Result<(), ()> {
+    expect_runtime_error("tests/runtime_error.er", ErrorKind::ZeroDivisionError, 5)
+}
+
+#[test]
+fn exec_varargs() -> Result<(), ()> {
+    expect_success("examples/varargs.er")
+}
+
+#[test]
+fn exec_varargs_err() -> Result<(), ()> {
+    expect_errors("tests/varargs.er", 6)
+}
+
 #[test]
 fn exec_with() -> Result<(), ()> {
     expect_success("examples/with.er")
 }
 
-fn expect_success(file_path: &'static str) -> Result<(), ()> {
-    let cfg = ErgConfig::with_main_path(PathBuf::from(file_path));
-    let mut vm = DummyVM::new(cfg);
-    match vm.exec() {
-        Ok(0) => Ok(()),
-        Ok(i) => {
-            println!("err: end with {i}");
-            Err(())
-        }
-        Err(errs) => {
-            errs.fmt_all_stderr();
-            Err(())
+/// CPython crashes on bytecode of another version, so it must not be generated
+#[test]
+fn compile_unsupported_python_version() {
+    // the magic number of Python 3.12
+    const PY312: u32 = 3531;
+    let mut cfg = ErgConfig::with_main_path(PathBuf::from("examples/helloworld.er"));
+    cfg.python_ver = Some(PY312);
+    let src = cfg.input.read();
+    let errs = Compiler::new(cfg).compile(src, "exec").unwrap_err();
+    assert_eq!(errs.first().unwrap().core.kind, ErrorKind::EnvironmentError);
+    let out = Command::new(env!("CARGO_BIN_EXE_erg"))
+        .args(["--mode", "compile", "-p", &PY312.to_string()])
+        .arg("examples/helloworld.er")
+        .output()
+        .unwrap();
+    assert_eq!(out.status.code(), Some(1));
+    assert!(String::from_utf8_lossy(&out.stderr).contains("unsupported Python version"));
+    assert!(!Path::new("examples/helloworld.pyc").exists());
+}
+
+#[test]
+fn exec_python_versions() -> Result<(), ()> {
+    for file_path in [
+        "examples/helloworld.er",
+        "examples/fib.er",
+        "examples/class.er",
+        "examples/trait.er",
+        "examples/record.er",
+        "examples/with.er",
+        "examples/assert_cast.er",
+        "examples/unpack.er",
+    ] {
+        for ver in PythonVersion::ALL {
+            expect_success_with(file_path, ver.magic_num())?;
         }
     }
+    Ok(())
 }
 
-fn expect_end_with(file_path: &'static str, code: i32) -> Result<(), ()> {
-    let cfg = ErgConfig::with_main_path(PathBuf::from(file_path));
-    let mut vm = DummyVM::new(cfg);
-    match vm.exec() {
-        Ok(0) => Err(()),
-        Ok(i) => {
-            if i == code {
+/// Runs `run` in a thread with a larger stack, as the binaries do on Windows
+/// (the default stack size of the test threads is too small for the debug build)
+fn exec_new_thread<F>(run: F) -> Result<(), ()>
+where
+    F: FnOnce() -> Result<(), ()> + Send + 'static,
+{
+    const STACK_SIZE: usize = 4 * 1024 * 1024;
+
+    let child = thread::Builder::new()
+        .stack_size(STACK_SIZE)
+        .spawn(run)
+        .unwrap();
+    // Wait for thread to join
+    child.join().unwrap()
+}
+
+/// Only compiles the script for the Python of `ver`
+fn expect_compile_result(
+    file_path: &'static str,
+    ver: PythonVersion,
+    should_succeed: bool,
+) -> Result<(), ()> {
+    exec_new_thread(move || {
+        let mut cfg = ErgConfig::with_main_path(PathBuf::from(file_path));
+        cfg.python_ver = Some(ver.magic_num());
+        let src = cfg.input.read();
+        let res = Compiler::new(cfg).compile(src, "exec");
+        match res {
+            Ok(_) if should_succeed => Ok(()),
+            Err(errs) if !should_succeed => {
+                errs.fmt_all_stderr();
                 Ok(())
-            } else {
-                println!("err: end with {i}");
+            }
+            Ok(_) => {
+                println!("err: {file_path} should not be compiled for Python {ver}");
+                Err(())
+            }
+            Err(errs) => {
+                errs.fmt_all_stderr();
                 Err(())
             }
         }
-        Err(errs) => {
+    })
+}
+
+/// Compiles the script for the Python of `magic_num`, and executes it with the Python.
+/// If the Python is not installed, the test fails unless `ERG_SKIP_MISSING_PYTHON` is set,
+/// in which case only the compilation is checked.
+fn expect_success_with(file_path: &'static str, magic_num: u32) -> Result<(), ()> {
+    exec_new_thread(move || {
+        let ver = PythonVersion::from_magic_num(magic_num).unwrap();
+        let mut cfg = ErgConfig::with_main_path(PathBuf::from(file_path));
+        cfg.python_ver = Some(magic_num);
+        let src = cfg.input.read();
+        let pyc_path = file_path
+            .split('/')
+            .next_back()
+            .unwrap()
+            .replace(".er", &format!("_{}.pyc", ver.minor()));
+        let mut compiler = Compiler::new(cfg);
+        if let Err(errs) = compiler.compile_and_dump_as_pyc(&pyc_path, src, "exec") {
             errs.fmt_all_stderr();
-            Err(())
+            return Err(());
         }
-    }
+        let bytes = read(&pyc_path).unwrap();
+        let magic_ok =
+            get_magic_num_from_bytes(&[bytes[0], bytes[1], bytes[2], bytes[3]]) == magic_num;
+        let installed = Command::new(ver.command())
+            .arg("--version")
+            .output()
+            .is_ok_and(|out| out.status.success());
+        let res = installed.then(|| Command::new(ver.command()).arg(&pyc_path).output());
+        remove_file(&pyc_path).unwrap();
+        remove_file(source_map_path(&pyc_path)).unwrap();
+        if !magic_ok {
+            println!("err: invalid magic number ({file_path}, Python {ver})");
+            return Err(());
+        }
+        match res {
+            Some(Ok(out)) if out.status.success() => Ok(()),
+            Some(Ok(out)) => {
+                println!("err: {file_path} failed on Python {ver}");
+                println!("{}", String::from_utf8_lossy(&out.stderr));
+                Err(())
+            }
+            Some(Err(err)) => {
+                println!("err: {err}");
+                Err(())
+            }
+            None if env::var_os("ERG_SKIP_MISSING_PYTHON").is_some() => {
+                println!("skipped: {} is not installed ({file_path})", ver.command());
+                Ok(())
+            }
+            None => {
+                println!(
+                    "err: {} is not installed (set ERG_SKIP_MISSING_PYTHON to skip it)",
+                    ver.command()
+                );
+                Err(())
+            }
+        }
+    })
+}
+
+fn expect_success(file_path: &'static str) -> Result<(), ()> {
+    exec_new_thread(move || {
+        let cfg = ErgConfig::with_main_path(PathBuf::from(file_path));
+        let mut vm = DummyVM::new(cfg);
+        match vm.exec() {
+            Ok(0) => Ok(()),
+            Ok(i) => {
+                println!("err: end with {i}");
+                Err(())
+            }
+            Err(errs) => {
+                errs.fmt_all_stderr();
+                Err(())
+            }
+        }
+    })
+}
+
+fn expect_end_with(file_path: &'static str, code: i32) -> Result<(), ()> {
+    exec_new_thread(move || {
+        let cfg = ErgConfig::with_main_path(PathBuf::from(file_path));
+        let mut vm = DummyVM::new(cfg);
+        match vm.exec() {
+            Ok(0) => Err(()),
+            Ok(i) => {
+                if i == code {
+                    Ok(())
+                } else {
+                    println!("err: end with {i}");
+                    Err(())
+                }
+            }
+            Err(errs) => {
+                errs.fmt_all_stderr();
+                Err(())
+            }
+        }
+    })
+}
+
+/// Expects a Python exception mapped to `kind` at line `lineno` of the Erg script
+fn expect_runtime_error(file_path: &'static str, kind: ErrorKind, lineno: usize) -> Result<(), ()> {
+    exec_new_thread(move || {
+        let cfg = ErgConfig::with_main_path(PathBuf::from(file_path));
+        let mut vm = DummyVM::new(cfg);
+        match vm.exec() {
+            Ok(i) => {
+                println!("err: end with {i}");
+                Err(())
+            }
+            Err(errs) => {
+                errs.fmt_all_stderr();
+                match errs.first() {
+                    Some(err)
+                        if err.core.kind == kind && err.core.loc.ln_begin() == Some(lineno) =>
+                    {
+                        Ok(())
+                    }
+                    _ => Err(()),
+                }
+            }
+        }
+    })
 }
 
 fn expect_failure(file_path: &'static str) -> Result<(), ()> {
-    let cfg = ErgConfig::with_main_path(PathBuf::from(file_path));
-    let mut vm = DummyVM::new(cfg);
-    match vm.exec() {
-        Ok(0) => Err(()),
-        Ok(_) => Ok(()),
-        Err(errs) => {
-            errs.fmt_all_stderr();
-            Ok(())
+    exec_new_thread(move || {
+        let cfg = ErgConfig::with_main_path(PathBuf::from(file_path));
+        let mut vm = DummyVM::new(cfg);
+        match vm.exec() {
+            Ok(0) => Err(()),
+            Ok(_) => Ok(()),
+            Err(errs) => {
+                errs.fmt_all_stderr();
+                Ok(())
+            }
         }
-    }
+    })
+}
+
+/// Same as `expect_failure`, but the number of the compile errors is also checked
+/// (so that a case in a file with several cases does not hide the others)
+fn expect_errors(file_path: &'static str, num_errs: usize) -> Result<(), ()> {
+    exec_new_thread(move || {
+        let cfg = ErgConfig::with_main_path(PathBuf::from(file_path));
+        let mut vm = DummyVM::new(cfg);
+        match vm.exec() {
+            Ok(i) => {
+                println!("err: end with {i}");
+                Err(())
+            }
+            Err(errs) => {
+                errs.fmt_all_stderr();
+                if errs.len() == num_errs {
+                    Ok(())
+                } else {
+                    println!("err: {} errors (expected {num_errs})", errs.len());
+                    Err(())
+                }
+            }
+        }
+    })
 }